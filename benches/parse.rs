@@ -0,0 +1,57 @@
+extern crate yaml_rust_davvid as yaml_rust;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use std::fmt::Write;
+use std::hint::black_box;
+use yaml_rust::parser::Parser;
+use yaml_rust::{YamlEmitter, YamlLoader};
+
+// A list of records like a typical configuration or data dump.
+fn records(n: usize) -> String {
+    let mut s = String::new();
+    for i in 0..n {
+        writeln!(s, "- id: {}", i).unwrap();
+        writeln!(s, "  name: \"record {}\"", i).unwrap();
+        writeln!(s, "  score: {}.5", i % 100).unwrap();
+        writeln!(s, "  tags: [a, b, c]").unwrap();
+        writeln!(s, "  nested: {{enabled: true, limit: ~}}").unwrap();
+    }
+    s
+}
+
+// Deeply nested flow collections.
+fn nested(depth: usize) -> String {
+    format!("{}x{}", "[".repeat(depth), "]".repeat(depth))
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let inputs = [("records", records(2000)), ("nested", nested(200))];
+    let mut group = c.benchmark_group("parse");
+    for (name, input) in &inputs {
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.bench_function(format!("events/{}", name), |b| {
+            b.iter(|| {
+                let mut parser = Parser::new(black_box(input.as_str()).chars());
+                for event in &mut parser {
+                    black_box(event.unwrap());
+                }
+            })
+        });
+        group.bench_function(format!("load/{}", name), |b| {
+            b.iter(|| YamlLoader::load_from_str(black_box(input)).unwrap())
+        });
+    }
+    group.finish();
+
+    let doc = YamlLoader::load_from_str(&inputs[0].1).unwrap().remove(0);
+    c.bench_function("emit/records", |b| {
+        b.iter(|| {
+            let mut out = String::new();
+            YamlEmitter::new(&mut out).dump(black_box(&doc)).unwrap();
+            out
+        })
+    });
+}
+
+criterion_group!(benches, bench_parse);
+criterion_main!(benches);
@@ -14,7 +14,7 @@ struct IncludeParser<'a> {
 }
 
 impl<'a> IncludeParser<'a> {
-    fn new(root: &'a Path) -> IncludeParser {
+    fn new(root: &'a Path) -> IncludeParser<'a> {
         IncludeParser { root }
     }
 }
@@ -0,0 +1,169 @@
+//! Layered configuration with typed access by dotted path.
+//!
+//! # Examples
+//!
+//! ```
+//! # extern crate yaml_rust_davvid as yaml_rust;
+//! use yaml_rust::config::Config;
+//!
+//! let mut config = Config::new();
+//! config
+//!     .add_str("server: {host: localhost, port: 80}\nworkers: [a, b]")
+//!     .unwrap();
+//! // later sources take precedence over earlier ones
+//! config.add_str("server: {port: 8080}").unwrap();
+//!
+//! assert_eq!(config.get::<i64>("server.port").unwrap(), 8080);
+//! assert_eq!(config.get::<String>("server.host").unwrap(), "localhost");
+//! assert_eq!(config.get::<String>("workers.1").unwrap(), "b");
+//! assert_eq!(config.get_or("server.timeout", 30i64).unwrap(), 30);
+//! assert!(config.get::<bool>("server.port").is_err());
+//! ```
+
+use crate::scanner::ScanError;
+use crate::yaml::{lookup_path, Yaml, YamlConversionError, YamlLoader};
+
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+/// The error returned by `Config` getters.
+#[derive(Clone, PartialEq, Debug, Eq)]
+pub enum ConfigError {
+    /// No source has a value at the path.
+    Missing(String),
+    /// The value at the path has the wrong type.
+    Type {
+        path: String,
+        error: YamlConversionError,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfigError::Missing(ref path) => write!(f, "{}: missing value", path),
+            ConfigError::Type {
+                ref path,
+                ref error,
+            } => write!(f, "{}: {}", path, error),
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            ConfigError::Missing(_) => None,
+            ConfigError::Type { ref error, .. } => Some(error),
+        }
+    }
+}
+
+/// A stack of documents, looked up from the most recently added.
+///
+/// Lookups resolve the whole path within one source: the first source that
+/// has a value at the path provides it. Hashes from different sources are not
+/// merged, so getting `server` returns the hash from a single source.
+#[derive(Clone, PartialEq, Debug, Default, Eq)]
+pub struct Config {
+    sources: Vec<Yaml>,
+}
+
+impl Config {
+    pub fn new() -> Config {
+        Config::default()
+    }
+
+    /// Add a source that takes precedence over the existing ones.
+    pub fn add_source(&mut self, doc: Yaml) -> &mut Self {
+        self.sources.push(doc);
+        self
+    }
+
+    /// Load every document in `source` and add them in order.
+    pub fn add_str(&mut self, source: &str) -> Result<&mut Self, ScanError> {
+        self.sources.extend(YamlLoader::load_from_str(source)?);
+        Ok(self)
+    }
+
+    /// The value at a dotted path such as `server.port`. Segments select a
+    /// key of a hash, or an element of an array by index. Integer keys are
+    /// matched too, so `ports.8080` finds `{ports: {8080: x}}`.
+    pub fn lookup(&self, path: &str) -> Option<&Yaml> {
+        self.sources
+            .iter()
+            .rev()
+            .find_map(|doc| lookup_path(doc, path))
+    }
+
+    /// The value at `path`, converted to `T`.
+    pub fn get<T>(&self, path: &str) -> Result<T, ConfigError>
+    where
+        T: TryFrom<Yaml, Error = YamlConversionError>,
+    {
+        match self.lookup(path) {
+            Some(value) => T::try_from(value.clone()).map_err(|error| ConfigError::Type {
+                path: path.to_owned(),
+                error,
+            }),
+            None => Err(ConfigError::Missing(path.to_owned())),
+        }
+    }
+
+    /// Like `get`, but returns `default` if no source has a value at `path`.
+    pub fn get_or<T>(&self, path: &str, default: T) -> Result<T, ConfigError>
+    where
+        T: TryFrom<Yaml, Error = YamlConversionError>,
+    {
+        match self.get(path) {
+            Err(ConfigError::Missing(_)) => Ok(default),
+            result => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_layers() {
+        let mut config = Config::new();
+        assert_eq!(config.lookup(""), None);
+        config
+            .add_str("a: {b: 1, c: [x, y]}\nports: {8080: http}\n---\nd: base")
+            .unwrap();
+        config.add_source(
+            YamlLoader::load_from_str("a: {b: 2}\nd: ~")
+                .unwrap()
+                .remove(0),
+        );
+
+        assert_eq!(config.get::<i64>("a.b"), Ok(2));
+        assert_eq!(config.get::<String>("a.c.0"), Ok("x".to_owned()));
+        assert_eq!(config.get::<String>("ports.8080"), Ok("http".to_owned()));
+        // an explicit null overrides lower layers
+        assert_eq!(config.lookup("d"), Some(&Yaml::Null));
+        assert_eq!(config.lookup("a.c.2"), None);
+        assert_eq!(config.lookup("a.b.c"), None);
+        assert_eq!(config.get_or("a.b", 5i64), Ok(2));
+        assert_eq!(config.get_or("a.z", 5i64), Ok(5));
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut config = Config::new();
+        config.add_str("a: {b: text}").unwrap();
+        let err = config.get::<i64>("a.b").unwrap_err();
+        assert_eq!(err.to_string(), "a.b: expected integer, found string");
+        assert!(err.source().is_some());
+        assert_eq!(config.get_or("a.b", 1i64), Err(err));
+
+        let err = config.get::<i64>("a.c").unwrap_err();
+        assert_eq!(err, ConfigError::Missing("a.c".to_owned()));
+        assert_eq!(err.to_string(), "a.c: missing value");
+
+        assert!(config.add_str("[").is_err());
+    }
+}
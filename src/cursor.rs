@@ -0,0 +1,321 @@
+//! The syntactic context at a position in source text, such as a block
+//! mapping key or a flow sequence entry, for editor tooling like completion
+//! and on-type formatting.
+//!
+//! Only the source before the position is scanned, so what follows it has
+//! no effect and documents that are being typed need not be complete.
+//!
+//! # Examples
+//!
+//! ```
+//! # extern crate yaml_rust_davvid as yaml_rust;
+//! use yaml_rust::cursor::{context_at, ContextKind};
+//!
+//! let s = "server:\n  host: web\n  po\n  ports: [80, ";
+//! let ctx = context_at(s, 24);
+//! assert_eq!(ctx.kind, ContextKind::BlockMappingKey);
+//! assert_eq!(ctx.scalar.map(|span| span.slice(s)), Some("po"));
+//! assert_eq!(ctx.indent, Some(2));
+//!
+//! let ctx = context_at(s, s.len());
+//! assert_eq!(ctx.kind, ContextKind::FlowSequence);
+//! assert_eq!(ctx.flow_level, 1);
+//! ```
+
+use crate::scanner::{Marker, Scanner, Span, Token, TokenType};
+
+/// What the source at a position is part of.
+#[derive(Clone, Copy, PartialEq, Debug, Eq)]
+pub enum ContextKind {
+    /// Outside of any collection, such as in an empty document.
+    Document,
+    BlockMappingKey,
+    BlockMappingValue,
+    /// An entry of a block sequence.
+    BlockSequence,
+    FlowMappingKey,
+    FlowMappingValue,
+    /// An entry of a flow sequence, including a single pair mapping in one.
+    FlowSequence,
+    Comment,
+}
+
+/// The context at a position, see `context_at`.
+#[derive(Clone, Copy, PartialEq, Debug, Eq)]
+pub struct CursorContext {
+    pub kind: ContextKind,
+    /// The scalar that ends at the position, such as a key being typed.
+    pub scalar: Option<Span>,
+    /// The number of flow collections open at the position.
+    pub flow_level: usize,
+    /// The column of the innermost open block collection.
+    pub indent: Option<usize>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Eq)]
+enum Collection {
+    BlockMapping,
+    BlockSequence,
+    FlowMapping,
+    FlowSequence,
+}
+
+#[derive(Debug)]
+struct Frame {
+    collection: Collection,
+    col: usize,
+    // whether a mapping expects a key rather than a value
+    key: bool,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    stack: Vec<Frame>,
+    line: usize,
+}
+
+impl State {
+    fn push(&mut self, collection: Collection, mark: Marker) {
+        self.stack.push(Frame {
+            collection,
+            col: mark.col(),
+            key: true,
+        });
+    }
+
+    fn set_key(&mut self, key: bool) {
+        if let Some(frame) = self.stack.last_mut() {
+            frame.key = key;
+        }
+    }
+
+    fn in_flow(&self) -> bool {
+        self.stack.iter().any(|frame| {
+            matches!(
+                frame.collection,
+                Collection::FlowMapping | Collection::FlowSequence
+            )
+        })
+    }
+
+    // Text starting a line at the indentation of a block mapping is a key,
+    // even before the scanner has seen its `:`.
+    fn line_start(&mut self, col: usize) {
+        if self.in_flow() {
+            return;
+        }
+        if let Some(frame) = self.stack.last_mut() {
+            if frame.collection == Collection::BlockMapping && frame.col == col {
+                frame.key = true;
+            }
+        }
+    }
+
+    fn token(&mut self, mark: Marker, token: &TokenType) {
+        if mark.line() > self.line {
+            self.line = mark.line();
+            self.line_start(mark.col());
+        }
+        match *token {
+            TokenType::BlockMappingStart => self.push(Collection::BlockMapping, mark),
+            TokenType::BlockSequenceStart => self.push(Collection::BlockSequence, mark),
+            TokenType::FlowMappingStart => self.push(Collection::FlowMapping, mark),
+            TokenType::FlowSequenceStart => self.push(Collection::FlowSequence, mark),
+            TokenType::BlockEnd | TokenType::FlowMappingEnd | TokenType::FlowSequenceEnd => {
+                self.stack.pop();
+            }
+            TokenType::Key | TokenType::FlowEntry => self.set_key(true),
+            TokenType::Value => self.set_key(false),
+            TokenType::DocumentStart | TokenType::DocumentEnd => self.stack.clear(),
+            _ => {}
+        }
+    }
+
+    // The cursor is on a line after the last token, indented by `col`, so
+    // the block collections indented more than it have ended.
+    fn cursor_line(&mut self, col: usize) {
+        if self.in_flow() {
+            return;
+        }
+        while self.stack.last().is_some_and(|frame| frame.col > col) {
+            self.stack.pop();
+        }
+        self.line_start(col);
+    }
+
+    fn kind(&self) -> ContextKind {
+        let frame = match self.stack.last() {
+            Some(frame) => frame,
+            None => return ContextKind::Document,
+        };
+        match (frame.collection, frame.key) {
+            (Collection::BlockMapping, true) => ContextKind::BlockMappingKey,
+            (Collection::BlockMapping, false) => ContextKind::BlockMappingValue,
+            (Collection::BlockSequence, _) => ContextKind::BlockSequence,
+            (Collection::FlowMapping, true) => ContextKind::FlowMappingKey,
+            (Collection::FlowMapping, false) => ContextKind::FlowMappingValue,
+            (Collection::FlowSequence, _) => ContextKind::FlowSequence,
+        }
+    }
+}
+
+/// The context at the character `index` of `source`, found by scanning the
+/// source before it. A scan error, such as an unclosed quote before the
+/// position, ends the scan, and the context is that of the tokens before
+/// the error.
+pub fn context_at(source: &str, index: usize) -> CursorContext {
+    let chars: Vec<char> = source.chars().take(index).collect();
+    let index = chars.len();
+    let mut scanner = Scanner::new(chars.iter().copied());
+    scanner.record_token_spans();
+
+    let line_start = chars
+        .iter()
+        .rposition(|&c| c == '\n' || c == '\r')
+        .map_or(0, |i| i + 1);
+    let mut state = State::default();
+    // where the last token ends, and its span if it is a scalar
+    let mut last_end = 0;
+    let mut scalar = None;
+    let mut scanned = true;
+    loop {
+        let Token(mark, token) = match scanner.next_token() {
+            Ok(Some(token)) => token,
+            Ok(None) => break,
+            Err(_) => {
+                scanned = false;
+                break;
+            }
+        };
+        // the end of the source closes what is open, but the cursor doesn't
+        if mark.index() >= index && matches!(token, TokenType::BlockEnd | TokenType::StreamEnd) {
+            continue;
+        }
+        state.token(mark, &token);
+        scalar = None;
+        last_end = match token {
+            TokenType::Scalar(..) => {
+                let span = scanner.take_token_span(mark.index());
+                scalar = span;
+                span.map_or(mark.index(), |span| span.end.index())
+            }
+            TokenType::Anchor(_) | TokenType::Alias(_) | TokenType::Tag(..) => scanner
+                .take_token_span(mark.index())
+                .map_or(mark.index(), |span| span.end.index()),
+            TokenType::DocumentStart | TokenType::DocumentEnd => mark.index() + 3,
+            TokenType::StreamStart(_) | TokenType::Key | TokenType::BlockMappingStart => {
+                mark.index()
+            }
+            _ => mark.index() + 1,
+        };
+    }
+
+    let line = &chars[line_start..];
+    if last_end <= line_start {
+        let col = line.iter().take_while(|&&c| c == ' ').count();
+        state.cursor_line(col);
+    }
+    let comment = scanned
+        && (last_end.max(line_start)..index)
+            .any(|i| chars[i] == '#' && (i == line_start || chars[i - 1].is_whitespace()));
+    let flow_level = state
+        .stack
+        .iter()
+        .filter(|frame| {
+            matches!(
+                frame.collection,
+                Collection::FlowMapping | Collection::FlowSequence
+            )
+        })
+        .count();
+    let indent = state
+        .stack
+        .iter()
+        .rev()
+        .find(|frame| {
+            matches!(
+                frame.collection,
+                Collection::BlockMapping | Collection::BlockSequence
+            )
+        })
+        .map(|frame| frame.col);
+    CursorContext {
+        kind: if comment {
+            ContextKind::Comment
+        } else {
+            state.kind()
+        },
+        scalar: scalar.filter(|span| !comment && span.end.index() == index),
+        flow_level,
+        indent,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // The context at the `|` in `s`, which is removed.
+    fn context(s: &str) -> (ContextKind, Option<String>) {
+        let index = s.chars().position(|c| c == '|').unwrap();
+        let source = s.replacen('|', "", 1);
+        let ctx = context_at(&source, index);
+        (
+            ctx.kind,
+            ctx.scalar.map(|span| span.slice(&source).to_owned()),
+        )
+    }
+
+    #[test]
+    fn test_block_context() {
+        use ContextKind::*;
+
+        assert_eq!(context("|"), (Document, None));
+        assert_eq!(context("wor|d"), (Document, Some("wor".to_owned())));
+        assert_eq!(
+            context("a: 1\nke|"),
+            (BlockMappingKey, Some("ke".to_owned()))
+        );
+        assert_eq!(context("a: 1|"), (BlockMappingValue, Some("1".to_owned())));
+        assert_eq!(context("a: |\nb: 2"), (BlockMappingValue, None));
+        assert_eq!(context("a:\n  |"), (BlockMappingValue, None));
+        assert_eq!(context("a:\n  b: 1\n  |"), (BlockMappingKey, None));
+        assert_eq!(context("a:\n  b: 1\n|"), (BlockMappingKey, None));
+        assert_eq!(context("a:\n  - x\n  - |"), (BlockSequence, None));
+        assert_eq!(context("a:\n  - x\n|"), (BlockMappingKey, None));
+        assert_eq!(context("- a: b\n  |"), (BlockMappingKey, None));
+        assert_eq!(context("- a: b\n|"), (BlockSequence, None));
+        assert_eq!(context("---\n|"), (Document, None));
+    }
+
+    #[test]
+    fn test_flow_context() {
+        use ContextKind::*;
+
+        assert_eq!(context("[a, |"), (FlowSequence, None));
+        assert_eq!(context("{a: 1, |}"), (FlowMappingKey, None));
+        assert_eq!(context("{a: |}"), (FlowMappingValue, None));
+        assert_eq!(context("x: {a: [1]|"), (FlowMappingValue, None));
+        assert_eq!(
+            context("x: [{a: 1}, b|"),
+            (FlowSequence, Some("b".to_owned()))
+        );
+
+        let ctx = context_at("x:\n  - [{a: ", 11);
+        assert_eq!(ctx.flow_level, 2);
+        assert_eq!(ctx.indent, Some(2));
+    }
+
+    #[test]
+    fn test_comment_context() {
+        use ContextKind::*;
+
+        assert_eq!(context("a: 1 # no|te"), (Comment, None));
+        assert_eq!(context("a:\n  # no|te"), (Comment, None));
+        assert_eq!(
+            context("a: b#c|"),
+            (BlockMappingValue, Some("b#c".to_owned()))
+        );
+        assert_eq!(context("a: 'x # y|"), (BlockMappingValue, None));
+    }
+}
@@ -0,0 +1,195 @@
+//! Sharing of repeated subtrees, so documents that contain many copies of
+//! the same block can be written with anchors and aliases.
+//!
+//! # Examples
+//!
+//! ```
+//! # extern crate yaml_rust_davvid as yaml_rust;
+//! use yaml_rust::{YamlEmitter, YamlLoader};
+//!
+//! let s = "
+//! web: {limits: {cpu: 1, memory: 512Mi}}
+//! worker: {limits: {cpu: 1, memory: 512Mi}}
+//! ";
+//! let doc = &YamlLoader::load_from_str(s).unwrap()[0];
+//! let shared = doc.dedup_subtrees();
+//! assert_eq!(shared.anchors.len(), 1);
+//!
+//! let mut out = String::new();
+//! let mut emitter = YamlEmitter::new(&mut out);
+//! emitter.anchors(&shared.anchors);
+//! emitter.dump(&shared.doc).unwrap();
+//! assert_eq!(
+//!     out,
+//!     "---\nweb: &a1\n  limits:\n    cpu: 1\n    memory: 512Mi\nworker: *a1"
+//! );
+//! assert_eq!(&YamlLoader::load_from_str(&out).unwrap()[0], doc);
+//! ```
+
+use crate::yaml::{Hash, Yaml};
+
+use std::collections::{BTreeMap, HashMap};
+
+/// A document whose repeated subtrees are stored once.
+#[derive(Clone, PartialEq, Debug, Eq)]
+pub struct Deduplicated {
+    /// The document, with every occurrence of a repeated subtree replaced
+    /// by `Yaml::Alias` of its id.
+    pub doc: Yaml,
+    /// The subtree for each alias id, itself deduplicated. Ids start from 1
+    /// and follow the order subtrees first occur in.
+    pub anchors: BTreeMap<usize, Yaml>,
+}
+
+impl Deduplicated {
+    /// Expand every alias, recovering the original document.
+    pub fn to_yaml(&self) -> Yaml {
+        self.expand(&self.doc)
+    }
+
+    fn expand(&self, node: &Yaml) -> Yaml {
+        match *node {
+            Yaml::Alias(id) => match self.anchors.get(&id) {
+                Some(shared) => self.expand(shared),
+                None => node.clone(),
+            },
+            Yaml::Array(ref v) => Yaml::Array(v.iter().map(|x| self.expand(x)).collect()),
+            Yaml::Hash(ref h) => {
+                Yaml::Hash(h.iter().map(|(k, v)| (k.clone(), self.expand(v))).collect())
+            }
+            Yaml::Tagged(ref tag, ref v) => Yaml::Tagged(tag.clone(), Box::new(self.expand(v))),
+            _ => node.clone(),
+        }
+    }
+}
+
+impl Yaml {
+    /// Find non-empty collections that occur more than once among the
+    /// values of this document and store each of them once. Hash keys are
+    /// left as they are.
+    ///
+    /// A subtree is only shared if it still occurs more than once after
+    /// its enclosing subtrees have been shared, so no anchor goes unused.
+    /// Pass the anchors to `YamlEmitter::anchors` to write the result.
+    pub fn dedup_subtrees(&self) -> Deduplicated {
+        let mut counts = HashMap::new();
+        count(self, &mut counts);
+        let mut dedup = Dedup {
+            counts,
+            ids: HashMap::new(),
+            anchors: BTreeMap::new(),
+        };
+        let doc = dedup.share(self, true);
+        Deduplicated {
+            doc,
+            anchors: dedup.anchors,
+        }
+    }
+}
+
+fn is_candidate(node: &Yaml) -> bool {
+    match *node {
+        Yaml::Array(ref v) => !v.is_empty(),
+        Yaml::Hash(ref h) => !h.is_empty(),
+        Yaml::Tagged(_, ref v) => is_candidate(v),
+        _ => false,
+    }
+}
+
+// Count the occurrences of each candidate, looking inside only the first
+// occurrence of each, as later ones become aliases.
+fn count<'a>(node: &'a Yaml, counts: &mut HashMap<&'a Yaml, usize>) {
+    if is_candidate(node) {
+        let n = counts.entry(node).or_insert(0);
+        *n += 1;
+        if *n > 1 {
+            return;
+        }
+    }
+    match *node {
+        Yaml::Array(ref v) => v.iter().for_each(|x| count(x, counts)),
+        Yaml::Hash(ref h) => h.values().for_each(|x| count(x, counts)),
+        Yaml::Tagged(_, ref v) => count(v, counts),
+        _ => {}
+    }
+}
+
+struct Dedup<'a> {
+    counts: HashMap<&'a Yaml, usize>,
+    ids: HashMap<&'a Yaml, usize>,
+    anchors: BTreeMap<usize, Yaml>,
+}
+
+impl<'a> Dedup<'a> {
+    // Copy `node`, replacing repeated subtrees other than itself if `root`
+    // is set.
+    fn share(&mut self, node: &'a Yaml, root: bool) -> Yaml {
+        if !root && self.counts.get(node).copied().unwrap_or(0) > 1 {
+            if let Some(&id) = self.ids.get(node) {
+                return Yaml::Alias(id);
+            }
+            let id = self.ids.len() + 1;
+            self.ids.insert(node, id);
+            let shared = self.share(node, true);
+            self.anchors.insert(id, shared);
+            return Yaml::Alias(id);
+        }
+        match *node {
+            Yaml::Array(ref v) => Yaml::Array(v.iter().map(|x| self.share(x, false)).collect()),
+            Yaml::Hash(ref h) => {
+                let mut hash = Hash::new();
+                for (k, v) in h {
+                    hash.insert(k.clone(), self.share(v, false));
+                }
+                Yaml::Hash(hash)
+            }
+            // the tag stays with the content
+            Yaml::Tagged(ref tag, ref v) => {
+                Yaml::Tagged(tag.clone(), Box::new(self.share(v, true)))
+            }
+            _ => node.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Yaml, YamlEmitter, YamlLoader};
+
+    #[test]
+    fn test_dedup_subtrees() {
+        let s = "
+a: &x {b: [1, 2], c: [3]}
+d: *x
+e: [[3], [1, 2], [3]]
+f: !t {g: 1}
+h: !t {g: 1}
+";
+        let doc = &YamlLoader::load_from_str(s).unwrap()[0];
+        let shared = doc.dedup_subtrees();
+        assert_eq!(shared.to_yaml(), *doc);
+        assert_eq!(shared.doc["a"], Yaml::Alias(1));
+        assert_eq!(shared.doc["d"], Yaml::Alias(1));
+        // [1, 2] occurs once within the shared hash and once in `e`
+        assert_eq!(
+            shared.anchors[&2],
+            YamlLoader::load_from_str("[1, 2]").unwrap()[0]
+        );
+        assert_eq!(shared.doc["e"][1], Yaml::Alias(2));
+        assert_eq!(shared.doc["e"][2], Yaml::Alias(3));
+        assert!(matches!(shared.doc["f"], Yaml::Alias(4)));
+        assert_eq!(shared.anchors.len(), 4);
+
+        let mut out = String::new();
+        let mut emitter = YamlEmitter::new(&mut out);
+        emitter.anchors(&shared.anchors);
+        emitter.dump(&shared.doc).unwrap();
+        assert_eq!(&YamlLoader::load_from_str(&out).unwrap()[0], doc);
+        assert_eq!(out.matches('&').count(), 4);
+
+        let unique = YamlLoader::load_from_str("[[1], [2]]").unwrap().remove(0);
+        let shared = unique.dedup_subtrees();
+        assert!(shared.anchors.is_empty());
+        assert_eq!(shared.doc, unique);
+    }
+}
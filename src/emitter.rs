@@ -1,13 +1,35 @@
-use crate::yaml::{Hash, Yaml};
+use crate::marked::{MarkedNode, MarkedValue};
+use crate::parser::Event;
+use crate::path::key_text;
+use crate::scanner::ScanError;
+use crate::yaml::{
+    application_tag, format_real, parse_f64, resolve_plain, resolve_scalar, with_path, Hash,
+    ScalarType, Schema, Tag, Yaml, YamlEncoding,
+};
 
+use encoding::types::RawEncoder;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
 use std::convert::From;
 use std::error::Error;
 use std::fmt::{self, Display};
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{self, AtomicUsize};
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, PartialEq, Debug, Eq)]
 pub enum EmitError {
     FmtError(fmt::Error),
     BadHashmapKey,
+    /// An event passed to `YamlEmitter::emit_event` can't be written at
+    /// this point of the stream.
+    InvalidEvent(&'static str),
+    /// The anchor name for the alias id is not a valid anchor, see
+    /// `is_valid_anchor`.
+    InvalidAnchor(usize),
 }
 
 impl Error for EmitError {
@@ -21,6 +43,10 @@ impl Display for EmitError {
         match *self {
             EmitError::FmtError(ref err) => Display::fmt(err, formatter),
             EmitError::BadHashmapKey => formatter.write_str("bad hashmap key"),
+            EmitError::InvalidEvent(reason) => write!(formatter, "invalid event: {}", reason),
+            EmitError::InvalidAnchor(id) => {
+                write!(formatter, "invalid anchor name for alias {}", id)
+            }
         }
     }
 }
@@ -31,19 +57,404 @@ impl From<fmt::Error> for EmitError {
     }
 }
 
+/// The line break written by `YamlEmitter`.
+#[derive(Clone, Copy, PartialEq, Debug, Default, Eq)]
+pub enum LineBreak {
+    /// `\n`, as used on Unix.
+    #[default]
+    Lf,
+    /// `\r\n`, as used on Windows.
+    CrLf,
+    /// `\r`.
+    Cr,
+}
+
+impl LineBreak {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineBreak::Lf => "\n",
+            LineBreak::CrLf => "\r\n",
+            LineBreak::Cr => "\r",
+        }
+    }
+}
+
+/// The order `YamlEmitter` writes the keys of hashes in, see
+/// `YamlEmitter::key_order`.
+#[derive(Clone, Default)]
+pub enum KeyOrder<'a> {
+    /// The order of the hash.
+    #[default]
+    Preserve,
+    /// Sorted by the `Ord` of `Yaml`, which puts string keys in byte order.
+    Alphabetical,
+    /// The keys in the list first, in its order, and then the others in the
+    /// order of the hash. Keys that are numbers or booleans match their
+    /// text.
+    Priority(Vec<String>),
+    /// Sorted by a comparator. Keys that compare equal keep their order.
+    Custom(&'a dyn Fn(&Yaml, &Yaml) -> Ordering),
+}
+
+impl<'a> KeyOrder<'a> {
+    /// The conventional order of the keys of Kubernetes manifests, with
+    /// `apiVersion`, `kind` and `metadata` first and `status` last of the
+    /// known keys.
+    pub fn kubernetes() -> KeyOrder<'a> {
+        let keys = [
+            "apiVersion",
+            "kind",
+            "metadata",
+            "name",
+            "namespace",
+            "spec",
+            "data",
+            "status",
+        ];
+        KeyOrder::Priority(keys.iter().map(|&key| key.to_owned()).collect())
+    }
+
+    // The entries of `h` in this order, with their positions in `h` to
+    // match them to their comments.
+    fn sort<'h>(&self, h: &'h Hash) -> Vec<(usize, (&'h Yaml, &'h Yaml))> {
+        let mut entries: Vec<_> = h.iter().enumerate().collect();
+        match *self {
+            KeyOrder::Preserve => {}
+            KeyOrder::Alphabetical => entries.sort_by(|a, b| a.1 .0.cmp(b.1 .0)),
+            KeyOrder::Priority(ref keys) => entries.sort_by_key(|(_, (k, _))| {
+                key_text(k)
+                    .and_then(|text| keys.iter().position(|key| *key == text))
+                    .unwrap_or(keys.len())
+            }),
+            KeyOrder::Custom(compare) => entries.sort_by(|a, b| compare(a.1 .0, b.1 .0)),
+        }
+        entries
+    }
+}
+
+impl<'a> fmt::Debug for KeyOrder<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            KeyOrder::Preserve => f.write_str("Preserve"),
+            KeyOrder::Alphabetical => f.write_str("Alphabetical"),
+            KeyOrder::Priority(ref keys) => f.debug_tuple("Priority").field(keys).finish(),
+            KeyOrder::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
+/// How `YamlEmitter` writes reals, see `YamlEmitter::float_format`.
+///
+/// Whatever the format, the text loads back as a real: one with no digits
+/// after the point is written like `2.`, and values that are not finite as
+/// `.inf`, `-.inf` and `.nan`.
+#[derive(Clone, Copy, PartialEq, Debug, Default, Eq)]
+pub struct FloatFormat {
+    /// Digits after the point, or the shortest text that loads back as the
+    /// same value.
+    pub precision: Option<usize>,
+    pub notation: FloatNotation,
+    /// Drop the zeros that end the digits after the point, keeping one, so
+    /// `1.50` is written `1.5` and `2.00` is written `2.0`.
+    pub trim_zeros: bool,
+}
+
+/// Whether `FloatFormat` writes an exponent.
+#[derive(Clone, Copy, PartialEq, Debug, Default, Eq)]
+pub enum FloatNotation {
+    /// The text of the real as it is, or with a `precision` the decimal
+    /// notation. Without a precision, reals that are not `Yaml::Real` text
+    /// such as those of `emit_event` are written like `1e20`.
+    #[default]
+    Auto,
+    /// Always without an exponent, such as `100000.0`.
+    Decimal,
+    /// Always with an exponent, such as `1.5e5`.
+    Scientific,
+}
+
+impl FloatFormat {
+    /// The text of `v` in this format.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::emitter::{FloatFormat, FloatNotation};
+    ///
+    /// let format = FloatFormat {
+    ///     precision: Some(3),
+    ///     ..FloatFormat::default()
+    /// };
+    /// assert_eq!(format.format(0.5), "0.500");
+    /// let format = FloatFormat {
+    ///     notation: FloatNotation::Scientific,
+    ///     trim_zeros: true,
+    ///     ..format
+    /// };
+    /// assert_eq!(format.format(1500.0), "1.5e3");
+    /// ```
+    pub fn format(&self, v: f64) -> String {
+        if !v.is_finite() {
+            return format_real(v);
+        }
+        let mut text = match (self.notation, self.precision) {
+            (FloatNotation::Auto, None) => return format_real(v),
+            (FloatNotation::Decimal, None) => format!("{}", v),
+            (FloatNotation::Auto, Some(p)) | (FloatNotation::Decimal, Some(p)) => {
+                format!("{:.*}", p, v)
+            }
+            (FloatNotation::Scientific, None) => format!("{:e}", v),
+            (FloatNotation::Scientific, Some(p)) => format!("{:.*e}", p, v),
+        };
+        let exponent = text.find('e').map(|i| text.split_off(i));
+        match text.find('.') {
+            Some(point) if self.trim_zeros => {
+                let digits = text.trim_end_matches('0').len().max(point + 2);
+                text.truncate(digits);
+            }
+            Some(_) => {}
+            None if exponent.is_some() => {}
+            None if self.precision == Some(0) => text.push('.'),
+            None => text.push_str(".0"),
+        }
+        text + exponent.as_deref().unwrap_or("")
+    }
+
+    // The text to write for the real `v`.
+    fn real_text<'v>(&self, v: &'v str) -> Cow<'v, str> {
+        match parse_f64(v) {
+            Some(f) if *self != FloatFormat::default() => Cow::Owned(self.format(f)),
+            _ => real_text(v),
+        }
+    }
+}
+
+/// A node with comments to write with it, for documents built by programs
+/// that describe what they generate. See `YamlEmitter::dump_commented`.
+///
+/// `value` holds the whole node. Collections built with `array` and `hash`
+/// also keep the comments of their children, which are matched to the
+/// items and entries of `value` by position.
+#[derive(Clone, PartialEq, Debug)]
+pub struct CommentedYaml {
+    /// Lines written above the node, or above its key in a hash.
+    pub above: Vec<String>,
+    /// Written at the end of the line the node starts on.
+    pub inline: Option<String>,
+    /// Blank lines written above the comments of a hash entry's value,
+    /// other than the first entry's, to group the entries.
+    pub blank_lines: usize,
+    pub value: Yaml,
+    children: Vec<CommentedYaml>,
+}
+
+impl CommentedYaml {
+    pub fn new(value: Yaml) -> CommentedYaml {
+        CommentedYaml {
+            above: Vec::new(),
+            inline: None,
+            blank_lines: 0,
+            value,
+            children: Vec::new(),
+        }
+    }
+
+    /// An array of the items, keeping their comments.
+    pub fn array<I: IntoIterator<Item = CommentedYaml>>(items: I) -> CommentedYaml {
+        let mut values = Vec::new();
+        let mut children = Vec::new();
+        for mut item in items {
+            values.push(mem::replace(&mut item.value, Yaml::Null));
+            children.push(item);
+        }
+        CommentedYaml {
+            children,
+            ..CommentedYaml::new(Yaml::Array(values))
+        }
+    }
+
+    /// A hash of the entries, keeping the comments of their values. A key
+    /// that is given again replaces the entry, which moves to the end as
+    /// with `Hash::insert`.
+    pub fn hash<I: IntoIterator<Item = (Yaml, CommentedYaml)>>(entries: I) -> CommentedYaml {
+        let mut values = Hash::new();
+        let mut children = Vec::new();
+        for (key, mut entry) in entries {
+            let value = mem::replace(&mut entry.value, Yaml::Null);
+            if values.contains_key(&key) {
+                let i = values.keys().position(|k| *k == key).unwrap();
+                children.remove(i);
+            }
+            children.push(entry);
+            values.insert(key, value);
+        }
+        CommentedYaml {
+            children,
+            ..CommentedYaml::new(Yaml::Hash(values))
+        }
+    }
+
+    /// Add a line to the comment above the node.
+    pub fn comment<S: Into<String>>(mut self, line: S) -> CommentedYaml {
+        self.above.push(line.into());
+        self
+    }
+
+    /// Set the comment at the end of the node's first line.
+    pub fn inline_comment<S: Into<String>>(mut self, text: S) -> CommentedYaml {
+        self.inline = Some(text.into());
+        self
+    }
+
+    /// Set the blank lines above the node, see `blank_lines`.
+    pub fn blank_lines(mut self, lines: usize) -> CommentedYaml {
+        self.blank_lines = lines;
+        self
+    }
+
+    /// Load all documents in `source`, with the comment lines and blank
+    /// lines above the entries of hashes at their roots, so that a file
+    /// people maintain can be changed and written again without losing how
+    /// its entries are grouped and described. Only comments that start
+    /// their line are kept, and blank lines between a comment and its
+    /// entry are dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::emitter::CommentedYaml;
+    /// use yaml_rust::YamlEmitter;
+    ///
+    /// let source = "name: web\nimage: app\n\n# scaling\nreplicas: 3\n\nports:\n  - 80\n";
+    /// let docs = CommentedYaml::load_from_str(source).unwrap();
+    /// let mut out = String::new();
+    /// let mut emitter = YamlEmitter::new(&mut out);
+    /// emitter.trailing_newline(true);
+    /// emitter.dump_commented(&docs[0]).unwrap();
+    /// assert_eq!(out, format!("---\n{}", source));
+    /// ```
+    pub fn load_from_str(source: &str) -> Result<Vec<CommentedYaml>, ScanError> {
+        // lines end where the scanner ends them, at "\r\n", "\r" or "\n"
+        let text = source.replace("\r\n", "\n");
+        let lines: Vec<&str> = text.split(['\n', '\r']).collect();
+        let docs = MarkedNode::load_from_str(source)?;
+        Ok(docs
+            .iter()
+            .map(|doc| match doc.value {
+                MarkedValue::Hash(ref entries) => {
+                    CommentedYaml::hash(entries.iter().map(|(k, v)| {
+                        let mut entry = CommentedYaml::new(v.to_yaml());
+                        // the comment and blank lines between the key and the
+                        // content above it
+                        let above = lines[..(k.marker.line() - 1).min(lines.len())]
+                            .iter()
+                            .rev()
+                            .take_while(|line| line.trim().is_empty() || line.starts_with('#'))
+                            .collect::<Vec<_>>();
+                        for line in above.iter().rev() {
+                            match line.strip_prefix('#') {
+                                Some(text) => entry
+                                    .above
+                                    .push(text.strip_prefix(' ').unwrap_or(text).to_owned()),
+                                None if entry.above.is_empty() => entry.blank_lines += 1,
+                                None => {}
+                            }
+                        }
+                        (k.to_yaml(), entry)
+                    }))
+                }
+                _ => CommentedYaml::new(doc.to_yaml()),
+            })
+            .collect())
+    }
+
+    fn child(comments: Option<&CommentedYaml>, i: usize) -> Option<&CommentedYaml> {
+        comments.and_then(|c| c.children.get(i))
+    }
+}
+
+impl From<Yaml> for CommentedYaml {
+    fn from(value: Yaml) -> CommentedYaml {
+        CommentedYaml::new(value)
+    }
+}
+
 pub struct YamlEmitter<'a> {
     writer: &'a mut dyn fmt::Write,
     best_indent: usize,
     compact: bool,
     multiline_strings: bool,
+    explicit_keys: bool,
+    line_break: LineBreak,
+    flow_leaves: usize,
+    best_width: usize,
+    anchors: Option<&'a BTreeMap<usize, Yaml>>,
+    anchor_prefix: String,
+    anchor_names: Option<&'a BTreeMap<usize, String>>,
+    // (handle, prefix) pairs written as `%TAG` directives
+    tag_handles: Vec<(String, String)>,
+    banner: Vec<String>,
+    header: Vec<String>,
+    trailing_newline: bool,
+    key_order: KeyOrder<'a>,
+    // the format of the reals being written, and by the keys they are the
+    // values of
+    float_format: FloatFormat,
+    key_float_formats: Vec<(String, FloatFormat)>,
+    strict_whitespace: bool,
+    // blank lines between the entries of a top-level hash, and between
+    // documents
+    key_blank_lines: usize,
+    document_blank_lines: usize,
 
     level: isize,
+    // the width of the `-`, `?`, `:` or `key:` the next value is written
+    // after on its line, for the width check of `flow_leaf`
+    prefix_width: usize,
+    // whether a document has been started, after which there is no banner
+    started: bool,
+    events: EventState,
+    // anchors written in the current document
+    anchored: BTreeSet<usize>,
+}
+
+// Where a node is written, mirroring `emit_node` and `emit_val`.
+#[derive(Clone, Copy)]
+enum Position {
+    Node,
+    Val { inline: bool },
+}
+
+// A collection opened by `emit_event`. Writing the collection is deferred
+// until its first child or its end, as an empty collection is written
+// inline.
+struct EventFrame {
+    mapping: bool,
+    position: Position,
+    tag: Option<Tag>,
+    opened: bool,
+    entries: usize,
+    // for mappings, whether the key of the current entry was a collection
+    // once it has been written
+    key: Option<bool>,
+}
+
+#[derive(Default)]
+struct EventState {
+    frames: Vec<EventFrame>,
+    in_document: bool,
+    has_root: bool,
+    documents: usize,
+    // the chunks of the next scalar so far
+    chunks: String,
 }
 
 pub type EmitResult = Result<(), EmitError>;
 
 // from serialize::json
-fn escape_str(wr: &mut dyn fmt::Write, v: &str) -> Result<(), fmt::Error> {
+pub(crate) fn escape_str(wr: &mut dyn fmt::Write, v: &str) -> Result<(), fmt::Error> {
     wr.write_str("\"")?;
 
     let mut start = 0;
@@ -106,13 +517,34 @@ fn escape_str(wr: &mut dyn fmt::Write, v: &str) -> Result<(), fmt::Error> {
 }
 
 impl<'a> YamlEmitter<'a> {
-    pub fn new(writer: &'a mut dyn fmt::Write) -> YamlEmitter {
+    pub fn new(writer: &'a mut dyn fmt::Write) -> YamlEmitter<'a> {
         YamlEmitter {
             writer,
             best_indent: 2,
             compact: true,
             level: -1,
             multiline_strings: false,
+            explicit_keys: false,
+            line_break: LineBreak::Lf,
+            flow_leaves: 0,
+            best_width: 80,
+            anchors: None,
+            anchor_prefix: "a".to_owned(),
+            anchor_names: None,
+            tag_handles: Vec::new(),
+            banner: Vec::new(),
+            header: Vec::new(),
+            trailing_newline: false,
+            key_order: KeyOrder::Preserve,
+            float_format: FloatFormat::default(),
+            key_float_formats: Vec::new(),
+            strict_whitespace: false,
+            key_blank_lines: 0,
+            document_blank_lines: 0,
+            prefix_width: 0,
+            started: false,
+            events: EventState::default(),
+            anchored: BTreeSet::new(),
         }
     }
 
@@ -122,8 +554,7 @@ impl<'a> YamlEmitter<'a> {
     /// [mappings](http://www.yaml.org/spec/1.2/spec.html#id2798057).
     ///
     /// In this form, blocks cannot have any properties (such as anchors
-    /// or tags), which should be OK, because this emitter doesn't
-    /// (currently) emit those anyways.
+    /// or tags), so a block with properties starts on the next line.
     pub fn compact(&mut self, compact: bool) {
         self.compact = compact;
     }
@@ -169,11 +600,810 @@ impl<'a> YamlEmitter<'a> {
         self.multiline_strings
     }
 
+    /// Write every hash key after an explicit `?`, with its value after a
+    /// `:` on the next line.
+    ///
+    /// Keys that are collections, and strings that can't be implicit keys
+    /// because they are written over several lines or longer than 1024
+    /// characters, always use this form.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use yaml_rust_davvid::{YamlEmitter, YamlLoader};
+    ///
+    /// let parsed = YamlLoader::load_from_str("a: 1\n[b, c]: {d: 2}").unwrap();
+    ///
+    /// let mut output = String::new();
+    /// # {
+    /// let mut emitter = YamlEmitter::new(&mut output);
+    /// emitter.explicit_keys(true);
+    /// emitter.dump(&parsed[0]).unwrap();
+    /// # }
+    ///
+    /// assert_eq!(output.as_str(), "\
+    /// ---
+    /// ? a
+    /// : 1
+    /// ? - b
+    ///   - c
+    /// : ? d
+    ///   : 2");
+    /// ```
+    pub fn explicit_keys(&mut self, explicit_keys: bool) {
+        self.explicit_keys = explicit_keys;
+    }
+
+    /// Determine if this emitter writes every hash key explicitly.
+    pub fn is_explicit_keys(&self) -> bool {
+        self.explicit_keys
+    }
+
+    // Whether `key` is written after an explicit `?`.
+    fn explicit_key(&self, key: &Yaml) -> bool {
+        self.explicit_keys || self.needs_explicit_key(key, 0)
+    }
+
+    // Whether `key` can't be an implicit key, with `prefix` characters
+    // written before it, such as its tag.
+    fn needs_explicit_key(&self, key: &Yaml, prefix: usize) -> bool {
+        match *key {
+            Yaml::Hash(_) | Yaml::Array(_) => true,
+            Yaml::Tagged(_, ref v) if v.is_complex_key() => is_block_collection(v),
+            Yaml::Tagged(ref tag, ref v) => {
+                let tag = self.tag_text(tag);
+                self.needs_explicit_key(v, prefix + tag.chars().count() + 1)
+            }
+            // the first alias of an id is written as the anchored node
+            Yaml::Alias(id) => match self.anchors.and_then(|anchors| anchors.get(&id)) {
+                Some(node) if !self.anchored.contains(&id) => {
+                    let anchor = self.anchor_name(id).unwrap_or_default();
+                    self.needs_explicit_key(node, prefix + anchor.chars().count() + 2)
+                }
+                _ => false,
+            },
+            _ => match key.string_text() {
+                Some(v) if self.is_literal(v) => true,
+                // escaping writes at most 6 characters for each byte
+                Some(v) if prefix + v.len() * 6 + 2 > MAX_IMPLICIT_KEY_LEN => {
+                    prefix + written_len(v) > MAX_IMPLICIT_KEY_LEN
+                }
+                _ => false,
+            },
+        }
+    }
+
+    /// Render nested arrays and hashes of at most `max_items` scalars in flow
+    /// style, such as `[1, 2]` or `{a: 1}`, if they fit within the width set
+    /// by `best_width` after indentation. Collections that contain other
+    /// collections, and the document's root, stay in block style.
+    ///
+    /// Zero, the default, turns this off.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use yaml_rust_davvid::{YamlEmitter, YamlLoader};
+    ///
+    /// let input = "ports: [80, 443]\nenv: {a: b}\nhosts: [[a, b], [c]]";
+    /// let parsed = YamlLoader::load_from_str(input).unwrap();
+    ///
+    /// let mut output = String::new();
+    /// # {
+    /// let mut emitter = YamlEmitter::new(&mut output);
+    /// emitter.flow_leaves(4);
+    /// emitter.dump(&parsed[0]).unwrap();
+    /// # }
+    ///
+    /// assert_eq!(output.as_str(), "\
+    /// ---
+    /// ports: [80, 443]
+    /// env: {a: b}
+    /// hosts:
+    ///   - [a, b]
+    ///   - [c]");
+    /// ```
+    pub fn flow_leaves(&mut self, max_items: usize) {
+        self.flow_leaves = max_items;
+    }
+
+    /// Set the preferred line width used by `flow_leaves`. Defaults to 80.
+    pub fn best_width(&mut self, width: usize) {
+        self.best_width = width;
+    }
+
+    /// Set the line break written between lines. Defaults to `LineBreak::Lf`.
+    ///
+    /// Line breaks inside strings are escaped or, with `multiline_strings`,
+    /// written as literal blocks using this line break.
+    pub fn line_break(&mut self, line_break: LineBreak) {
+        self.line_break = line_break;
+    }
+
+    /// Determine which line break this emitter writes.
+    pub fn get_line_break(&self) -> LineBreak {
+        self.line_break
+    }
+
+    /// Write `banner` as comment lines before the first document, such as a
+    /// notice that the file is generated. Each line of `banner` is written
+    /// as a separate comment line.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::{Yaml, YamlEmitter};
+    ///
+    /// let mut out = String::new();
+    /// let mut emitter = YamlEmitter::new(&mut out);
+    /// emitter.banner("Generated by gen-config.\nDO NOT EDIT.");
+    /// emitter.document_header("service: web");
+    /// emitter.trailing_newline(true);
+    /// emitter.dump(&Yaml::Integer(1)).unwrap();
+    /// emitter.dump(&Yaml::Integer(2)).unwrap();
+    /// assert_eq!(
+    ///     out,
+    ///     "# Generated by gen-config.\n# DO NOT EDIT.\n# service: web\n---\n1\n# service: web\n---\n2\n"
+    /// );
+    /// ```
+    pub fn banner(&mut self, banner: &str) {
+        self.banner = banner.lines().map(str::to_owned).collect();
+    }
+
+    /// Write `header` as comment lines before every document, after the
+    /// banner. Each line of `header` is written as a separate comment line.
+    pub fn document_header(&mut self, header: &str) {
+        self.header = header.lines().map(str::to_owned).collect();
+    }
+
+    /// Whether every document is followed by a line break, as text files
+    /// usually end with one. Defaults to false, which leaves the last line
+    /// of a document unterminated.
+    pub fn trailing_newline(&mut self, trailing_newline: bool) {
+        self.trailing_newline = trailing_newline;
+    }
+
+    /// Set the order the keys of hashes are written in, such as
+    /// `KeyOrder::kubernetes()` for manifests. Defaults to
+    /// `KeyOrder::Preserve`. Events given to `emit_event` are written in
+    /// their order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::emitter::KeyOrder;
+    /// use yaml_rust::{YamlEmitter, YamlLoader};
+    ///
+    /// let doc = YamlLoader::load_from_str("spec: {b: 1, a: 2}\nkind: Pod\napiVersion: v1").unwrap();
+    /// let mut out = String::new();
+    /// let mut emitter = YamlEmitter::new(&mut out);
+    /// emitter.key_order(KeyOrder::kubernetes());
+    /// emitter.dump(&doc[0]).unwrap();
+    /// assert_eq!(out, "---\napiVersion: v1\nkind: Pod\nspec:\n  b: 1\n  a: 2");
+    /// ```
+    pub fn key_order(&mut self, key_order: KeyOrder<'a>) {
+        self.key_order = key_order;
+    }
+
+    /// Set how reals are written, such as with a fixed number of digits
+    /// after the point. Defaults to `FloatFormat::default()`, which writes
+    /// the text of a `Yaml::Real` as it is.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::emitter::FloatFormat;
+    /// use yaml_rust::{Yaml, YamlEmitter, YamlLoader};
+    ///
+    /// let doc = YamlLoader::load_from_str("{rate: 0.5, ratio: [1.26, 3.0]}").unwrap();
+    /// let mut out = String::new();
+    /// let mut emitter = YamlEmitter::new(&mut out);
+    /// emitter.float_format(FloatFormat {
+    ///     precision: Some(2),
+    ///     ..FloatFormat::default()
+    /// });
+    /// emitter.key_float_format("ratio", FloatFormat {
+    ///     precision: Some(1),
+    ///     trim_zeros: true,
+    ///     ..FloatFormat::default()
+    /// });
+    /// emitter.dump(&doc[0]).unwrap();
+    /// assert_eq!(out, "---\nrate: 0.50\nratio:\n  - 1.3\n  - 3.0");
+    /// ```
+    pub fn float_format(&mut self, format: FloatFormat) {
+        self.float_format = format;
+    }
+
+    /// Set how the reals within the values of hash entries with `key` are
+    /// written, instead of the `float_format`. The format of a key nested
+    /// within such a value applies within its own value. Keys that are
+    /// numbers or booleans match their text.
+    pub fn key_float_format(&mut self, key: &str, format: FloatFormat) {
+        self.key_float_formats.retain(|(k, _)| k != key);
+        self.key_float_formats.push((key.to_owned(), format));
+    }
+
+    // The format of the reals within the value of `key`.
+    fn value_float_format(&self, key: &Yaml) -> FloatFormat {
+        key_text(key)
+            .and_then(|text| self.key_float_formats.iter().find(|(k, _)| *k == text))
+            .map_or(self.float_format, |&(_, format)| format)
+    }
+
+    /// Guarantee output that strict checks of whitespace accept: no line
+    /// ends with spaces or tabs, and every document ends with exactly one
+    /// line break, as with `trailing_newline`. Strings with a line that
+    /// ends with whitespace are quoted rather than written as literal
+    /// blocks, empty lines of literal blocks are not indented, and spaces
+    /// at the ends of comments are dropped. Defaults to false.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::{Yaml, YamlEmitter, YamlLoader};
+    ///
+    /// let doc = YamlLoader::load_from_str("a: \"x\\n\\ny\"\nb: \"x \\ny\"").unwrap();
+    /// let mut out = String::new();
+    /// let mut emitter = YamlEmitter::new(&mut out);
+    /// emitter.multiline_strings(true);
+    /// emitter.strict_whitespace(true);
+    /// emitter.blank_lines(1, 1);
+    /// emitter.dump(&doc[0]).unwrap();
+    /// emitter.dump(&Yaml::Integer(1)).unwrap();
+    /// assert_eq!(out, "---\na: |\n  x\n\n  y\n\nb: \"x \\ny\"\n\n---\n1\n");
+    /// ```
+    pub fn strict_whitespace(&mut self, strict: bool) {
+        self.strict_whitespace = strict;
+    }
+
+    /// Set the number of blank lines written between the entries of a hash
+    /// at the top of a document, and between documents. Both default to 0.
+    pub fn blank_lines(&mut self, between_keys: usize, between_documents: usize) {
+        self.key_blank_lines = between_keys;
+        self.document_blank_lines = between_documents;
+    }
+
+    // Whether the string `v` is written as a literal block. Line breaks
+    // other than "\n" would be read back as "\n", or end the block, so
+    // strings with them are quoted.
+    fn is_literal(&self, v: &str) -> bool {
+        self.multiline_strings
+            && v.contains('\n')
+            && !v.contains(['\r', '\u{85}', '\u{2028}', '\u{2029}'])
+            && !(self.strict_whitespace && v.lines().any(|line| line.ends_with([' ', '\t'])))
+    }
+
+    // Write the banner before the first document, and the header, the
+    // `%TAG` directives and `---` of every document.
+    fn write_document_start(&mut self) -> EmitResult {
+        self.level = -1;
+        if !self.started {
+            self.started = true;
+            let banner = mem::take(&mut self.banner);
+            let result = self.write_comment_lines(&banner);
+            self.banner = banner;
+            result?;
+        } else {
+            for _ in 0..self.document_blank_lines {
+                self.write_break()?;
+            }
+        }
+        let header = mem::take(&mut self.header);
+        let result = self.write_comment_lines(&header);
+        self.header = header;
+        result?;
+        self.write_tag_directives()?;
+        write!(self.writer, "---")?;
+        Ok(())
+    }
+
+    fn write_document_end(&mut self) -> EmitResult {
+        if self.trailing_newline || self.strict_whitespace {
+            self.write_break()?;
+        }
+        Ok(())
+    }
+
+    fn write_break(&mut self) -> EmitResult {
+        self.writer.write_str(self.line_break.as_str())?;
+        Ok(())
+    }
+
+    /// Write `Yaml::Alias` nodes using `anchors`, which gives the node for
+    /// each alias id, as from `Yaml::dedup_subtrees`. The first alias of an
+    /// id in a document is written as the node with the anchor `a<id>`, and
+    /// later ones as an alias of it. Without anchors, aliases are skipped.
+    ///
+    /// `Yaml::dedup_subtrees` numbers the shared subtrees in the order it
+    /// finds them, so the same document is always written with the same
+    /// anchors.
+    pub fn anchors(&mut self, anchors: &'a BTreeMap<usize, Yaml>) {
+        self.anchors = Some(anchors);
+    }
+
+    /// Set the text before the alias id in anchor names, which defaults to
+    /// `a`. An anchor name that is not valid makes dumping fail with
+    /// `EmitError::InvalidAnchor`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use std::collections::BTreeMap;
+    /// use yaml_rust::{YamlEmitter, YamlLoader};
+    ///
+    /// let doc = &YamlLoader::load_from_str("a: [1, 2]\nb: [1, 2]\nc: {x: y}\nd: {x: y}").unwrap()[0];
+    /// let shared = doc.dedup_subtrees();
+    /// let mut names = BTreeMap::new();
+    /// names.insert(2, "xy".to_owned());
+    /// let mut out = String::new();
+    /// let mut emitter = YamlEmitter::new(&mut out);
+    /// emitter.anchors(&shared.anchors);
+    /// emitter.anchor_prefix("shared-");
+    /// emitter.anchor_names(&names);
+    /// emitter.dump(&shared.doc).unwrap();
+    /// assert_eq!(out, "---\na: &shared-1\n  - 1\n  - 2\nb: *shared-1\nc: &xy\n  x: y\nd: *xy");
+    /// ```
+    pub fn anchor_prefix(&mut self, prefix: &str) {
+        self.anchor_prefix = prefix.to_owned();
+    }
+
+    /// Use `names` as the anchor names of the alias ids it has, instead of
+    /// the prefix and the id. Names are checked when they are written, and
+    /// one that is not valid makes dumping fail with
+    /// `EmitError::InvalidAnchor`.
+    pub fn anchor_names(&mut self, names: &'a BTreeMap<usize, String>) {
+        self.anchor_names = Some(names);
+    }
+
+    // The anchor name for the alias id `id`.
+    fn anchor_name(&self, id: usize) -> Result<String, EmitError> {
+        let name = match self.anchor_names.and_then(|names| names.get(&id)) {
+            Some(name) => name.clone(),
+            None => format!("{}{}", self.anchor_prefix, id),
+        };
+        if !is_valid_anchor(&name) {
+            return Err(EmitError::InvalidAnchor(id));
+        }
+        Ok(name)
+    }
+
+    /// Write tags that start with `prefix` in shorthand form using `handle`,
+    /// such as `!k!Pod` for `tag:kubernetes.io,2019:Pod`, and start every
+    /// document with the `%TAG` directive that declares it. Registering a
+    /// handle again replaces its prefix. If several prefixes match a tag,
+    /// the longest is used.
+    ///
+    /// # Panics
+    ///
+    /// If `handle` is not `!`, `!!` or a named handle such as `!k!`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::yaml::Tag;
+    /// use yaml_rust::{Yaml, YamlEmitter};
+    ///
+    /// let tag = Tag::new("tag:kubernetes.io,2019:", "Pod");
+    /// let doc = Yaml::Tagged(tag, Box::new(Yaml::from_str("web")));
+    /// let mut out = String::new();
+    /// let mut emitter = YamlEmitter::new(&mut out);
+    /// emitter.register_tag_handle("!k!", "tag:kubernetes.io,2019:");
+    /// emitter.dump(&doc).unwrap();
+    /// assert_eq!(out, "%TAG !k! tag:kubernetes.io,2019:\n---\n!k!Pod web");
+    /// ```
+    pub fn register_tag_handle(&mut self, handle: &str, prefix: &str) {
+        let named = handle.len() > 2
+            && handle.starts_with('!')
+            && handle.ends_with('!')
+            && handle[1..handle.len() - 1]
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-');
+        assert!(
+            handle == "!" || handle == "!!" || named,
+            "invalid tag handle {:?}",
+            handle
+        );
+        self.tag_handles.retain(|(h, _)| h != handle);
+        self.tag_handles
+            .push((handle.to_owned(), prefix.to_owned()));
+    }
+
+    // Write the `%TAG` directives of the registered handles.
+    fn write_tag_directives(&mut self) -> EmitResult {
+        for i in 0..self.tag_handles.len() {
+            let (ref handle, ref prefix) = self.tag_handles[i];
+            write!(self.writer, "%TAG {} {}", handle, prefix)?;
+            self.write_break()?;
+        }
+        Ok(())
+    }
+
+    // The text of `tag`, in shorthand form if a registered handle's prefix
+    // starts the tag and the rest can be written after the handle.
+    fn tag_text(&self, tag: &Tag) -> String {
+        if tag.handle.starts_with('!') {
+            return tag.to_string();
+        }
+        let full = format!("{}{}", tag.handle, tag.suffix);
+        self.tag_handles
+            .iter()
+            .filter_map(|(handle, prefix)| {
+                let rest = full.strip_prefix(prefix.as_str())?;
+                let plain = !rest.is_empty()
+                    && !rest.contains(|c: char| c.is_whitespace() || "!,[]{}".contains(c));
+                Some((prefix.len(), handle, rest)).filter(|_| plain)
+            })
+            .max_by_key(|&(len, ..)| len)
+            .map_or_else(
+                || tag.to_string(),
+                |(_, handle, rest)| format!("{}{}", handle, rest),
+            )
+    }
+
+    /// Write a single parser event, so documents can be produced
+    /// incrementally without building a `Yaml` tree. Events must form a
+    /// well-nested stream like the one `Parser` produces, except that
+    /// `StreamStart` and `StreamEnd` are optional.
+    ///
+    /// The output matches `dump` of the equivalent tree, with plain scalars
+    /// resolved as they would be loaded. `ScalarChunk` events are joined to
+    /// the scalar they precede. `flow_leaves` is not applied, and anchors
+    /// are dropped; an `Alias` event is an error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::scanner::TScalarStyle;
+    /// use yaml_rust::{Event, YamlEmitter};
+    ///
+    /// let mut out = String::new();
+    /// let mut emitter = YamlEmitter::new(&mut out);
+    /// emitter.emit_event(Event::DocumentStart).unwrap();
+    /// emitter.emit_event(Event::mapping_start()).unwrap();
+    /// emitter.emit_event(Event::scalar("rows", TScalarStyle::Plain)).unwrap();
+    /// emitter.emit_event(Event::sequence_start()).unwrap();
+    /// for row in 1..=2 {
+    ///     emitter.emit_event(Event::scalar(row.to_string(), TScalarStyle::Plain)).unwrap();
+    /// }
+    /// emitter.emit_event(Event::SequenceEnd).unwrap();
+    /// emitter.emit_event(Event::MappingEnd).unwrap();
+    /// emitter.emit_event(Event::DocumentEnd).unwrap();
+    /// assert_eq!(out, "---\nrows:\n  - 1\n  - 2");
+    /// ```
+    pub fn emit_event(&mut self, event: Event) -> EmitResult {
+        let mapping = matches!(event, Event::MappingStart(..) | Event::MappingEnd);
+        match event {
+            Event::Nothing | Event::StreamStart | Event::StreamEnd => Ok(()),
+            Event::DocumentStart => {
+                if self.events.in_document {
+                    return Err(EmitError::InvalidEvent(
+                        "document started inside a document",
+                    ));
+                }
+                if self.events.documents > 0 {
+                    if !(self.trailing_newline || self.strict_whitespace) {
+                        self.write_break()?;
+                    }
+                    if !self.tag_handles.is_empty() {
+                        // directives may only follow a document that is ended
+                        write!(self.writer, "...")?;
+                        self.write_break()?;
+                    }
+                }
+                self.write_document_start()?;
+                self.write_break()?;
+                self.events.in_document = true;
+                self.events.has_root = false;
+                Ok(())
+            }
+            Event::DocumentEnd => {
+                if !self.events.frames.is_empty() {
+                    return Err(EmitError::InvalidEvent(
+                        "document ended inside a collection",
+                    ));
+                }
+                self.events.in_document = false;
+                self.events.documents += 1;
+                self.write_document_end()
+            }
+            Event::Alias(_) => Err(EmitError::InvalidEvent("aliases can't be emitted")),
+            Event::ScalarChunk(v) => {
+                self.events.chunks.push_str(&v);
+                Ok(())
+            }
+            Event::Scalar(mut v, style, _, tag) => {
+                if !self.events.chunks.is_empty() {
+                    v = mem::take(&mut self.events.chunks) + &v;
+                }
+                let node = resolve_scalar(v, style, &tag);
+                let explicit = self.explicit_key(&node);
+                if let Position::Val { .. } = self.begin_event_node(explicit)? {
+                    write!(self.writer, " ")?;
+                }
+                self.emit_node(&node)?;
+                self.end_event_node(explicit)
+            }
+            Event::SequenceStart(_, tag) | Event::MappingStart(_, tag) => {
+                let position = self.begin_event_node(true)?;
+                self.events.frames.push(EventFrame {
+                    mapping,
+                    position,
+                    tag: application_tag(&tag),
+                    opened: false,
+                    entries: 0,
+                    key: None,
+                });
+                Ok(())
+            }
+            Event::SequenceEnd | Event::MappingEnd => {
+                let frame = match self.events.frames.pop() {
+                    Some(frame) if frame.mapping == mapping => frame,
+                    _ => return Err(EmitError::InvalidEvent("unmatched collection end")),
+                };
+                if frame.key.is_some() {
+                    return Err(EmitError::InvalidEvent("mapping ended before a value"));
+                }
+                if frame.opened {
+                    self.level -= 1;
+                } else {
+                    if let Position::Val { .. } = frame.position {
+                        write!(self.writer, " ")?;
+                    }
+                    if let Some(ref tag) = frame.tag {
+                        let tag = self.tag_text(tag);
+                        write!(self.writer, "{} ", tag)?;
+                    }
+                    self.writer.write_str(if mapping { "{}" } else { "[]" })?;
+                }
+                self.end_event_node(true)
+            }
+        }
+    }
+
+    // Write what precedes a node within its parent, returning where the node
+    // itself goes. `explicit` is whether the node is written after `?` if it
+    // is a key.
+    fn begin_event_node(&mut self, explicit: bool) -> Result<Position, EmitError> {
+        self.open_event_frame()?;
+        let frame = match self.events.frames.last() {
+            Some(frame) => frame,
+            None if !self.events.in_document => {
+                return Err(EmitError::InvalidEvent("node outside of a document"))
+            }
+            None if self.events.has_root => {
+                return Err(EmitError::InvalidEvent(
+                    "document has more than one root node",
+                ))
+            }
+            None => return Ok(Position::Node),
+        };
+        let (mapping, entries, key) = (frame.mapping, frame.entries, frame.key);
+        if let Some(complex) = key {
+            return Ok(Position::Val { inline: complex });
+        }
+        if entries > 0 {
+            self.write_break()?;
+            self.write_indent()?;
+        }
+        if !mapping {
+            write!(self.writer, "-")?;
+            Ok(Position::Val { inline: true })
+        } else if explicit {
+            write!(self.writer, "?")?;
+            Ok(Position::Val { inline: true })
+        } else {
+            Ok(Position::Node)
+        }
+    }
+
+    // Write the start of the innermost collection once it has a child.
+    fn open_event_frame(&mut self) -> EmitResult {
+        let (position, opened) = match self.events.frames.last() {
+            Some(frame) => (frame.position, frame.opened),
+            None => return Ok(()),
+        };
+        if opened {
+            return Ok(());
+        }
+        let tag = self.events.frames.last_mut().unwrap().tag.take();
+        let own_line = match (tag, position) {
+            (Some(tag), Position::Node) => {
+                let tag = self.tag_text(&tag);
+                write!(self.writer, "{}", tag)?;
+                true
+            }
+            (Some(tag), Position::Val { .. }) => {
+                let tag = self.tag_text(&tag);
+                write!(self.writer, " {}", tag)?;
+                true
+            }
+            (None, Position::Node) => false,
+            (None, Position::Val { inline }) => {
+                if inline && self.compact {
+                    write!(self.writer, " ")?;
+                    false
+                } else {
+                    true
+                }
+            }
+        };
+        if own_line {
+            self.write_break()?;
+            self.level += 1;
+            self.write_indent()?;
+            self.level -= 1;
+        }
+        self.level += 1;
+        self.events.frames.last_mut().unwrap().opened = true;
+        Ok(())
+    }
+
+    // Record a finished node in its parent.
+    fn end_event_node(&mut self, explicit: bool) -> EmitResult {
+        let frame = match self.events.frames.last_mut() {
+            Some(frame) => frame,
+            None => {
+                self.events.has_root = true;
+                return Ok(());
+            }
+        };
+        if !frame.mapping || frame.key.is_some() {
+            frame.entries += 1;
+            frame.key = None;
+            return Ok(());
+        }
+        frame.key = Some(explicit);
+        if explicit {
+            self.write_break()?;
+            self.write_indent()?;
+        }
+        write!(self.writer, ":")?;
+        Ok(())
+    }
+
+    /// Write a document to a UTF-8 file, replacing its contents.
+    pub fn dump_to_file<P: AsRef<Path>>(path: P, doc: &Yaml) -> Result<(), EncodeError> {
+        let path = path.as_ref();
+        let file = File::create(path).map_err(|err| with_path(err, path))?;
+        write_file(file, doc).map_err(|err| encode_error_with_path(err, path))
+    }
+
+    /// Like `dump_to_file`, but the document is written to a temporary file
+    /// next to `path` which is then renamed over it, so readers never observe
+    /// a partially written file.
+    pub fn dump_to_file_atomic<P: AsRef<Path>>(path: P, doc: &Yaml) -> Result<(), EncodeError> {
+        let path = path.as_ref();
+        let (file, tmp) =
+            create_temporary(path).map_err(|err| encode_error_with_path(err.into(), path))?;
+        let result =
+            write_file(file, doc).and_then(|()| fs::rename(&tmp, path).map_err(EncodeError::from));
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        result.map_err(|err| encode_error_with_path(err, path))
+    }
+
     pub fn dump(&mut self, doc: &Yaml) -> EmitResult {
         // write DocumentStart
-        writeln!(self.writer, "---")?;
-        self.level = -1;
-        self.emit_node(doc)
+        self.write_document_start()?;
+        self.write_break()?;
+        self.anchored.clear();
+        self.emit_node(doc)?;
+        self.write_document_end()
+    }
+
+    /// Write a document like `dump`, with the comments of its nodes. The
+    /// children of collections written in flow style, such as empty ones or
+    /// those chosen by `flow_leaves`, and of tagged collections, are written
+    /// without their comments.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::emitter::CommentedYaml;
+    /// use yaml_rust::{Yaml, YamlEmitter};
+    ///
+    /// let port = CommentedYaml::new(Yaml::Integer(80))
+    ///     .comment("The port to listen on.")
+    ///     .inline_comment("default");
+    /// let doc = CommentedYaml::hash(vec![(
+    ///     Yaml::from_str("server"),
+    ///     CommentedYaml::hash(vec![(Yaml::from_str("port"), port)]).inline_comment("generated"),
+    /// )]);
+    ///
+    /// let mut out = String::new();
+    /// YamlEmitter::new(&mut out).dump_commented(&doc).unwrap();
+    /// assert_eq!(out, "---\nserver: # generated\n  # The port to listen on.\n  port: 80 # default");
+    /// ```
+    pub fn dump_commented(&mut self, doc: &CommentedYaml) -> EmitResult {
+        self.write_document_start()?;
+        if let Some(ref text) = doc.inline {
+            self.write_inline_comment(text)?;
+        }
+        self.write_break()?;
+        self.anchored.clear();
+        self.write_comment_lines(&doc.above)?;
+        match doc.value {
+            Yaml::Array(ref v) => self.emit_array(v, Some(doc))?,
+            Yaml::Hash(ref h) => self.emit_hash(h, Some(doc))?,
+            ref node => self.emit_node(node)?,
+        }
+        self.write_document_end()
+    }
+
+    // Write `lines` as comments, each followed by a break and the indent.
+    fn write_comment_lines(&mut self, lines: &[String]) -> EmitResult {
+        for line in lines.iter().flat_map(|line| comment_lines(line)) {
+            let line = if self.strict_whitespace {
+                line.trim_end()
+            } else {
+                line
+            };
+            if line.is_empty() {
+                self.writer.write_str("#")?;
+            } else {
+                write!(self.writer, "# {}", line)?;
+            }
+            self.write_break()?;
+            self.write_indent()?;
+        }
+        Ok(())
+    }
+
+    fn write_inline_comment(&mut self, text: &str) -> EmitResult {
+        let text: Vec<&str> = comment_lines(text).collect();
+        let text = text.join(" ");
+        if !self.strict_whitespace {
+            write!(self.writer, " # {}", text)?;
+        } else if text.trim_end().is_empty() {
+            self.writer.write_str(" #")?;
+        } else {
+            write!(self.writer, " # {}", text.trim_end())?;
+        }
+        Ok(())
+    }
+
+    // An emitter writing to `writer` at the same point as this one.
+    fn fork<'b>(&self, writer: &'b mut dyn fmt::Write) -> YamlEmitter<'b>
+    where
+        'a: 'b,
+    {
+        YamlEmitter {
+            writer,
+            best_indent: self.best_indent,
+            compact: self.compact,
+            multiline_strings: self.multiline_strings,
+            explicit_keys: self.explicit_keys,
+            line_break: self.line_break,
+            flow_leaves: self.flow_leaves,
+            best_width: self.best_width,
+            anchors: self.anchors,
+            anchor_prefix: self.anchor_prefix.clone(),
+            anchor_names: self.anchor_names,
+            tag_handles: self.tag_handles.clone(),
+            banner: Vec::new(),
+            header: Vec::new(),
+            trailing_newline: false,
+            key_order: self.key_order.clone(),
+            float_format: self.float_format,
+            key_float_formats: self.key_float_formats.clone(),
+            strict_whitespace: self.strict_whitespace,
+            key_blank_lines: self.key_blank_lines,
+            document_blank_lines: self.document_blank_lines,
+            level: self.level,
+            prefix_width: self.prefix_width,
+            started: true,
+            events: EventState::default(),
+            anchored: self.anchored.clone(),
+        }
     }
 
     fn write_indent(&mut self) -> EmitResult {
@@ -188,29 +1418,34 @@ impl<'a> YamlEmitter<'a> {
         Ok(())
     }
 
-    fn emit_node(&mut self, node: &Yaml) -> EmitResult {
-        match *node {
-            Yaml::Array(ref v) => self.emit_array(v),
-            Yaml::Hash(ref h) => self.emit_hash(h),
-            Yaml::String(ref v) => {
-                if self.multiline_strings && v.contains('\n') {
-                    write!(self.writer, "|")?;
-                    self.level += 1;
-                    for line in v.lines() {
-                        writeln!(self.writer)?;
-                        self.write_indent()?;
-                        // It's literal text, so don't escape special chars!
-                        write!(self.writer, "{}", line)?;
-                    }
-                    self.level -= 1;
-                } else if need_quotes(v) {
-                    escape_str(self.writer, v)?;
-                } else {
-                    write!(self.writer, "{}", v)?;
+    fn emit_str(&mut self, v: &str) -> EmitResult {
+        if self.is_literal(v) {
+            write!(self.writer, "|")?;
+            self.level += 1;
+            for line in v.lines() {
+                self.write_break()?;
+                if !(self.strict_whitespace && line.is_empty()) {
+                    self.write_indent()?;
                 }
-
-                Ok(())
+                // It's literal text, so don't escape special chars!
+                write!(self.writer, "{}", line)?;
             }
+            self.level -= 1;
+        } else if need_quotes(v) {
+            escape_str(self.writer, v)?;
+        } else {
+            write!(self.writer, "{}", v)?;
+        }
+
+        Ok(())
+    }
+
+    fn emit_node(&mut self, node: &Yaml) -> EmitResult {
+        match *node {
+            Yaml::Array(ref v) => self.emit_array(v, None),
+            Yaml::Hash(ref h) => self.emit_hash(h, None),
+            Yaml::String(ref v) => self.emit_str(v),
+            Yaml::Interned(ref v) => self.emit_str(v),
             Yaml::Boolean(v) => {
                 if v {
                     self.writer.write_str("true")?;
@@ -224,58 +1459,111 @@ impl<'a> YamlEmitter<'a> {
                 Ok(())
             }
             Yaml::Real(ref v) => {
-                write!(self.writer, "{}", v)?;
+                self.writer.write_str(&self.float_format.real_text(v))?;
                 Ok(())
             }
             Yaml::Null | Yaml::BadValue => {
                 write!(self.writer, "~")?;
                 Ok(())
             }
-            // XXX(chenyh) Alias
-            _ => Ok(()),
+            Yaml::Tagged(ref tag, ref v) => {
+                let tag = self.tag_text(tag);
+                write!(self.writer, "{}", tag)?;
+                if is_block_collection(v) {
+                    self.write_break()?;
+                    self.level += 1;
+                    self.write_indent()?;
+                    self.level -= 1;
+                } else {
+                    write!(self.writer, " ")?;
+                }
+                self.emit_node(v)
+            }
+            Yaml::Alias(id) => match self.anchors.and_then(|anchors| anchors.get(&id)) {
+                Some(node) if self.anchored.insert(id) => {
+                    write!(self.writer, "&{}", self.anchor_name(id)?)?;
+                    if is_block_collection(node) {
+                        self.write_break()?;
+                        self.level += 1;
+                        self.write_indent()?;
+                        self.level -= 1;
+                    } else {
+                        write!(self.writer, " ")?;
+                    }
+                    self.emit_node(node)
+                }
+                Some(_) => {
+                    write!(self.writer, "*{}", self.anchor_name(id)?)?;
+                    Ok(())
+                }
+                None => Ok(()),
+            },
         }
     }
 
-    fn emit_array(&mut self, v: &[Yaml]) -> EmitResult {
+    fn emit_array(&mut self, v: &[Yaml], comments: Option<&CommentedYaml>) -> EmitResult {
         if v.is_empty() {
             write!(self.writer, "[]")?;
         } else {
             self.level += 1;
             for (cnt, x) in v.iter().enumerate() {
                 if cnt > 0 {
-                    writeln!(self.writer)?;
+                    self.write_break()?;
                     self.write_indent()?;
                 }
+                let comments = CommentedYaml::child(comments, cnt);
+                if let Some(comments) = comments {
+                    self.write_comment_lines(&comments.above)?;
+                }
                 write!(self.writer, "-")?;
-                self.emit_val(true, x)?;
+                self.prefix_width = 1;
+                self.emit_val(true, x, comments)?;
             }
             self.level -= 1;
         }
         Ok(())
     }
 
-    fn emit_hash(&mut self, h: &Hash) -> EmitResult {
+    fn emit_hash(&mut self, h: &Hash, comments: Option<&CommentedYaml>) -> EmitResult {
         if h.is_empty() {
             self.writer.write_str("{}")?;
         } else {
             self.level += 1;
-            for (cnt, (k, v)) in h.iter().enumerate() {
-                let complex_key = matches!(*k, Yaml::Hash(_) | Yaml::Array(_));
+            for (cnt, (i, (k, v))) in self.key_order.sort(h).into_iter().enumerate() {
+                let complex_key = self.explicit_key(k);
+                let comments = CommentedYaml::child(comments, i);
                 if cnt > 0 {
-                    writeln!(self.writer)?;
+                    self.write_break()?;
+                    let mut blank_lines = comments.map_or(0, |c| c.blank_lines);
+                    if self.level == 0 {
+                        blank_lines = blank_lines.max(self.key_blank_lines);
+                    }
+                    for _ in 0..blank_lines {
+                        self.write_break()?;
+                    }
                     self.write_indent()?;
                 }
+                if let Some(comments) = comments {
+                    self.write_comment_lines(&comments.above)?;
+                }
+                let format = self.value_float_format(k);
                 if complex_key {
                     write!(self.writer, "?")?;
-                    self.emit_val(true, k)?;
-                    writeln!(self.writer)?;
+                    self.prefix_width = 1;
+                    self.emit_val(true, k, None)?;
+                    self.write_break()?;
                     self.write_indent()?;
                     write!(self.writer, ":")?;
-                    self.emit_val(true, v)?;
+                    self.prefix_width = 1;
+                    let format = mem::replace(&mut self.float_format, format);
+                    self.emit_val(true, v, comments)?;
+                    self.float_format = format;
                 } else {
-                    self.emit_node(k)?;
+                    self.emit_key(k)?;
                     write!(self.writer, ":")?;
-                    self.emit_val(false, v)?;
+                    let format = mem::replace(&mut self.float_format, format);
+                    self.emit_val(false, v, comments)?;
+                    self.float_format = format;
                 }
             }
             self.level -= 1;
@@ -283,33 +1571,79 @@ impl<'a> YamlEmitter<'a> {
         Ok(())
     }
 
+    // Write a simple key, measuring it for the width check of `flow_leaf`
+    // if flow leaves are written.
+    fn emit_key(&mut self, k: &Yaml) -> EmitResult {
+        if self.flow_leaves == 0 {
+            return self.emit_node(k);
+        }
+        let mut out = String::new();
+        let mut emitter = self.fork(&mut out);
+        emitter.emit_node(k)?;
+        self.anchored = mem::take(&mut emitter.anchored);
+        self.writer.write_str(&out)?;
+        self.prefix_width = out.chars().count() + 1;
+        Ok(())
+    }
+
     /// Emit a yaml as a hash or array value: i.e., which should appear
     /// following a ":" or "-", either after a space, or on a new line.
     /// If `inline` is true, then the preceding characters are distinct
     /// and short enough to respect the compact flag.
-    fn emit_val(&mut self, inline: bool, val: &Yaml) -> EmitResult {
+    fn emit_val(
+        &mut self,
+        inline: bool,
+        val: &Yaml,
+        comments: Option<&CommentedYaml>,
+    ) -> EmitResult {
+        let text = match comments.and_then(|c| c.inline.as_deref()) {
+            Some(text) => text,
+            None => return self.emit_val_body(inline, val, comments),
+        };
+        // the comment ends the first line the value is written on
+        let mut out = String::new();
+        let mut emitter = self.fork(&mut out);
+        emitter.emit_val_body(inline, val, comments)?;
+        self.anchored = mem::take(&mut emitter.anchored);
+        let (first, rest) = out.split_at(out.find(self.line_break.as_str()).unwrap_or(out.len()));
+        self.writer.write_str(first)?;
+        self.write_inline_comment(text)?;
+        self.writer.write_str(rest)?;
+        Ok(())
+    }
+
+    fn emit_val_body(
+        &mut self,
+        inline: bool,
+        val: &Yaml,
+        comments: Option<&CommentedYaml>,
+    ) -> EmitResult {
+        if let Some(flow) = self.flow_leaf(val)? {
+            write!(self.writer, " {}", flow)?;
+            return Ok(());
+        }
         match *val {
             Yaml::Array(ref v) => {
                 if (inline && self.compact) || v.is_empty() {
                     write!(self.writer, " ")?;
                 } else {
-                    writeln!(self.writer)?;
+                    self.write_break()?;
                     self.level += 1;
                     self.write_indent()?;
                     self.level -= 1;
                 }
-                self.emit_array(v)
+                self.emit_array(v, comments)
             }
             Yaml::Hash(ref h) => {
                 if (inline && self.compact) || h.is_empty() {
                     write!(self.writer, " ")?;
                 } else {
-                    writeln!(self.writer)?;
+                    self.write_break()?;
                     self.level += 1;
                     self.write_indent()?;
                     self.level -= 1;
                 }
-                self.emit_hash(h)
+                self.emit_hash(h, comments)
             }
             _ => {
                 write!(self.writer, " ")?;
@@ -317,6 +1651,387 @@ impl<'a> YamlEmitter<'a> {
             }
         }
     }
+
+    /// Render `val` in flow style if `flow_leaves` allows it.
+    fn flow_leaf(&mut self, val: &Yaml) -> Result<Option<String>, EmitError> {
+        let max = self.flow_leaves;
+        let is_leaf = |y: &Yaml| match *y {
+            Yaml::Array(_) | Yaml::Hash(_) | Yaml::Alias(_) => false,
+            Yaml::Tagged(_, ref v) => !is_block_collection(v),
+            _ => true,
+        };
+        let mut out = String::new();
+        let anchored = {
+            let mut emitter = self.fork(&mut out);
+            // multiline strings are escaped, as literal blocks can't be used
+            emitter.multiline_strings = false;
+            match *val {
+                Yaml::Array(ref v) if !v.is_empty() && v.len() <= max && v.iter().all(is_leaf) => {
+                    emitter.writer.write_str("[")?;
+                    for (cnt, x) in v.iter().enumerate() {
+                        if cnt > 0 {
+                            emitter.writer.write_str(", ")?;
+                        }
+                        emitter.emit_node(x)?;
+                    }
+                    emitter.writer.write_str("]")?;
+                }
+                Yaml::Hash(ref h)
+                    if !h.is_empty()
+                        && h.len() <= max
+                        && h.iter().all(|(k, v)| is_leaf(k) && is_leaf(v)) =>
+                {
+                    emitter.writer.write_str("{")?;
+                    for (cnt, (_, (k, v))) in self.key_order.sort(h).into_iter().enumerate() {
+                        if cnt > 0 {
+                            emitter.writer.write_str(", ")?;
+                        }
+                        emitter.emit_node(k)?;
+                        emitter.writer.write_str(": ")?;
+                        emitter.float_format = self.value_float_format(k);
+                        emitter.emit_node(v)?;
+                    }
+                    emitter.writer.write_str("}")?;
+                }
+                _ => return Ok(None),
+            }
+            emitter.anchored
+        };
+        // the value follows its indentation, prefix and a space
+        let column = self.level.max(0) as usize * self.best_indent + self.prefix_width + 1;
+        if column + out.chars().count() > self.best_width {
+            return Ok(None);
+        }
+        self.anchored = anchored;
+        Ok(Some(out))
+    }
+}
+
+#[derive(Debug)]
+pub enum EncodeError {
+    IO(io::Error),
+    Emit(EmitError),
+}
+
+impl Error for EncodeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            EncodeError::IO(ref err) => Some(err),
+            EncodeError::Emit(ref err) => Some(err),
+        }
+    }
+}
+
+impl Display for EncodeError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EncodeError::IO(ref err) => Display::fmt(err, formatter),
+            EncodeError::Emit(ref err) => Display::fmt(err, formatter),
+        }
+    }
+}
+
+impl From<io::Error> for EncodeError {
+    fn from(error: io::Error) -> Self {
+        EncodeError::IO(error)
+    }
+}
+
+/// Writes documents to a byte stream in a chosen encoding. This is the
+/// counterpart of `YamlDecoder`.
+///
+/// UTF-16 and UTF-32 output starts with a byte order mark by default, UTF-8
+/// output does not.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate yaml_rust_davvid as yaml_rust;
+/// use yaml_rust::emitter::YamlEncoder;
+/// use yaml_rust::yaml::{YamlDecoder, YamlEncoding};
+/// use yaml_rust::YamlLoader;
+///
+/// let docs = YamlLoader::load_from_str("a: 1").unwrap();
+/// let mut bytes = Vec::new();
+/// YamlEncoder::write(&mut bytes)
+///     .encoding(YamlEncoding::Utf16Le)
+///     .encode(&docs[0])
+///     .unwrap();
+/// assert!(bytes.starts_with(b"\xff\xfe-\0-\0-\0"));
+///
+/// let decoded = YamlDecoder::read(&bytes as &[u8]).decode().unwrap();
+/// assert_eq!(decoded, docs);
+/// ```
+pub struct YamlEncoder<W: io::Write> {
+    writer: W,
+    encoding: YamlEncoding,
+    bom: Option<bool>,
+    started: bool,
+}
+
+impl<W: io::Write> YamlEncoder<W> {
+    pub fn write(writer: W) -> YamlEncoder<W> {
+        YamlEncoder {
+            writer,
+            encoding: YamlEncoding::Utf8,
+            bom: None,
+            started: false,
+        }
+    }
+
+    /// Set the output encoding. Defaults to UTF-8.
+    pub fn encoding(&mut self, encoding: YamlEncoding) -> &mut Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Write a byte order mark at the start of the stream, or not.
+    pub fn bom(&mut self, bom: bool) -> &mut Self {
+        self.bom = Some(bom);
+        self
+    }
+
+    /// Encode a document. Each call appends another document to the stream.
+    pub fn encode(&mut self, doc: &Yaml) -> Result<(), EncodeError> {
+        self.encode_with(doc, |_| {})
+    }
+
+    /// Encode a document using an emitter set up by `configure`.
+    pub fn encode_with<F>(&mut self, doc: &Yaml, configure: F) -> Result<(), EncodeError>
+    where
+        F: FnOnce(&mut YamlEmitter),
+    {
+        let mut writer = EncodingWriter {
+            writer: &mut self.writer,
+            encoder: self.encoding.raw_encoder(),
+            buffer: Vec::new(),
+            error: None,
+        };
+        let bom = self.bom.unwrap_or(self.encoding != YamlEncoding::Utf8);
+        let result = (|| {
+            if bom && !self.started {
+                fmt::Write::write_char(&mut writer, '\u{feff}')?;
+            }
+            let mut emitter = YamlEmitter::new(&mut writer);
+            configure(&mut emitter);
+            emitter.dump(doc)?;
+            let line_break = emitter.get_line_break();
+            fmt::Write::write_str(&mut writer, line_break.as_str())?;
+            Ok(())
+        })();
+        self.started = true;
+        // A formatting error is how the writer reports a failed write.
+        match (result, writer.error) {
+            (_, Some(err)) => Err(EncodeError::IO(err)),
+            (Err(err), None) => Err(EncodeError::Emit(err)),
+            (Ok(()), None) => Ok(()),
+        }
+    }
+
+    /// Consume the encoder, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Writes a stream of documents to an `io::Write` one at a time, such as a
+/// bundle of manifests. Each document can be preceded by comments, and every
+/// document by the same directives. The writer is flushed after each
+/// document, so output appears as it is produced.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate yaml_rust_davvid as yaml_rust;
+/// use yaml_rust::emitter::DocumentWriter;
+/// use yaml_rust::scanner::TScalarStyle;
+/// use yaml_rust::{Event, Yaml};
+///
+/// let mut writer = DocumentWriter::new(Vec::new());
+/// writer.directive("%YAML 1.2");
+/// writer.comment("first").write(&Yaml::Integer(1)).unwrap();
+/// writer
+///     .write_events(vec![
+///         Event::sequence_start(),
+///         Event::scalar("a", TScalarStyle::Plain),
+///         Event::SequenceEnd,
+///     ])
+///     .unwrap();
+/// let out = String::from_utf8(writer.into_inner()).unwrap();
+/// assert_eq!(out, "# first\n%YAML 1.2\n---\n1\n...\n%YAML 1.2\n---\n- a\n");
+/// ```
+pub struct DocumentWriter<W: io::Write> {
+    writer: W,
+    directives: Vec<String>,
+    comments: Vec<String>,
+    line_break: LineBreak,
+    documents: usize,
+}
+
+impl<W: io::Write> DocumentWriter<W> {
+    pub fn new(writer: W) -> DocumentWriter<W> {
+        DocumentWriter {
+            writer,
+            directives: Vec::new(),
+            comments: Vec::new(),
+            line_break: LineBreak::default(),
+            documents: 0,
+        }
+    }
+
+    /// Add a directive line, such as `%YAML 1.2`, written before every
+    /// document. Documents are then ended with `...` so the directives of the
+    /// next one can follow.
+    pub fn directive(&mut self, directive: &str) -> &mut Self {
+        self.directives.push(directive.to_owned());
+        self
+    }
+
+    /// Add a comment before the next document. Each line of `comment` is
+    /// written as a separate comment line.
+    pub fn comment(&mut self, comment: &str) -> &mut Self {
+        self.comments
+            .extend(comment_lines(comment).map(str::to_owned));
+        self
+    }
+
+    /// Set the line break used for the output. Defaults to `LineBreak::Lf`.
+    pub fn line_break(&mut self, line_break: LineBreak) -> &mut Self {
+        self.line_break = line_break;
+        self
+    }
+
+    /// The number of documents written so far.
+    pub fn documents(&self) -> usize {
+        self.documents
+    }
+
+    /// Write a document.
+    pub fn write(&mut self, doc: &Yaml) -> Result<(), EncodeError> {
+        self.write_document(|emitter| emitter.dump(doc))
+    }
+
+    /// Write a document given as the events of its root node, as accepted
+    /// by `YamlEmitter::emit_event`. Events are written as they are
+    /// produced, so the document is never held in memory.
+    pub fn write_events<I>(&mut self, events: I) -> Result<(), EncodeError>
+    where
+        I: IntoIterator<Item = Event>,
+    {
+        self.write_document(|emitter| {
+            emitter.emit_event(Event::DocumentStart)?;
+            for event in events {
+                emitter.emit_event(event)?;
+            }
+            emitter.emit_event(Event::DocumentEnd)
+        })
+    }
+
+    fn write_document<F>(&mut self, emit: F) -> Result<(), EncodeError>
+    where
+        F: FnOnce(&mut YamlEmitter) -> EmitResult,
+    {
+        let line_break = self.line_break.as_str();
+        let mut writer = EncodingWriter {
+            writer: &mut self.writer,
+            encoder: YamlEncoding::Utf8.raw_encoder(),
+            buffer: Vec::new(),
+            error: None,
+        };
+        let result = (|| {
+            use std::fmt::Write;
+            if self.documents > 0 && !self.directives.is_empty() {
+                write!(writer, "...{}", line_break)?;
+            }
+            for comment in self.comments.drain(..) {
+                if comment.is_empty() {
+                    write!(writer, "#{}", line_break)?;
+                } else {
+                    write!(writer, "# {}{}", comment, line_break)?;
+                }
+            }
+            for directive in &self.directives {
+                write!(writer, "{}{}", directive, line_break)?;
+            }
+            let mut emitter = YamlEmitter::new(&mut writer);
+            emitter.line_break(self.line_break);
+            emit(&mut emitter)?;
+            writer.write_str(line_break)?;
+            Ok(())
+        })();
+        self.documents += 1;
+        match (result, writer.error) {
+            (_, Some(err)) => Err(EncodeError::IO(err)),
+            (Err(err), None) => Err(EncodeError::Emit(err)),
+            (Ok(()), None) => Ok(self.writer.flush()?),
+        }
+    }
+
+    /// Consume the writer, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+fn write_file(file: File, doc: &Yaml) -> Result<(), EncodeError> {
+    let mut encoder = YamlEncoder::write(io::BufWriter::new(file));
+    encoder.encode(doc)?;
+    let file = encoder
+        .into_inner()
+        .into_inner()
+        .map_err(|err| err.into_error())?;
+    file.sync_all()?;
+    Ok(())
+}
+
+// Create a hidden file in the same directory, so renaming it over `path` is
+// atomic. Each call creates a new file, even for the same path in several
+// threads at once.
+fn create_temporary(path: &Path) -> io::Result<(File, PathBuf)> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let name = path.file_name().map(|name| name.to_string_lossy());
+    loop {
+        let tmp = path.with_file_name(format!(
+            ".{}.{}.{}.tmp",
+            name.as_deref().unwrap_or_default(),
+            std::process::id(),
+            COUNTER.fetch_add(1, atomic::Ordering::Relaxed)
+        ));
+        match OpenOptions::new().write(true).create_new(true).open(&tmp) {
+            Ok(file) => return Ok((file, tmp)),
+            // left behind by an earlier process with the same id
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+fn encode_error_with_path(err: EncodeError, path: &Path) -> EncodeError {
+    match err {
+        EncodeError::IO(err) => EncodeError::IO(with_path(err, path)),
+        err => err,
+    }
+}
+
+/// Adapts an `io::Write` to the `fmt::Write` the emitter expects, encoding
+/// text as it is written.
+struct EncodingWriter<'w, W> {
+    writer: &'w mut W,
+    encoder: Box<dyn RawEncoder>,
+    buffer: Vec<u8>,
+    error: Option<io::Error>,
+}
+
+impl<'w, W: io::Write> fmt::Write for EncodingWriter<'w, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.buffer.clear();
+        // Every Unicode string is representable in the supported encodings.
+        self.encoder.raw_feed(s, &mut self.buffer);
+        self.writer.write_all(&self.buffer).map_err(|err| {
+            self.error = Some(err);
+            fmt::Error
+        })
+    }
 }
 
 /// Check if the string requires quoting.
@@ -333,6 +2048,67 @@ impl<'a> YamlEmitter<'a> {
 /// * When the string is null or ~ (otherwise, it would be considered as a null value);
 /// * When the string looks like a number, such as integers (e.g. 2, 14, etc.), floats (e.g. 2.6, 14.9) and exponential numbers (e.g. 12e7, etc.) (otherwise, it would be treated as a numeric value);
 /// * When the string looks like a date (e.g. 2014-12-31) (otherwise it would be automatically converted into a Unix timestamp).
+// Whether `node` is emitted in block style, starting on a line of its own.
+fn is_block_collection(node: &Yaml) -> bool {
+    match *node {
+        Yaml::Array(ref v) => !v.is_empty(),
+        Yaml::Hash(ref h) => !h.is_empty(),
+        _ => false,
+    }
+}
+
+// The lines of comment text as `str::lines` splits them, but at every
+// character that ends a line in YAML 1.2 or 1.1, so that no part of a
+// comment is read back as content.
+fn comment_lines(text: &str) -> impl Iterator<Item = &str> {
+    let breaks = ['\n', '\r', '\u{85}', '\u{2028}', '\u{2029}'];
+    let text = text.strip_suffix("\r\n").unwrap_or(text);
+    let text = text.strip_suffix(breaks).unwrap_or(text);
+    text.split("\r\n").flat_map(move |line| line.split(breaks))
+}
+
+// The text to write for a `Yaml::Real`. Reals that would load back as
+// an integer or a string, such as `1` or `inf` from formatting an `f64`,
+// are rewritten as `1.0` and `.inf`; other text, such as an integer too
+// large for an `i64`, is written as it is.
+fn real_text(v: &str) -> Cow<'_, str> {
+    match parse_f64(v) {
+        Some(f) if !f.is_finite() || resolve_plain(v, Schema::Core) == ScalarType::Integer => {
+            Cow::Owned(format_real(f))
+        }
+        _ => Cow::Borrowed(v),
+    }
+}
+
+/// Whether `name` can be written as an anchor, `&name`, and an alias,
+/// `*name`. The YAML spec allows any printable characters other than
+/// spaces, line breaks, the byte order mark and the flow indicators
+/// `,[]{}`. Names of letters, digits, `_` and `-` are also read by parsers
+/// that allow fewer characters, such as this crate's.
+pub fn is_valid_anchor(name: &str) -> bool {
+    !name.is_empty()
+        && name.chars().all(|c| {
+            matches!(c,
+                '\x21'..='\x7e' | '\u{85}' | '\u{a0}'..='\u{d7ff}' | '\u{e000}'..='\u{fffd}' | '\u{10000}'..)
+                && !matches!(c, ',' | '[' | ']' | '{' | '}' | '\u{feff}')
+        })
+}
+
+// The longest an implicit key may be, in characters as written.
+const MAX_IMPLICIT_KEY_LEN: usize = 1024;
+
+// The number of characters `string` takes when written as a scalar.
+fn written_len(string: &str) -> usize {
+    if need_quotes(string) {
+        let mut quoted = String::new();
+        // writing to a String can't fail
+        let _ = escape_str(&mut quoted, string);
+        quoted.chars().count()
+    } else {
+        string.chars().count()
+    }
+}
+
 fn need_quotes(string: &str) -> bool {
     fn need_quotes_spaces(string: &str) -> bool {
         string.starts_with(' ') || string.ends_with(' ')
@@ -386,6 +2162,8 @@ fn need_quotes(string: &str) -> bool {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::{LineBreaks, ScannerConfig, TScalarStyle};
     use crate::YamlLoader;
 
     #[test]
@@ -457,6 +2235,298 @@ products:
         assert_eq!(doc, doc_new);
     }
 
+    #[test]
+    fn test_emit_explicit_keys() {
+        let long = "k".repeat(1100);
+        // 600 characters written as 1202
+        let quoted = "\"".repeat(600);
+        let mut h = Hash::new();
+        h.insert(Yaml::String("a\nb\n".to_owned()), Yaml::Integer(1));
+        h.insert(Yaml::String(long.clone()), Yaml::Integer(2));
+        h.insert(Yaml::String(quoted.clone()), Yaml::Integer(3));
+        h.insert(Yaml::String("x".to_owned()), Yaml::Integer(4));
+        let doc = Yaml::Hash(h);
+        let dump = |multiline_strings, explicit_keys| {
+            let mut writer = String::new();
+            let mut emitter = YamlEmitter::new(&mut writer);
+            emitter.multiline_strings(multiline_strings);
+            emitter.explicit_keys(explicit_keys);
+            emitter.dump(&doc).unwrap();
+            writer
+        };
+
+        let writer = dump(false, false);
+        assert!(writer.contains(&format!("\n? {}\n: 2\n", long)));
+        assert!(writer.contains("\n? \"\\\""));
+        assert!(writer.ends_with("\nx: 4"));
+        let writer = dump(true, false);
+        assert!(writer.starts_with("---\n? |\n  a\n  b\n: 1\n"));
+        let writer = dump(true, true);
+        assert!(writer.ends_with("\n? x\n: 4"));
+
+        for (multiline_strings, explicit_keys) in [(false, false), (true, false), (true, true)] {
+            let writer = dump(multiline_strings, explicit_keys);
+            assert_eq!(YamlLoader::load_from_str(&writer).unwrap()[0], doc);
+            // events are written the same way
+            let mut events = String::new();
+            let mut emitter = YamlEmitter::new(&mut events);
+            emitter.multiline_strings(multiline_strings);
+            emitter.explicit_keys(explicit_keys);
+            for event in Parser::new(writer.chars()) {
+                emitter.emit_event(event.unwrap().0).unwrap();
+            }
+            assert_eq!(events, writer);
+        }
+    }
+
+    #[test]
+    fn test_emit_nested_complex_keys() {
+        let s = "? {? [a, {b: c}]: d, e: f}\n: 1\n? [[x], {y: z}]\n: 2\n? !t {k: v}\n: 3\n";
+        let doc = &YamlLoader::load_from_str(s).unwrap()[0];
+        let mut writer = String::new();
+        YamlEmitter::new(&mut writer).dump(doc).unwrap();
+        assert_eq!(
+            writer,
+            "---
+? ? - a
+    - b: c
+  : d
+  e: f
+: 1
+? - - x
+  - y: z
+: 2
+? !t
+  k: v
+: 3"
+        );
+        assert_eq!(&YamlLoader::load_from_str(&writer).unwrap()[0], doc);
+
+        let mut writer = String::new();
+        let mut emitter = YamlEmitter::new(&mut writer);
+        emitter.compact(false);
+        emitter.dump(doc).unwrap();
+        assert_eq!(&YamlLoader::load_from_str(&writer).unwrap()[0], doc);
+    }
+
+    #[test]
+    fn test_emit_anchored_and_tagged_keys() {
+        let mut anchors = BTreeMap::new();
+        anchors.insert(1, YamlLoader::load_from_str("a: 1").unwrap().remove(0));
+        // 1022 characters are short enough for a key without its tag
+        let long = Yaml::String("k".repeat(1022));
+        let mut h = Hash::new();
+        h.insert(Yaml::Alias(1), Yaml::Integer(1));
+        h.insert(Yaml::from_str("b"), Yaml::Alias(1));
+        h.insert(
+            Yaml::Tagged(Tag::new("!", "t"), Box::new(long)),
+            Yaml::Integer(2),
+        );
+        let mut writer = String::new();
+        let mut emitter = YamlEmitter::new(&mut writer);
+        emitter.anchors(&anchors);
+        emitter.dump(&Yaml::Hash(h)).unwrap();
+        assert!(writer.starts_with("---\n? &a1\n  a: 1\n: 1\nb: *a1\n? !t kkk"));
+        assert!(writer.ends_with("\n: 2"));
+        let doc = &YamlLoader::load_from_str(&writer).unwrap()[0];
+        assert_eq!(doc.as_hash().unwrap().len(), 3);
+        assert_eq!(doc["b"]["a"].as_i64(), Some(1));
+    }
+
+    #[test]
+    fn test_anchor_names() {
+        assert!(is_valid_anchor("a1"));
+        assert!(is_valid_anchor("k8s:pod/web.1"));
+        assert!(is_valid_anchor("名前"));
+        for name in [
+            "",
+            "a b",
+            "a,b",
+            "[a]",
+            "{a}",
+            "a\tb",
+            "a\nb",
+            "\u{feff}a",
+            "\u{7}",
+        ] {
+            assert!(!is_valid_anchor(name), "{:?}", name);
+        }
+
+        let doc = &YamlLoader::load_from_str("- {a: 1}\n- {a: 1}\n- [b]\n- [b]").unwrap()[0];
+        let shared = doc.dedup_subtrees();
+        let dump = |prefix: &str, names: &BTreeMap<usize, String>| {
+            let mut out = String::new();
+            let mut emitter = YamlEmitter::new(&mut out);
+            emitter.anchors(&shared.anchors);
+            emitter.anchor_prefix(prefix);
+            emitter.anchor_names(names);
+            emitter.dump(&shared.doc).map(|_| out)
+        };
+        let mut names = BTreeMap::new();
+        let out = dump("node_", &names).unwrap();
+        assert_eq!(
+            out,
+            "---\n- &node_1\n  a: 1\n- *node_1\n- &node_2\n  - b\n- *node_2"
+        );
+        assert_eq!(&YamlLoader::load_from_str(&out).unwrap()[0], doc);
+        names.insert(2, "list".to_owned());
+        let out = dump("node_", &names).unwrap();
+        assert!(out.ends_with("- &list\n  - b\n- *list"));
+        assert_eq!(
+            dump("", &BTreeMap::new()).unwrap(),
+            dump("", &names).unwrap().replace("list", "2")
+        );
+
+        names.insert(1, "two words".to_owned());
+        assert_eq!(dump("a", &names), Err(EmitError::InvalidAnchor(1)));
+        assert_eq!(
+            dump("a[", &BTreeMap::new()),
+            Err(EmitError::InvalidAnchor(1))
+        );
+        assert_eq!(
+            EmitError::InvalidAnchor(1).to_string(),
+            "invalid anchor name for alias 1"
+        );
+    }
+
+    #[test]
+    fn test_strict_whitespace() {
+        let s =
+            "a: [[1, 2], {}]\nb: {c: [{d: 1}]}\n? [k]\n: v\ne: \"x\\n\\ny\\n\"\nf: \"x\\n  \\n\"\n";
+        let doc = &YamlLoader::load_from_str(s).unwrap()[0];
+        for compact in [true, false] {
+            let mut out = String::new();
+            let mut emitter = YamlEmitter::new(&mut out);
+            emitter.compact(compact);
+            emitter.multiline_strings(true);
+            emitter.strict_whitespace(true);
+            emitter.dump(doc).unwrap();
+            let commented = CommentedYaml::hash(vec![(
+                Yaml::from_str("a"),
+                CommentedYaml::new(Yaml::Null)
+                    .comment("x \n\t\ny")
+                    .inline_comment(" "),
+            )]);
+            emitter.dump_commented(&commented).unwrap();
+            assert!(
+                out.ends_with("a: ~ #\n") && !out.ends_with("\n\n"),
+                "{}",
+                out
+            );
+            for line in out.lines() {
+                assert!(!line.ends_with([' ', '\t']), "{:?} in {}", line, out);
+            }
+            assert!(out.contains("e: |\n  x\n\n  y\n"));
+            assert!(out.contains("f: \"x\\n  \\n\"\n"));
+            let docs = YamlLoader::load_from_str(&out).unwrap();
+            assert_eq!(docs[0]["f"], doc["f"]);
+        }
+
+        let mut out = String::new();
+        let mut emitter = YamlEmitter::new(&mut out);
+        emitter.blank_lines(2, 1);
+        emitter.trailing_newline(true);
+        for s in ["{a: {x: 1, y: 2}, b: [{c: 1, d: 2}]}", "[1]"] {
+            emitter
+                .dump(&YamlLoader::load_from_str(s).unwrap()[0])
+                .unwrap();
+        }
+        assert_eq!(
+            out,
+            "---\na:\n  x: 1\n  y: 2\n\n\nb:\n  - c: 1\n    d: 2\n\n---\n- 1\n"
+        );
+    }
+
+    #[test]
+    fn test_commented_blank_lines() {
+        let source = "# head\na: 1\n\n\n# b\n\nb:\n  c: |\n    x\n\n    # not a comment\n  d: 2\n\ne: [1]\n---\n\n- 1\n";
+        let docs = CommentedYaml::load_from_str(source).unwrap();
+        let mut out = String::new();
+        let mut emitter = YamlEmitter::new(&mut out);
+        emitter.trailing_newline(true);
+        emitter.multiline_strings(true);
+        emitter.strict_whitespace(true);
+        for doc in &docs {
+            emitter.dump_commented(doc).unwrap();
+        }
+        assert_eq!(
+            out,
+            "---\n# head\na: 1\n\n\n# b\nb:\n  c: |\n    x\n\n    # not a comment\n  d: 2\n\ne:\n  - 1\n---\n- 1\n"
+        );
+        let loaded = YamlLoader::load_from_str(&out).unwrap();
+        assert_eq!(loaded, YamlLoader::load_from_str(source).unwrap());
+
+        // a lone carriage return ends a line too
+        let docs = CommentedYaml::load_from_str("a: 1\r\r# b\r\rb: 2\r\nc: 3\r").unwrap();
+        let entries = &docs[0].children;
+        assert_eq!(
+            entries.iter().map(|c| c.blank_lines).collect::<Vec<_>>(),
+            [0, 1, 0]
+        );
+        assert_eq!(entries[1].above, ["b"]);
+        for s in ["\r?", "a: 1\r\r\rb: 2\r", "\r\r\n?\r: x"] {
+            CommentedYaml::load_from_str(s).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_float_format() {
+        let format = |notation, precision, trim_zeros| FloatFormat {
+            precision,
+            notation,
+            trim_zeros,
+        };
+        for (format, v, text) in [
+            (format(FloatNotation::Auto, None, false), 1e20, "1e20"),
+            (format(FloatNotation::Auto, Some(2), false), 2.0, "2.00"),
+            (format(FloatNotation::Auto, Some(2), true), 2.0, "2.0"),
+            (format(FloatNotation::Auto, Some(0), false), 2.4, "2."),
+            (format(FloatNotation::Auto, Some(3), true), 0.125, "0.125"),
+            (
+                format(FloatNotation::Decimal, None, false),
+                1e20,
+                "100000000000000000000.0",
+            ),
+            (format(FloatNotation::Decimal, None, false), -0.25, "-0.25"),
+            (format(FloatNotation::Scientific, None, false), 1.0, "1e0"),
+            (
+                format(FloatNotation::Scientific, Some(2), false),
+                0.0015,
+                "1.50e-3",
+            ),
+            (
+                format(FloatNotation::Scientific, Some(2), true),
+                0.0015,
+                "1.5e-3",
+            ),
+            (
+                format(FloatNotation::Scientific, Some(2), true),
+                f64::NAN,
+                ".nan",
+            ),
+        ] {
+            let text_of = format.format(v);
+            assert_eq!(text_of, text, "{:?} {}", format, v);
+            let back = &YamlLoader::load_from_str(&text_of).unwrap()[0];
+            assert!(matches!(back, Yaml::Real(_)), "{}", text_of);
+        }
+
+        let doc = &YamlLoader::load_from_str(
+            "a: 1.5\nb: {c: 0.25, d: [2.0]}\nflow: [{b: 1.0}]\ntext: 1.50",
+        )
+        .unwrap()[0];
+        let mut out = String::new();
+        let mut emitter = YamlEmitter::new(&mut out);
+        emitter.flow_leaves(2);
+        emitter.key_float_format("b", format(FloatNotation::Auto, Some(3), false));
+        emitter.key_float_format("d", format(FloatNotation::Scientific, None, false));
+        emitter.dump(doc).unwrap();
+        assert_eq!(
+            out,
+            "---\na: 1.5\nb:\n  c: 0.250\n  d: [2e0]\nflow:\n  - {b: 1.000}\ntext: 1.50"
+        );
+    }
+
     #[test]
     fn test_emit_avoid_quotes() {
         let s = r#"---
@@ -659,6 +2729,426 @@ a:
         assert_eq!(s, writer);
     }
 
+    #[test]
+    fn test_encoder() {
+        let docs = YamlLoader::load_from_str("a: 你好\nb: [1, 2]").unwrap();
+        let expected = "---\na: 你好\nb:\n  - 1\n  - 2\n";
+        let encodings = [
+            (YamlEncoding::Utf8, 0),
+            (YamlEncoding::Utf16Le, 2),
+            (YamlEncoding::Utf16Be, 2),
+            (YamlEncoding::Utf32Le, 4),
+            (YamlEncoding::Utf32Be, 4),
+        ];
+        for (encoding, bom_len) in encodings {
+            let mut encoder = YamlEncoder::write(Vec::new());
+            encoder.encoding(encoding).encode(&docs[0]).unwrap();
+            let bytes = encoder.into_inner();
+            assert_eq!(YamlEncoding::detect(&bytes), (encoding, bom_len));
+            let mut decoder = crate::yaml::YamlDecoder::read(&bytes as &[u8]);
+            assert_eq!(decoder.decode().unwrap(), docs);
+            assert_eq!(decoder.encoding(), Some(encoding));
+        }
+
+        let mut encoder = YamlEncoder::write(Vec::new());
+        encoder.bom(true).encode(&docs[0]).unwrap();
+        let bytes = encoder.into_inner();
+        assert!(bytes.starts_with(b"\xef\xbb\xbf---"));
+        assert_eq!(&bytes[3..], expected.as_bytes());
+    }
+
+    #[test]
+    fn test_dump_to_file() {
+        let dir = std::env::temp_dir().join(format!("yaml-rust-dump-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("doc.yaml");
+        let docs = YamlLoader::load_from_str("a: [1, 2]").unwrap();
+
+        YamlEmitter::dump_to_file(&path, &docs[0]).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "---\na:\n  - 1\n  - 2\n"
+        );
+
+        let docs = YamlLoader::load_from_str("b: c").unwrap();
+        YamlEmitter::dump_to_file_atomic(&path, &docs[0]).unwrap();
+        assert_eq!(YamlLoader::load_from_file(&path).unwrap(), docs);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        // threads writing the same file each use a temporary file of their own
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    let doc = Yaml::Array(vec![Yaml::Integer(i); 1000]);
+                    YamlEmitter::dump_to_file_atomic(&path, &doc)
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap().unwrap();
+        }
+        let doc = YamlLoader::load_from_file(&path).unwrap().remove(0);
+        assert_eq!(doc.as_vec().unwrap().len(), 1000);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        let bad = dir.join("missing").join("doc.yaml");
+        for result in [
+            YamlEmitter::dump_to_file(&bad, &docs[0]),
+            YamlEmitter::dump_to_file_atomic(&bad, &docs[0]),
+        ] {
+            match result {
+                Err(EncodeError::IO(err)) => {
+                    assert!(err.to_string().starts_with(&bad.display().to_string()))
+                }
+                other => panic!("unexpected result: {:?}", other),
+            }
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_line_break() {
+        let s = "a:\n  - 1\n  - b: |\n      x\n      y\nc: {}";
+        let docs = YamlLoader::load_from_str(s).unwrap();
+        for line_break in [LineBreak::Lf, LineBreak::CrLf, LineBreak::Cr] {
+            let mut writer = String::new();
+            {
+                let mut emitter = YamlEmitter::new(&mut writer);
+                emitter.line_break(line_break);
+                emitter.multiline_strings(true);
+                assert_eq!(emitter.get_line_break(), line_break);
+                emitter.dump(&docs[0]).unwrap();
+            }
+            let expected = "---\na:\n  - 1\n  - b: |\n      x\n      y\nc: {}";
+            assert_eq!(writer, expected.replace('\n', line_break.as_str()));
+            assert_eq!(YamlLoader::load_from_str(&writer).unwrap(), docs);
+        }
+
+        let mut encoder = YamlEncoder::write(Vec::new());
+        encoder
+            .encode_with(&docs[0], |emitter| emitter.line_break(LineBreak::CrLf))
+            .unwrap();
+        let bytes = encoder.into_inner();
+        assert!(bytes.ends_with(b"c: {}\r\n"));
+        assert!(!bytes.windows(2).any(|w| w[1] == b'\n' && w[0] != b'\r'));
+
+        // other line breaks in a string keep it out of a literal block
+        for s in ["a\rb: 1\nc", "x\r\ny", "x\ny\u{85}z: 2"] {
+            let doc = Yaml::Array(vec![Yaml::from_str(s)]);
+            let mut writer = String::new();
+            let mut emitter = YamlEmitter::new(&mut writer);
+            emitter.multiline_strings(true);
+            emitter.dump(&doc).unwrap();
+            assert!(!writer.contains('|'), "{}", writer);
+            assert_eq!(YamlLoader::load_from_str(&writer).unwrap()[0], doc);
+        }
+    }
+
+    #[test]
+    fn test_flow_leaves() {
+        let s = r#"
+a: [1, "x, y", "multi\nline", ~]
+b:
+  - {k: v, "}": "{"}
+  - [1, 2, 3, 4, 5]
+  - []
+c: [aaaaaaaaaa, bbbbbbbbbb, cccccccccc, dddd]
+? [k1, k2]
+: v
+"#;
+        let docs = YamlLoader::load_from_str(s).unwrap();
+        let mut writer = String::new();
+        {
+            let mut emitter = YamlEmitter::new(&mut writer);
+            emitter.flow_leaves(4);
+            emitter.best_width(40);
+            emitter.multiline_strings(true);
+            emitter.dump(&docs[0]).unwrap();
+        }
+        let expected = r#"---
+a: [1, "x, y", "multi\nline", ~]
+b:
+  - {k: v, "}": "{"}
+  - - 1
+    - 2
+    - 3
+    - 4
+    - 5
+  - []
+c:
+  - aaaaaaaaaa
+  - bbbbbbbbbb
+  - cccccccccc
+  - dddd
+? [k1, k2]
+: v"#;
+        assert_eq!(writer, expected);
+        assert_eq!(YamlLoader::load_from_str(&writer).unwrap(), docs);
+    }
+
+    #[test]
+    fn test_flow_leaves_settings() {
+        let key = "k".repeat(30);
+        let s = format!("{}: [aaaaaa, bbbbbb, cccccc]\nb: [!x v, 2]\n", key);
+        let docs =
+            YamlLoader::load_from_str(&s.replace("!x", "!<tag:example.com,2000:x>")).unwrap();
+        let mut writer = String::new();
+        {
+            let mut emitter = YamlEmitter::new(&mut writer);
+            emitter.flow_leaves(4);
+            emitter.best_width(30);
+            emitter.register_tag_handle("!e!", "tag:example.com,2000:");
+            emitter.dump(&docs[0]).unwrap();
+        }
+        // the list fits the width on its own, but not after the key
+        let expected = format!(
+            "%TAG !e! tag:example.com,2000:\n---\n{}:\n  - aaaaaa\n  - bbbbbb\n  - cccccc\nb: [!e!x v, 2]",
+            key
+        );
+        assert_eq!(writer, expected);
+        assert_eq!(YamlLoader::load_from_str(&writer).unwrap(), docs);
+    }
+
+    #[test]
+    fn test_encoder_multiple_documents() {
+        let mut encoder = YamlEncoder::write(Vec::new());
+        encoder.encoding(YamlEncoding::Utf16Be);
+        encoder.encode(&Yaml::Integer(1)).unwrap();
+        let multiline = YamlLoader::load_from_str("a: \"b\\nc\\n\"")
+            .unwrap()
+            .remove(0);
+        encoder
+            .encode_with(&multiline, |emitter| emitter.multiline_strings(true))
+            .unwrap();
+        let bytes = encoder.into_inner();
+        // only the first document starts with a byte order mark
+        assert_eq!(bytes.windows(2).filter(|w| *w == b"\xfe\xff").count(), 1);
+        let docs = crate::yaml::YamlDecoder::read(&bytes as &[u8])
+            .decode()
+            .unwrap();
+        assert_eq!(docs, vec![Yaml::Integer(1), multiline]);
+    }
+
+    #[test]
+    fn test_document_writer() {
+        let docs = YamlLoader::load_from_str("a: [1, 2]\n---\nb: {c: d}").unwrap();
+        let mut writer = DocumentWriter::new(Vec::new());
+        writer.comment("generated\n\ndo not edit");
+        for doc in &docs {
+            writer.write(doc).unwrap();
+        }
+        assert_eq!(writer.documents(), 2);
+        let out = String::from_utf8(writer.into_inner()).unwrap();
+        assert_eq!(
+            out,
+            "# generated\n#\n# do not edit\n---\na:\n  - 1\n  - 2\n---\nb:\n  c: d\n"
+        );
+        assert_eq!(YamlLoader::load_from_str(&out).unwrap(), docs);
+
+        let mut writer = DocumentWriter::new(Vec::new());
+        let err = writer.write_events(vec![Event::sequence_start()]);
+        assert!(matches!(
+            err,
+            Err(EncodeError::Emit(EmitError::InvalidEvent(_)))
+        ));
+    }
+
+    #[test]
+    fn test_register_tag_handle() {
+        let s = "%TAG !k! tag:k8s.io,2019:\n--- !k!Pod\nname: !k!Name web\nlocal: !x 1\nodd: !<tag:k8s.io,2019:a,b> 2\n";
+        let doc = YamlLoader::load_from_str(s).unwrap().remove(0);
+        let mut out = String::new();
+        let mut emitter = YamlEmitter::new(&mut out);
+        emitter.register_tag_handle("!k!", "tag:other:");
+        emitter.register_tag_handle("!k!", "tag:k8s.io,2019:");
+        emitter.register_tag_handle("!p!", "tag:k8s.io,");
+        emitter.dump(&doc).unwrap();
+        assert_eq!(
+            out,
+            "%TAG !k! tag:k8s.io,2019:\n%TAG !p! tag:k8s.io,\n---\n!k!Pod\nname: !k!Name web\nlocal: !x \"1\"\nodd: !<tag:k8s.io,2019:a,b> \"2\""
+        );
+        assert_eq!(YamlLoader::load_from_str(&out).unwrap()[0], doc);
+
+        let mut out = String::new();
+        let mut emitter = YamlEmitter::new(&mut out);
+        emitter.register_tag_handle("!k!", "tag:k8s.io,2019:");
+        for _ in 0..2 {
+            for event in [
+                Event::DocumentStart,
+                Event::sequence_start().with_tag("tag:k8s.io,2019:", "List"),
+                Event::SequenceEnd,
+                Event::DocumentEnd,
+            ] {
+                emitter.emit_event(event).unwrap();
+            }
+        }
+        assert_eq!(
+            out,
+            "%TAG !k! tag:k8s.io,2019:\n---\n!k!List []\n...\n%TAG !k! tag:k8s.io,2019:\n---\n!k!List []"
+        );
+        assert_eq!(YamlLoader::load_from_str(&out).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_emit_reals() {
+        let reals = [
+            (Yaml::from_f64(1.0), "1.0"),
+            (Yaml::from_f64(f64::NAN), ".nan"),
+            (Yaml::Real(format!("{}", 2.0)), "2.0"),
+            (Yaml::Real(format!("{}", f64::INFINITY)), ".inf"),
+            (Yaml::Real(format!("{}", -f64::INFINITY)), "-.inf"),
+            (Yaml::Real("1e3".to_owned()), "1e3"),
+            (Yaml::Real("0.50".to_owned()), "0.50"),
+            (Yaml::Real("-0".to_owned()), "-0.0"),
+        ];
+        for (real, text) in reals {
+            let doc = Yaml::Array(vec![real]);
+            let mut out = String::new();
+            YamlEmitter::new(&mut out).dump(&doc).unwrap();
+            assert_eq!(out, format!("---\n- {}", text));
+            let back = YamlLoader::load_from_str(&out).unwrap().remove(0);
+            assert!(matches!(back[0], Yaml::Real(_)), "{}", text);
+        }
+
+        // integers too large for an `i64` load as reals and are written
+        // with all their digits
+        let s = "[18446744073709551615, -99999999999999999999]";
+        let doc = YamlLoader::load_from_str(s).unwrap().remove(0);
+        let mut out = String::new();
+        YamlEmitter::new(&mut out).dump(&doc).unwrap();
+        assert_eq!(out, "---\n- 18446744073709551615\n- -99999999999999999999");
+        assert_eq!(YamlLoader::load_from_str(&out).unwrap()[0], doc);
+    }
+
+    #[test]
+    fn test_emit_tagged() {
+        let s = r#"---
+ref: !Ref name
+quoted: !Ref "true"
+seq: !GetAZs
+  - a
+  - b
+map: !vault
+  id: 3
+? !Pair
+  - 1
+  - 2
+: !<tag:example.com,2000:app/foo> []
+list:
+  - !Sub x
+  - !Join
+    - a"#;
+
+        let docs = YamlLoader::load_from_str(s).unwrap();
+        let mut writer = String::new();
+        YamlEmitter::new(&mut writer).dump(&docs[0]).unwrap();
+        assert_eq!(s, writer);
+
+        let mut writer = String::new();
+        let mut emitter = YamlEmitter::new(&mut writer);
+        emitter.flow_leaves(2);
+        emitter.dump(&docs[0]).unwrap();
+        assert_eq!(YamlLoader::load_from_str(&writer).unwrap(), docs);
+    }
+
+    fn emit_events(source: &str, compact: bool) -> String {
+        let mut parser = crate::parser::Parser::new(source.chars());
+        let mut out = String::new();
+        let mut emitter = YamlEmitter::new(&mut out);
+        emitter.compact(compact);
+        loop {
+            let (ev, _) = parser.next_event().unwrap();
+            if ev == Event::StreamEnd {
+                break;
+            }
+            emitter.emit_event(ev).unwrap();
+        }
+        out
+    }
+
+    #[test]
+    fn test_emit_event() {
+        let s = "
+a: [1, [2, 3], {}]
+b:
+  - {c: 'true', d: [[]]}
+  - - x
+    - y
+? [k, {l: m}]
+: {n: ~}
+t: !Seq [!Ref r, !Map {}]
+u: !Map {v: 1}
+---
+- !Seq []
+---
+plain";
+        for &compact in &[true, false] {
+            let mut dumped = Vec::new();
+            for doc in YamlLoader::load_from_str(s).unwrap() {
+                let mut out = String::new();
+                let mut emitter = YamlEmitter::new(&mut out);
+                emitter.compact(compact);
+                emitter.dump(&doc).unwrap();
+                dumped.push(out);
+            }
+            assert_eq!(emit_events(s, compact), dumped.join("\n"));
+        }
+    }
+
+    #[test]
+    fn test_emit_event_invalid() {
+        use crate::scanner::TScalarStyle;
+
+        let emit = |events: Vec<Event>| {
+            let mut out = String::new();
+            let mut emitter = YamlEmitter::new(&mut out);
+            events.into_iter().try_for_each(|ev| emitter.emit_event(ev))
+        };
+        let scalar = || Event::scalar("x", TScalarStyle::Plain);
+        assert_eq!(
+            emit(vec![scalar()]),
+            Err(EmitError::InvalidEvent("node outside of a document"))
+        );
+        assert_eq!(
+            emit(vec![Event::DocumentStart, scalar(), scalar()]),
+            Err(EmitError::InvalidEvent(
+                "document has more than one root node"
+            ))
+        );
+        assert_eq!(
+            emit(vec![Event::DocumentStart, Event::Alias(1)]),
+            Err(EmitError::InvalidEvent("aliases can't be emitted"))
+        );
+        assert_eq!(
+            emit(vec![
+                Event::DocumentStart,
+                Event::mapping_start(),
+                scalar(),
+                Event::MappingEnd
+            ]),
+            Err(EmitError::InvalidEvent("mapping ended before a value"))
+        );
+        assert_eq!(
+            emit(vec![
+                Event::DocumentStart,
+                Event::sequence_start(),
+                Event::MappingEnd
+            ]),
+            Err(EmitError::InvalidEvent("unmatched collection end"))
+        );
+        assert_eq!(
+            emit(vec![
+                Event::DocumentStart,
+                Event::sequence_start(),
+                Event::DocumentEnd
+            ]),
+            Err(EmitError::InvalidEvent(
+                "document ended inside a collection"
+            ))
+        );
+    }
+
     #[test]
     fn test_nested_hashes() {
         let s = r#"---
@@ -680,4 +3170,204 @@ a:
 
         assert_eq!(s, writer);
     }
+
+    #[test]
+    fn test_banner() {
+        let mut out = String::new();
+        {
+            let mut emitter = YamlEmitter::new(&mut out);
+            emitter.banner("DO NOT EDIT\n\nsee gen.py");
+            emitter.document_header("part");
+            emitter.trailing_newline(true);
+            emitter.register_tag_handle("!e!", "tag:example.com:");
+            for i in 0..2 {
+                emitter.emit_event(Event::DocumentStart).unwrap();
+                emitter
+                    .emit_event(Event::scalar(i.to_string(), TScalarStyle::Plain))
+                    .unwrap();
+                emitter.emit_event(Event::DocumentEnd).unwrap();
+            }
+        }
+        let directive = "%TAG !e! tag:example.com:";
+        assert_eq!(
+            out,
+            format!(
+                "# DO NOT EDIT\n#\n# see gen.py\n# part\n{0}\n---\n0\n...\n# part\n{0}\n---\n1\n",
+                directive
+            )
+        );
+
+        // without a trailing newline documents are still separated
+        let mut out = String::new();
+        {
+            let mut emitter = YamlEmitter::new(&mut out);
+            emitter.banner("b");
+            emitter.line_break(LineBreak::CrLf);
+            emitter.emit_event(Event::DocumentStart).unwrap();
+            emitter.emit_event(Event::DocumentEnd).unwrap();
+            emitter.emit_event(Event::DocumentStart).unwrap();
+            emitter
+                .emit_event(Event::scalar("x", TScalarStyle::Plain))
+                .unwrap();
+            emitter.emit_event(Event::DocumentEnd).unwrap();
+        }
+        assert_eq!(out, "# b\r\n---\r\n\r\n---\r\nx");
+    }
+
+    #[test]
+    fn test_key_order() {
+        let dump = |doc: &Yaml, key_order: KeyOrder, flow_leaves: usize| {
+            let mut out = String::new();
+            {
+                let mut emitter = YamlEmitter::new(&mut out);
+                emitter.key_order(key_order);
+                emitter.flow_leaves(flow_leaves);
+                emitter.dump(doc).unwrap();
+            }
+            out
+        };
+        let doc =
+            &YamlLoader::load_from_str("b: {z: 1, 2: x, y: 2}\nc: 3\n1: 4\na: [{q: 5, p: 6}]")
+                .unwrap()[0];
+
+        assert_eq!(
+            dump(doc, KeyOrder::Alphabetical, 0),
+            "---\n1: 4\na:\n  - p: 6\n    q: 5\nb:\n  2: x\n  y: 2\n  z: 1\nc: 3"
+        );
+        assert_eq!(
+            dump(doc, KeyOrder::Alphabetical, 3),
+            "---\n1: 4\na:\n  - {p: 6, q: 5}\nb: {2: x, y: 2, z: 1}\nc: 3"
+        );
+        let priority = KeyOrder::Priority(vec!["a".to_owned(), "y".to_owned(), "2".to_owned()]);
+        assert_eq!(
+            dump(doc, priority, 0),
+            "---\na:\n  - q: 5\n    p: 6\nb:\n  y: 2\n  2: x\n  z: 1\nc: 3\n1: 4"
+        );
+        let reverse = |a: &Yaml, b: &Yaml| b.cmp(a);
+        assert_eq!(
+            dump(doc, KeyOrder::Custom(&reverse), 0),
+            "---\nc: 3\nb:\n  z: 1\n  y: 2\n  2: x\na:\n  - q: 5\n    p: 6\n1: 4"
+        );
+        assert_eq!(
+            dump(doc, KeyOrder::Preserve, 0),
+            dump(doc, KeyOrder::default(), 0)
+        );
+
+        // comments stay with their entries
+        let commented = CommentedYaml::hash(vec![
+            (
+                Yaml::from_str("b"),
+                CommentedYaml::from(Yaml::Integer(1)).comment("bee"),
+            ),
+            (
+                Yaml::from_str("a"),
+                CommentedYaml::from(Yaml::Integer(2)).inline_comment("ay"),
+            ),
+        ]);
+        let mut out = String::new();
+        {
+            let mut emitter = YamlEmitter::new(&mut out);
+            emitter.key_order(KeyOrder::Alphabetical);
+            emitter.dump_commented(&commented).unwrap();
+        }
+        assert_eq!(out, "---\na: 2 # ay\n# bee\nb: 1");
+    }
+
+    #[test]
+    fn test_dump_commented() {
+        let text = |v: &str| CommentedYaml::new(Yaml::from_str(v));
+        let doc = CommentedYaml::hash(vec![
+            (
+                Yaml::from_str("list"),
+                CommentedYaml::array(vec![
+                    text("a").comment("first").inline_comment("a"),
+                    CommentedYaml::array(vec![text("b"), text("c").comment("nested")])
+                        .inline_comment("inner"),
+                    CommentedYaml::hash(vec![(Yaml::from_str("d"), text("e").comment(""))]),
+                ])
+                .comment("a list")
+                .comment("of things"),
+            ),
+            (
+                Yaml::from_str("text"),
+                CommentedYaml::new(Yaml::String("x\ny\n".to_owned())).inline_comment("two\nlines"),
+            ),
+            (
+                Yaml::from_str("empty"),
+                CommentedYaml::array(vec![]).inline_comment("none"),
+            ),
+        ])
+        .comment("top")
+        .inline_comment("doc");
+
+        let mut out = String::new();
+        {
+            let mut emitter = YamlEmitter::new(&mut out);
+            emitter.multiline_strings(true);
+            emitter.dump_commented(&doc).unwrap();
+        }
+        let expected = "--- # doc
+# top
+# a list
+# of things
+list:
+  # first
+  - a # a
+  - - b # inner
+    # nested
+    - c
+  - #
+    d: e
+text: | # two lines
+  x
+  y
+empty: [] # none";
+        assert_eq!(out, expected);
+        assert_eq!(YamlLoader::load_from_str(&out).unwrap()[0], doc.value);
+
+        let mut out = String::new();
+        {
+            let mut emitter = YamlEmitter::new(&mut out);
+            emitter.line_break(LineBreak::CrLf);
+            emitter.compact(false);
+            emitter.dump_commented(&doc).unwrap();
+        }
+        assert!(out.contains("list:\r\n  # first\r\n  - a # a\r\n  - # inner\r\n"));
+        assert_eq!(YamlLoader::load_from_str(&out).unwrap()[0], doc.value);
+
+        // a key given again replaces the entry and its comments
+        let doc = CommentedYaml::hash(vec![
+            (Yaml::from_str("a"), text("1").comment("old")),
+            (Yaml::from_str("b"), text("2")),
+            (Yaml::from_str("a"), text("3").comment("new")),
+        ]);
+        let mut out = String::new();
+        YamlEmitter::new(&mut out).dump_commented(&doc).unwrap();
+        assert_eq!(out, "---\nb: 2\n# new\na: 3");
+
+        // every line break in comment text starts a new comment line
+        let doc = CommentedYaml::hash(vec![
+            (
+                Yaml::from_str("a"),
+                text("1").inline_comment("note\rinjected: 2"),
+            ),
+            (
+                Yaml::from_str("b"),
+                text("2").comment("x\revil: 3\r\ny\u{85}z: 4\u{2028}w: 5"),
+            ),
+        ]);
+        let mut out = String::new();
+        YamlEmitter::new(&mut out).dump_commented(&doc).unwrap();
+        assert_eq!(
+            out,
+            "---\na: 1 # note injected: 2\n# x\n# evil: 3\n# y\n# z: 4\n# w: 5\nb: 2"
+        );
+        assert_eq!(YamlLoader::load_from_str(&out).unwrap()[0], doc.value);
+        let mut loader = YamlLoader::new();
+        loader.scanner_config(ScannerConfig {
+            line_breaks: LineBreaks::Yaml11,
+            ..ScannerConfig::default()
+        });
+        assert_eq!(loader.parse_from_str(&out).unwrap()[0], doc.value);
+    }
 }
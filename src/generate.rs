@@ -0,0 +1,189 @@
+//! Generation of arbitrary `Yaml` trees for fuzzing, enabled by the
+//! `arbitrary` feature.
+//!
+//! `Yaml` implements `arbitrary::Arbitrary` using the default
+//! `GenerateOptions`. Use `arbitrary_yaml` to pick different constraints.
+//!
+//! Generated trees contain no `Alias` or `BadValue` nodes, every `Real`
+//! holds a valid float and hash keys are scalars, so they can be emitted
+//! and loaded back.
+
+use crate::yaml::{format_real, Hash, Yaml};
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+/// The characters that generated strings are made of.
+#[derive(Clone, Copy, PartialEq, Debug, Eq)]
+pub enum Charset {
+    /// ASCII letters and digits.
+    Alphanumeric,
+    /// Printable ASCII characters, including space.
+    Printable,
+    /// Any Unicode scalar value. This exercises quoting and escaping, which
+    /// the emitter does not handle for every character.
+    Unicode,
+}
+
+/// Constraints on the trees produced by `arbitrary_yaml`.
+#[derive(Clone, Copy, PartialEq, Debug, Eq)]
+pub struct GenerateOptions {
+    /// Nesting depth of collections. Zero only generates scalars.
+    pub max_depth: usize,
+    /// Maximum number of elements in a collection.
+    pub max_len: usize,
+    /// Maximum number of characters in a string.
+    pub max_str_len: usize,
+    pub charset: Charset,
+}
+
+impl Default for GenerateOptions {
+    fn default() -> Self {
+        GenerateOptions {
+            max_depth: 4,
+            max_len: 8,
+            max_str_len: 16,
+            charset: Charset::Printable,
+        }
+    }
+}
+
+impl<'a> Arbitrary<'a> for Yaml {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        arbitrary_yaml(u, &GenerateOptions::default())
+    }
+}
+
+/// Generate a `Yaml` tree within the limits of `options`.
+///
+/// ```
+/// # extern crate yaml_rust_davvid as yaml_rust;
+/// use arbitrary::Unstructured;
+/// use yaml_rust::generate::{arbitrary_yaml, GenerateOptions};
+///
+/// let options = GenerateOptions {
+///     max_depth: 0,
+///     ..GenerateOptions::default()
+/// };
+/// let mut u = Unstructured::new(&[4, 1, 2, 3, 4, 5, 6, 7, 8]);
+/// let yaml = arbitrary_yaml(&mut u, &options).unwrap();
+/// assert!(yaml.as_vec().is_none() && yaml.as_hash().is_none());
+/// ```
+pub fn arbitrary_yaml(u: &mut Unstructured, options: &GenerateOptions) -> Result<Yaml> {
+    generate(u, options, options.max_depth)
+}
+
+fn generate(u: &mut Unstructured, options: &GenerateOptions, depth: usize) -> Result<Yaml> {
+    let kinds = if depth == 0 { 5 } else { 7 };
+    Ok(match u.choose_index(kinds)? {
+        5 => {
+            let len = u.int_in_range(0..=options.max_len)?;
+            let mut v = Vec::with_capacity(len);
+            for _ in 0..len {
+                v.push(generate(u, options, depth - 1)?);
+            }
+            Yaml::Array(v)
+        }
+        6 => {
+            let len = u.int_in_range(0..=options.max_len)?;
+            let mut h = Hash::new();
+            for _ in 0..len {
+                let kind = u.choose_index(5)?;
+                let key = scalar(u, options, kind)?;
+                h.insert(key, generate(u, options, depth - 1)?);
+            }
+            Yaml::Hash(h)
+        }
+        kind => scalar(u, options, kind)?,
+    })
+}
+
+fn scalar(u: &mut Unstructured, options: &GenerateOptions, kind: usize) -> Result<Yaml> {
+    Ok(match kind {
+        0 => Yaml::Null,
+        1 => Yaml::Boolean(u.arbitrary()?),
+        2 => Yaml::Integer(u.arbitrary()?),
+        // Debug formatting, which `format_real` uses, always includes a '.'
+        // or an exponent, so the value loads back as a real rather than an
+        // integer
+        3 => Yaml::Real(format_real(u.arbitrary()?)),
+        _ => Yaml::String(string(u, options)?),
+    })
+}
+
+fn string(u: &mut Unstructured, options: &GenerateOptions) -> Result<String> {
+    const ALPHANUMERIC: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+    let len = u.int_in_range(0..=options.max_str_len)?;
+    let mut s = String::with_capacity(len);
+    for _ in 0..len {
+        let c = match options.charset {
+            Charset::Alphanumeric => *u.choose(ALPHANUMERIC)? as char,
+            Charset::Printable => u.int_in_range(b' '..=b'~')? as char,
+            Charset::Unicode => u.arbitrary()?,
+        };
+        s.push(c);
+    }
+    Ok(s)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{YamlEmitter, YamlLoader};
+
+    fn depth(yaml: &Yaml) -> usize {
+        match *yaml {
+            Yaml::Array(ref v) => 1 + v.iter().map(depth).max().unwrap_or(0),
+            Yaml::Hash(ref h) => 1 + h.values().map(depth).max().unwrap_or(0),
+            _ => 0,
+        }
+    }
+
+    fn bytes(seed: u32) -> Vec<u8> {
+        // a simple LCG is enough to get varied input
+        let mut state = seed;
+        (0..4096)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> 16) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_generate_roundtrip() {
+        let options = GenerateOptions {
+            max_depth: 3,
+            ..GenerateOptions::default()
+        };
+        for seed in 0..64 {
+            let data = bytes(seed);
+            let mut u = Unstructured::new(&data);
+            let yaml = arbitrary_yaml(&mut u, &options).unwrap();
+            assert!(depth(&yaml) <= 3);
+
+            let mut out = String::new();
+            YamlEmitter::new(&mut out).dump(&yaml).unwrap();
+            let docs = YamlLoader::load_from_str(&out).unwrap();
+            assert_eq!(docs[0], yaml, "emitted as {:?}", out);
+        }
+    }
+
+    #[test]
+    fn test_charset() {
+        let options = GenerateOptions {
+            max_depth: 0,
+            max_str_len: 64,
+            charset: Charset::Alphanumeric,
+            ..GenerateOptions::default()
+        };
+        for seed in 0..64 {
+            let data = bytes(seed);
+            let mut u = Unstructured::new(&data);
+            if let Yaml::String(s) = arbitrary_yaml(&mut u, &options).unwrap() {
+                assert!(s.chars().all(|c| c.is_ascii_alphanumeric()));
+            }
+        }
+        let data = bytes(0);
+        assert!(Yaml::arbitrary(&mut Unstructured::new(&data)).is_ok());
+    }
+}
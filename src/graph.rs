@@ -0,0 +1,326 @@
+//! A document model that preserves YAML's node graph.
+//!
+//! `YamlLoader` copies the anchored value wherever an alias refers to it.
+//! `YamlGraph` instead stores every node once in an arena and lets aliases
+//! share it, so a change to an anchored node is visible through all of its
+//! aliases and recursive structures can be represented.
+
+use crate::parser::{Event, MarkedEventReceiver, Parser};
+use crate::scanner::{Marker, ScanError};
+use crate::yaml::{application_tag, resolve_scalar, Hash, Tag, Yaml};
+
+use std::collections::BTreeMap;
+use std::ops::{Index, IndexMut};
+
+/// Identifies a node within a `YamlGraph`.
+#[derive(Clone, Copy, PartialEq, PartialOrd, Debug, Eq, Ord, Hash)]
+pub struct NodeId(usize);
+
+/// A node of a `YamlGraph`. Collections refer to their children by ID.
+#[derive(Clone, PartialEq, Debug, Eq)]
+pub enum Node {
+    /// Any `Yaml` value other than an array or hash.
+    Scalar(Yaml),
+    Array(Vec<NodeId>),
+    /// Key and value pairs in document order.
+    Hash(Vec<(NodeId, NodeId)>),
+    /// An array or hash with an application tag, see `Yaml::Tagged`.
+    Tagged(Tag, NodeId),
+}
+
+/// A stream of documents whose aliased nodes are shared.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate yaml_rust_davvid as yaml_rust;
+/// use yaml_rust::graph::{Node, YamlGraph};
+/// use yaml_rust::Yaml;
+///
+/// let mut graph = YamlGraph::load_from_str("a: &x [1]\nb: *x").unwrap();
+/// let root = graph.documents()[0];
+/// let a = graph.get(root, &Yaml::from_str("a")).unwrap();
+/// let b = graph.get(root, &Yaml::from_str("b")).unwrap();
+/// assert_eq!(a, b);
+///
+/// let two = graph.add(Node::Scalar(Yaml::Integer(2)));
+/// if let Node::Array(ref mut v) = graph[a] {
+///     v.push(two);
+/// }
+/// let doc = graph.to_yaml(root).unwrap();
+/// assert_eq!(doc["b"][1].as_i64(), Some(2));
+/// ```
+#[derive(Clone, PartialEq, Debug, Default, Eq)]
+pub struct YamlGraph {
+    nodes: Vec<Node>,
+    documents: Vec<NodeId>,
+}
+
+impl YamlGraph {
+    pub fn new() -> YamlGraph {
+        YamlGraph::default()
+    }
+
+    pub fn load_from_str(source: &str) -> Result<YamlGraph, ScanError> {
+        let mut loader = GraphLoader::default();
+        let mut parser = Parser::new(source.chars());
+        parser.load(&mut loader, true)?;
+        Ok(loader.graph)
+    }
+
+    /// The root node of each document in the stream.
+    pub fn documents(&self) -> &[NodeId] {
+        &self.documents
+    }
+
+    /// Add a node that is not yet referred to by any other node.
+    pub fn add(&mut self, node: Node) -> NodeId {
+        self.nodes.push(node);
+        NodeId(self.nodes.len() - 1)
+    }
+
+    /// Append a document whose root is `id`.
+    pub fn push_document(&mut self, id: NodeId) {
+        self.documents.push(id);
+    }
+
+    /// The number of nodes in the graph.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Look up the value for a scalar `key` in the hash `id`, or in the hash
+    /// under its tag.
+    pub fn get(&self, id: NodeId, key: &Yaml) -> Option<NodeId> {
+        match self[id] {
+            Node::Tagged(_, inner) => self.get(inner, key),
+            Node::Hash(ref entries) => entries
+                .iter()
+                .find(|&&(k, _)| matches!(self[k], Node::Scalar(ref s) if s == key))
+                .map(|&(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Copy the subtree rooted at `id` into a `Yaml` tree, duplicating shared
+    /// nodes. Returns `None` if the subtree contains a cycle.
+    pub fn to_yaml(&self, id: NodeId) -> Option<Yaml> {
+        let mut path = Vec::new();
+        self.to_yaml_inner(id, &mut path)
+    }
+
+    fn to_yaml_inner(&self, id: NodeId, path: &mut Vec<NodeId>) -> Option<Yaml> {
+        if path.contains(&id) {
+            return None;
+        }
+        path.push(id);
+        let yaml = match self[id] {
+            Node::Scalar(ref v) => v.clone(),
+            Node::Array(ref v) => {
+                let mut array = Vec::with_capacity(v.len());
+                for &child in v {
+                    array.push(self.to_yaml_inner(child, path)?);
+                }
+                Yaml::Array(array)
+            }
+            Node::Hash(ref v) => {
+                let mut hash = Hash::new();
+                for &(key, value) in v {
+                    let key = self.to_yaml_inner(key, path)?;
+                    hash.insert(key, self.to_yaml_inner(value, path)?);
+                }
+                Yaml::Hash(hash)
+            }
+            Node::Tagged(ref tag, inner) => {
+                Yaml::Tagged(tag.clone(), Box::new(self.to_yaml_inner(inner, path)?))
+            }
+        };
+        path.pop();
+        Some(yaml)
+    }
+}
+
+impl Index<NodeId> for YamlGraph {
+    type Output = Node;
+
+    fn index(&self, id: NodeId) -> &Node {
+        &self.nodes[id.0]
+    }
+}
+
+impl IndexMut<NodeId> for YamlGraph {
+    fn index_mut(&mut self, id: NodeId) -> &mut Node {
+        &mut self.nodes[id.0]
+    }
+}
+
+#[derive(Default)]
+struct GraphLoader {
+    graph: YamlGraph,
+    // open collections, the pending key of each open hash, and the node to
+    // attach when it ends, which is the tagged node of a tagged collection
+    stack: Vec<(NodeId, Option<NodeId>, NodeId)>,
+    root: Option<NodeId>,
+    anchor_map: BTreeMap<usize, NodeId>,
+}
+
+impl GraphLoader {
+    fn add(&mut self, node: Node, aid: usize) -> NodeId {
+        let id = self.graph.add(node);
+        // valid anchor id starts from 1
+        if aid > 0 {
+            self.anchor_map.insert(aid, id);
+        }
+        id
+    }
+
+    // Add an open collection. Its anchor refers to the tagged node if it has
+    // an application tag.
+    fn open(&mut self, node: Node, aid: usize, tag: Option<Tag>) {
+        let (id, outer) = match tag {
+            Some(tag) => {
+                let id = self.graph.add(node);
+                (id, self.add(Node::Tagged(tag, id), aid))
+            }
+            None => {
+                let id = self.add(node, aid);
+                (id, id)
+            }
+        };
+        self.stack.push((id, None, outer));
+    }
+
+    fn attach(&mut self, id: NodeId) {
+        let (parent, key, _) = match self.stack.last_mut() {
+            Some(top) => top,
+            None => {
+                self.root = Some(id);
+                return;
+            }
+        };
+        match self.graph[*parent] {
+            Node::Array(ref mut v) => v.push(id),
+            Node::Hash(ref mut v) => match key.take() {
+                Some(k) => v.push((k, id)),
+                None => *key = Some(id),
+            },
+            Node::Scalar(_) | Node::Tagged(..) => unreachable!(),
+        }
+    }
+}
+
+impl MarkedEventReceiver for GraphLoader {
+    fn on_event(&mut self, ev: Event, _: Marker) {
+        match ev {
+            Event::DocumentEnd => {
+                let root = match self.root.take() {
+                    Some(id) => id,
+                    // empty document
+                    None => self.graph.add(Node::Scalar(Yaml::BadValue)),
+                };
+                self.graph.push_document(root);
+            }
+            Event::SequenceStart(aid, tag) => {
+                // collections are added before their children, so aliases
+                // inside them can refer back to them
+                self.open(Node::Array(Vec::new()), aid, application_tag(&tag));
+            }
+            Event::MappingStart(aid, tag) => {
+                self.open(Node::Hash(Vec::new()), aid, application_tag(&tag));
+            }
+            Event::SequenceEnd | Event::MappingEnd => {
+                let (_, _, id) = self.stack.pop().unwrap();
+                self.attach(id);
+            }
+            Event::Scalar(v, style, aid, tag) => {
+                let id = self.add(Node::Scalar(resolve_scalar(v, style, &tag)), aid);
+                self.attach(id);
+            }
+            Event::Alias(aid) => {
+                let id = match self.anchor_map.get(&aid) {
+                    Some(&id) => id,
+                    None => self.graph.add(Node::Scalar(Yaml::BadValue)),
+                };
+                self.attach(id);
+            }
+            _ => { /* ignore */ }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::YamlLoader;
+
+    #[test]
+    fn test_matches_loader() {
+        let s = "
+a: &x
+  b: [1, 2.5, 'c', !!int 3, !!bool nope]
+c: *x
+? [k]
+: v
+d: &t !t [1, !u {e: f}]
+e: *t
+---
+---
+- ~
+";
+        let graph = YamlGraph::load_from_str(s).unwrap();
+        let docs = YamlLoader::load_from_str(s).unwrap();
+        assert_eq!(graph.documents().len(), docs.len());
+        for (&id, doc) in graph.documents().iter().zip(&docs) {
+            assert_eq!(graph.to_yaml(id).as_ref(), Some(doc));
+        }
+    }
+
+    #[test]
+    fn test_shared_alias() {
+        let graph = YamlGraph::load_from_str("[&x {a: 1}, *x, *x]").unwrap();
+        let root = graph.documents()[0];
+        match graph[root] {
+            Node::Array(ref v) => {
+                assert_eq!(v.len(), 3);
+                assert!(v.iter().all(|&id| id == v[0]));
+            }
+            _ => panic!("expected an array"),
+        }
+        // the array, the hash, the key and the value
+        assert_eq!(graph.len(), 4);
+    }
+
+    #[test]
+    fn test_cycle() {
+        let mut graph = YamlGraph::load_from_str("&x\na: [*x]").unwrap();
+        let root = graph.documents()[0];
+        let a = graph.get(root, &Yaml::from_str("a")).unwrap();
+        assert_eq!(graph[a], Node::Array(vec![root]));
+        assert_eq!(graph.to_yaml(root), None);
+
+        // breaking the cycle makes the document representable again
+        let null = graph.add(Node::Scalar(Yaml::Null));
+        graph[a] = Node::Array(vec![null]);
+        let doc = graph.to_yaml(root).unwrap();
+        assert_eq!(doc, YamlLoader::load_from_str("a: [~]").unwrap()[0]);
+    }
+
+    #[test]
+    fn test_build() {
+        let mut graph = YamlGraph::new();
+        assert!(graph.is_empty());
+        let shared = graph.add(Node::Scalar(Yaml::from_str("x")));
+        let root = graph.add(Node::Array(vec![shared, shared]));
+        graph.push_document(root);
+        assert_eq!(graph.documents(), &[root]);
+        assert_eq!(graph.get(root, &Yaml::Null), None);
+        assert_eq!(
+            graph.to_yaml(root),
+            Some(Yaml::Array(vec![Yaml::from_str("x"), Yaml::from_str("x")]))
+        );
+    }
+}
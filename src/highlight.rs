@@ -0,0 +1,259 @@
+//! Classification of source text for syntax highlighting, using the same
+//! scanner as the parser.
+//!
+//! # Examples
+//!
+//! ```
+//! # extern crate yaml_rust_davvid as yaml_rust;
+//! use yaml_rust::highlight::{highlight, TokenClass};
+//!
+//! let s = "name: &n web # the service\nalias: *n";
+//! let classes: Vec<_> = highlight(s)
+//!     .unwrap()
+//!     .into_iter()
+//!     .map(|(span, class)| (span.slice(s), class))
+//!     .collect();
+//! assert_eq!(
+//!     classes,
+//!     vec![
+//!         ("name", TokenClass::Key),
+//!         (":", TokenClass::Punctuation),
+//!         ("&n", TokenClass::Anchor),
+//!         ("web", TokenClass::Scalar),
+//!         ("# the service", TokenClass::Comment),
+//!         ("alias", TokenClass::Key),
+//!         (":", TokenClass::Punctuation),
+//!         ("*n", TokenClass::Alias),
+//!     ]
+//! );
+//! ```
+
+use crate::scanner::{Marker, ScanError, Scanner, Span, Token, TokenType};
+
+/// What a span of source text is.
+#[derive(Clone, Copy, PartialEq, Debug, Eq)]
+pub enum TokenClass {
+    /// A scalar used as a mapping key.
+    Key,
+    Scalar,
+    Anchor,
+    Alias,
+    Tag,
+    Comment,
+    /// Indicators such as `-`, `:`, `,`, brackets and `---`.
+    Punctuation,
+    /// A `%YAML` or `%TAG` directive.
+    Directive,
+}
+
+/// Split `source` into classified spans, in order. Whitespace is not
+/// covered by any span, and a comment on the header line of a block scalar
+/// is part of the scalar's span.
+pub fn highlight(source: &str) -> Result<Vec<(Span, TokenClass)>, ScanError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut scanner = Scanner::new(source.chars());
+    scanner.record_token_spans();
+
+    let mut spans = Vec::new();
+    let mut cursor = Marker::new(0, 0, 1, 0);
+    let mut key = false;
+    while let Some(Token(mark, token)) = scanner.next_token()? {
+        let width = |n: usize| Some(span_of(&chars, mark, n));
+        let class_span = match token {
+            TokenType::Key => {
+                key = true;
+                if chars.get(mark.index()) == Some(&'?') {
+                    width(1).map(|span| (TokenClass::Punctuation, span))
+                } else {
+                    None
+                }
+            }
+            TokenType::Scalar(..) => {
+                let class = if key {
+                    TokenClass::Key
+                } else {
+                    TokenClass::Scalar
+                };
+                key = false;
+                scanner
+                    .take_token_span(mark.index())
+                    .map(|span| (class, span))
+            }
+            TokenType::Anchor(_) => scanner
+                .take_token_span(mark.index())
+                .map(|span| (TokenClass::Anchor, span)),
+            TokenType::Alias(_) => {
+                key = false;
+                scanner
+                    .take_token_span(mark.index())
+                    .map(|span| (TokenClass::Alias, span))
+            }
+            TokenType::Tag(..) => scanner
+                .take_token_span(mark.index())
+                .map(|span| (TokenClass::Tag, span)),
+            TokenType::VersionDirective(..) | TokenType::TagDirective(..) => {
+                let len = directive_len(&chars[mark.index()..]);
+                width(len).map(|span| (TokenClass::Directive, span))
+            }
+            TokenType::DocumentStart | TokenType::DocumentEnd => {
+                width(3).map(|span| (TokenClass::Punctuation, span))
+            }
+            TokenType::BlockEntry
+            | TokenType::FlowEntry
+            | TokenType::Value
+            | TokenType::FlowSequenceStart
+            | TokenType::FlowSequenceEnd
+            | TokenType::FlowMappingStart
+            | TokenType::FlowMappingEnd => width(1).map(|span| (TokenClass::Punctuation, span)),
+            _ => None,
+        };
+        if !matches!(
+            token,
+            TokenType::Key | TokenType::Anchor(_) | TokenType::Tag(..)
+        ) {
+            key = false;
+        }
+
+        let start = class_span.map_or(mark, |(_, span)| span.start);
+        if start.index() > cursor.index() {
+            comments(&chars, cursor, start.index(), &mut spans);
+            cursor = start;
+        }
+        if let Some((class, span)) = class_span {
+            spans.push((span, class));
+            cursor = span.end;
+        }
+    }
+    Ok(spans)
+}
+
+fn advance(chars: &[char], mark: Marker) -> Marker {
+    let i = mark.index();
+    let byte = mark.byte() + chars[i].len_utf8();
+    let line_break = match chars[i] {
+        '\n' => true,
+        '\r' => chars.get(i + 1) != Some(&'\n'),
+        _ => false,
+    };
+    if line_break {
+        Marker::new(i + 1, byte, mark.line() + 1, 0)
+    } else {
+        Marker::new(i + 1, byte, mark.line(), mark.col() + 1)
+    }
+}
+
+// The span of `len` characters on one line, starting at `start`.
+fn span_of(chars: &[char], start: Marker, len: usize) -> Span {
+    let end = (0..len).fold(start, |mark, _| advance(chars, mark));
+    Span { start, end }
+}
+
+// A directive runs to the end of its line, less any comment.
+fn directive_len(line: &[char]) -> usize {
+    let mut len = line
+        .iter()
+        .position(|&c| c == '\n' || c == '\r')
+        .unwrap_or(line.len());
+    if let Some(comment) = (1..len).find(|&i| line[i] == '#' && line[i - 1].is_whitespace()) {
+        len = comment;
+    }
+    while len > 0 && line[len - 1].is_whitespace() {
+        len -= 1;
+    }
+    len
+}
+
+// Find the comments between tokens, from `from` up to index `to`.
+fn comments(chars: &[char], from: Marker, to: usize, out: &mut Vec<(Span, TokenClass)>) {
+    let mut mark = from;
+    while mark.index() < to {
+        if chars[mark.index()] != '#' {
+            mark = advance(chars, mark);
+            continue;
+        }
+        let start = mark;
+        while mark.index() < to && chars[mark.index()] != '\n' && chars[mark.index()] != '\r' {
+            mark = advance(chars, mark);
+        }
+        out.push((Span { start, end: mark }, TokenClass::Comment));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn classes(s: &str) -> Vec<(&str, TokenClass)> {
+        highlight(s)
+            .unwrap()
+            .into_iter()
+            .map(|(span, class)| (span.slice(s), class))
+            .collect()
+    }
+
+    #[test]
+    fn test_highlight() {
+        use TokenClass::*;
+
+        let s = "%YAML 1.2 # version
+---
+# list
+- !tag 'a ''b'''
+- \"c\\\"d\"
+- ? [k]
+  : {x: 1, y: 2}
+- |
+  block
+  text
+- plain
+  folded # trailing
+...
+";
+        assert_eq!(
+            classes(s),
+            vec![
+                ("%YAML 1.2", Directive),
+                ("# version", Comment),
+                ("---", Punctuation),
+                ("# list", Comment),
+                ("-", Punctuation),
+                ("!tag", Tag),
+                ("'a ''b'''", Scalar),
+                ("-", Punctuation),
+                ("\"c\\\"d\"", Scalar),
+                ("-", Punctuation),
+                ("?", Punctuation),
+                ("[", Punctuation),
+                ("k", Scalar),
+                ("]", Punctuation),
+                (":", Punctuation),
+                ("{", Punctuation),
+                ("x", Key),
+                (":", Punctuation),
+                ("1", Scalar),
+                (",", Punctuation),
+                ("y", Key),
+                (":", Punctuation),
+                ("2", Scalar),
+                ("}", Punctuation),
+                ("-", Punctuation),
+                ("|\n  block\n  text", Scalar),
+                ("-", Punctuation),
+                ("plain\n  folded", Scalar),
+                ("# trailing", Comment),
+                ("...", Punctuation),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_highlight_markers() {
+        let s = "a:\r\n  - é # c\r\n";
+        let spans = highlight(s).unwrap();
+        let (comment, class) = spans[spans.len() - 1];
+        assert_eq!(class, TokenClass::Comment);
+        assert_eq!((comment.start.line(), comment.start.col()), (2, 6));
+        assert_eq!(comment.slice(s), "# c");
+        assert!(highlight("a: 'b").is_err());
+    }
+}
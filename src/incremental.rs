@@ -0,0 +1,429 @@
+//! Loading a source again after an edit, parsing only the part of it that
+//! the edit can change, for editors that load on every keystroke.
+//!
+//! An edit inside one entry of a hash at the root of a document parses
+//! only that entry, when the document's keys start their lines. Any other
+//! edit parses the documents it touches. Either way the documents are the
+//! ones `YamlLoader::load_from_str` loads from the edited source.
+//!
+//! Entries are not parsed on their own in documents with anchors, aliases
+//! or directives, which reach across entries.
+//!
+//! # Examples
+//!
+//! ```
+//! # extern crate yaml_rust_davvid as yaml_rust;
+//! use yaml_rust::incremental::IncrementalLoader;
+//!
+//! let mut loader = IncrementalLoader::new("a: 1\nb: [x]\n---\nc: 3\n".to_owned());
+//! let parsed = loader.edit(9..10, "y").unwrap();
+//! assert_eq!(&loader.source()[parsed], "b: [y]\n");
+//! assert_eq!(loader.documents()[0]["b"][0].as_str(), Some("y"));
+//!
+//! assert!(loader.edit(9..10, "[").is_err());
+//! assert!(loader.documents().is_empty());
+//! ```
+
+use crate::scanner::ScanError;
+use crate::yaml::{Yaml, YamlLoader};
+
+use std::mem;
+use std::ops::Range;
+
+/// A source and the documents loaded from it, which are kept up to date
+/// as the source is edited.
+#[derive(Clone, Debug)]
+pub struct IncrementalLoader {
+    source: String,
+    docs: Vec<Yaml>,
+    layouts: Vec<Layout>,
+    error: Option<ScanError>,
+}
+
+// Where a document is in the source, in bytes.
+#[derive(Clone, Debug)]
+struct Layout {
+    // the source the document is loaded from, which runs from the end of
+    // the previous document's span, and to the end of the source for the
+    // last document
+    extent: Range<usize>,
+    span: Range<usize>,
+    // where each key of the root hash starts, if its entries can be parsed
+    // on their own
+    keys: Option<Vec<usize>>,
+}
+
+impl Layout {
+    fn shift(&mut self, delta: isize) {
+        self.extent = shift(self.extent.start, delta)..shift(self.extent.end, delta);
+        self.span = shift(self.span.start, delta)..shift(self.span.end, delta);
+        if let Some(ref mut keys) = self.keys {
+            for key in keys {
+                *key = shift(*key, delta);
+            }
+        }
+    }
+}
+
+fn shift(offset: usize, delta: isize) -> usize {
+    (offset as isize + delta) as usize
+}
+
+impl IncrementalLoader {
+    /// Load all documents in `source`. If it fails to load, the error is
+    /// kept, see `error`.
+    pub fn new(source: String) -> IncrementalLoader {
+        let mut loader = IncrementalLoader {
+            source,
+            docs: Vec::new(),
+            layouts: Vec::new(),
+            error: None,
+        };
+        let _ = loader.parse_all();
+        loader
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The documents of the source, or none if it failed to load.
+    pub fn documents(&self) -> &[Yaml] {
+        &self.docs
+    }
+
+    /// Why the source failed to load, if it did.
+    pub fn error(&self) -> Option<&ScanError> {
+        self.error.as_ref()
+    }
+
+    /// Replace the bytes of the source in `range` with `text` and load the
+    /// documents again, returning the range of the edited source that was
+    /// parsed. After an error the documents are empty, and the next edit
+    /// parses the whole source.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` is out of bounds or does not lie on `char`
+    /// boundaries, as `String::replace_range` does.
+    pub fn edit(&mut self, range: Range<usize>, text: &str) -> Result<Range<usize>, ScanError> {
+        let entry = self.find_entry(&range);
+        let docs = self.find_documents(&range);
+        let len = self.source.len();
+        self.source.replace_range(range, text);
+        let delta = self.source.len() as isize - len as isize;
+        if let Some((doc, index, entry)) = entry {
+            if let Some(parsed) = self.parse_entry(doc, index, entry, delta) {
+                return Ok(parsed);
+            }
+        }
+        match docs {
+            Some((first, last)) => self.parse_documents(first, last, delta),
+            None => self.parse_all(),
+        }
+    }
+
+    // The document, index and extent of the root hash entry that an edit
+    // of `range` stays within, if the entry can be parsed on its own. The
+    // line break that ends the entry must be kept, so that the entry can't
+    // run on into the next one.
+    fn find_entry(&self, range: &Range<usize>) -> Option<(usize, usize, Range<usize>)> {
+        if self.error.is_some() {
+            return None;
+        }
+        let doc = self
+            .layouts
+            .iter()
+            .position(|layout| layout.span.start <= range.start && range.end <= layout.span.end)?;
+        let layout = &self.layouts[doc];
+        let keys = layout.keys.as_ref()?;
+        let index = keys.iter().rposition(|&key| key < range.start)?;
+        let end = keys.get(index + 1).copied().unwrap_or(layout.span.end);
+        let text = &self.source[keys[index]..end];
+        if range.end >= end || !text.ends_with('\n') || lines(text).any(ends_document) {
+            return None;
+        }
+        Some((doc, index, keys[index]..end))
+    }
+
+    // Parse the entry of document `doc` at `index` again, if it is still a
+    // single entry with a key that is not in the rest of the hash.
+    fn parse_entry(
+        &mut self,
+        doc: usize,
+        index: usize,
+        entry: Range<usize>,
+        delta: isize,
+    ) -> Option<Range<usize>> {
+        let range = entry.start..shift(entry.end, delta);
+        if lines(&self.source[range.clone()])
+            .any(|line| ends_document(line) || line.starts_with("---"))
+        {
+            return None;
+        }
+        let (mut docs, layouts) = parse(&self.source, range.clone()).ok()?;
+        if docs.len() != 1 || layouts[0].keys.as_deref() != Some(&[range.start]) {
+            return None;
+        }
+        let (key, value) = match docs.pop() {
+            Some(Yaml::Hash(h)) => h.into_iter().next()?,
+            _ => return None,
+        };
+        let hash = match self.docs[doc] {
+            Yaml::Hash(ref mut h) => h,
+            _ => return None,
+        };
+        if hash
+            .keys()
+            .enumerate()
+            .any(|(i, k)| i != index && *k == key)
+        {
+            return None;
+        }
+        let mut entry = Some((key, value));
+        *hash = mem::take(hash)
+            .into_iter()
+            .enumerate()
+            .map(|(i, old)| {
+                if i == index {
+                    entry.take().unwrap()
+                } else {
+                    old
+                }
+            })
+            .collect();
+
+        let layout = &mut self.layouts[doc];
+        layout.extent.end = shift(layout.extent.end, delta);
+        layout.span.end = shift(layout.span.end, delta);
+        if let Some(ref mut keys) = layout.keys {
+            for key in &mut keys[index + 1..] {
+                *key = shift(*key, delta);
+            }
+        }
+        for layout in &mut self.layouts[doc + 1..] {
+            layout.shift(delta);
+        }
+        Some(range)
+    }
+
+    // The first and last of the documents that an edit of `range` can
+    // change. An edit of the first line of a document can join it to the
+    // previous one, and an edit of a document with or followed by a `...`
+    // can join the next one to it.
+    fn find_documents(&self, range: &Range<usize>) -> Option<(usize, usize)> {
+        if self.error.is_some() || self.layouts.is_empty() {
+            return None;
+        }
+        let last_doc = self.layouts.len() - 1;
+        let mut first = self
+            .layouts
+            .iter()
+            .position(|layout| layout.extent.end >= range.start)
+            .unwrap_or(last_doc);
+        let mut last = self
+            .layouts
+            .iter()
+            .rposition(|layout| layout.extent.start <= range.end)
+            .unwrap_or(0)
+            .max(first);
+        let span = &self.layouts[first].span;
+        if first > 0 && !self.source[span.start..range.start.max(span.start)].contains(['\n', '\r'])
+        {
+            first -= 1;
+        }
+        if last < last_doc {
+            let end = self.layouts[last + 1].span.start;
+            if lines(&self.source[self.layouts[last].span.start..end]).any(ends_document) {
+                last += 1;
+            }
+        }
+        Some((first, last))
+    }
+
+    // Parse documents `first` to `last` again.
+    fn parse_documents(
+        &mut self,
+        first: usize,
+        last: usize,
+        delta: isize,
+    ) -> Result<Range<usize>, ScanError> {
+        let extent = self.layouts[first].extent.start..shift(self.layouts[last].extent.end, delta);
+        let (docs, layouts) = match parse(&self.source, extent.clone()) {
+            Ok(parsed) => parsed,
+            Err(_) => return self.parse_all(),
+        };
+        for layout in &mut self.layouts[last + 1..] {
+            layout.shift(delta);
+        }
+        // text without documents belongs to the next document, or the
+        // previous one at the end of the source
+        if layouts.is_empty() {
+            if let Some(next) = self.layouts.get_mut(last + 1) {
+                next.extent.start = extent.start;
+            } else if first > 0 {
+                self.layouts[first - 1].extent.end = extent.end;
+            }
+        }
+        self.docs.splice(first..=last, docs);
+        self.layouts.splice(first..=last, layouts);
+        Ok(extent)
+    }
+
+    fn parse_all(&mut self) -> Result<Range<usize>, ScanError> {
+        let range = 0..self.source.len();
+        match parse(&self.source, range.clone()) {
+            Ok((docs, layouts)) => {
+                self.docs = docs;
+                self.layouts = layouts;
+                self.error = None;
+                Ok(range)
+            }
+            Err(err) => {
+                self.docs.clear();
+                self.layouts.clear();
+                self.error = Some(err.clone());
+                Err(err)
+            }
+        }
+    }
+}
+
+// YAML ends lines at a carriage return as well as a line feed.
+fn lines(text: &str) -> impl Iterator<Item = &str> {
+    text.split(['\n', '\r'])
+}
+
+fn ends_document(line: &str) -> bool {
+    line.starts_with("...")
+}
+
+// Load the documents in `source[range]`, with where they are in `source`.
+fn parse(source: &str, range: Range<usize>) -> Result<(Vec<Yaml>, Vec<Layout>), ScanError> {
+    let text = &source[range.clone()];
+    let loaded = YamlLoader::new().parse_from_str_with_roots(text)?;
+    let mut offsets = ByteOffsets::new(text, range.start);
+    let mut docs = Vec::with_capacity(loaded.len());
+    let mut layouts: Vec<Layout> = Vec::with_capacity(loaded.len());
+    let mut prev_end = range.start;
+    for (doc, span, root) in loaded {
+        let start = offsets.byte(span.start.index());
+        let block = root.start.is_some_and(|mark| {
+            let i = offsets.byte(mark.index());
+            !source[i..].starts_with('{')
+        });
+        let keys: Vec<usize> = root
+            .keys
+            .iter()
+            .map(|mark| offsets.byte(mark.index()))
+            .collect();
+        let end = offsets.byte(span.end.index());
+        let separate = block
+            && !root.anchors
+            && root.keys.iter().all(|mark| mark.col() == 0)
+            && doc.as_hash().is_some_and(|h| h.len() == keys.len())
+            && !lines(&source[prev_end..start]).any(|line| line.starts_with('%'));
+        layouts.push(Layout {
+            extent: prev_end..end,
+            span: start..end,
+            keys: separate.then_some(keys),
+        });
+        docs.push(doc);
+        prev_end = end;
+    }
+    if let Some(last) = layouts.last_mut() {
+        last.extent.end = range.end;
+    }
+    Ok((docs, layouts))
+}
+
+// Converts character indices of a text, which must not decrease, to byte
+// offsets in the source that the text starts at `origin` of.
+struct ByteOffsets<'a> {
+    text: &'a str,
+    origin: usize,
+    index: usize,
+    byte: usize,
+}
+
+impl<'a> ByteOffsets<'a> {
+    fn new(text: &'a str, origin: usize) -> ByteOffsets<'a> {
+        ByteOffsets {
+            text,
+            origin,
+            index: 0,
+            byte: 0,
+        }
+    }
+
+    fn byte(&mut self, index: usize) -> usize {
+        while self.index < index {
+            match self.text[self.byte..].chars().next() {
+                Some(c) => self.byte += c.len_utf8(),
+                None => break,
+            }
+            self.index += 1;
+        }
+        self.origin + self.byte
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Make each edit to `source` and compare with loading it from scratch.
+    fn check_edits(source: &str, edits: &[&str]) {
+        for start in 0..=source.len() {
+            for len in 0..3.min(source.len() - start + 1) {
+                let end = start + len;
+                if !source.is_char_boundary(start) || !source.is_char_boundary(end) {
+                    continue;
+                }
+                for text in edits {
+                    let mut loader = IncrementalLoader::new(source.to_owned());
+                    let result = loader.edit(start..end, text);
+                    let edited = loader.source().to_owned();
+                    match YamlLoader::load_from_str(&edited) {
+                        Ok(docs) => {
+                            assert!(result.is_ok(), "{:?}", edited);
+                            assert_eq!(loader.documents(), &docs[..], "{:?}", edited);
+                        }
+                        Err(err) => assert_eq!(result, Err(err), "{:?}", edited),
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_edits_match_load() {
+        let edits = [
+            "", "x", " ", "\n", ":", "- ", "'", "[", "#", "---\n", "...\n", "&a ", "é: 2\n",
+        ];
+        check_edits("a: 1\nb:\n  - c\n  - {d: e}\n# note\nf: |\n  g\n", &edits);
+        check_edits("a: 1\n---\n- b\n...\nc: d\n--- e\n", &edits);
+        check_edits("%TAG ! tag:x,1:\n--- \na: !b c\n", &edits);
+        check_edits("a: &x 1\nb: *x\n", &edits);
+        check_edits("ä: ö\r\nü:\r\n  ß: 1\r\n", &edits);
+        check_edits("{a: 1,\nb: 2}\n", &edits);
+        check_edits("a: 1\n...\n---\nb: 2\n", &edits);
+        check_edits("a: 1\n...\n# c\n...\nb: 2\n", &edits);
+        check_edits("--- a\n...\n%YAML 1.2\n--- b\n...\n", &edits);
+    }
+
+    #[test]
+    fn test_parsed_ranges() {
+        let mut loader = IncrementalLoader::new("a: 1\nb: [x]\n---\nc: 3\n".to_owned());
+        assert_eq!(loader.edit(3..4, "2"), Ok(0..5));
+        assert_eq!(loader.documents()[0]["a"], Yaml::Integer(2));
+        // a new entry parses the document
+        assert_eq!(loader.edit(5..5, "z: 0\n"), Ok(0..17));
+        assert_eq!(loader.edit(24..25, "4"), Ok(21..26));
+        assert_eq!(loader.documents()[1]["c"], Yaml::Integer(4));
+        // joining the documents parses both
+        assert_eq!(loader.edit(17..21, ""), Ok(0..22));
+        assert_eq!(loader.documents().len(), 1);
+        assert_eq!(loader.documents()[0]["c"], Yaml::Integer(4));
+    }
+}
@@ -0,0 +1,163 @@
+//! An index of the nodes of a document by their dotted path, for looking up
+//! many paths in the same document without walking it each time.
+//!
+//! Paths are written as for `config::Config::lookup`: segments separated by
+//! dots, each a hash key or an array index, with integer keys matched by
+//! their number. Looking up a path in the index gives the same node as
+//! walking the document, in constant time.
+//!
+//! # Examples
+//!
+//! ```
+//! # extern crate yaml_rust_davvid as yaml_rust;
+//! use yaml_rust::YamlLoader;
+//!
+//! let doc = &YamlLoader::load_from_str("site: {title: Home, tags: [a, b]}").unwrap()[0];
+//! let index = doc.to_index();
+//! assert_eq!(index.get("site.title").and_then(|n| n.as_str()), Some("Home"));
+//! assert_eq!(index.get("site.tags.1").and_then(|n| n.as_str()), Some("b"));
+//! assert!(index.get("site.missing").is_none());
+//!
+//! let paths: Vec<&str> = index.iter().map(|(path, _)| path).collect();
+//! assert_eq!(paths, ["", "site", "site.title", "site.tags", "site.tags.0", "site.tags.1"]);
+//! ```
+//!
+//! The index borrows the document, so the document can't be changed while
+//! the index is in use. Build a new index after changing it.
+
+use crate::yaml::{get_str_key, Hash, Yaml};
+
+use hashlink::LinkedHashMap;
+
+/// The nodes of a document by their dotted path, in document order, see
+/// `Yaml::to_index`.
+#[derive(Clone, Debug, Default)]
+pub struct YamlIndex<'a> {
+    nodes: LinkedHashMap<String, &'a Yaml>,
+}
+
+impl<'a> YamlIndex<'a> {
+    /// The node at `path`, where the empty path is the document itself.
+    pub fn get(&self, path: &str) -> Option<&'a Yaml> {
+        self.nodes.get(path).copied()
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.nodes.contains_key(path)
+    }
+
+    /// The number of nodes that can be looked up.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Iterate over the paths and their nodes in document order, parents
+    /// before their children.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &'a Yaml)> + '_ {
+        self.nodes.iter().map(|(path, &node)| (path.as_str(), node))
+    }
+
+    fn insert(&mut self, path: &mut String, node: &'a Yaml) {
+        self.nodes.insert(path.clone(), node);
+        let len = path.len();
+        match *node {
+            Yaml::Array(ref v) => {
+                for (i, child) in v.iter().enumerate() {
+                    push_segment(path, len, &i.to_string());
+                    self.insert(path, child);
+                }
+            }
+            Yaml::Hash(ref h) => {
+                for (key, child) in h {
+                    if let Some(segment) = segment(h, key) {
+                        push_segment(path, len, &segment);
+                        self.insert(path, child);
+                    }
+                }
+            }
+            _ => {}
+        }
+        path.truncate(len);
+    }
+}
+
+// Replace what follows the first `len` bytes of `path` with `segment`.
+fn push_segment(path: &mut String, len: usize, segment: &str) {
+    path.truncate(len);
+    if len > 0 {
+        path.push('.');
+    }
+    path.push_str(segment);
+}
+
+// The path segment that selects `key` of `hash`, if a path can select it.
+// A string key is found before an integer key written the same way, and a
+// key that is empty or has a dot can't be written as a segment.
+fn segment(hash: &Hash, key: &Yaml) -> Option<String> {
+    match *key {
+        Yaml::String(_) | Yaml::Interned(_) => {
+            let s = key.string_text().unwrap();
+            Some(s.to_owned()).filter(|s| !s.is_empty() && !s.contains('.'))
+        }
+        Yaml::Integer(i) => {
+            let s = i.to_string();
+            if get_str_key(hash, &s).is_some() {
+                None
+            } else {
+                Some(s)
+            }
+        }
+        _ => None,
+    }
+}
+
+impl Yaml {
+    /// Index every node of this document by its dotted path, see the
+    /// `index` module.
+    pub fn to_index(&self) -> YamlIndex<'_> {
+        let mut index = YamlIndex::default();
+        index.insert(&mut String::new(), self);
+        index
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::yaml::{lookup_path, YamlLoader};
+
+    #[test]
+    fn test_index_matches_lookup() {
+        let s = "
+a: {b: [1, {c: 2}], 3: x, '3': y}
+4: [z]
+d.e: 5
+'': 6
+? [k]
+: 7
+t: !tag {u: 8}
+";
+        let doc = &YamlLoader::load_from_str(s).unwrap()[0];
+        let index = doc.to_index();
+        let paths = [
+            "", "a", "a.b", "a.b.0", "a.b.1", "a.b.1.c", "a.3", "4", "4.0", "t",
+        ];
+        for path in &paths {
+            assert!(index.get(path).is_some(), "{}", path);
+        }
+        assert_eq!(index.len(), paths.len());
+        for (path, node) in index.iter() {
+            assert!(
+                std::ptr::eq(lookup_path(doc, path).unwrap(), node),
+                "{}",
+                path
+            );
+        }
+        assert_eq!(index.get("a.3").unwrap().as_str(), Some("y"));
+        assert!(!index.contains("d.e"));
+        assert!(!index.contains("t.u"));
+    }
+}
@@ -0,0 +1,577 @@
+//! Documents whose collections are parsed on first access.
+//!
+//! Loading scans each document once, building only the root and its direct
+//! children. A nested collection keeps the span of source it occupies and
+//! is parsed again, one level at a time, when it is first accessed, so a
+//! program that reads a few values from a large document only builds those.
+//!
+//! # Examples
+//!
+//! ```
+//! # extern crate yaml_rust_davvid as yaml_rust;
+//! use yaml_rust::lazy::LazyNode;
+//! use yaml_rust::Yaml;
+//!
+//! let s = "
+//! metadata: {name: web}
+//! spec:
+//!   containers:
+//!     - image: nginx
+//! ";
+//! let docs = LazyNode::load_from_str(s).unwrap();
+//! let metadata = docs[0].get("metadata").unwrap().unwrap();
+//! assert!(!metadata.is_loaded());
+//! assert_eq!(metadata.get("name").unwrap().unwrap().as_scalar(), Some(&Yaml::from_str("web")));
+//! assert!(metadata.is_loaded());
+//! assert!(!docs[0].get("spec").unwrap().unwrap().is_loaded());
+//! ```
+
+use crate::parser::{Event, Parser};
+use crate::scanner::{Marker, ScanError, Span};
+use crate::yaml::{application_tag, resolve_scalar, Hash, Tag, Yaml};
+
+use std::cell::{OnceCell, RefCell};
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+/// A node of a lazily loaded document. Cloning is cheap, and clones share
+/// the loaded children.
+#[derive(Clone)]
+pub struct LazyNode<'a> {
+    inner: Rc<Inner<'a>>,
+}
+
+enum Inner<'a> {
+    Scalar(Yaml),
+    Collection {
+        shared: Rc<Shared<'a>>,
+        span: Span,
+        mapping: bool,
+        tag: Option<Tag>,
+        // the `%TAG` directives of the document, for parsing the span
+        tags: Rc<HashMap<String, String>>,
+        // the error if the span failed to parse on its own
+        children: OnceCell<Result<Children<'a>, ScanError>>,
+    },
+}
+
+enum Children<'a> {
+    Array(Vec<LazyNode<'a>>),
+    Hash(Vec<(Yaml, LazyNode<'a>)>),
+}
+
+// State shared by the nodes of one stream.
+struct Shared<'a> {
+    source: &'a str,
+    // definitions of each anchor name, in document order
+    anchors: RefCell<HashMap<String, Vec<Anchor<'a>>>>,
+}
+
+struct Anchor<'a> {
+    start: usize,
+    end: usize,
+    node: LazyNode<'a>,
+}
+
+impl<'a> LazyNode<'a> {
+    /// Load all documents in `source`. The whole stream is scanned, so
+    /// syntax errors are reported here. Accessors only fail if a span
+    /// fails to parse on its own.
+    pub fn load_from_str(source: &'a str) -> Result<Vec<LazyNode<'a>>, ScanError> {
+        let shared = Rc::new(Shared {
+            source,
+            anchors: RefCell::new(HashMap::new()),
+        });
+        let mut parser = Parser::new(source.chars());
+        let mut collector = Collector {
+            parser: &mut parser,
+            shared: &shared,
+            text: source,
+            origin: Marker::new(0, 0, 1, 0),
+            register: true,
+            tags: Rc::default(),
+        };
+        let mut docs = Vec::new();
+        loop {
+            match collector.parser.next_event()?.0 {
+                Event::StreamEnd => return Ok(docs),
+                Event::DocumentStart => {
+                    collector.tags = Rc::new(collector.parser.tag_directives().clone());
+                    let (ev, mark) = collector.parser.next_event()?;
+                    docs.push(collector.node(ev, mark, true)?);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn scalar(value: Yaml) -> LazyNode<'a> {
+        LazyNode {
+            inner: Rc::new(Inner::Scalar(value)),
+        }
+    }
+
+    /// The value of a scalar node.
+    pub fn as_scalar(&self) -> Option<&Yaml> {
+        match *self.inner {
+            Inner::Scalar(ref value) => Some(value),
+            Inner::Collection { .. } => None,
+        }
+    }
+
+    /// The source a collection node occupies, not including its tag or
+    /// anchor.
+    pub fn span(&self) -> Option<Span> {
+        match *self.inner {
+            Inner::Collection { span, .. } => Some(span),
+            Inner::Scalar(_) => None,
+        }
+    }
+
+    /// Whether the children of a collection have been parsed. Scalars are
+    /// always loaded.
+    pub fn is_loaded(&self) -> bool {
+        match *self.inner {
+            Inner::Collection { ref children, .. } => children.get().is_some(),
+            Inner::Scalar(_) => true,
+        }
+    }
+
+    /// The number of elements or entries of a collection, loading it.
+    /// Scalars have none.
+    pub fn len(&self) -> Result<usize, ScanError> {
+        Ok(match self.children()? {
+            Some(Children::Array(v)) => v.len(),
+            Some(Children::Hash(v)) => v.len(),
+            None => 0,
+        })
+    }
+
+    pub fn is_empty(&self) -> Result<bool, ScanError> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Look up the value for a string key in a hash node, loading it. As
+    /// with `YamlLoader`, the last of duplicate keys wins.
+    pub fn get(&self, key: &str) -> Result<Option<&LazyNode<'a>>, ScanError> {
+        Ok(match self.children()? {
+            Some(Children::Hash(entries)) => entries
+                .iter()
+                .rfind(|(k, _)| matches!(*k, Yaml::String(ref s) if s == key))
+                .map(|(_, v)| v),
+            _ => None,
+        })
+    }
+
+    /// The element at `idx` of an array node, loading it.
+    pub fn get_index(&self, idx: usize) -> Result<Option<&LazyNode<'a>>, ScanError> {
+        Ok(match self.children()? {
+            Some(Children::Array(v)) => v.get(idx),
+            _ => None,
+        })
+    }
+
+    /// Load the whole node as a `Yaml` tree. A recursive alias, which
+    /// `YamlLoader` rejects, loads as `Yaml::BadValue`.
+    pub fn to_yaml(&self) -> Result<Yaml, ScanError> {
+        let tag = match *self.inner {
+            Inner::Scalar(ref value) => return Ok(value.clone()),
+            Inner::Collection { ref tag, .. } => tag,
+        };
+        let node = match self.children()? {
+            Some(Children::Array(v)) => {
+                Yaml::Array(v.iter().map(LazyNode::to_yaml).collect::<Result<_, _>>()?)
+            }
+            Some(Children::Hash(v)) => {
+                let mut hash = Hash::new();
+                for (key, value) in v {
+                    hash.insert(key.clone(), value.to_yaml()?);
+                }
+                Yaml::Hash(hash)
+            }
+            None => unreachable!("collections have children"),
+        };
+        Ok(match *tag {
+            Some(ref tag) => Yaml::Tagged(tag.clone(), Box::new(node)),
+            None => node,
+        })
+    }
+
+    // The children of a collection, parsing its span on first access. A
+    // span that failed to parse fails again on every access.
+    fn children(&self) -> Result<Option<&Children<'a>>, ScanError> {
+        match *self.inner {
+            Inner::Scalar(_) => Ok(None),
+            Inner::Collection {
+                ref shared,
+                span,
+                mapping,
+                ref tags,
+                ref children,
+                ..
+            } => match children.get_or_init(|| parse_span(shared, span, mapping, tags)) {
+                Ok(children) => Ok(Some(children)),
+                Err(err) => Err(err.clone()),
+            },
+        }
+    }
+}
+
+impl<'a> fmt::Debug for LazyNode<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self.inner {
+            Inner::Scalar(ref value) => f.debug_tuple("Scalar").field(value).finish(),
+            Inner::Collection {
+                span,
+                mapping,
+                ref children,
+                ..
+            } => f
+                .debug_struct(if mapping { "Hash" } else { "Array" })
+                .field("span", &span)
+                .field("loaded", &children.get().is_some())
+                .finish(),
+        }
+    }
+}
+
+// Parse the children of the collection at `span`. The text before the
+// collection on its first line is replaced by spaces, so block collections
+// keep their indentation.
+fn parse_span<'a>(
+    shared: &Rc<Shared<'a>>,
+    span: Span,
+    mapping: bool,
+    tags: &Rc<HashMap<String, String>>,
+) -> Result<Children<'a>, ScanError> {
+    let text = format!("{:1$}{2}", "", span.start.col(), span.slice(shared.source));
+    let mut parser = Parser::new(text.chars());
+    for name in shared.anchors.borrow().keys() {
+        parser.define_anchor(name.clone());
+    }
+    for (handle, prefix) in tags.iter() {
+        parser.define_tag_directive(handle.clone(), prefix.clone());
+    }
+    let mut collector = Collector {
+        parser: &mut parser,
+        shared,
+        text: &text,
+        origin: Marker::new(
+            span.start.index() - span.start.col(),
+            span.start.byte() - span.start.col(),
+            span.start.line(),
+            0,
+        ),
+        register: false,
+        tags: Rc::clone(tags),
+    };
+    loop {
+        match collector.parser.next_event()? {
+            (Event::SequenceStart(..), _) if !mapping => break,
+            (Event::MappingStart(..), _) if mapping => break,
+            (Event::StreamStart, _) | (Event::DocumentStart, _) => {}
+            (_, mark) => return Err(ScanError::new(mark, "expected a collection")),
+        }
+    }
+    collector.collect(mapping).map(|(children, _)| children)
+}
+
+// Builds the children of one collection from the events of a parser
+// positioned inside it.
+struct Collector<'p, 's, 'a, T: Iterator<Item = char>> {
+    parser: &'p mut Parser<T>,
+    shared: &'s Rc<Shared<'a>>,
+    // the text being parsed, which starts at `origin` in the source
+    text: &'s str,
+    origin: Marker,
+    // whether anchors are recorded, which happens in the first scan only
+    register: bool,
+    // the `%TAG` directives of the current document
+    tags: Rc<HashMap<String, String>>,
+}
+
+impl<'p, 's, 'a, T: Iterator<Item = char>> Collector<'p, 's, 'a, T> {
+    // Translate a marker in the parsed text to one in the source.
+    fn absolute(&self, mark: Marker) -> Marker {
+        Marker::new(
+            self.origin.index() + mark.index(),
+            self.origin.byte() + mark.byte(),
+            self.origin.line() + mark.line() - 1,
+            mark.col(),
+        )
+    }
+
+    // Consume the rest of the current collection, returning its children
+    // and the marker of its end event.
+    fn collect(&mut self, mapping: bool) -> Result<(Children<'a>, Marker), ScanError> {
+        let mut nodes = Vec::new();
+        loop {
+            let (ev, mark) = self.parser.next_event()?;
+            match ev {
+                Event::SequenceEnd | Event::MappingEnd => {
+                    let children = if mapping {
+                        let mut entries = Vec::with_capacity(nodes.len() / 2);
+                        let mut nodes = nodes.into_iter();
+                        while let (Some(key), Some(value)) = (nodes.next(), nodes.next()) {
+                            entries.push((LazyNode::to_yaml(&key)?, value));
+                        }
+                        Children::Hash(entries)
+                    } else {
+                        Children::Array(nodes)
+                    };
+                    return Ok((children, mark));
+                }
+                ev => nodes.push(self.node(ev, mark, false)?),
+            }
+        }
+    }
+
+    // Build the node starting with `ev`. Collections are skipped over
+    // unless `load` is set.
+    fn node(&mut self, ev: Event, mark: Marker, load: bool) -> Result<LazyNode<'a>, ScanError> {
+        let start = self.absolute(mark);
+        let (node, aid) = match ev {
+            Event::Scalar(v, style, aid, tag) => {
+                (LazyNode::scalar(resolve_scalar(v, style, &tag)), aid)
+            }
+            Event::Alias(id) => {
+                let name = self.parser.anchor_name(id).unwrap_or_default();
+                return Ok(self.resolve(name, start.index()));
+            }
+            Event::SequenceStart(aid, ref tag) | Event::MappingStart(aid, ref tag) => {
+                let mapping = matches!(ev, Event::MappingStart(..));
+                let (children, end) = if load {
+                    let (children, end) = self.collect(mapping)?;
+                    (OnceCell::from(Ok(children)), end)
+                } else {
+                    (OnceCell::new(), self.skip()?)
+                };
+                // a flow collection ends after its closing bracket
+                let mut end = self.absolute(end);
+                if matches!(
+                    self.text.as_bytes().get(mark.byte()),
+                    Some(b'[') | Some(b'{')
+                ) {
+                    end = Marker::new(end.index() + 1, end.byte() + 1, end.line(), end.col() + 1);
+                }
+                let node = LazyNode {
+                    inner: Rc::new(Inner::Collection {
+                        shared: Rc::clone(self.shared),
+                        span: Span { start, end },
+                        mapping,
+                        tag: application_tag(tag),
+                        tags: Rc::clone(&self.tags),
+                        children,
+                    }),
+                };
+                (node, aid)
+            }
+            _ => return Err(ScanError::new(mark, "expected a node")),
+        };
+        if aid > 0 && self.register {
+            let name = self.parser.anchor_name(aid).unwrap_or_default().to_owned();
+            let end = node.span().map_or(start.index(), |span| span.end.index());
+            self.shared
+                .anchors
+                .borrow_mut()
+                .entry(name)
+                .or_default()
+                .push(Anchor {
+                    start: start.index(),
+                    end,
+                    node: node.clone(),
+                });
+        }
+        Ok(node)
+    }
+
+    // Consume the rest of the current collection, recording any anchors in
+    // it, and return the marker of its end event.
+    fn skip(&mut self) -> Result<Marker, ScanError> {
+        loop {
+            let (ev, mark) = self.parser.next_event()?;
+            match ev {
+                Event::SequenceEnd | Event::MappingEnd => return Ok(mark),
+                ev => {
+                    self.node(ev, mark, false)?;
+                }
+            }
+        }
+    }
+
+    // The node an alias at `index` refers to: the latest definition of the
+    // anchor that ends before the alias.
+    fn resolve(&self, name: &str, index: usize) -> LazyNode<'a> {
+        let anchors = self.shared.anchors.borrow();
+        anchors
+            .get(name)
+            .and_then(|defs| defs.iter().rev().find(|def| def.end <= index))
+            .filter(|def| def.start < index)
+            .map_or_else(|| LazyNode::scalar(Yaml::BadValue), |def| def.node.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::YamlLoader;
+
+    #[test]
+    fn test_matches_loader() {
+        let s = "
+a: &x
+  b: [1, 2.5, 'c', {d: [e]}]
+  f: |
+    block
+c: *x
+? [k]
+: v
+list:
+- - nested
+  - &y {z: 1}
+- *y
+- !tag {t: 1}
+g: [[1], 2]
+---
+- &s scalar
+- *s
+";
+        let lazy = LazyNode::load_from_str(s).unwrap();
+        let docs = YamlLoader::load_from_str(s).unwrap();
+        let converted: Vec<_> = lazy.iter().map(|doc| doc.to_yaml().unwrap()).collect();
+        assert_eq!(converted, docs);
+    }
+
+    #[test]
+    fn test_loads_on_access() {
+        let s = "a: {b: [1, 2]}\nc: [3]";
+        let doc = &LazyNode::load_from_str(s).unwrap()[0];
+        assert!(doc.is_loaded());
+        let a = doc.get("a").unwrap().unwrap();
+        assert!(!a.is_loaded());
+        assert_eq!(a.span().unwrap().slice(s), "{b: [1, 2]}");
+
+        let b = a.get("b").unwrap().unwrap();
+        assert!(a.is_loaded());
+        assert!(!b.is_loaded());
+        assert_eq!(
+            b.get_index(1).unwrap().unwrap().as_scalar(),
+            Some(&Yaml::Integer(2))
+        );
+        assert_eq!(b.len().unwrap(), 2);
+        assert!(!doc.get("c").unwrap().unwrap().is_loaded());
+        assert!(doc.get("d").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_multibyte_source() {
+        let s = "é: {ü: [ß, 1]}\nb:\n  ñ: {c: 2}\n";
+        let doc = &LazyNode::load_from_str(s).unwrap()[0];
+        let a = doc.get("é").unwrap().unwrap();
+        assert_eq!(a.span().unwrap().slice(s), "{ü: [ß, 1]}");
+        let list = a.get("ü").unwrap().unwrap();
+        assert_eq!(list.span().unwrap().slice(s), "[ß, 1]");
+        assert_eq!(
+            list.get_index(0).unwrap().unwrap().as_scalar(),
+            Some(&Yaml::from_str("ß"))
+        );
+        let n = doc.get("b").unwrap().unwrap().get("ñ").unwrap().unwrap();
+        assert_eq!(n.span().unwrap().slice(s), "{c: 2}");
+        assert_eq!(
+            doc.to_yaml().unwrap(),
+            YamlLoader::load_from_str(s).unwrap().remove(0)
+        );
+    }
+
+    #[test]
+    fn test_last_duplicate_key() {
+        let s = "a: 1\nb: {c: x}\na: 2\nb: {c: y}\n";
+        let doc = &LazyNode::load_from_str(s).unwrap()[0];
+        assert_eq!(
+            doc.get("a").unwrap().unwrap().as_scalar(),
+            Some(&Yaml::Integer(2))
+        );
+        let b = doc.get("b").unwrap().unwrap();
+        assert_eq!(
+            b.get("c").unwrap().unwrap().as_scalar(),
+            Some(&Yaml::from_str("y"))
+        );
+    }
+
+    #[test]
+    fn test_span_error() {
+        let s = "a: [1, 2]\n";
+        let doc = &LazyNode::load_from_str(s).unwrap()[0];
+        let a = doc.get("a").unwrap().unwrap();
+        let (shared, span) = match *a.inner {
+            Inner::Collection {
+                ref shared, span, ..
+            } => (Rc::clone(shared), span),
+            Inner::Scalar(_) => unreachable!(),
+        };
+        // a span cut short of its closing bracket
+        let end = Marker::new(
+            span.end.index() - 1,
+            span.end.byte() - 1,
+            span.end.line(),
+            span.end.col() - 1,
+        );
+        let cut = LazyNode {
+            inner: Rc::new(Inner::Collection {
+                shared,
+                span: Span {
+                    start: span.start,
+                    end,
+                },
+                mapping: false,
+                tag: None,
+                tags: Rc::default(),
+                children: OnceCell::new(),
+            }),
+        };
+        assert!(cut.get_index(0).is_err());
+        assert!(cut.is_loaded());
+        assert!(cut.len().is_err());
+        assert!(cut.to_yaml().is_err());
+    }
+
+    #[test]
+    fn test_alias_into_later_span() {
+        // the alias is only resolved when `b` is parsed on its own
+        let s = "
+a: &x
+  - 1
+b:
+  c: *x
+  d: &x 2
+  e: *x
+";
+        let doc = &LazyNode::load_from_str(s).unwrap()[0];
+        let b = doc.get("b").unwrap().unwrap();
+        assert_eq!(
+            b.get("c").unwrap().unwrap().to_yaml().unwrap(),
+            Yaml::Array(vec![Yaml::Integer(1)])
+        );
+        assert_eq!(
+            b.get("e").unwrap().unwrap().as_scalar(),
+            Some(&Yaml::Integer(2))
+        );
+    }
+
+    #[test]
+    fn test_tag_directives() {
+        let s = "%TAG !e! tag:example.com,2000:\n---\na:\n  b: {c: !e!x 1}\n";
+        let doc = &LazyNode::load_from_str(s).unwrap()[0];
+        let b = doc.get("a").unwrap().unwrap().get("b").unwrap().unwrap();
+        assert!(!b.is_loaded());
+        match b.get("c").unwrap().unwrap().to_yaml().unwrap() {
+            Yaml::Tagged(tag, _) => assert_eq!(tag, Tag::new("tag:example.com,2000:", "x")),
+            other => panic!("expected a tagged node, found {:?}", other),
+        }
+        assert_eq!(
+            doc.to_yaml().unwrap(),
+            YamlLoader::load_from_str(s).unwrap().remove(0)
+        );
+    }
+}
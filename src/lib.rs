@@ -32,18 +32,44 @@
 //! ```
 
 #![doc(html_root_url = "https://docs.rs/yaml-rust-davvid/0.5.2")]
-#![cfg_attr(feature = "cargo-clippy", allow(renamed_and_removed_lints))]
-#![cfg_attr(feature = "cargo-clippy", warn(cognitive_complexity))]
-#![cfg_attr(
-    feature = "cargo-clippy",
-    allow(match_same_arms, should_implement_trait)
-)]
+#![warn(clippy::cognitive_complexity)]
+#![allow(clippy::match_same_arms, clippy::should_implement_trait)]
 
 extern crate hashlink;
 
+pub mod config;
+pub mod cursor;
+pub mod dedup;
 pub mod emitter;
+#[cfg(feature = "arbitrary")]
+pub mod generate;
+pub mod graph;
+pub mod highlight;
+pub mod incremental;
+pub mod index;
+pub mod lazy;
+pub mod marked;
+pub mod merge;
+#[cfg(feature = "memmap2")]
+pub mod mmap;
+pub mod node;
+pub mod number;
+pub mod overrides;
 pub mod parser;
+pub mod path;
+pub mod records;
+pub mod redact;
+pub mod roundtrip;
 pub mod scanner;
+pub mod schema;
+pub mod shared;
+pub mod stats;
+pub mod template;
+#[cfg(feature = "tokio")]
+pub mod tokio;
+#[cfg(feature = "toml")]
+pub mod toml;
+pub mod visit;
 pub mod yaml;
 
 // reexport key APIs
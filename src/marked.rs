@@ -0,0 +1,509 @@
+//! A document tree that remembers where and how each node was written.
+//!
+//! Every `MarkedNode` carries the position it starts at and its tag, and
+//! scalars keep their text and quoting style alongside the resolved value,
+//! so `"true"` can be told apart from `true` and `'1.0'` from `1.0`.
+//!
+//! # Examples
+//!
+//! ```
+//! # extern crate yaml_rust_davvid as yaml_rust;
+//! use yaml_rust::marked::MarkedNode;
+//! use yaml_rust::scanner::TScalarStyle;
+//! use yaml_rust::Yaml;
+//!
+//! let docs = MarkedNode::load_from_str("a: 'true'\nb: !!str 1.0").unwrap();
+//! let a = docs[0].get("a").unwrap();
+//! assert_eq!(a.text(), Some("true"));
+//! assert_eq!(a.style(), Some(TScalarStyle::SingleQuoted));
+//! assert_eq!(a.to_yaml(), Yaml::String("true".to_owned()));
+//!
+//! let b = docs[0].get("b").unwrap();
+//! assert_eq!(b.tag.as_ref().unwrap().suffix, "str");
+//! assert_eq!(b.marker.line(), 2);
+//!
+//! // keys have markers of their own, to point at `b:` rather than its value
+//! let (key, value) = docs[0].get_entry("b").unwrap();
+//! assert_eq!((key.marker.line(), key.marker.col()), (2, 0));
+//! assert_eq!((value.marker.line(), value.marker.col()), (2, 9));
+//! ```
+//!
+//! Each node also has an id that is unique among the nodes loaded
+//! together, and the id of its parent, so that a `NodeIndex` can find the
+//! node a diagnostic refers to and the nodes around it.
+//!
+//! ```
+//! # extern crate yaml_rust_davvid as yaml_rust;
+//! use yaml_rust::marked::{MarkedNode, NodeIndex};
+//!
+//! let docs = MarkedNode::load_from_str("server: {port: 80}").unwrap();
+//! let index = NodeIndex::new(&docs);
+//! let port = docs[0].get("server").and_then(|s| s.get("port")).unwrap();
+//! assert_eq!(index.key_of(port).and_then(MarkedNode::text), Some("port"));
+//! let server = index.parent(port).unwrap();
+//! assert_eq!(index.key_of(server).and_then(MarkedNode::text), Some("server"));
+//! assert_eq!(index.get(port.id), Some(port));
+//! ```
+
+use crate::parser::{Event, MarkedEventReceiver, Parser};
+use crate::scanner::{Marker, ScanError, TScalarStyle, TokenType};
+use crate::yaml::{resolve_scalar, Hash, Yaml};
+
+use std::collections::BTreeMap;
+
+pub use crate::yaml::Tag;
+
+/// The content of a `MarkedNode`.
+#[derive(Clone, PartialEq, Debug)]
+pub enum MarkedValue {
+    Scalar {
+        /// The value the scalar resolves to, as `YamlLoader` would load it.
+        value: Yaml,
+        /// The scalar's content before it was resolved, with quotes removed
+        /// and escapes processed.
+        text: String,
+        style: TScalarStyle,
+    },
+    Array(Vec<MarkedNode>),
+    /// Key and value pairs in document order.
+    Hash(Vec<(MarkedNode, MarkedNode)>),
+}
+
+/// Identifies a node among the nodes loaded together. Nodes are numbered
+/// from 0 in the order they start in the source, so loading the same
+/// source again gives the same ids.
+#[derive(Clone, Copy, PartialEq, PartialOrd, Debug, Eq, Ord, Hash)]
+pub struct NodeId(usize);
+
+impl NodeId {
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// A node along with its position and tag. An alias loads as a copy of the
+/// node it refers to, including that node's marker, with new ids.
+#[derive(Clone, PartialEq, Debug)]
+pub struct MarkedNode {
+    pub id: NodeId,
+    /// The collection the node is in, or `None` for a document root. Keys
+    /// and values of a hash both have the hash as their parent.
+    pub parent: Option<NodeId>,
+    pub marker: Marker,
+    pub tag: Option<Tag>,
+    pub value: MarkedValue,
+}
+
+impl MarkedNode {
+    /// Load all documents in `source`.
+    pub fn load_from_str(source: &str) -> Result<Vec<MarkedNode>, ScanError> {
+        let mut loader = MarkedLoader::default();
+        let mut parser = Parser::new(source.chars());
+        parser.load(&mut loader, true)?;
+        Ok(loader.docs)
+    }
+
+    /// The text of a scalar node, before it was resolved to a value.
+    pub fn text(&self) -> Option<&str> {
+        match self.value {
+            MarkedValue::Scalar { ref text, .. } => Some(text),
+            _ => None,
+        }
+    }
+
+    /// The quoting style of a scalar node.
+    pub fn style(&self) -> Option<TScalarStyle> {
+        match self.value {
+            MarkedValue::Scalar { style, .. } => Some(style),
+            _ => None,
+        }
+    }
+
+    /// Look up the value for a string key in a hash node.
+    pub fn get(&self, key: &str) -> Option<&MarkedNode> {
+        self.get_entry(key).map(|(_, v)| v)
+    }
+
+    /// Look up the key node and the value for a string key in a hash node,
+    /// such as to report a problem with an entry at its key.
+    pub fn get_entry(&self, key: &str) -> Option<(&MarkedNode, &MarkedNode)> {
+        match self.value {
+            MarkedValue::Hash(ref entries) => entries
+                .iter()
+                .find(|(k, _)| matches!(k.value, MarkedValue::Scalar { value: Yaml::String(ref s), .. } if s == key))
+                .map(|(k, v)| (k, v)),
+            _ => None,
+        }
+    }
+
+    /// The element at `idx` of an array node.
+    pub fn get_index(&self, idx: usize) -> Option<&MarkedNode> {
+        match self.value {
+            MarkedValue::Array(ref v) => v.get(idx),
+            _ => None,
+        }
+    }
+
+    /// Convert to a plain `Yaml` tree, discarding markers and styles.
+    /// Application tags are kept as `Yaml::Tagged`, as `YamlLoader` loads
+    /// them.
+    pub fn to_yaml(&self) -> Yaml {
+        let node = match self.value {
+            MarkedValue::Scalar { ref value, .. } => return value.clone(),
+            MarkedValue::Array(ref v) => Yaml::Array(v.iter().map(MarkedNode::to_yaml).collect()),
+            MarkedValue::Hash(ref v) => {
+                let mut hash = Hash::new();
+                for (key, value) in v {
+                    hash.insert(key.to_yaml(), value.to_yaml());
+                }
+                Yaml::Hash(hash)
+            }
+        };
+        match self.tag {
+            Some(ref tag) if !tag.is_core() => Yaml::Tagged(tag.clone(), Box::new(node)),
+            _ => node,
+        }
+    }
+}
+
+/// The nodes of documents loaded together by their ids, to find a node
+/// from its id and navigate from a node to the ones that contain it.
+#[derive(Clone, Debug)]
+pub struct NodeIndex<'a> {
+    nodes: Vec<Option<&'a MarkedNode>>,
+}
+
+impl<'a> NodeIndex<'a> {
+    pub fn new(docs: &'a [MarkedNode]) -> NodeIndex<'a> {
+        let mut index = NodeIndex { nodes: Vec::new() };
+        for doc in docs {
+            index.add(doc);
+        }
+        index
+    }
+
+    fn add(&mut self, node: &'a MarkedNode) {
+        let i = node.id.index();
+        if i >= self.nodes.len() {
+            self.nodes.resize(i + 1, None);
+        }
+        self.nodes[i] = Some(node);
+        match node.value {
+            MarkedValue::Scalar { .. } => {}
+            MarkedValue::Array(ref v) => v.iter().for_each(|item| self.add(item)),
+            MarkedValue::Hash(ref h) => {
+                for (key, value) in h {
+                    self.add(key);
+                    self.add(value);
+                }
+            }
+        }
+    }
+
+    /// The node with the id `id`.
+    pub fn get(&self, id: NodeId) -> Option<&'a MarkedNode> {
+        self.nodes.get(id.index()).copied().flatten()
+    }
+
+    /// The collection `node` is in.
+    pub fn parent(&self, node: &MarkedNode) -> Option<&'a MarkedNode> {
+        node.parent.and_then(|id| self.get(id))
+    }
+
+    /// The key that `node` is the value for, if it is a value in a hash.
+    pub fn key_of(&self, node: &MarkedNode) -> Option<&'a MarkedNode> {
+        match self.parent(node)?.value {
+            MarkedValue::Hash(ref h) => h
+                .iter()
+                .find(|(_, value)| value.id == node.id)
+                .map(|(key, _)| key),
+            _ => None,
+        }
+    }
+
+    /// The collections that contain `node`, from its parent to the root of
+    /// its document.
+    pub fn ancestors(&self, node: &MarkedNode) -> Vec<&'a MarkedNode> {
+        let mut ancestors = Vec::new();
+        let mut node = self.parent(node);
+        while let Some(parent) = node {
+            ancestors.push(parent);
+            node = self.parent(parent);
+        }
+        ancestors
+    }
+}
+
+#[derive(Default)]
+struct MarkedLoader {
+    docs: Vec<MarkedNode>,
+    // (node, anchor_id) of open collections, and the pending key of hashes
+    doc_stack: Vec<(MarkedNode, usize)>,
+    key_stack: Vec<Option<MarkedNode>>,
+    anchor_map: BTreeMap<usize, MarkedNode>,
+    next_id: usize,
+}
+
+impl MarkedLoader {
+    // The id for a new node and the id of its parent.
+    fn new_id(&mut self) -> (NodeId, Option<NodeId>) {
+        let id = NodeId(self.next_id);
+        self.next_id += 1;
+        (id, self.doc_stack.last().map(|(node, _)| node.id))
+    }
+
+    // Give a copy of a node and its children new ids.
+    fn renumber(&mut self, node: &mut MarkedNode, parent: Option<NodeId>) {
+        node.id = NodeId(self.next_id);
+        node.parent = parent;
+        self.next_id += 1;
+        let id = node.id;
+        match node.value {
+            MarkedValue::Scalar { .. } => {}
+            MarkedValue::Array(ref mut v) => {
+                v.iter_mut().for_each(|item| self.renumber(item, Some(id)))
+            }
+            MarkedValue::Hash(ref mut h) => {
+                for (key, value) in h {
+                    self.renumber(key, Some(id));
+                    self.renumber(value, Some(id));
+                }
+            }
+        }
+    }
+
+    fn insert_new_node(&mut self, node: (MarkedNode, usize)) {
+        // valid anchor id starts from 1
+        if node.1 > 0 {
+            self.anchor_map.insert(node.1, node.0.clone());
+        }
+        let parent = match self.doc_stack.last_mut() {
+            Some(parent) => parent,
+            None => {
+                self.docs.push(node.0);
+                return;
+            }
+        };
+        match parent.0.value {
+            MarkedValue::Array(ref mut v) => v.push(node.0),
+            MarkedValue::Hash(ref mut h) => {
+                let cur_key = self.key_stack.last_mut().unwrap();
+                match cur_key.take() {
+                    Some(key) => h.push((key, node.0)),
+                    None => *cur_key = Some(node.0),
+                }
+            }
+            MarkedValue::Scalar { .. } => unreachable!(),
+        }
+    }
+}
+
+fn collection(
+    ids: (NodeId, Option<NodeId>),
+    value: MarkedValue,
+    marker: Marker,
+    tag: Option<TokenType>,
+) -> MarkedNode {
+    MarkedNode {
+        id: ids.0,
+        parent: ids.1,
+        marker,
+        tag: to_tag(tag),
+        value,
+    }
+}
+
+fn to_tag(tag: Option<TokenType>) -> Option<Tag> {
+    match tag {
+        Some(TokenType::Tag(handle, suffix)) => Some(Tag { handle, suffix }),
+        _ => None,
+    }
+}
+
+impl MarkedEventReceiver for MarkedLoader {
+    fn on_event(&mut self, ev: Event, marker: Marker) {
+        match ev {
+            Event::SequenceStart(aid, tag) => {
+                let ids = self.new_id();
+                let node = collection(ids, MarkedValue::Array(Vec::new()), marker, tag);
+                self.doc_stack.push((node, aid));
+            }
+            Event::MappingStart(aid, tag) => {
+                let ids = self.new_id();
+                let node = collection(ids, MarkedValue::Hash(Vec::new()), marker, tag);
+                self.doc_stack.push((node, aid));
+                self.key_stack.push(None);
+            }
+            Event::SequenceEnd => {
+                let node = self.doc_stack.pop().unwrap();
+                self.insert_new_node(node);
+            }
+            Event::MappingEnd => {
+                self.key_stack.pop().unwrap();
+                let node = self.doc_stack.pop().unwrap();
+                self.insert_new_node(node);
+            }
+            Event::Scalar(text, style, aid, tag) => {
+                let value = resolve_scalar(text.clone(), style, &tag);
+                let (id, parent) = self.new_id();
+                let node = MarkedNode {
+                    id,
+                    parent,
+                    marker,
+                    tag: to_tag(tag),
+                    value: MarkedValue::Scalar { value, text, style },
+                };
+                self.insert_new_node((node, aid));
+            }
+            Event::Alias(id) => {
+                let node = match self.anchor_map.get(&id) {
+                    Some(node) => {
+                        let mut node = node.clone();
+                        let parent = self.doc_stack.last().map(|(node, _)| node.id);
+                        self.renumber(&mut node, parent);
+                        node
+                    }
+                    None => {
+                        let (id, parent) = self.new_id();
+                        MarkedNode {
+                            id,
+                            parent,
+                            marker,
+                            tag: None,
+                            value: MarkedValue::Scalar {
+                                value: Yaml::BadValue,
+                                text: String::new(),
+                                style: TScalarStyle::Plain,
+                            },
+                        }
+                    }
+                };
+                self.insert_new_node((node, 0));
+            }
+            _ => { /* ignore */ }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::YamlLoader;
+
+    #[test]
+    fn test_matches_loader() {
+        let s = "
+a: &x
+  b: [1, 2.5, 'c', !!int 3, !!bool nope]
+c: *x
+? [k]
+: v
+d: !pair {k: !Ref v}
+---
+- ~
+- \"\\t\"
+";
+        let marked = MarkedNode::load_from_str(s).unwrap();
+        let docs = YamlLoader::load_from_str(s).unwrap();
+        let converted: Vec<_> = marked.iter().map(MarkedNode::to_yaml).collect();
+        assert_eq!(converted, docs);
+    }
+
+    #[test]
+    fn test_text_and_style() {
+        let s = "
+- true
+- \"true\"
+- '1.0'
+- 1.0
+- |
+  block
+- !custom 7
+";
+        let doc = &MarkedNode::load_from_str(s).unwrap()[0];
+        let styles: Vec<_> = (0..6)
+            .map(|i| doc.get_index(i).unwrap().style().unwrap())
+            .collect();
+        assert_eq!(
+            styles,
+            vec![
+                TScalarStyle::Plain,
+                TScalarStyle::DoubleQuoted,
+                TScalarStyle::SingleQuoted,
+                TScalarStyle::Plain,
+                TScalarStyle::Literal,
+                TScalarStyle::Plain,
+            ]
+        );
+        assert_eq!(doc.get_index(0).unwrap().to_yaml(), Yaml::Boolean(true));
+        assert_eq!(
+            doc.get_index(1).unwrap().to_yaml(),
+            Yaml::String("true".to_owned())
+        );
+        assert_eq!(doc.get_index(4).unwrap().text(), Some("block\n"));
+
+        let custom = doc.get_index(5).unwrap();
+        assert_eq!(
+            custom.tag,
+            Some(Tag {
+                handle: "!".to_owned(),
+                suffix: "custom".to_owned()
+            })
+        );
+        assert_eq!(custom.text(), Some("7"));
+        assert_eq!((custom.marker.line(), custom.marker.col()), (8, 10));
+
+        assert_eq!(doc.text(), None);
+        assert_eq!(doc.style(), None);
+        assert!(doc.get("a").is_none());
+        assert!(doc.get_index(6).is_none());
+    }
+
+    #[test]
+    fn test_node_ids() {
+        let s = "
+a: &x {b: [1]}
+c: *x
+---
+- d
+";
+        let docs = MarkedNode::load_from_str(s).unwrap();
+        let index = NodeIndex::new(&docs);
+        // every node has a unique id in source order
+        let mut ids = Vec::new();
+        let mut stack: Vec<&MarkedNode> = docs.iter().rev().collect();
+        while let Some(node) = stack.pop() {
+            ids.push(node.id.index());
+            assert_eq!(index.get(node.id), Some(node));
+            match node.value {
+                MarkedValue::Scalar { .. } => {}
+                MarkedValue::Array(ref v) => stack.extend(v.iter().rev()),
+                MarkedValue::Hash(ref h) => {
+                    stack.extend(h.iter().rev().flat_map(|(k, v)| [v, k]));
+                }
+            }
+        }
+        assert_eq!(ids, (0..ids.len()).collect::<Vec<_>>());
+
+        let one = docs[0]
+            .get("c")
+            .and_then(|c| c.get("b"))
+            .and_then(|b| b.get_index(0));
+        let one = one.unwrap();
+        let keys: Vec<_> = index
+            .ancestors(one)
+            .into_iter()
+            .filter_map(|node| index.key_of(node).and_then(MarkedNode::text))
+            .collect();
+        assert_eq!(keys, ["b", "c"]);
+        assert_eq!(index.key_of(one), None);
+        assert_eq!(index.parent(&docs[0]), None);
+        assert_eq!(index.parent(docs[1].get_index(0).unwrap()), Some(&docs[1]));
+        let key = match docs[0].value {
+            MarkedValue::Hash(ref h) => &h[0].0,
+            _ => unreachable!(),
+        };
+        assert_eq!(index.parent(key), Some(&docs[0]));
+        assert_eq!(index.key_of(key), None);
+    }
+}
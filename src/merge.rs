@@ -0,0 +1,189 @@
+//! Merging one document into another, such as an overlay of settings
+//! into a base configuration.
+//!
+//! `Yaml::merge` merges hashes key by key, keeping the position of keys
+//! that both have and adding the others at the end, and otherwise lets the
+//! merged node replace the one it is merged into. A `MergeResolver` given
+//! to `Yaml::merge_with` decides instead wherever both documents have a
+//! node, so rules such as concatenating some lists can be kept in one
+//! place.
+//!
+//! # Examples
+//!
+//! ```
+//! # extern crate yaml_rust_davvid as yaml_rust;
+//! use yaml_rust::merge::merge_by_key;
+//! use yaml_rust::path::YamlPath;
+//! use yaml_rust::{Yaml, YamlLoader};
+//!
+//! let mut base = YamlLoader::load_from_str("
+//! image: app:1
+//! ports: [80]
+//! env: [{name: A, value: '1'}, {name: B, value: '2'}]
+//! ").unwrap().remove(0);
+//! let overlay = YamlLoader::load_from_str("
+//! image: app:2
+//! ports: [443]
+//! env: [{name: B, value: '3'}, {name: C, value: '4'}]
+//! ").unwrap().remove(0);
+//!
+//! let resolver = |path: &YamlPath, left: &Yaml, right: &Yaml| -> Option<Yaml> {
+//!     match (path.to_string().as_str(), left, right) {
+//!         ("$.ports", Yaml::Array(l), Yaml::Array(r)) => Some(Yaml::Array([&l[..], r].concat())),
+//!         ("$.env", Yaml::Array(l), Yaml::Array(r)) => Some(Yaml::Array(merge_by_key(l, r, "name"))),
+//!         _ => None,
+//!     }
+//! };
+//! base.merge_with(overlay, &resolver);
+//! let expected = YamlLoader::load_from_str("
+//! image: app:2
+//! ports: [80, 443]
+//! env: [{name: A, value: '1'}, {name: B, value: '3'}, {name: C, value: '4'}]
+//! ").unwrap();
+//! assert_eq!(base, expected[0]);
+//! ```
+
+use crate::path::{PathElement, YamlPath};
+use crate::yaml::{Array, Yaml};
+
+/// Decides the node a merge keeps where both documents have one, see
+/// `Yaml::merge_with`. Closures taking the same arguments are resolvers.
+pub trait MergeResolver {
+    /// The node to keep at `path`, where `left` is the node merged into and
+    /// `right` the node merged, or `None` to merge them as `Yaml::merge`
+    /// does.
+    fn resolve(&self, path: &YamlPath, left: &Yaml, right: &Yaml) -> Option<Yaml>;
+}
+
+impl<F: Fn(&YamlPath, &Yaml, &Yaml) -> Option<Yaml>> MergeResolver for F {
+    fn resolve(&self, path: &YamlPath, left: &Yaml, right: &Yaml) -> Option<Yaml> {
+        self(path, left, right)
+    }
+}
+
+// Leaves every conflict to the usual rules.
+struct DefaultResolver;
+
+impl MergeResolver for DefaultResolver {
+    fn resolve(&self, _: &YamlPath, _: &Yaml, _: &Yaml) -> Option<Yaml> {
+        None
+    }
+}
+
+impl Yaml {
+    /// Merge `other` into this node, see the `merge` module.
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::YamlLoader;
+    ///
+    /// let mut doc = YamlLoader::load_from_str("{a: {x: 1, y: 2}, b: [1]}").unwrap().remove(0);
+    /// let overlay = YamlLoader::load_from_str("{a: {y: 3, z: 4}, b: [2]}").unwrap().remove(0);
+    /// doc.merge(overlay);
+    /// assert_eq!(doc, YamlLoader::load_from_str("{a: {x: 1, y: 3, z: 4}, b: [2]}").unwrap()[0]);
+    /// ```
+    pub fn merge(&mut self, other: Yaml) {
+        self.merge_with(other, &DefaultResolver);
+    }
+
+    /// Merge `other` into this node, asking `resolver` first wherever both
+    /// have a node, starting with this node itself.
+    pub fn merge_with<R: MergeResolver + ?Sized>(&mut self, other: Yaml, resolver: &R) {
+        merge_at(self, other, &mut YamlPath::new(), resolver);
+    }
+}
+
+fn merge_at<R: MergeResolver + ?Sized>(
+    left: &mut Yaml,
+    right: Yaml,
+    path: &mut YamlPath,
+    resolver: &R,
+) {
+    if let Some(node) = resolver.resolve(path, left, &right) {
+        *left = node;
+        return;
+    }
+    match (left, right) {
+        (Yaml::Hash(l), Yaml::Hash(r)) => {
+            for (key, value) in r {
+                match l.get_mut(&key) {
+                    Some(node) => {
+                        path.push(PathElement::Key(key));
+                        merge_at(node, value, path, resolver);
+                        path.pop();
+                    }
+                    None => {
+                        l.insert(key, value);
+                    }
+                }
+            }
+        }
+        (left, right) => *left = right,
+    }
+}
+
+/// The items of `left` with those of `right` merged in by the string value
+/// of `key`: an item of `right` is merged into the item of `left` with the
+/// same value, as `Yaml::merge` does, or else added at the end. Items
+/// without the key are always added.
+pub fn merge_by_key(left: &[Yaml], right: &[Yaml], key: &str) -> Array {
+    let mut items = left.to_vec();
+    for item in right {
+        let id = item[key].as_str();
+        match items
+            .iter_mut()
+            .find(|old| id.is_some() && old[key].as_str() == id)
+        {
+            Some(old) => old.merge(item.clone()),
+            None => items.push(item.clone()),
+        }
+    }
+    items
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::YamlLoader;
+    use std::cell::RefCell;
+
+    fn load(s: &str) -> Yaml {
+        YamlLoader::load_from_str(s).unwrap().remove(0)
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut doc = load("{a: {x: 1}, b: 2, c: {y: 1}}");
+        doc.merge(load("{c: 3, a: {z: [1]}, d: 4}"));
+        assert_eq!(doc, load("{a: {x: 1, z: [1]}, b: 2, c: 3, d: 4}"));
+        let mut doc = load("[1]");
+        doc.merge(load("{a: 1}"));
+        assert_eq!(doc, load("{a: 1}"));
+
+        // the resolver sees every place both have a node, outermost first
+        let seen = RefCell::new(Vec::new());
+        let resolver = |path: &YamlPath, left: &Yaml, right: &Yaml| -> Option<Yaml> {
+            seen.borrow_mut().push(path.to_string());
+            match (left, right) {
+                (Yaml::Integer(l), Yaml::Integer(r)) => Some(Yaml::Integer(l + r)),
+                _ => None,
+            }
+        };
+        let mut doc = load("{a: {x: 1, y: [1]}, b: 2}");
+        doc.merge_with(load("{a: {x: 2, y: [2], z: 3}, c: 4}"), &resolver);
+        assert_eq!(doc, load("{a: {x: 3, y: [2], z: 3}, b: 2, c: 4}"));
+        assert_eq!(*seen.borrow(), ["$", "$.a", "$.a.x", "$.a.y"]);
+
+        let items = merge_by_key(
+            &load("[{name: a, v: 1}, {v: 2}]").into_vec().unwrap(),
+            &load("[{v: 3}, {name: a, w: 4}, {name: b}]")
+                .into_vec()
+                .unwrap(),
+            "name",
+        );
+        assert_eq!(
+            Yaml::Array(items),
+            load("[{name: a, v: 1, w: 4}, {v: 2}, {v: 3}, {name: b}]")
+        );
+    }
+}
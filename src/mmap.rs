@@ -0,0 +1,134 @@
+//! Loading YAML from memory-mapped files, enabled by the `memmap2` feature.
+//!
+//! `YamlLoader::load_from_mmap` parses a file straight from the mapping,
+//! without first reading it into a buffer. `MappedSource` keeps a mapping
+//! open so that a UTF-8 file can be borrowed as a `&str`, such as for
+//! `LazyNode`, which then only builds the nodes that are accessed.
+//!
+//! Mapping a file is unsafe because the mapped memory changes if the file
+//! is modified, including by another process, while it is mapped. Callers
+//! must make sure that the file is not modified or truncated until the
+//! mapping is dropped.
+//!
+//! # Examples
+//!
+//! ```
+//! # extern crate yaml_rust_davvid as yaml_rust;
+//! use yaml_rust::mmap::MappedSource;
+//! use yaml_rust::Yaml;
+//!
+//! let path = std::env::temp_dir().join("yaml-rust-mmap-doc.yaml");
+//! std::fs::write(&path, "name: web\nspec: {replicas: 3}\n").unwrap();
+//! // nothing modifies the file while it is mapped
+//! let source = unsafe { MappedSource::open(&path) }.unwrap();
+//! let docs = source.load_lazy().unwrap();
+//! let replicas = docs[0].get("spec").unwrap().unwrap().get("replicas").unwrap().unwrap();
+//! assert_eq!(replicas.as_scalar(), Some(&Yaml::Integer(3)));
+//! # drop(docs);
+//! # drop(source);
+//! # std::fs::remove_file(&path).unwrap();
+//! ```
+
+use crate::lazy::LazyNode;
+use crate::yaml::{LoadError, Yaml, YamlDecoder, YamlLoader};
+
+use memmap2::Mmap;
+use std::fs::File;
+use std::path::Path;
+use std::str;
+
+/// A file mapped into memory.
+pub struct MappedSource {
+    map: Mmap,
+    name: String,
+}
+
+impl MappedSource {
+    /// Map the file at `path`. Errors are annotated with the path.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified until the `MappedSource` is dropped,
+    /// see the module documentation.
+    pub unsafe fn open<P: AsRef<Path>>(path: P) -> Result<MappedSource, LoadError> {
+        let path = path.as_ref();
+        let name = path.display().to_string();
+        let map = File::open(path)
+            .and_then(|file| Mmap::map(&file))
+            .map_err(|err| LoadError::from(err).with_source_name(name.as_str()))?;
+        Ok(MappedSource { map, name })
+    }
+
+    /// The mapped bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.map
+    }
+
+    /// The mapped file as text, without a byte order mark. Fails if the
+    /// file is not UTF-8.
+    pub fn as_str(&self) -> Result<&str, LoadError> {
+        let bytes = self.map.strip_prefix(b"\xef\xbb\xbf").unwrap_or(&self.map);
+        str::from_utf8(bytes).map_err(|err| {
+            LoadError::Decode(format!("input is not UTF-8: {}", err).into())
+                .with_source_name(self.name.as_str())
+        })
+    }
+
+    /// Load all documents, detecting the encoding with `YamlDecoder`.
+    pub fn load(&self) -> Result<Vec<Yaml>, LoadError> {
+        YamlDecoder::read(self.as_bytes())
+            .decode()
+            .map_err(|err| err.with_source_name(self.name.as_str()))
+    }
+
+    /// Load all documents as `LazyNode`s that borrow the mapping. The file
+    /// must be UTF-8.
+    pub fn load_lazy(&self) -> Result<Vec<LazyNode<'_>>, LoadError> {
+        LazyNode::load_from_str(self.as_str()?)
+            .map_err(|err| LoadError::from(err).with_source_name(self.name.as_str()))
+    }
+}
+
+impl<'a> YamlLoader<'a> {
+    /// Load all documents from a file like `load_from_file`, parsing them
+    /// from a memory mapping of the file instead of reading it.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified while it is loaded, see the `mmap`
+    /// module documentation.
+    pub unsafe fn load_from_mmap<P: AsRef<Path>>(path: P) -> Result<Vec<Yaml>, LoadError> {
+        MappedSource::open(path)?.load()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_mmap() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("yaml-rust-mmap-{}.yaml", std::process::id()));
+        std::fs::write(&path, b"\xff\xfea\x00:\x00 \x001\x00").unwrap();
+        let docs = unsafe { YamlLoader::load_from_mmap(&path) }.unwrap();
+        assert_eq!(docs[0]["a"], Yaml::Integer(1));
+        let source = unsafe { MappedSource::open(&path) }.unwrap();
+        let err = source.load_lazy().err().unwrap();
+        assert!(err
+            .to_string()
+            .ends_with("input is not UTF-8: invalid utf-8 sequence of 1 bytes from index 0"));
+        drop(source);
+
+        std::fs::write(&path, "\u{feff}a: [1, 2]\nb: x: y\n").unwrap();
+        let source = unsafe { MappedSource::open(&path) }.unwrap();
+        assert!(source.as_str().unwrap().starts_with("a:"));
+        let err = source.load().unwrap_err();
+        assert_eq!(err.source_name(), Some(path.display().to_string().as_str()));
+        drop(source);
+        std::fs::remove_file(&path).unwrap();
+
+        let err = unsafe { MappedSource::open(&path) }.err().unwrap();
+        assert!(matches!(err, LoadError::WithSource { .. }));
+    }
+}
@@ -0,0 +1,584 @@
+//! Document trees that keep their hashes in another kind of map than
+//! `Yaml`'s `LinkedHashMap`, loaded straight from the source.
+//!
+//! A `Node<B>` has the variants of `Yaml`, with hashes of the type the
+//! `Backend` `B` chooses:
+//!
+//! - `Sorted` keeps the entries of every hash ordered by key, in a
+//!   `BTreeMap`, without sorting them after loading.
+//! - `Indexed` keeps them in document order in an `IndexMap`, for code that
+//!   works with `indexmap`. It needs the `indexmap` feature.
+//!
+//! Scalars resolve, and application tags are kept, as `YamlLoader` loads
+//! them by default.
+//!
+//! # Examples
+//!
+//! ```
+//! # extern crate yaml_rust_davvid as yaml_rust;
+//! use yaml_rust::node::{Node, Sorted};
+//! use yaml_rust::YamlLoader;
+//!
+//! let docs = Node::<Sorted>::load_from_str("{b: 1, a: {d: 2, c: 3}}").unwrap();
+//! let keys: Vec<_> = docs[0]["a"].entries().filter_map(|(k, _)| k.as_str()).collect();
+//! assert_eq!(keys, ["c", "d"]);
+//! assert_eq!(docs[0]["b"].as_i64(), Some(1));
+//!
+//! // back to a `Yaml` tree, in the sorted order
+//! let yaml = docs[0].to_yaml();
+//! assert_eq!(yaml, YamlLoader::load_from_str("{a: {c: 3, d: 2}, b: 1}").unwrap()[0]);
+//! ```
+
+use crate::parser::{Event, MarkedEventReceiver, Parser};
+use crate::scanner::{Marker, ScanError};
+use crate::yaml::{application_tag, parse_f64, resolve_scalar, Hash, Tag, Yaml};
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::hash::{self, Hasher};
+use std::mem;
+use std::ops::Index;
+
+/// How a `Node` stores its hashes.
+pub trait Backend: Sized + 'static {
+    /// A hash, from key nodes to value nodes. Equality, ordering and hashing
+    /// must agree with each other.
+    type Map: Clone + Ord + hash::Hash + fmt::Debug + Default;
+
+    /// Add an entry to `map`, replacing the value of an equal key.
+    fn insert(map: &mut Self::Map, key: Node<Self>, value: Node<Self>);
+
+    /// The value for `key` in `map`.
+    fn get<'a>(map: &'a Self::Map, key: &Node<Self>) -> Option<&'a Node<Self>>;
+
+    /// The entries of `map`, in the order it keeps them.
+    fn iter(map: &Self::Map) -> Box<dyn Iterator<Item = (&Node<Self>, &Node<Self>)> + '_>;
+
+    /// A `BadValue` to lend out when indexing misses.
+    fn bad_value() -> &'static Node<Self>;
+}
+
+/// Hashes ordered by key, in a `BTreeMap`.
+#[derive(Clone, Copy, PartialEq, Debug, Eq)]
+pub enum Sorted {}
+
+impl Backend for Sorted {
+    type Map = BTreeMap<Node<Sorted>, Node<Sorted>>;
+
+    fn insert(map: &mut Self::Map, key: Node<Self>, value: Node<Self>) {
+        map.insert(key, value);
+    }
+
+    fn get<'a>(map: &'a Self::Map, key: &Node<Self>) -> Option<&'a Node<Self>> {
+        map.get(key)
+    }
+
+    fn iter(map: &Self::Map) -> Box<dyn Iterator<Item = (&Node<Self>, &Node<Self>)> + '_> {
+        Box::new(map.iter())
+    }
+
+    fn bad_value() -> &'static Node<Self> {
+        static BAD_VALUE: Node<Sorted> = Node::BadValue;
+        &BAD_VALUE
+    }
+}
+
+/// Hashes in document order, in an `IndexMap`.
+#[cfg(feature = "indexmap")]
+#[derive(Clone, Copy, PartialEq, Debug, Eq)]
+pub enum Indexed {}
+
+/// An `IndexMap` that compares its entries in order, as `Yaml`'s hashes
+/// do, so that it can be a hash key itself. Derefs to the `IndexMap`.
+#[cfg(feature = "indexmap")]
+#[derive(Clone, Debug, Default)]
+pub struct IndexHash(pub indexmap::IndexMap<Node<Indexed>, Node<Indexed>>);
+
+#[cfg(feature = "indexmap")]
+impl std::ops::Deref for IndexHash {
+    type Target = indexmap::IndexMap<Node<Indexed>, Node<Indexed>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl std::ops::DerefMut for IndexHash {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl PartialEq for IndexHash {
+    fn eq(&self, other: &IndexHash) -> bool {
+        self.0.iter().eq(other.0.iter())
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl Eq for IndexHash {}
+
+#[cfg(feature = "indexmap")]
+impl PartialOrd for IndexHash {
+    fn partial_cmp(&self, other: &IndexHash) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl Ord for IndexHash {
+    fn cmp(&self, other: &IndexHash) -> Ordering {
+        self.0.iter().cmp(other.0.iter())
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl hash::Hash for IndexHash {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.len().hash(state);
+        for entry in self.0.iter() {
+            entry.hash(state);
+        }
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl Backend for Indexed {
+    type Map = IndexHash;
+
+    fn insert(map: &mut Self::Map, key: Node<Self>, value: Node<Self>) {
+        map.0.insert(key, value);
+    }
+
+    fn get<'a>(map: &'a Self::Map, key: &Node<Self>) -> Option<&'a Node<Self>> {
+        map.0.get(key)
+    }
+
+    fn iter(map: &Self::Map) -> Box<dyn Iterator<Item = (&Node<Self>, &Node<Self>)> + '_> {
+        Box::new(map.0.iter())
+    }
+
+    fn bad_value() -> &'static Node<Self> {
+        static BAD_VALUE: Node<Indexed> = Node::BadValue;
+        &BAD_VALUE
+    }
+}
+
+/// A node of a document whose hashes are stored as `B` chooses. The
+/// variants are those of `Yaml`, except that aliases are always resolved.
+#[non_exhaustive]
+pub enum Node<B: Backend> {
+    /// A real, as written in the document, see `Yaml::Real`.
+    Real(String),
+    Integer(i64),
+    String(String),
+    Boolean(bool),
+    Array(Vec<Node<B>>),
+    Hash(B::Map),
+    /// A node with an application tag, see `Yaml::Tagged`.
+    Tagged(Tag, Box<Node<B>>),
+    Null,
+    /// A scalar that is invalid for its tag, or a missing node found by
+    /// indexing.
+    BadValue,
+}
+
+// The traits are implemented by hand: derived ones would require them of
+// `B::Map`, which requires them of `Node<B>` in turn.
+
+impl<B: Backend> Clone for Node<B> {
+    fn clone(&self) -> Self {
+        match *self {
+            Node::Real(ref v) => Node::Real(v.clone()),
+            Node::Integer(v) => Node::Integer(v),
+            Node::String(ref v) => Node::String(v.clone()),
+            Node::Boolean(v) => Node::Boolean(v),
+            Node::Array(ref v) => Node::Array(v.clone()),
+            Node::Hash(ref v) => Node::Hash(v.clone()),
+            Node::Tagged(ref tag, ref v) => Node::Tagged(tag.clone(), v.clone()),
+            Node::Null => Node::Null,
+            Node::BadValue => Node::BadValue,
+        }
+    }
+}
+
+impl<B: Backend> Node<B> {
+    // The position of the variant, which orders nodes of different types.
+    fn rank(&self) -> u8 {
+        match *self {
+            Node::Real(_) => 0,
+            Node::Integer(_) => 1,
+            Node::String(_) => 2,
+            Node::Boolean(_) => 3,
+            Node::Array(_) => 4,
+            Node::Hash(_) => 5,
+            Node::Tagged(..) => 6,
+            Node::Null => 7,
+            Node::BadValue => 8,
+        }
+    }
+}
+
+impl<B: Backend> PartialEq for Node<B> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<B: Backend> Eq for Node<B> {}
+
+impl<B: Backend> PartialOrd for Node<B> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<B: Backend> Ord for Node<B> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Node::Real(a), Node::Real(b)) | (Node::String(a), Node::String(b)) => a.cmp(b),
+            (Node::Integer(a), Node::Integer(b)) => a.cmp(b),
+            (Node::Boolean(a), Node::Boolean(b)) => a.cmp(b),
+            (Node::Array(a), Node::Array(b)) => a.cmp(b),
+            (Node::Hash(a), Node::Hash(b)) => a.cmp(b),
+            (Node::Tagged(ta, a), Node::Tagged(tb, b)) => ta.cmp(tb).then_with(|| a.cmp(b)),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl<B: Backend> hash::Hash for Node<B> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+        match *self {
+            Node::Real(ref v) | Node::String(ref v) => v.hash(state),
+            Node::Integer(v) => v.hash(state),
+            Node::Boolean(v) => v.hash(state),
+            Node::Array(ref v) => v.hash(state),
+            Node::Hash(ref v) => v.hash(state),
+            Node::Tagged(ref tag, ref v) => {
+                tag.hash(state);
+                v.hash(state);
+            }
+            Node::Null | Node::BadValue => {}
+        }
+    }
+}
+
+impl<B: Backend> fmt::Debug for Node<B> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Node::Real(ref v) => f.debug_tuple("Real").field(v).finish(),
+            Node::Integer(v) => f.debug_tuple("Integer").field(&v).finish(),
+            Node::String(ref v) => f.debug_tuple("String").field(v).finish(),
+            Node::Boolean(v) => f.debug_tuple("Boolean").field(&v).finish(),
+            Node::Array(ref v) => f.debug_tuple("Array").field(v).finish(),
+            Node::Hash(ref v) => f.debug_tuple("Hash").field(v).finish(),
+            Node::Tagged(ref tag, ref v) => f.debug_tuple("Tagged").field(tag).field(v).finish(),
+            Node::Null => f.write_str("Null"),
+            Node::BadValue => f.write_str("BadValue"),
+        }
+    }
+}
+
+impl<B: Backend> Node<B> {
+    /// Load all documents in `source`.
+    pub fn load_from_str(source: &str) -> Result<Vec<Node<B>>, ScanError> {
+        let mut loader = NodeLoader::new();
+        Parser::new(source.chars()).load(&mut loader, true)?;
+        Ok(loader.docs)
+    }
+
+    /// The value under any application tags, see `Yaml::untagged`.
+    pub fn untagged(&self) -> &Node<B> {
+        match *self {
+            Node::Tagged(_, ref v) => v.untagged(),
+            _ => self,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match *self.untagged() {
+            Node::String(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self.untagged() {
+            Node::Integer(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self.untagged() {
+            Node::Real(ref v) => parse_f64(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self.untagged() {
+            Node::Boolean(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_vec(&self) -> Option<&Vec<Node<B>>> {
+        match *self.untagged() {
+            Node::Array(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_hash(&self) -> Option<&B::Map> {
+        match *self.untagged() {
+            Node::Hash(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn is_badvalue(&self) -> bool {
+        matches!(*self, Node::BadValue)
+    }
+
+    /// Look up a string key in a hash.
+    pub fn get(&self, key: &str) -> Option<&Node<B>> {
+        self.as_hash()
+            .and_then(|h| B::get(h, &Node::String(key.to_owned())))
+    }
+
+    /// The entries of a hash in the order its backend keeps them. Yields
+    /// nothing if `self` is not a hash.
+    pub fn entries(&self) -> impl Iterator<Item = (&Node<B>, &Node<B>)> {
+        self.as_hash().into_iter().flat_map(B::iter)
+    }
+
+    /// Convert to a `Yaml` tree, with hash entries in the order the backend
+    /// keeps them.
+    pub fn to_yaml(&self) -> Yaml {
+        match *self {
+            Node::Real(ref v) => Yaml::Real(v.clone()),
+            Node::Integer(v) => Yaml::Integer(v),
+            Node::String(ref v) => Yaml::String(v.clone()),
+            Node::Boolean(v) => Yaml::Boolean(v),
+            Node::Array(ref v) => Yaml::Array(v.iter().map(Node::to_yaml).collect()),
+            Node::Hash(ref h) => Yaml::Hash(
+                B::iter(h)
+                    .map(|(k, v)| (k.to_yaml(), v.to_yaml()))
+                    .collect(),
+            ),
+            Node::Tagged(ref tag, ref v) => Yaml::Tagged(tag.clone(), Box::new(v.to_yaml())),
+            Node::Null => Yaml::Null,
+            Node::BadValue => Yaml::BadValue,
+        }
+    }
+
+    // A resolved scalar, or `BadValue` for an alias `Yaml` can't hold.
+    fn from_scalar(value: Yaml) -> Node<B> {
+        match value {
+            Yaml::Real(v) => Node::Real(v),
+            Yaml::Integer(v) => Node::Integer(v),
+            Yaml::String(v) => Node::String(v),
+            Yaml::Boolean(v) => Node::Boolean(v),
+            Yaml::Tagged(tag, v) => Node::Tagged(tag, Box::new(Node::from_scalar(*v))),
+            Yaml::Null => Node::Null,
+            _ => Node::BadValue,
+        }
+    }
+}
+
+impl<'a, B: Backend> Index<&'a str> for Node<B> {
+    type Output = Node<B>;
+
+    fn index(&self, key: &'a str) -> &Node<B> {
+        self.get(key).unwrap_or(B::bad_value())
+    }
+}
+
+impl<B: Backend> Index<usize> for Node<B> {
+    type Output = Node<B>;
+
+    fn index(&self, idx: usize) -> &Node<B> {
+        self.as_vec()
+            .and_then(|v| v.get(idx))
+            .unwrap_or(B::bad_value())
+    }
+}
+
+impl<B: Backend> From<Yaml> for Node<B> {
+    /// Convert a `Yaml` tree, adding hash entries to the backend's map in
+    /// their order. Aliases become `BadValue`.
+    fn from(yaml: Yaml) -> Node<B> {
+        match yaml {
+            Yaml::Array(v) => Node::Array(v.into_iter().map(Node::from).collect()),
+            Yaml::Hash(h) => Node::Hash(hash_from::<B>(h)),
+            Yaml::Tagged(tag, v) => Node::Tagged(tag, Box::new(Node::from(*v))),
+            scalar => Node::from_scalar(scalar),
+        }
+    }
+}
+
+fn hash_from<B: Backend>(hash: Hash) -> B::Map {
+    let mut map = B::Map::default();
+    for (k, v) in hash {
+        B::insert(&mut map, Node::from(k), Node::from(v));
+    }
+    map
+}
+
+struct NodeLoader<B: Backend> {
+    docs: Vec<Node<B>>,
+    // (node, anchor_id) of open collections with their application tags,
+    // and the pending key of hashes
+    doc_stack: Vec<(Node<B>, usize, Option<Tag>)>,
+    key_stack: Vec<Option<Node<B>>>,
+    anchor_map: BTreeMap<usize, Node<B>>,
+}
+
+impl<B: Backend> NodeLoader<B> {
+    fn new() -> NodeLoader<B> {
+        NodeLoader {
+            docs: Vec::new(),
+            doc_stack: Vec::new(),
+            key_stack: Vec::new(),
+            anchor_map: BTreeMap::new(),
+        }
+    }
+
+    fn insert_new_node(&mut self, node: Node<B>, aid: usize) {
+        // valid anchor id starts from 1
+        if aid > 0 {
+            self.anchor_map.insert(aid, node.clone());
+        }
+        let parent = match self.doc_stack.last_mut() {
+            Some(parent) => parent,
+            None => {
+                self.docs.push(node);
+                return;
+            }
+        };
+        match parent.0 {
+            Node::Array(ref mut v) => v.push(node),
+            Node::Hash(ref mut h) => {
+                let cur_key = self.key_stack.last_mut().unwrap();
+                match cur_key.take() {
+                    Some(key) => B::insert(h, key, node),
+                    None => *cur_key = Some(node),
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn end_collection(&mut self) {
+        let (node, aid, tag) = self.doc_stack.pop().unwrap();
+        let node = match tag {
+            Some(tag) => Node::Tagged(tag, Box::new(node)),
+            None => node,
+        };
+        self.insert_new_node(node, aid);
+    }
+}
+
+impl<B: Backend> MarkedEventReceiver for NodeLoader<B> {
+    fn on_event(&mut self, ev: Event, _: Marker) {
+        match ev {
+            Event::SequenceStart(aid, tag) => {
+                let tag = application_tag(&tag);
+                self.doc_stack.push((Node::Array(Vec::new()), aid, tag));
+            }
+            Event::MappingStart(aid, tag) => {
+                let tag = application_tag(&tag);
+                self.doc_stack
+                    .push((Node::Hash(B::Map::default()), aid, tag));
+                self.key_stack.push(None);
+            }
+            Event::SequenceEnd => self.end_collection(),
+            Event::MappingEnd => {
+                self.key_stack.pop().unwrap();
+                self.end_collection();
+            }
+            Event::Scalar(v, style, aid, tag) => {
+                let node = Node::from_scalar(resolve_scalar(v, style, &tag));
+                self.insert_new_node(node, aid);
+            }
+            Event::Alias(id) => {
+                let node = match self.anchor_map.get(&id) {
+                    Some(node) => node.clone(),
+                    None => Node::BadValue,
+                };
+                self.insert_new_node(node, 0);
+            }
+            _ => { /* ignore */ }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::YamlLoader;
+
+    #[test]
+    fn test_sorted() {
+        let s = "
+z: &x {b: [2, 1], a: !t {d: 1, c: 2}}
+y: *x
+? {k: 1}
+: v
+---
+- 3
+";
+        let docs = Node::<Sorted>::load_from_str(s).unwrap();
+        assert_eq!(docs.len(), 2);
+        let keys: Vec<_> = docs[0].entries().map(|(k, _)| k.to_yaml()).collect();
+        assert_eq!(
+            keys,
+            YamlLoader::load_from_str("[y, z, {k: 1}]").unwrap()[0]
+                .as_vec()
+                .unwrap()
+                .clone()
+        );
+        let nested: Vec<_> = docs[0]["y"]["a"]
+            .entries()
+            .map(|(k, _)| k.clone())
+            .collect();
+        assert_eq!(nested, [Node::String("c".into()), Node::String("d".into())]);
+        assert!(matches!(docs[0]["z"]["a"], Node::Tagged(..)));
+        assert_eq!(docs[0]["z"]["b"][1].as_i64(), Some(1));
+        assert!(docs[0]["z"]["c"].is_badvalue());
+        assert_eq!(docs[1][0].as_i64(), Some(3));
+
+        // the same as converting what `YamlLoader` loads
+        let yaml = YamlLoader::load_from_str(s).unwrap();
+        let converted: Vec<Node<Sorted>> = yaml.into_iter().map(Node::from).collect();
+        assert_eq!(docs, converted);
+    }
+
+    #[cfg(feature = "indexmap")]
+    #[test]
+    fn test_indexed() {
+        let s = "{z: 1, 2: b, a: {y: [c], x: !!str 3}}";
+        let docs = Node::<Indexed>::load_from_str(s).unwrap();
+        let hash = docs[0].as_hash().unwrap();
+        assert_eq!(hash.get_index(1).unwrap().1.as_str(), Some("b"));
+        assert_eq!(
+            docs[0]["a"]
+                .as_hash()
+                .unwrap()
+                .get_index_of(&Node::String("x".into())),
+            Some(1)
+        );
+        assert_eq!(docs[0]["a"]["x"].as_str(), Some("3"));
+        assert_eq!(docs[0].to_yaml(), YamlLoader::load_from_str(s).unwrap()[0]);
+
+        // entries compare in order
+        let swapped = Node::<Indexed>::load_from_str("{2: b, z: 1, a: {y: [c], x: '3'}}").unwrap();
+        assert_ne!(docs[0], swapped[0]);
+    }
+}
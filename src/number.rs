@@ -0,0 +1,206 @@
+//! One type for the numbers of a document, whichever variant holds them.
+//!
+//! `Yaml` keeps integers in `Yaml::Integer` and reals as the text of
+//! `Yaml::Real`, and an integer too large for an `i64` loads as a real. A
+//! `Number` holds any of them as an `i64`, a `u64` or an `f64`. Its
+//! accessors only succeed when the value converts without loss, and it
+//! converts back to the `Yaml` that loads as the same value.
+//!
+//! # Examples
+//!
+//! ```
+//! # extern crate yaml_rust_davvid as yaml_rust;
+//! use yaml_rust::number::Number;
+//! use yaml_rust::{Yaml, YamlLoader};
+//!
+//! let doc = YamlLoader::load_from_str("[-1, 18446744073709551615, 2.5, .inf]").unwrap().remove(0);
+//! let numbers: Vec<Number> = doc.as_vec().unwrap().iter().filter_map(Yaml::as_number).collect();
+//! assert_eq!(numbers[0].as_i64(), Some(-1));
+//! assert_eq!(numbers[1].as_u64(), Some(u64::MAX));
+//! assert_eq!(numbers[1].as_i64(), None);
+//! assert_eq!(numbers[2].as_f64(), Some(2.5));
+//! assert_eq!(numbers[3].as_f64(), Some(f64::INFINITY));
+//!
+//! assert_eq!(Yaml::from(Number::from(7u64)), Yaml::Integer(7));
+//! assert_eq!(Yaml::from(Number::from(0.5)), Yaml::Real("0.5".to_owned()));
+//! ```
+
+use crate::yaml::{format_real, parse_f64, Yaml};
+
+use std::fmt;
+
+/// An integer or a real, see the `number` module.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Number(N);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum N {
+    PosInt(u64),
+    // always below zero
+    NegInt(i64),
+    Float(f64),
+}
+
+// 2^63 and 2^64, the first values past the range of `i64` and `u64`
+const I64_END: f64 = 9_223_372_036_854_775_808.0;
+const U64_END: f64 = 18_446_744_073_709_551_616.0;
+
+impl Number {
+    /// Whether the number is an integer that fits an `i64`.
+    pub fn is_i64(&self) -> bool {
+        self.as_i64().is_some()
+    }
+
+    /// Whether the number is an integer that fits a `u64`.
+    pub fn is_u64(&self) -> bool {
+        self.as_u64().is_some()
+    }
+
+    /// Whether the number is a real, even one with no fraction.
+    pub fn is_f64(&self) -> bool {
+        matches!(self.0, N::Float(_))
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self.0 {
+            N::PosInt(u) => i64::try_from(u).ok(),
+            N::NegInt(i) => Some(i),
+            N::Float(_) => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self.0 {
+            N::PosInt(u) => Some(u),
+            N::NegInt(_) | N::Float(_) => None,
+        }
+    }
+
+    /// The value of a real, or of an integer that an `f64` represents
+    /// exactly.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self.0 {
+            N::PosInt(u) => {
+                let f = u as f64;
+                Some(f).filter(|&f| f < U64_END && f as u64 == u)
+            }
+            N::NegInt(i) => {
+                let f = i as f64;
+                Some(f).filter(|&f| f >= -I64_END && f as i64 == i)
+            }
+            N::Float(f) => Some(f),
+        }
+    }
+}
+
+impl From<i64> for Number {
+    fn from(i: i64) -> Number {
+        if i < 0 {
+            Number(N::NegInt(i))
+        } else {
+            Number(N::PosInt(i as u64))
+        }
+    }
+}
+
+impl From<u64> for Number {
+    fn from(u: u64) -> Number {
+        Number(N::PosInt(u))
+    }
+}
+
+impl From<f64> for Number {
+    fn from(f: f64) -> Number {
+        Number(N::Float(f))
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            N::PosInt(u) => write!(f, "{}", u),
+            N::NegInt(i) => write!(f, "{}", i),
+            N::Float(v) => f.write_str(&format_real(v)),
+        }
+    }
+}
+
+impl From<Number> for Yaml {
+    /// An integer that fits an `i64` becomes `Yaml::Integer`, and any other
+    /// number the `Yaml::Real` that loads as it.
+    fn from(n: Number) -> Yaml {
+        match n.0 {
+            N::Float(f) => Yaml::from_f64(f),
+            _ => match n.as_i64() {
+                Some(i) => Yaml::Integer(i),
+                None => Yaml::Real(n.to_string()),
+            },
+        }
+    }
+}
+
+impl Yaml {
+    /// The number an integer or a real holds, see the `number` module.
+    /// A real whose text is a whole number too large for an `i64` is an
+    /// integer here.
+    pub fn as_number(&self) -> Option<Number> {
+        match *self {
+            Yaml::Integer(i) => Some(Number::from(i)),
+            Yaml::Real(ref v) => {
+                let digits = v.strip_prefix('+').unwrap_or(v);
+                if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) {
+                    if let Ok(u) = digits.parse::<u64>() {
+                        return Some(Number::from(u));
+                    }
+                }
+                parse_f64(v).map(Number::from)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::YamlLoader;
+
+    #[test]
+    fn test_number() {
+        let doc = YamlLoader::load_from_str(
+            "[0, -9223372036854775808, 9223372036854775808, 18446744073709551616, 1.0, -.inf, a]",
+        )
+        .unwrap()
+        .remove(0);
+        let numbers: Vec<Option<Number>> =
+            doc.as_vec().unwrap().iter().map(Yaml::as_number).collect();
+        assert_eq!(
+            numbers,
+            [
+                Some(Number::from(0i64)),
+                Some(Number::from(i64::MIN)),
+                Some(Number::from(1u64 << 63)),
+                Some(Number::from(U64_END)),
+                Some(Number::from(1.0)),
+                Some(Number::from(f64::NEG_INFINITY)),
+                None,
+            ]
+        );
+        assert_eq!(Number::from(0i64), Number::from(0u64));
+        assert!(!Number::from(1.0).is_i64());
+        assert_eq!(Number::from(1u64 << 63).as_i64(), None);
+        assert_eq!(Number::from(i64::MIN).as_f64(), Some(-I64_END));
+        assert_eq!(Number::from(u64::MAX).as_f64(), None);
+        assert_eq!(Number::from((1u64 << 53) + 1).as_f64(), None);
+
+        // numbers convert back to what loads as them
+        for node in doc.as_vec().unwrap() {
+            if let Some(n) = node.as_number() {
+                let yaml = Yaml::from(n);
+                assert_eq!(yaml.as_number(), Some(n));
+                let loaded = YamlLoader::load_from_str(&n.to_string()).unwrap().remove(0);
+                assert_eq!(loaded, yaml);
+            }
+        }
+    }
+}
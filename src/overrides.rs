@@ -0,0 +1,245 @@
+//! Overrides written like the `--set` flag of Helm, such as
+//! `image.tag=v2,replicas=3`, applied to a document with `Yaml::set`.
+//!
+//! Overrides are separated by commas, and each is a path and a value
+//! separated by the first `=`. A path is keys separated by dots, each
+//! followed by any number of array indices such as `[0]`. A value wrapped
+//! in braces, such as `{a,b}`, is an array of the values separated by
+//! commas inside it. A backslash makes the next character literal, so
+//! `"a\.b=x\,y"` sets the key `a.b` to `x,y`.
+//!
+//! `apply` resolves each value like a plain YAML scalar with
+//! `Yaml::from_str`, so `3` is an integer and `true` a boolean, and
+//! `apply_strings` keeps every value a string like Helm's `--set-string`.
+//! Unlike Helm, `null` sets a null rather than removing the key.
+//!
+//! # Examples
+//!
+//! ```
+//! # extern crate yaml_rust_davvid as yaml_rust;
+//! use yaml_rust::{overrides, Yaml, YamlLoader};
+//!
+//! let mut doc = YamlLoader::load_from_str("image: {tag: v1}").unwrap().remove(0);
+//! overrides::apply(&mut doc, r"image.tag=v2,replicas=3,hosts={a\,b,c}").unwrap();
+//! assert_eq!(doc["image"]["tag"].as_str(), Some("v2"));
+//! assert_eq!(doc["replicas"], Yaml::Integer(3));
+//! assert_eq!(doc["hosts"][0].as_str(), Some("a,b"));
+//! assert_eq!(doc["hosts"][1].as_str(), Some("c"));
+//! ```
+
+use crate::path::{self, Intermediates, PathElement, SetError, YamlPath};
+use crate::yaml::Yaml;
+
+use std::error::Error;
+use std::fmt;
+
+/// Why `apply` failed.
+#[derive(Clone, PartialEq, Debug, Eq)]
+pub enum OverrideError {
+    /// An override that can't be parsed, and why.
+    Syntax { text: String, reason: &'static str },
+    /// An override whose path runs into a node it can't go through. The
+    /// overrides before it have been applied.
+    Set(SetError),
+}
+
+impl fmt::Display for OverrideError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OverrideError::Syntax { ref text, reason } => {
+                write!(f, "invalid override {:?}: {}", text, reason)
+            }
+            OverrideError::Set(ref err) => fmt::Display::fmt(err, f),
+        }
+    }
+}
+
+impl Error for OverrideError {}
+
+impl From<SetError> for OverrideError {
+    fn from(err: SetError) -> OverrideError {
+        OverrideError::Set(err)
+    }
+}
+
+/// Apply `overrides` to `doc`, resolving the type of each value. Nothing
+/// is applied if any override can't be parsed.
+pub fn apply(doc: &mut Yaml, overrides: &str) -> Result<(), OverrideError> {
+    apply_with(doc, overrides, Yaml::from_str)
+}
+
+/// Like `apply`, keeping every value a string.
+pub fn apply_strings(doc: &mut Yaml, overrides: &str) -> Result<(), OverrideError> {
+    apply_with(doc, overrides, |v| Yaml::String(v.to_owned()))
+}
+
+fn apply_with(
+    doc: &mut Yaml,
+    overrides: &str,
+    resolve: fn(&str) -> Yaml,
+) -> Result<(), OverrideError> {
+    let parsed = parse(overrides)?;
+    for (steps, value) in parsed {
+        let value = match value {
+            Value::Scalar(v) => resolve(&v),
+            Value::List(items) => Yaml::Array(items.iter().map(|v| resolve(v)).collect()),
+        };
+        path::set(
+            doc,
+            &steps,
+            value,
+            Intermediates::Create,
+            &mut YamlPath::new(),
+        )?;
+    }
+    Ok(())
+}
+
+enum Value {
+    Scalar(String),
+    List(Vec<String>),
+}
+
+// A character of the input, and whether it was escaped with a backslash.
+type Char = (char, bool);
+
+fn parse(overrides: &str) -> Result<Vec<(Vec<PathElement>, Value)>, OverrideError> {
+    let mut chars = Vec::new();
+    let mut it = overrides.chars();
+    while let Some(c) = it.next() {
+        match c {
+            '\\' => chars.push((it.next().unwrap_or('\\'), true)),
+            c => chars.push((c, false)),
+        }
+    }
+    if chars.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut parsed = Vec::new();
+    for item in split(&chars, ',', true) {
+        let syntax = |reason| OverrideError::Syntax {
+            text: text(item),
+            reason,
+        };
+        let eq = item
+            .iter()
+            .position(|&c| c == ('=', false))
+            .ok_or_else(|| syntax("expected '=' after the path"))?;
+        let steps = parse_path(&item[..eq]).ok_or_else(|| syntax("invalid path"))?;
+        let value = &item[eq + 1..];
+        let value = match (value.first(), value.last()) {
+            (Some(&('{', false)), Some(&('}', false))) => {
+                let inner = &value[1..value.len() - 1];
+                if inner.is_empty() {
+                    Value::List(Vec::new())
+                } else {
+                    Value::List(split(inner, ',', false).into_iter().map(text).collect())
+                }
+            }
+            _ => Value::Scalar(text(value)),
+        };
+        parsed.push((steps, value));
+    }
+    Ok(parsed)
+}
+
+// Split at every unescaped `sep`, outside of braces if `nested`.
+fn split(chars: &[Char], sep: char, nested: bool) -> Vec<&[Char]> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, &c) in chars.iter().enumerate() {
+        match c {
+            ('{', false) if nested => depth += 1,
+            ('}', false) if nested => depth = depth.saturating_sub(1),
+            (c, false) if c == sep && depth == 0 => {
+                parts.push(&chars[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&chars[start..]);
+    parts
+}
+
+fn text(chars: &[Char]) -> String {
+    chars.iter().map(|&(c, _)| c).collect()
+}
+
+fn parse_path(chars: &[Char]) -> Option<Vec<PathElement>> {
+    let mut steps = Vec::new();
+    for segment in split(chars, '.', false) {
+        let open = segment
+            .iter()
+            .position(|&c| c == ('[', false))
+            .unwrap_or(segment.len());
+        if segment.is_empty() {
+            return None;
+        }
+        if open > 0 {
+            steps.push(PathElement::Key(Yaml::String(text(&segment[..open]))));
+        }
+        let mut rest = &segment[open..];
+        while !rest.is_empty() {
+            let close = rest.iter().position(|&c| c == (']', false))?;
+            let digits = text(&rest[1..close]);
+            if rest[0] != ('[', false) || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            steps.push(PathElement::Index(digits.parse().ok()?));
+            rest = &rest[close + 1..];
+        }
+    }
+    Some(steps)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::YamlLoader;
+
+    #[test]
+    fn test_apply() {
+        let mut doc = YamlLoader::load_from_str("a: {b: [1, 2]}\nname: web")
+            .unwrap()
+            .remove(0);
+        apply(
+            &mut doc,
+            r"a.b[1]=3,a.c=true,name=,list={},nested[0][1]=x,dotted\.key=1\,5,eq=a=b",
+        )
+        .unwrap();
+        assert_eq!(doc["a"]["b"][1], Yaml::Integer(3));
+        assert_eq!(doc["a"]["c"], Yaml::Boolean(true));
+        assert_eq!(doc["name"].as_str(), Some(""));
+        assert_eq!(doc["list"], Yaml::Array(Vec::new()));
+        assert_eq!(doc["nested"][0][1].as_str(), Some("x"));
+        assert_eq!(doc["dotted.key"].as_str(), Some("1,5"));
+        assert_eq!(doc["eq"].as_str(), Some("a=b"));
+
+        apply_strings(&mut doc, "a.b={4,true},port=8080").unwrap();
+        assert_eq!(doc["a"]["b"][1].as_str(), Some("true"));
+        assert_eq!(doc["port"].as_str(), Some("8080"));
+        apply(&mut doc, "").unwrap();
+
+        for (overrides, reason) in [
+            ("a.b", "expected '=' after the path"),
+            ("a..b=1", "invalid path"),
+            ("a[x]=1", "invalid path"),
+            ("a[1=1", "invalid path"),
+            ("c=1,=2", "invalid path"),
+        ] {
+            let before = doc.clone();
+            match apply(&mut doc, overrides) {
+                Err(OverrideError::Syntax { reason: r, .. }) => {
+                    assert_eq!(r, reason, "{}", overrides)
+                }
+                other => panic!("{}: {:?}", overrides, other),
+            }
+            assert_eq!(doc, before);
+        }
+        let err = apply(&mut doc, "z=1,name.first=a").unwrap_err();
+        assert_eq!(err.to_string(), "expected hash at $.name, found string");
+        assert_eq!(doc["z"], Yaml::Integer(1));
+    }
+}
@@ -1,5 +1,7 @@
+use crate::emitter::{EmitResult, YamlEmitter};
 use crate::scanner::*;
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
 
 #[derive(Clone, Copy, PartialEq, Debug, Eq)]
 enum State {
@@ -32,6 +34,7 @@ enum State {
 /// `Event` is used with the low-level event base parsing API,
 /// see `EventReceiver` trait.
 #[derive(Clone, PartialEq, Debug, Eq)]
+#[non_exhaustive]
 pub enum Event {
     /// Reserved for internal use
     Nothing,
@@ -43,15 +46,48 @@ pub enum Event {
     Alias(usize),
     /// Value, style, anchor_id, tag
     Scalar(String, TScalarStyle, usize, Option<TokenType>),
-    /// Anchor ID
-    SequenceStart(usize),
+    /// Part of the value of the next `Scalar`, with
+    /// `ScannerConfig::block_scalar_chunk_bytes`. `Parser::load` and
+    /// `YamlLoader` join the chunks, so receivers see whole scalars unless
+    /// they take chunks, see `MarkedEventReceiver::takes_scalar_chunks`.
+    ScalarChunk(String),
+    /// Anchor ID, tag
+    SequenceStart(usize, Option<TokenType>),
     SequenceEnd,
-    /// Anchor ID
-    MappingStart(usize),
+    /// Anchor ID, tag
+    MappingStart(usize, Option<TokenType>),
     MappingEnd,
 }
 
 impl Event {
+    /// A scalar with no anchor or tag. Plain scalars are resolved when they
+    /// are loaded, so `Event::scalar("1", TScalarStyle::Plain)` is an integer.
+    pub fn scalar<S: Into<String>>(value: S, style: TScalarStyle) -> Event {
+        Event::Scalar(value.into(), style, 0, None)
+    }
+
+    /// The start of a sequence with no anchor or tag.
+    pub fn sequence_start() -> Event {
+        Event::SequenceStart(0, None)
+    }
+
+    /// The start of a mapping with no anchor or tag.
+    pub fn mapping_start() -> Event {
+        Event::MappingStart(0, None)
+    }
+
+    /// Set the tag of a scalar or collection start event. Other events are
+    /// returned unchanged.
+    pub fn with_tag<H: Into<String>, S: Into<String>>(self, handle: H, suffix: S) -> Event {
+        let tag = Some(TokenType::Tag(handle.into(), suffix.into()));
+        match self {
+            Event::Scalar(v, style, aid, _) => Event::Scalar(v, style, aid, tag),
+            Event::SequenceStart(aid, _) => Event::SequenceStart(aid, tag),
+            Event::MappingStart(aid, _) => Event::MappingStart(aid, tag),
+            ev => ev,
+        }
+    }
+
     fn empty_scalar() -> Event {
         // a null scalar
         Event::Scalar("~".to_owned(), TScalarStyle::Plain, 0, None)
@@ -72,8 +108,49 @@ pub struct Parser<T> {
     token: Option<Token>,
     current: Option<(Event, Marker)>,
     anchors: HashMap<String, usize>,
+    // anchor_id -> where its `&name` is, for the current document
+    anchor_marks: HashMap<usize, Marker>,
     anchor_id: usize,
     tag_directives: HashMap<String, String>,
+    config: ScannerConfig,
+    // anchors registered in the current document
+    document_anchors: usize,
+    // documents started so far
+    documents: usize,
+    // set once `next_event` has returned the end of the stream or an error
+    finished: bool,
+    stats: ParserStats,
+    depth: usize,
+    // whether to time parsing, and when the first event was requested and
+    // when parsing stopped
+    record_time: bool,
+    started: Option<Instant>,
+    stopped: Option<Instant>,
+    // estimated entries of the collection started by the last event
+    capacity_hint: Option<usize>,
+    // the `%YAML` directive of the current document
+    version: Option<(u32, u32, Marker)>,
+    // the anchor and tag of a scalar whose chunks are being delivered
+    chunked_scalar: Option<(usize, Option<TokenType>)>,
+}
+
+/// Counters describing the work a `Parser` has done, see `Parser::stats`.
+#[derive(Clone, Copy, PartialEq, Debug, Default, Eq)]
+pub struct ParserStats {
+    /// Tokens produced by the scanner.
+    pub tokens: usize,
+    /// Events produced, counting `StreamEnd` once.
+    pub events: usize,
+    /// The deepest nesting of collections.
+    pub max_depth: usize,
+    /// Input consumed, measured in UTF-8 bytes whatever the encoding of the
+    /// source.
+    pub bytes: usize,
+    /// Time from the first event until the end of the stream or an error,
+    /// or until now if parsing has not stopped. This includes the time the
+    /// consumer spends between events, such as building a `Yaml` tree.
+    /// Zero unless timing was turned on with `Parser::record_time`.
+    pub elapsed: Duration,
 }
 
 pub trait EventReceiver {
@@ -82,6 +159,14 @@ pub trait EventReceiver {
 
 pub trait MarkedEventReceiver {
     fn on_event(&mut self, ev: Event, _mark: Marker);
+
+    /// Whether `Parser::load` passes on the `ScalarChunk` events of a
+    /// scalar as they are parsed, followed by the `Scalar` event with the
+    /// rest of its content, instead of joining them into one `Scalar`. A
+    /// receiver that takes chunks can handle a scalar larger than memory.
+    fn takes_scalar_chunks(&self) -> bool {
+        false
+    }
 }
 
 impl<R: EventReceiver> MarkedEventReceiver for R {
@@ -90,12 +175,145 @@ impl<R: EventReceiver> MarkedEventReceiver for R {
     }
 }
 
+/// Events with the marks they were parsed at, recorded to be replayed later
+/// into any `MarkedEventReceiver` or a `YamlEmitter`, such as a cached parse
+/// that is transformed before it is loaded, or a fixed stream to test a
+/// receiver with.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate yaml_rust_davvid as yaml_rust;
+/// use yaml_rust::parser::EventLog;
+/// use yaml_rust::{Event, Yaml, YamlEmitter, YamlLoader};
+///
+/// let log = EventLog::record("name: web\nport: 80").unwrap();
+/// let upper: EventLog = log
+///     .into_iter()
+///     .map(|(ev, mark)| match ev {
+///         Event::Scalar(v, style, aid, tag) => (Event::Scalar(v.to_uppercase(), style, aid, tag), mark),
+///         ev => (ev, mark),
+///     })
+///     .collect();
+///
+/// let docs = YamlLoader::new().parse_from_events(&upper).unwrap();
+/// assert_eq!(docs[0]["NAME"].as_str(), Some("WEB"));
+///
+/// let mut out = String::new();
+/// upper.emit(&mut YamlEmitter::new(&mut out)).unwrap();
+/// assert_eq!(out, "---\nNAME: WEB\nPORT: 80");
+/// ```
+#[derive(Clone, PartialEq, Debug, Default, Eq)]
+pub struct EventLog {
+    events: Vec<(Event, Marker)>,
+}
+
+impl EventLog {
+    pub fn new() -> EventLog {
+        EventLog::default()
+    }
+
+    /// Record the events of every document in `source`.
+    pub fn record(source: &str) -> Result<EventLog, ScanError> {
+        let mut log = EventLog::new();
+        Parser::new(source.chars()).load(&mut log, true)?;
+        Ok(log)
+    }
+
+    pub fn push(&mut self, ev: Event, mark: Marker) {
+        self.events.push((ev, mark));
+    }
+
+    pub fn events(&self) -> &[(Event, Marker)] {
+        &self.events
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Send the events to `recv`, in the order they were recorded.
+    pub fn replay<R: MarkedEventReceiver + ?Sized>(&self, recv: &mut R) {
+        for &(ref ev, mark) in &self.events {
+            recv.on_event(ev.clone(), mark);
+        }
+    }
+
+    /// Write the events with `YamlEmitter::emit_event`, which fails on an
+    /// `Alias` event.
+    pub fn emit(&self, emitter: &mut YamlEmitter) -> EmitResult {
+        for (ev, _) in &self.events {
+            emitter.emit_event(ev.clone())?;
+        }
+        Ok(())
+    }
+
+    pub fn into_events(self) -> Vec<(Event, Marker)> {
+        self.events
+    }
+}
+
+impl MarkedEventReceiver for EventLog {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        self.push(ev, mark);
+    }
+}
+
+impl From<Vec<(Event, Marker)>> for EventLog {
+    fn from(events: Vec<(Event, Marker)>) -> EventLog {
+        EventLog { events }
+    }
+}
+
+impl FromIterator<(Event, Marker)> for EventLog {
+    fn from_iter<I: IntoIterator<Item = (Event, Marker)>>(iter: I) -> EventLog {
+        EventLog {
+            events: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl Extend<(Event, Marker)> for EventLog {
+    fn extend<I: IntoIterator<Item = (Event, Marker)>>(&mut self, iter: I) {
+        self.events.extend(iter);
+    }
+}
+
+impl IntoIterator for EventLog {
+    type Item = (Event, Marker);
+    type IntoIter = std::vec::IntoIter<(Event, Marker)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.events.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a EventLog {
+    type Item = &'a (Event, Marker);
+    type IntoIter = std::slice::Iter<'a, (Event, Marker)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.events.iter()
+    }
+}
+
 pub type ParseResult = Result<(Event, Marker), ScanError>;
 
 impl<T: Iterator<Item = char>> Parser<T> {
     pub fn new(src: T) -> Parser<T> {
+        Parser::new_with_config(src, ScannerConfig::default())
+    }
+
+    /// Create a parser that enforces the limits in `config`.
+    pub fn new_with_config(src: T, config: ScannerConfig) -> Parser<T> {
+        let mut scanner = Scanner::new_with_config(src, config);
+        scanner.record_capacity_hints();
         Parser {
-            scanner: Scanner::new(src),
+            scanner,
             states: Vec::new(),
             state: State::StreamStart,
             marks: Vec::new(),
@@ -103,29 +321,162 @@ impl<T: Iterator<Item = char>> Parser<T> {
             current: None,
 
             anchors: HashMap::new(),
+            anchor_marks: HashMap::new(),
             // valid anchor_id starts from 1
             anchor_id: 1,
             tag_directives: HashMap::new(),
+            config,
+            document_anchors: 0,
+            documents: 0,
+            finished: false,
+            stats: ParserStats::default(),
+            depth: 0,
+            record_time: false,
+            started: None,
+            stopped: None,
+            capacity_hint: None,
+            version: None,
+            chunked_scalar: None,
+        }
+    }
+
+    /// What the parser has done so far.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::parser::Parser;
+    ///
+    /// let mut parser = Parser::new("a: [1, {b: 2}]".chars());
+    /// for event in &mut parser {
+    ///     event.unwrap();
+    /// }
+    /// let stats = parser.stats();
+    /// assert_eq!(stats.max_depth, 3);
+    /// assert_eq!(stats.events, 14);
+    /// assert_eq!(stats.bytes, 14);
+    /// ```
+    pub fn stats(&self) -> ParserStats {
+        let elapsed = match (self.started, self.stopped) {
+            (Some(started), Some(stopped)) => stopped - started,
+            (Some(started), None) => started.elapsed(),
+            (None, _) => Duration::ZERO,
+        };
+        ParserStats {
+            bytes: self.scanner.bytes(),
+            elapsed,
+            ..self.stats
         }
     }
 
+    /// Measure the time parsing takes, reported as `ParserStats::elapsed`.
+    /// Off by default, so parsing never reads the clock unless asked to,
+    /// which also keeps it usable on targets without one. Turn it on
+    /// before the first event.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use std::time::Duration;
+    /// use yaml_rust::parser::Parser;
+    ///
+    /// let mut parser = Parser::new("[1]".chars());
+    /// parser.record_time(true);
+    /// for event in &mut parser {
+    ///     event.unwrap();
+    /// }
+    /// assert!(parser.stats().elapsed > Duration::ZERO);
+    /// ```
+    pub fn record_time(&mut self, enable: bool) -> &mut Self {
+        self.record_time = enable;
+        self
+    }
+
     pub fn peek(&mut self) -> Result<&(Event, Marker), ScanError> {
         match self.current {
             Some(ref x) => Ok(x),
             None => {
-                self.current = Some(self.next()?);
+                self.current = Some(self.next_event()?);
                 self.peek()
             }
         }
     }
 
-    pub fn next(&mut self) -> ParseResult {
+    /// Parse the next event. After `Event::StreamEnd`, further calls return
+    /// it again, where the `Iterator` implementation ends.
+    pub fn next_event(&mut self) -> ParseResult {
         match self.current {
             None => self.parse(),
             Some(_) => Ok(self.current.take().unwrap()),
         }
     }
 
+    /// The name of the anchor with the given ID, if it is defined in the
+    /// current document and has not been overridden.
+    pub fn anchor_name(&self, id: usize) -> Option<&str> {
+        self.anchors
+            .iter()
+            .find(|&(_, &anchor_id)| anchor_id == id)
+            .map(|(name, _)| name.as_ref())
+    }
+
+    /// The version declared by the `%YAML` directive of the current
+    /// document as `(major, minor)`, from its `DocumentStart` event until
+    /// the next one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::parser::Parser;
+    /// use yaml_rust::Event;
+    ///
+    /// let mut parser = Parser::new("%YAML 1.1\n--- a\n...\n--- b".chars());
+    /// let mut versions = Vec::new();
+    /// while let Ok((event, _)) = parser.next_event() {
+    ///     match event {
+    ///         Event::DocumentStart => versions.push(parser.document_version()),
+    ///         Event::StreamEnd => break,
+    ///         _ => {}
+    ///     }
+    /// }
+    /// assert_eq!(versions, [Some((1, 1)), None]);
+    /// ```
+    pub fn document_version(&self) -> Option<(u32, u32)> {
+        self.version.map(|(major, minor, _)| (major, minor))
+    }
+
+    // Where the `%YAML` directive of the current document is.
+    pub(crate) fn document_version_mark(&self) -> Option<Marker> {
+        self.version.map(|(_, _, mark)| mark)
+    }
+
+    /// An estimate of how many entries the collection started by the last
+    /// event parsed has, which is the event `peek` returns if it was
+    /// called. Flow collections are estimated from their commas, and block
+    /// collections are not estimated.
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::parser::{Event, Parser};
+    ///
+    /// let mut parser = Parser::new("a: [1, [2, 3], 'x, y']".chars());
+    /// let mut hints = Vec::new();
+    /// while let Ok((ev, _)) = parser.next_event() {
+    ///     match ev {
+    ///         Event::SequenceStart(..) | Event::MappingStart(..) => hints.push(parser.capacity_hint()),
+    ///         Event::StreamEnd => break,
+    ///         _ => {}
+    ///     }
+    /// }
+    /// assert_eq!(hints, [None, Some(3), Some(2)]);
+    /// ```
+    pub fn capacity_hint(&self) -> Option<usize> {
+        self.capacity_hint
+    }
+
     fn peek_token(&mut self) -> Result<&Token, ScanError> {
         match self.token {
             None => {
@@ -143,7 +494,10 @@ impl<T: Iterator<Item = char>> Parser<T> {
                 None => Err(ScanError::new(self.scanner.mark(), "unexpected eof")),
                 Some(e) => Err(e),
             },
-            Some(tok) => Ok(tok),
+            Some(tok) => {
+                self.stats.tokens += 1;
+                Ok(tok)
+            }
         }
     }
 
@@ -168,8 +522,60 @@ impl<T: Iterator<Item = char>> Parser<T> {
         if self.state == State::End {
             return Ok((Event::StreamEnd, self.scanner.mark()));
         }
+        if self.record_time && self.started.is_none() {
+            self.started = Some(Instant::now());
+        }
+        self.capacity_hint = None;
+        let result = self.parse_event();
+        match result {
+            Ok((ref ev, _)) => {
+                self.stats.events += 1;
+                match *ev {
+                    Event::SequenceStart(..) | Event::MappingStart(..) => {
+                        self.depth += 1;
+                        self.stats.max_depth = self.stats.max_depth.max(self.depth);
+                    }
+                    Event::SequenceEnd | Event::MappingEnd => self.depth -= 1,
+                    Event::StreamEnd => self.stop_timing(),
+                    _ => {}
+                }
+            }
+            Err(_) => self.stop_timing(),
+        }
+        result
+    }
+
+    fn stop_timing(&mut self) {
+        if self.started.is_some() {
+            self.stopped = Some(Instant::now());
+        }
+    }
+
+    fn parse_event(&mut self) -> ParseResult {
         let (ev, mark) = self.state_machine()?;
         // println!("EV {:?}", ev);
+        if ev == Event::DocumentStart {
+            self.documents += 1;
+            if self
+                .config
+                .max_documents
+                .is_some_and(|max| self.documents > max)
+            {
+                return Err(ScanError::new(
+                    mark,
+                    "stream exceeds the maximum number of documents",
+                ));
+            }
+        }
+        // the events returned so far and this one
+        if let Some(max) = self.config.max_events {
+            if self.stats.events + 1 > max {
+                return Err(ScanError::new(
+                    mark,
+                    "stream exceeds the maximum number of events",
+                ));
+            }
+        }
         Ok((ev, mark))
     }
 
@@ -179,7 +585,7 @@ impl<T: Iterator<Item = char>> Parser<T> {
         multi: bool,
     ) -> Result<(), ScanError> {
         if !self.scanner.stream_started() {
-            let (ev, mark) = self.next()?;
+            let (ev, mark) = self.next_event()?;
             assert_eq!(ev, Event::StreamStart);
             recv.on_event(ev, mark);
         }
@@ -190,13 +596,11 @@ impl<T: Iterator<Item = char>> Parser<T> {
             return Ok(());
         }
         loop {
-            let (ev, mark) = self.next()?;
+            let (ev, mark) = self.next_event()?;
             if ev == Event::StreamEnd {
                 recv.on_event(ev, mark);
                 return Ok(());
             }
-            // clear anchors before a new document
-            self.anchors.clear();
             self.load_document(ev, mark, recv)?;
             if !multi {
                 break;
@@ -214,11 +618,11 @@ impl<T: Iterator<Item = char>> Parser<T> {
         assert_eq!(first_ev, Event::DocumentStart);
         recv.on_event(first_ev, mark);
 
-        let (ev, mark) = self.next()?;
+        let (ev, mark) = self.next_event()?;
         self.load_node(ev, mark, recv)?;
 
         // DOCUMENT-END is expected.
-        let (ev, mark) = self.next()?;
+        let (ev, mark) = self.next_event()?;
         assert_eq!(ev, Event::DocumentEnd);
         recv.on_event(ev, mark);
 
@@ -236,11 +640,25 @@ impl<T: Iterator<Item = char>> Parser<T> {
                 recv.on_event(first_ev, mark);
                 Ok(())
             }
-            Event::SequenceStart(_) => {
+            Event::ScalarChunk(_) if recv.takes_scalar_chunks() => {
+                let (mut ev, mut mark) = (first_ev, mark);
+                while let Event::ScalarChunk(_) = ev {
+                    recv.on_event(ev, mark);
+                    (ev, mark) = self.next_event()?;
+                }
+                recv.on_event(ev, mark);
+                Ok(())
+            }
+            Event::ScalarChunk(value) => {
+                let (ev, mark) = self.join_chunks(value, mark)?;
+                recv.on_event(ev, mark);
+                Ok(())
+            }
+            Event::SequenceStart(..) => {
                 recv.on_event(first_ev, mark);
                 self.load_sequence(recv)
             }
-            Event::MappingStart(_) => {
+            Event::MappingStart(..) => {
                 recv.on_event(first_ev, mark);
                 self.load_mapping(recv)
             }
@@ -251,18 +669,33 @@ impl<T: Iterator<Item = char>> Parser<T> {
         }
     }
 
+    // Read the rest of a scalar delivered in chunks, the first of which is
+    // `value`, as one `Scalar` event.
+    pub(crate) fn join_chunks(&mut self, mut value: String, mark: Marker) -> ParseResult {
+        loop {
+            match self.next_event()? {
+                (Event::ScalarChunk(chunk), _) => value.push_str(&chunk),
+                (Event::Scalar(rest, style, aid, tag), _) => {
+                    value.push_str(&rest);
+                    return Ok((Event::Scalar(value, style, aid, tag), mark));
+                }
+                _ => unreachable!(),
+            }
+        }
+    }
+
     fn load_mapping<R: MarkedEventReceiver>(&mut self, recv: &mut R) -> Result<(), ScanError> {
-        let (mut key_ev, mut key_mark) = self.next()?;
+        let (mut key_ev, mut key_mark) = self.next_event()?;
         while key_ev != Event::MappingEnd {
             // key
             self.load_node(key_ev, key_mark, recv)?;
 
             // value
-            let (ev, mark) = self.next()?;
+            let (ev, mark) = self.next_event()?;
             self.load_node(ev, mark, recv)?;
 
             // next event
-            let (ev, mark) = self.next()?;
+            let (ev, mark) = self.next_event()?;
             key_ev = ev;
             key_mark = mark;
         }
@@ -271,12 +704,12 @@ impl<T: Iterator<Item = char>> Parser<T> {
     }
 
     fn load_sequence<R: MarkedEventReceiver>(&mut self, recv: &mut R) -> Result<(), ScanError> {
-        let (mut ev, mut mark) = self.next()?;
+        let (mut ev, mut mark) = self.next_event()?;
         while ev != Event::SequenceEnd {
             self.load_node(ev, mark, recv)?;
 
             // next event
-            let (next_ev, next_mark) = self.next()?;
+            let (next_ev, next_mark) = self.next_event()?;
             ev = next_ev;
             mark = next_mark;
         }
@@ -373,21 +806,19 @@ impl<T: Iterator<Item = char>> Parser<T> {
             Tag { handle: String, prefix: String },
         }
 
+        self.version = None;
         loop {
             // Without NLL, split the peek and the action
-            let action = match self.peek_token()?.1 {
-                TokenType::VersionDirective(_, _) => {
-                    // XXX parsing with warning according to spec
-                    //if major != 1 || minor > 2 {
-                    //    return Err(ScanError::new(tok.0,
-                    //        "found incompatible YAML document"));
-                    //}
+            let action = match *self.peek_token()? {
+                // whether the version is supported is up to the consumer,
+                // see `document_version`
+                Token(mark, TokenType::VersionDirective(major, minor)) => {
+                    self.version = Some((major, minor, mark));
                     DirectiveAction::None
                 }
-                TokenType::TagDirective(ref handle, ref prefix) => {
+                Token(_, TokenType::TagDirective(ref handle, ref prefix)) => {
                     let handle = String::clone(handle);
-                    let mut prefix = String::clone(prefix);
-                    prefix.pop();
+                    let prefix = String::clone(prefix);
                     DirectiveAction::Tag { handle, prefix }
                 }
                 _ => break,
@@ -446,10 +877,45 @@ impl<T: Iterator<Item = char>> Parser<T> {
 
         // TODO tag handling
         self.state = State::DocumentStart;
+        // anchors are local to a document
+        self.anchors.clear();
+        self.anchor_marks.clear();
+        self.document_anchors = 0;
+        self.scanner.start_document();
         Ok((Event::DocumentEnd, marker))
     }
 
-    fn register_anchor(&mut self, name: String, _: &Marker) -> Result<usize, ScanError> {
+    // The `%TAG` directives in effect, handle to prefix.
+    pub(crate) fn tag_directives(&self) -> &HashMap<String, String> {
+        &self.tag_directives
+    }
+
+    // Resolve `handle` to `prefix` before parsing, for parsing part of a
+    // document whose `%TAG` directives are outside that part.
+    pub(crate) fn define_tag_directive(&mut self, handle: String, prefix: String) {
+        self.tag_directives.insert(handle, prefix);
+    }
+
+    /// Make an anchor known before parsing, for parsing part of a document
+    /// whose aliases refer to anchors outside that part. Returns the id its
+    /// aliases will have.
+    pub(crate) fn define_anchor(&mut self, name: String) -> usize {
+        let id = self.anchor_id;
+        self.anchor_id += 1;
+        self.anchors.insert(name, id);
+        id
+    }
+
+    fn register_anchor(&mut self, name: String, mark: &Marker) -> Result<usize, ScanError> {
+        self.document_anchors += 1;
+        if let Some(max) = self.config.max_anchors {
+            if self.document_anchors > max {
+                return Err(ScanError::new(
+                    *mark,
+                    "document exceeds the maximum number of anchors",
+                ));
+            }
+        }
         // anchors can be overridden/reused
         // if self.anchors.contains_key(name) {
         //     return Err(ScanError::new(*mark,
@@ -458,9 +924,16 @@ impl<T: Iterator<Item = char>> Parser<T> {
         let new_id = self.anchor_id;
         self.anchor_id += 1;
         self.anchors.insert(name, new_id);
+        self.anchor_marks.insert(new_id, *mark);
         Ok(new_id)
     }
 
+    // Where the anchor with the given ID is written in the current
+    // document, as opposed to the node it is attached to.
+    pub(crate) fn anchor_mark(&self, id: usize) -> Option<Marker> {
+        self.anchor_marks.get(&id).copied()
+    }
+
     fn parse_node(&mut self, block: bool, indentless_sequence: bool) -> ParseResult {
         let mut anchor_id = 0;
         let mut tag = None;
@@ -511,51 +984,57 @@ impl<T: Iterator<Item = char>> Parser<T> {
             }
             _ => {}
         }
+        if let Some(TokenType::Tag(handle, suffix)) = tag {
+            tag = Some(match self.tag_directives.get(&handle) {
+                Some(prefix) => TokenType::Tag(prefix.clone(), suffix),
+                None => TokenType::Tag(handle, suffix),
+            });
+        }
+        if let Some((id, tg)) = self.chunked_scalar.take() {
+            anchor_id = id;
+            tag = tg;
+        }
         match *self.peek_token()? {
             Token(mark, TokenType::BlockEntry) if indentless_sequence => {
                 self.state = State::IndentlessSequenceEntry;
-                Ok((Event::SequenceStart(anchor_id), mark))
+                Ok((Event::SequenceStart(anchor_id, tag), mark))
             }
             Token(_, TokenType::Scalar(..)) => {
                 self.pop_state();
                 if let Token(mark, TokenType::Scalar(style, v)) = self.fetch_token() {
-                    Ok((
-                        if let Some(TokenType::Tag(handle, suffix)) = tag {
-                            let t = self.tag_directives.get(&handle);
-                            Event::Scalar(
-                                v,
-                                style,
-                                anchor_id,
-                                Some(if let Some(s) = t {
-                                    TokenType::Tag((*s).clone(), suffix)
-                                } else {
-                                    TokenType::Tag(handle, suffix)
-                                }),
-                            )
-                        } else {
-                            Event::Scalar(v, style, anchor_id, tag)
-                        },
-                        mark,
-                    ))
+                    Ok((Event::Scalar(v, style, anchor_id, tag), mark))
+                } else {
+                    unreachable!()
+                }
+            }
+            Token(_, TokenType::ScalarChunk(_)) => {
+                // come back for the rest of the scalar, which pops the state
+                // as usual
+                if let Token(mark, TokenType::ScalarChunk(v)) = self.fetch_token() {
+                    self.state = State::BlockNode;
+                    self.chunked_scalar = Some((anchor_id, tag));
+                    Ok((Event::ScalarChunk(v), mark))
                 } else {
                     unreachable!()
                 }
             }
             Token(mark, TokenType::FlowSequenceStart) => {
                 self.state = State::FlowSequenceFirstEntry;
-                Ok((Event::SequenceStart(anchor_id), mark))
+                self.capacity_hint = self.scanner.take_capacity_hint(mark.index());
+                Ok((Event::SequenceStart(anchor_id, tag), mark))
             }
             Token(mark, TokenType::FlowMappingStart) => {
                 self.state = State::FlowMappingFirstKey;
-                Ok((Event::MappingStart(anchor_id), mark))
+                self.capacity_hint = self.scanner.take_capacity_hint(mark.index());
+                Ok((Event::MappingStart(anchor_id, tag), mark))
             }
             Token(mark, TokenType::BlockSequenceStart) if block => {
                 self.state = State::BlockSequenceFirstEntry;
-                Ok((Event::SequenceStart(anchor_id), mark))
+                Ok((Event::SequenceStart(anchor_id, tag), mark))
             }
             Token(mark, TokenType::BlockMappingStart) if block => {
                 self.state = State::BlockMappingFirstKey;
-                Ok((Event::MappingStart(anchor_id), mark))
+                Ok((Event::MappingStart(anchor_id, tag), mark))
             }
             // ex 7.2, an empty scalar can follow a secondary tag
             Token(mark, _) if tag.is_some() || anchor_id > 0 => {
@@ -752,7 +1231,7 @@ impl<T: Iterator<Item = char>> Parser<T> {
             Token(mark, TokenType::Key) => {
                 self.state = State::FlowSequenceEntryMappingKey;
                 self.skip();
-                Ok((Event::MappingStart(0), mark))
+                Ok((Event::MappingStart(0, None), mark))
             }
             _ => {
                 self.push_state(State::FlowSequenceEntry);
@@ -863,9 +1342,43 @@ impl<T: Iterator<Item = char>> Parser<T> {
     }
 }
 
+/// Pull the next event, or `None` once `Event::StreamEnd` or an error has
+/// been returned, so a parser can be filtered or mapped like any other
+/// iterator.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate yaml_rust_davvid as yaml_rust;
+/// use yaml_rust::parser::Parser;
+/// use yaml_rust::Event;
+///
+/// let scalars: Vec<String> = Parser::new("[a, {b: c}]".chars())
+///     .filter_map(|ev| match ev {
+///         Ok((Event::Scalar(value, ..), _)) => Some(value),
+///         _ => None,
+///     })
+///     .collect();
+/// assert_eq!(scalars, ["a", "b", "c"]);
+/// ```
+impl<T: Iterator<Item = char>> Iterator for Parser<T> {
+    type Item = ParseResult;
+
+    fn next(&mut self) -> Option<ParseResult> {
+        if self.finished {
+            return None;
+        }
+        let result = self.next_event();
+        self.finished = !matches!(result, Ok((ref ev, _)) if *ev != Event::StreamEnd);
+        Some(result)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::{Event, Parser};
+    use crate::scanner::{ScanError, ScannerConfig, TScalarStyle};
+    use std::time::Duration;
 
     #[test]
     fn test_peek_eq_parse() {
@@ -884,9 +1397,247 @@ a5: *x
         let mut p = Parser::new(s.chars());
         while {
             let event_peek = p.peek().unwrap().clone();
-            let event = p.next().unwrap();
+            let event = p.next_event().unwrap();
             assert_eq!(event, event_peek);
             event.0 != Event::StreamEnd
         } {}
     }
+
+    #[test]
+    fn test_next_event() {
+        let events: Vec<Event> = Parser::new("- a\n- *x".chars())
+            .map_while(Result::ok)
+            .map(|(ev, _)| ev)
+            .collect();
+        assert_eq!(
+            events,
+            [
+                Event::StreamStart,
+                Event::DocumentStart,
+                Event::sequence_start(),
+                Event::scalar("a", TScalarStyle::Plain),
+            ]
+        );
+
+        let mut p = Parser::new("a".chars());
+        let results: Vec<_> = p.by_ref().collect();
+        assert_eq!(results.len(), 5);
+        assert_eq!(results[4].as_ref().unwrap().0, Event::StreamEnd);
+        assert!(p.next().is_none());
+
+        let mut p = Parser::new("- *x".chars());
+        assert!(p.by_ref().last().unwrap().is_err());
+        assert!(p.next().is_none());
+    }
+
+    #[test]
+    fn test_record_time() {
+        let mut p = Parser::new("[1, [2]]".chars());
+        p.by_ref().for_each(drop);
+        assert_eq!(p.stats().elapsed, Duration::ZERO);
+        assert_eq!(p.stats().max_depth, 2);
+
+        let mut p = Parser::new("[1, [2]]".chars());
+        p.record_time(true);
+        p.by_ref().for_each(drop);
+        let elapsed = p.stats().elapsed;
+        assert!(elapsed > Duration::ZERO);
+        assert_eq!(p.stats().elapsed, elapsed);
+    }
+
+    fn parse_all(s: &str, config: ScannerConfig) -> Result<(), ScanError> {
+        let mut p = Parser::new_with_config(s.chars(), config);
+        while p.next_event()?.0 != Event::StreamEnd {}
+        Ok(())
+    }
+
+    #[test]
+    fn test_implicit_key_len() {
+        let s = format!("{}: value", "k".repeat(2000));
+        let err = parse_all(&s, ScannerConfig::default()).unwrap_err();
+        assert_eq!(err.info(), "mapping values are not allowed in this context");
+        let config = ScannerConfig {
+            max_implicit_key_len: 4096,
+            ..ScannerConfig::default()
+        };
+        assert!(parse_all(&s, config).is_ok());
+    }
+
+    #[test]
+    fn test_document_limits() {
+        let config = ScannerConfig {
+            max_document_bytes: Some(20),
+            max_anchors: Some(2),
+            ..ScannerConfig::default()
+        };
+        // the limits apply to each document separately
+        let s = "--- [&a 1, &b 2]\n--- [&a 1, &b 2]\n--- [1, 2, 3, 4]\n";
+        assert!(parse_all(s, config).is_ok());
+
+        let err = parse_all("[&a 1, &b 2, &c 3]", config).unwrap_err();
+        assert_eq!(err.info(), "document exceeds the maximum number of anchors");
+        assert_eq!(err.marker().col(), 13);
+
+        let err = parse_all("--- [1]\n--- [1, 2, 3, 4, 5, 6, 7, 8]", config).unwrap_err();
+        assert_eq!(err.info(), "document exceeds the maximum size in bytes");
+        assert_eq!(err.marker().line(), 2);
+    }
+
+    #[test]
+    fn test_stream_limits() {
+        let config = ScannerConfig {
+            max_documents: Some(2),
+            ..ScannerConfig::default()
+        };
+        assert!(parse_all("--- 1\n--- 2\n", config).is_ok());
+        let err = parse_all("--- 1\n--- 2\n--- 3\n", config).unwrap_err();
+        assert_eq!(err.info(), "stream exceeds the maximum number of documents");
+        assert_eq!(err.marker().line(), 3);
+
+        // stream start and end, document start and end, and the scalar
+        let config = ScannerConfig {
+            max_events: Some(5),
+            ..ScannerConfig::default()
+        };
+        assert!(parse_all("1", config).is_ok());
+        let err = parse_all("[1]", config).unwrap_err();
+        assert_eq!(err.info(), "stream exceeds the maximum number of events");
+    }
+
+    #[test]
+    fn test_block_scalar_chunks() {
+        let config = ScannerConfig {
+            block_scalar_chunk_bytes: Some(8),
+            ..ScannerConfig::default()
+        };
+        let s =
+            "a: &x !t |\n  0123456789\n  abc\n\n  def\n\nb: >-\n  one\n  two\n  three\n\n  four\n";
+        let mut p = Parser::new_with_config(s.chars(), config);
+        let mut scalars = Vec::new();
+        let mut chunks = Vec::new();
+        loop {
+            match p.next_event().unwrap().0 {
+                Event::ScalarChunk(v) => {
+                    assert!(v.len() >= 8);
+                    chunks.push(v);
+                }
+                Event::Scalar(v, _, aid, tag) => {
+                    scalars.push((chunks.concat() + &v, chunks.len(), aid, tag.is_some()));
+                    chunks.clear();
+                }
+                Event::StreamEnd => break,
+                _ => {}
+            }
+        }
+        assert_eq!(
+            scalars,
+            [
+                ("a".to_owned(), 0, 0, false),
+                ("0123456789\nabc\n\ndef\n".to_owned(), 1, 1, true),
+                ("b".to_owned(), 0, 0, false),
+                ("one two three\nfour".to_owned(), 1, 0, false),
+            ]
+        );
+
+        // loaders see whole scalars
+        let mut loader = crate::YamlLoader::new();
+        loader.scanner_config(config);
+        let docs = loader.parse_from_str(s).unwrap();
+        assert_eq!(docs, crate::YamlLoader::load_from_str(s).unwrap());
+
+        // receivers see chunks only if they take them
+        use super::MarkedEventReceiver;
+        use crate::scanner::Marker;
+        struct Sizes(Vec<usize>, bool);
+        impl MarkedEventReceiver for Sizes {
+            fn on_event(&mut self, ev: Event, _: Marker) {
+                match ev {
+                    Event::ScalarChunk(v) | Event::Scalar(v, ..) => self.0.push(v.len()),
+                    _ => {}
+                }
+            }
+            fn takes_scalar_chunks(&self) -> bool {
+                self.1
+            }
+        }
+        for (takes, sizes) in [(false, &[1, 20, 1, 18][..]), (true, &[1, 10, 10, 1, 13, 5])] {
+            let mut recv = Sizes(Vec::new(), takes);
+            Parser::new_with_config(s.chars(), config)
+                .load(&mut recv, false)
+                .unwrap();
+            assert_eq!(recv.0, sizes);
+        }
+    }
+
+    #[test]
+    fn test_byte_limits() {
+        let config = ScannerConfig {
+            max_scalar_bytes: Some(6),
+            ..ScannerConfig::default()
+        };
+        // content is counted after folding and escapes, in UTF-8 bytes
+        let s = "- abc\n  de\n- \"\\x41\\x42\"\n- |-\n  ab\n  cd\n\n- 日本\n- [a, b, c, d]\n";
+        assert!(parse_all(s, config).is_ok());
+        for (s, line) in [
+            ("a: 1234567", 1),
+            ("- 'abc\n\n  defg'", 1),
+            ("- \"\\x41\\x42\\x43\\x44\\x45\\x46\\x47\"", 1),
+            ("x:\n- 日本語", 2),
+            ("--- |\n  abc\n  def\n", 2),
+        ] {
+            let err = parse_all(s, config).unwrap_err();
+            assert_eq!(
+                err.info(),
+                "scalar exceeds the maximum size in bytes",
+                "{:?}",
+                s
+            );
+            assert_eq!(err.marker().line(), line);
+        }
+
+        let config = ScannerConfig {
+            max_document_bytes: Some(14),
+            ..ScannerConfig::default()
+        };
+        assert!(parse_all("--- [日本]\n--- [日本]\n", config).is_ok());
+        let err = parse_all("--- [1]\n--- [日本語日本]\n", config).unwrap_err();
+        assert_eq!(err.info(), "document exceeds the maximum size in bytes");
+        assert_eq!(err.marker().line(), 2);
+        // a long scalar fails before it has been read to its end
+        let s = format!("a: {}\n", "x".repeat(100));
+        let err = parse_all(&s, config).unwrap_err();
+        assert_eq!(err.marker().col(), 15);
+    }
+
+    #[test]
+    fn test_event_log() {
+        use super::{EventLog, EventReceiver};
+        use crate::YamlLoader;
+
+        let s = "a: &x [1, 2]\nb: *x\n--- !t c\n";
+        let log = EventLog::record(s).unwrap();
+        assert_eq!(log.events()[0].0, Event::StreamStart);
+        assert_eq!(log.events().last().unwrap().0, Event::StreamEnd);
+
+        let mut direct = EventLog::new();
+        Parser::new(s.chars()).load(&mut direct, true).unwrap();
+        let mut replayed = EventLog::new();
+        log.replay(&mut replayed);
+        assert_eq!(replayed, direct);
+        assert_eq!(replayed.events()[3].1.line(), 1);
+
+        struct Count(usize);
+        impl EventReceiver for Count {
+            fn on_event(&mut self, _: Event) {
+                self.0 += 1;
+            }
+        }
+        let mut count = Count(0);
+        log.replay(&mut count);
+        assert_eq!(count.0, log.len());
+
+        let docs = YamlLoader::new().parse_from_events(&log).unwrap();
+        assert_eq!(docs, YamlLoader::load_from_str(s).unwrap());
+        assert!(EventLog::record("[").is_err());
+    }
 }
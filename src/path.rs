@@ -0,0 +1,937 @@
+//! Paths from a document root to the nodes below it, traversal that reports
+//! them, flattening of a tree into a map from leaf paths to values, and
+//! setting a node by its flattened path.
+//!
+//! Paths written as text have one syntax wherever they are taken: by
+//! `Yaml::set`, `config::Config::lookup`, `yaml::extract` and
+//! `shared::Overlay`. Segments are separated by `.` and each names a hash
+//! key, or an array index if the node is an array and the segment is a
+//! number. An index can also be written in brackets after a segment, as
+//! `flatten` writes it, so `env[1].name` and `env.1.name` are the same path.
+//! A key matches a number or boolean key with the same text. The paths of
+//! an `index::YamlIndex` are written with dots only.
+//!
+//! # Examples
+//!
+//! ```
+//! # extern crate yaml_rust_davvid as yaml_rust;
+//! use yaml_rust::path::PathElement;
+//! use yaml_rust::{Yaml, YamlLoader};
+//!
+//! let mut doc = YamlLoader::load_from_str("db: {user: app, password: hunter2}").unwrap().remove(0);
+//! doc.walk_mut(|path, node| {
+//!     if path.last() == Some(&PathElement::Key(Yaml::from_str("password"))) {
+//!         *node = Yaml::String("***".to_owned());
+//!     }
+//! });
+//! assert_eq!(doc["db"]["password"].as_str(), Some("***"));
+//!
+//! let mut paths = Vec::new();
+//! doc.walk(|path, _| paths.push(path.to_string()));
+//! assert_eq!(paths, vec!["$", "$.db", "$.db.user", "$.db.password"]);
+//! ```
+
+use crate::yaml::{Hash, Yaml};
+
+use hashlink::LinkedHashMap;
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt;
+use std::mem;
+use std::ops::Deref;
+
+/// A step from a node to one of its children.
+#[derive(Clone, PartialEq, Debug, Eq)]
+pub enum PathElement {
+    /// An element of an array.
+    Index(usize),
+    /// The value for a key of a hash.
+    Key(Yaml),
+}
+
+impl fmt::Display for PathElement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PathElement::Index(i) => write!(f, "[{}]", i),
+            PathElement::Key(ref key) => match key.string_text() {
+                Some(s) => write!(f, ".{}", s),
+                None => write!(f, "[{:?}]", key),
+            },
+        }
+    }
+}
+
+impl From<usize> for PathElement {
+    fn from(i: usize) -> PathElement {
+        PathElement::Index(i)
+    }
+}
+
+impl<'a> From<&'a str> for PathElement {
+    fn from(key: &'a str) -> PathElement {
+        PathElement::Key(Yaml::String(key.to_owned()))
+    }
+}
+
+/// The steps from a document root to a node. Displays as `$` followed by
+/// each step, such as `$.servers[0].name`.
+#[derive(Clone, PartialEq, Debug, Default, Eq)]
+pub struct YamlPath {
+    elements: Vec<PathElement>,
+}
+
+impl YamlPath {
+    /// The path to the document root.
+    pub fn new() -> YamlPath {
+        YamlPath::default()
+    }
+
+    pub fn elements(&self) -> &[PathElement] {
+        &self.elements
+    }
+
+    /// The step to the node from its parent, or `None` for the root.
+    pub fn last(&self) -> Option<&PathElement> {
+        self.elements.last()
+    }
+
+    /// The number of steps from the root.
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    pub fn push(&mut self, element: PathElement) {
+        self.elements.push(element);
+    }
+
+    pub fn pop(&mut self) -> Option<PathElement> {
+        self.elements.pop()
+    }
+}
+
+impl fmt::Display for YamlPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("$")?;
+        for element in &self.elements {
+            write!(f, "{}", element)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether `Yaml::set_with` creates the nodes on the way to the one it
+/// sets.
+#[derive(Clone, Copy, PartialEq, Debug, Default, Eq)]
+pub enum Intermediates {
+    /// Create missing and null nodes as hashes or arrays, padding arrays
+    /// with `Yaml::Null` up to the index.
+    #[default]
+    Create,
+    /// Every node above the one being set must exist. The last step may
+    /// still add a key to a hash or append to an array.
+    Require,
+}
+
+/// Why `Yaml::set` failed.
+#[derive(Clone, PartialEq, Debug, Eq)]
+pub enum SetError {
+    /// The node at the path is not the kind of collection the next step
+    /// needs, such as a scalar where a hash is expected.
+    Conflict {
+        path: YamlPath,
+        expected: &'static str,
+        found: &'static str,
+    },
+    /// The node at the path does not exist, with `Intermediates::Require`.
+    Missing(YamlPath),
+}
+
+impl fmt::Display for SetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SetError::Conflict {
+                ref path,
+                expected,
+                found,
+            } => write!(f, "expected {} at {}, found {}", expected, path, found),
+            SetError::Missing(ref path) => write!(f, "no node at {}", path),
+        }
+    }
+}
+
+impl Error for SetError {}
+
+/// Why `Yaml::try_index` failed, with the path to where it stopped.
+#[derive(Clone, PartialEq, Debug, Eq)]
+pub enum IndexError {
+    /// The node at the path is not a collection the step can index, such
+    /// as a scalar where a hash is expected.
+    Conflict {
+        path: YamlPath,
+        expected: &'static str,
+        found: &'static str,
+    },
+    /// The collection has no key or item for the last step of the path.
+    Missing(YamlPath),
+}
+
+impl fmt::Display for IndexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            IndexError::Conflict {
+                ref path,
+                expected,
+                found,
+            } => write!(f, "expected {} at {}, found {}", expected, path, found),
+            IndexError::Missing(ref path) => write!(f, "no node at {}", path),
+        }
+    }
+}
+
+impl Error for IndexError {}
+
+/// A node found by `Yaml::try_index`, with the path to it from the node the
+/// chain started at. Derefs to the node.
+#[derive(Clone, PartialEq, Debug)]
+pub struct YamlRef<'a> {
+    node: &'a Yaml,
+    path: YamlPath,
+}
+
+impl<'a> YamlRef<'a> {
+    pub fn node(&self) -> &'a Yaml {
+        self.node
+    }
+
+    pub fn path(&self) -> &YamlPath {
+        &self.path
+    }
+
+    /// Index the node further, see `Yaml::try_index`.
+    pub fn try_index<I: Into<PathElement>>(mut self, index: I) -> Result<YamlRef<'a>, IndexError> {
+        let step = index.into();
+        let expected = match step {
+            PathElement::Key(_) => "hash",
+            PathElement::Index(_) => "array",
+        };
+        let child = match (&step, self.node) {
+            (PathElement::Key(key), Yaml::Hash(h)) => h.get(key).or_else(|| {
+                // a key written as text, like the paths of `Yaml::set`
+                h.iter()
+                    .find(|(k, _)| key_text(k).as_deref() == key.as_str())
+                    .map(|(_, v)| v)
+            }),
+            (&PathElement::Index(i), Yaml::Array(v)) => v.get(i),
+            (&PathElement::Index(i), Yaml::Hash(h)) => h.get(&Yaml::Integer(i as i64)),
+            (_, node) => {
+                return Err(IndexError::Conflict {
+                    path: self.path,
+                    expected,
+                    found: node.type_str(),
+                })
+            }
+        };
+        self.path.push(step);
+        match child {
+            Some(node) => Ok(YamlRef {
+                node,
+                path: self.path,
+            }),
+            None => Err(IndexError::Missing(self.path)),
+        }
+    }
+}
+
+impl<'a> Deref for YamlRef<'a> {
+    type Target = Yaml;
+
+    fn deref(&self) -> &Yaml {
+        self.node
+    }
+}
+
+impl Yaml {
+    /// Call `f` with every node of the tree and the path to it, depth first
+    /// and parents before their children. Hash keys are part of the paths
+    /// to their values rather than visited themselves, and the content of a
+    /// `Yaml::Tagged` node is visited through its tag.
+    pub fn walk<F: FnMut(&YamlPath, &Yaml)>(&self, mut f: F) {
+        walk(self, &mut YamlPath::new(), &mut f);
+    }
+
+    /// Like `walk`, with mutable access to each node. A node is visited
+    /// before its children, so replacing it changes which children are
+    /// visited.
+    pub fn walk_mut<F: FnMut(&YamlPath, &mut Yaml)>(&mut self, mut f: F) {
+        walk_mut(self, &mut YamlPath::new(), &mut f);
+    }
+
+    /// Find the values of `key` in every hash of the tree, such as every
+    /// `image` of a bundle of manifests, with their paths in document
+    /// order. Values that match inside a value that matches are found too.
+    /// Keys that are numbers or booleans match their text.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::YamlLoader;
+    ///
+    /// let doc = YamlLoader::load_from_str("
+    /// containers: [{name: app, image: web:1}]
+    /// initContainers: [{name: init, image: busybox}]
+    /// ").unwrap().remove(0);
+    /// let images: Vec<_> = doc
+    ///     .deep_get_all("image")
+    ///     .into_iter()
+    ///     .map(|(path, value)| format!("{} {}", path, value.as_str().unwrap()))
+    ///     .collect();
+    /// assert_eq!(images, ["$.containers[0].image web:1", "$.initContainers[0].image busybox"]);
+    /// ```
+    pub fn deep_get_all(&self, key: &str) -> Vec<(YamlPath, &Yaml)> {
+        let mut found = Vec::new();
+        walk(self, &mut YamlPath::new(), &mut |path, node| {
+            if let Some(PathElement::Key(k)) = path.last() {
+                if key_text(k).as_deref() == Some(key) {
+                    found.push((path.clone(), node));
+                }
+            }
+        });
+        found
+    }
+
+    /// Map the path of every leaf to its value, such as `a.b[0].c` for
+    /// `{a: {b: [{c: 1}]}}` with the separator `.`. Hash keys are joined by
+    /// `separator` and array indices appended in brackets. Scalars, tagged
+    /// nodes and empty collections are leaves, and a leaf root has the
+    /// empty path.
+    ///
+    /// Keys that are numbers or booleans are written as text; values under
+    /// any other key that is not a string are left out.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::{Yaml, YamlLoader};
+    ///
+    /// let doc = YamlLoader::load_from_str("db: {hosts: [a, b], port: 5432}").unwrap().remove(0);
+    /// let flat = doc.flatten("_");
+    /// let keys: Vec<_> = flat.keys().map(String::as_str).collect();
+    /// assert_eq!(keys, ["db_hosts[0]", "db_hosts[1]", "db_port"]);
+    /// assert_eq!(flat["db_port"], Yaml::Integer(5432));
+    /// assert_eq!(Yaml::unflatten(flat, "_"), doc);
+    /// ```
+    pub fn flatten(&self, separator: &str) -> LinkedHashMap<String, Yaml> {
+        let mut flat = LinkedHashMap::new();
+        flatten(self, &mut String::new(), separator, &mut flat);
+        flat
+    }
+
+    /// Build a tree from paths written by `flatten` and their values. Hash
+    /// keys are loaded as strings, arrays are padded with `Yaml::Null` up
+    /// to the largest index, and a later path replaces any leaf an earlier
+    /// one put in its way. Returns `Yaml::Null` if there are no entries.
+    pub fn unflatten<I, K>(entries: I, separator: &str) -> Yaml
+    where
+        I: IntoIterator<Item = (K, Yaml)>,
+        K: AsRef<str>,
+    {
+        let mut root = Yaml::Null;
+        for (path, value) in entries {
+            let steps = parse_flat_path(path.as_ref(), separator);
+            insert(&mut root, &steps, value);
+        }
+        root
+    }
+
+    /// Set the node at `path`, creating the hashes and arrays on the way as
+    /// needed. Returns the node that was replaced, if there was one. See
+    /// the `path` module for how paths are written; a number segment only
+    /// indexes an array that is already there, so `env.0` on a document
+    /// without `env` creates the hash `{0: ...}`.
+    ///
+    /// Path keys are strings, but match an existing number or boolean key
+    /// with the same text, so `ports.8080` finds the key `8080` of
+    /// `{ports: {8080: http}}`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::{Yaml, YamlLoader};
+    ///
+    /// let mut doc = YamlLoader::load_from_str("image: {tag: v1}").unwrap().remove(0);
+    /// let old = doc.set("image.tag", Yaml::from_str("v2")).unwrap();
+    /// assert_eq!(old, Some(Yaml::from_str("v1")));
+    /// doc.set("env[1].name", Yaml::from_str("DEBUG")).unwrap();
+    /// assert_eq!(doc["env"][0], Yaml::Null);
+    /// assert_eq!(doc["env"][1]["name"].as_str(), Some("DEBUG"));
+    /// doc.set("env.1.value", Yaml::Integer(1)).unwrap();
+    /// assert_eq!(doc["env"][1]["value"].as_i64(), Some(1));
+    /// assert!(doc.set("image.tag.major", Yaml::Integer(2)).is_err());
+    /// ```
+    pub fn set(&mut self, path: &str, value: Yaml) -> Result<Option<Yaml>, SetError> {
+        self.set_with(path, value, Intermediates::Create)
+    }
+
+    /// Index a hash by a key or an array by a position like `Index` does,
+    /// but fail with the path taken and the type found instead of giving
+    /// `Yaml::BadValue`. The result can be indexed further, and its errors
+    /// have the path from `self`.
+    ///
+    /// A key that is not in a hash also matches a number or boolean key
+    /// written as text, as with `set`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::YamlLoader;
+    ///
+    /// let doc = YamlLoader::load_from_str("servers: [{name: web, port: 80}]").unwrap().remove(0);
+    /// let port = doc.try_index("servers").and_then(|v| v.try_index(0)?.try_index("port"));
+    /// assert_eq!(port.unwrap().as_i64(), Some(80));
+    ///
+    /// let err = doc.try_index("servers").and_then(|v| v.try_index("web")).unwrap_err();
+    /// assert_eq!(err.to_string(), "expected hash at $.servers, found array");
+    /// let err = doc.try_index("servers").and_then(|v| v.try_index(1)).unwrap_err();
+    /// assert_eq!(err.to_string(), "no node at $.servers[1]");
+    /// ```
+    pub fn try_index<I: Into<PathElement>>(&self, index: I) -> Result<YamlRef<'_>, IndexError> {
+        YamlRef {
+            node: self,
+            path: YamlPath::new(),
+        }
+        .try_index(index)
+    }
+
+    /// Like `set`, choosing whether missing nodes on the way are created.
+    pub fn set_with(
+        &mut self,
+        path: &str,
+        value: Yaml,
+        intermediates: Intermediates,
+    ) -> Result<Option<Yaml>, SetError> {
+        let steps = parse_flat_path(path, ".");
+        set(self, &steps, value, intermediates, &mut YamlPath::new())
+    }
+}
+
+pub(crate) fn set(
+    node: &mut Yaml,
+    steps: &[PathElement],
+    value: Yaml,
+    intermediates: Intermediates,
+    path: &mut YamlPath,
+) -> Result<Option<Yaml>, SetError> {
+    let (step, rest) = match steps.split_first() {
+        Some(split) => split,
+        None => return Ok(Some(mem::replace(node, value))),
+    };
+    let step = &*resolve_step(node, step);
+    let create = intermediates == Intermediates::Create;
+    let expected = match *step {
+        PathElement::Key(_) => "hash",
+        PathElement::Index(_) => "array",
+    };
+    if node.is_null() && create {
+        *node = match *step {
+            PathElement::Key(_) => Yaml::Hash(Hash::new()),
+            PathElement::Index(_) => Yaml::Array(Vec::new()),
+        };
+    }
+    let child = match (step, &mut *node) {
+        (PathElement::Key(key), Yaml::Hash(h)) => {
+            let existing = if h.contains_key(key) {
+                Some(key.clone())
+            } else {
+                h.keys()
+                    .find(|k| key_text(k).as_deref() == key.as_str())
+                    .cloned()
+            };
+            match existing {
+                Some(existing) => h.get_mut(&existing),
+                None if create || rest.is_empty() => {
+                    path.push(step.clone());
+                    let child = h.entry(key.clone()).or_insert(Yaml::Null);
+                    return set(child, rest, value, intermediates, path).map(|_| None);
+                }
+                None => None,
+            }
+        }
+        (&PathElement::Index(i), Yaml::Array(v)) => {
+            if i >= v.len() && (create || (rest.is_empty() && i == v.len())) {
+                v.resize(i + 1, Yaml::Null);
+                path.push(step.clone());
+                return set(&mut v[i], rest, value, intermediates, path).map(|_| None);
+            }
+            v.get_mut(i)
+        }
+        (_, node) => {
+            return Err(SetError::Conflict {
+                path: path.clone(),
+                expected,
+                found: node.type_str(),
+            })
+        }
+    };
+    path.push(step.clone());
+    match child {
+        Some(child) => set(child, rest, value, intermediates, path),
+        None => Err(SetError::Missing(path.clone())),
+    }
+}
+
+// The text `flatten` writes for a hash key, if it writes one.
+pub(crate) fn key_text(key: &Yaml) -> Option<String> {
+    match *key {
+        Yaml::String(ref s) | Yaml::Real(ref s) => Some(s.clone()),
+        Yaml::Interned(ref s) => Some(s.to_string()),
+        Yaml::Integer(i) => Some(i.to_string()),
+        Yaml::Boolean(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn flatten(
+    node: &Yaml,
+    path: &mut String,
+    separator: &str,
+    flat: &mut LinkedHashMap<String, Yaml>,
+) {
+    let len = path.len();
+    match *node {
+        Yaml::Array(ref v) if !v.is_empty() => {
+            for (i, child) in v.iter().enumerate() {
+                path.push_str(&format!("[{}]", i));
+                flatten(child, path, separator, flat);
+                path.truncate(len);
+            }
+        }
+        Yaml::Hash(ref h) if !h.is_empty() => {
+            for (key, child) in h {
+                let key = match key_text(key) {
+                    Some(key) => key,
+                    None => continue,
+                };
+                if !path.is_empty() {
+                    path.push_str(separator);
+                }
+                path.push_str(&key);
+                flatten(child, path, separator, flat);
+                path.truncate(len);
+            }
+        }
+        _ => {
+            flat.insert(path.clone(), node.clone());
+        }
+    }
+}
+
+// The index `step` takes into an array: its index, or its key if that is
+// written as a number.
+pub(crate) fn step_index(step: &PathElement) -> Option<usize> {
+    match *step {
+        PathElement::Index(i) => Some(i),
+        PathElement::Key(ref key) => match key.string_text() {
+            Some(s) if s.bytes().all(|b| b.is_ascii_digit()) => s.parse().ok(),
+            _ => None,
+        },
+    }
+}
+
+// The step a path segment takes from `node`. Segments are parsed without
+// looking at the tree, so the `0` of `a.0` is a key until it meets an
+// array, where it is an index.
+pub(crate) fn resolve_step<'a>(node: &Yaml, step: &'a PathElement) -> Cow<'a, PathElement> {
+    match (step_index(step), node) {
+        (Some(i), Yaml::Array(_)) => Cow::Owned(PathElement::Index(i)),
+        _ => Cow::Borrowed(step),
+    }
+}
+
+// The child of `node` that `step` selects, and the step that selects it
+// exactly: a key matches itself first, then a key with the same text.
+pub(crate) fn child<'a>(node: &'a Yaml, step: &PathElement) -> Option<(PathElement, &'a Yaml)> {
+    match (&*resolve_step(node, step), node) {
+        (PathElement::Key(key), Yaml::Hash(h)) => h
+            .get_key_value(key)
+            .or_else(|| {
+                h.iter()
+                    .find(|(k, _)| key_text(k).as_deref() == key.as_str())
+            })
+            .map(|(k, child)| (PathElement::Key(k.clone()), child)),
+        (&PathElement::Index(i), Yaml::Array(v)) => {
+            v.get(i).map(|child| (PathElement::Index(i), child))
+        }
+        _ => None,
+    }
+}
+
+// The node at the end of `steps` from `node`.
+pub(crate) fn lookup<'a>(node: &'a Yaml, steps: &[PathElement]) -> Option<&'a Yaml> {
+    steps
+        .iter()
+        .try_fold(node, |node, step| child(node, step).map(|(_, child)| child))
+}
+
+// The steps of a path written by `flatten`, or of any path with the
+// separator `.`.
+pub(crate) fn parse_flat_path(path: &str, separator: &str) -> Vec<PathElement> {
+    let mut steps = Vec::new();
+    if path.is_empty() {
+        return steps;
+    }
+    let parts: Vec<&str> = if separator.is_empty() {
+        vec![path]
+    } else {
+        path.split(separator).collect()
+    };
+    for part in parts {
+        // indices are taken from the end, so a key may contain brackets
+        let mut name = part;
+        let mut indices = Vec::new();
+        while let Some(body) = name.strip_suffix(']') {
+            let open = match body.rfind('[') {
+                Some(open) => open,
+                None => break,
+            };
+            let digits = &body[open + 1..];
+            match digits.parse::<usize>() {
+                Ok(i) if digits.bytes().all(|b| b.is_ascii_digit()) => indices.push(i),
+                _ => break,
+            }
+            name = &body[..open];
+        }
+        if !name.is_empty() || indices.is_empty() {
+            steps.push(PathElement::Key(Yaml::String(name.to_owned())));
+        }
+        steps.extend(indices.into_iter().rev().map(PathElement::Index));
+    }
+    steps
+}
+
+fn insert(node: &mut Yaml, steps: &[PathElement], value: Yaml) {
+    let (step, rest) = match steps.split_first() {
+        Some(split) => split,
+        None => {
+            *node = value;
+            return;
+        }
+    };
+    match *step {
+        PathElement::Key(ref key) => {
+            if node.as_hash().is_none() {
+                *node = Yaml::Hash(Hash::new());
+            }
+            if let Yaml::Hash(ref mut h) = *node {
+                let child = h.entry(key.clone()).or_insert(Yaml::Null);
+                insert(child, rest, value);
+            }
+        }
+        PathElement::Index(i) => {
+            if node.as_vec().is_none() {
+                *node = Yaml::Array(Vec::new());
+            }
+            if let Yaml::Array(ref mut v) = *node {
+                if v.len() <= i {
+                    v.resize(i + 1, Yaml::Null);
+                }
+                insert(&mut v[i], rest, value);
+            }
+        }
+    }
+}
+
+fn walk<'a, F: FnMut(&YamlPath, &'a Yaml)>(node: &'a Yaml, path: &mut YamlPath, f: &mut F) {
+    f(path, node);
+    let node = match *node {
+        Yaml::Tagged(_, ref v) => v,
+        _ => node,
+    };
+    match *node {
+        Yaml::Array(ref v) => {
+            for (i, child) in v.iter().enumerate() {
+                path.push(PathElement::Index(i));
+                walk(child, path, f);
+                path.pop();
+            }
+        }
+        Yaml::Hash(ref h) => {
+            for (key, child) in h {
+                path.push(PathElement::Key(key.clone()));
+                walk(child, path, f);
+                path.pop();
+            }
+        }
+        _ => {}
+    }
+}
+
+fn walk_mut<F: FnMut(&YamlPath, &mut Yaml)>(node: &mut Yaml, path: &mut YamlPath, f: &mut F) {
+    f(path, node);
+    let node = match *node {
+        Yaml::Tagged(_, ref mut v) => v,
+        ref mut node => node,
+    };
+    match *node {
+        Yaml::Array(ref mut v) => {
+            for (i, child) in v.iter_mut().enumerate() {
+                path.push(PathElement::Index(i));
+                walk_mut(child, path, f);
+                path.pop();
+            }
+        }
+        Yaml::Hash(ref mut h) => {
+            for (key, child) in h.iter_mut() {
+                path.push(PathElement::Key(key.clone()));
+                walk_mut(child, path, f);
+                path.pop();
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::yaml::{extract, lookup_path};
+    use crate::YamlLoader;
+
+    #[test]
+    fn test_walk_paths() {
+        let s = "
+servers:
+  - name: a
+    ports: [80, 443]
+  - !host {name: b}
+1: one
+";
+        let doc = &YamlLoader::load_from_str(s).unwrap()[0];
+        let mut visited = Vec::new();
+        doc.walk(|path, node| visited.push((path.to_string(), node.as_str().is_some())));
+        assert_eq!(
+            visited,
+            vec![
+                ("$".to_owned(), false),
+                ("$.servers".to_owned(), false),
+                ("$.servers[0]".to_owned(), false),
+                ("$.servers[0].name".to_owned(), true),
+                ("$.servers[0].ports".to_owned(), false),
+                ("$.servers[0].ports[0]".to_owned(), false),
+                ("$.servers[0].ports[1]".to_owned(), false),
+                ("$.servers[1]".to_owned(), false),
+                ("$.servers[1].name".to_owned(), true),
+                ("$[Integer(1)]".to_owned(), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_flatten() {
+        let s = "
+app:
+  name: web
+  ports: [80, [443, 8443]]
+  empty: {}
+  tagged: !t {x: 1}
+8080: true
+";
+        let doc = YamlLoader::load_from_str(s).unwrap().remove(0);
+        let flat = doc.flatten(".");
+        let keys: Vec<_> = flat.keys().map(String::as_str).collect();
+        assert_eq!(
+            keys,
+            [
+                "app.name",
+                "app.ports[0]",
+                "app.ports[1][0]",
+                "app.ports[1][1]",
+                "app.empty",
+                "app.tagged",
+                "8080"
+            ]
+        );
+        let back = Yaml::unflatten(flat, ".");
+        assert_eq!(back["app"], doc["app"]);
+        assert_eq!(back["8080"], Yaml::Boolean(true));
+
+        assert_eq!(Yaml::Integer(1).flatten(".")[""], Yaml::Integer(1));
+        assert_eq!(
+            Yaml::unflatten(Vec::<(String, Yaml)>::new(), "."),
+            Yaml::Null
+        );
+        let from_props = Yaml::unflatten(
+            vec![
+                ("[1].a[b]", Yaml::Integer(1)),
+                ("[0]", Yaml::Integer(0)),
+                ("[1].c", Yaml::Integer(2)),
+            ],
+            ".",
+        );
+        assert_eq!(
+            from_props,
+            YamlLoader::load_from_str("[0, {'a[b]': 1, c: 2}]").unwrap()[0]
+        );
+    }
+
+    #[test]
+    fn test_set() {
+        let mut doc = YamlLoader::load_from_str("ports: {8080: http}\nlist: [a]\nname: web")
+            .unwrap()
+            .remove(0);
+        let old = doc.set("ports.8080", Yaml::from_str("https")).unwrap();
+        assert_eq!(old, Some(Yaml::from_str("http")));
+        assert_eq!(doc["ports"][8080].as_str(), Some("https"));
+        assert_eq!(doc.set("new.deep[0]", Yaml::Integer(1)), Ok(None));
+        assert_eq!(doc["new"]["deep"][0], Yaml::Integer(1));
+
+        let err = doc.set("name[0]", Yaml::Null).unwrap_err();
+        assert_eq!(err.to_string(), "expected array at $.name, found string");
+
+        let require = Intermediates::Require;
+        assert_eq!(
+            doc.set_with("list[1]", Yaml::from_str("b"), require),
+            Ok(None)
+        );
+        assert_eq!(doc["list"][1].as_str(), Some("b"));
+        assert_eq!(
+            doc.set_with("ports.443", Yaml::from_str("tls"), require),
+            Ok(None)
+        );
+        let err = doc.set_with("list[3]", Yaml::Null, require).unwrap_err();
+        assert_eq!(err.to_string(), "no node at $.list[3]");
+        let err = doc
+            .set_with("missing.key", Yaml::Null, require)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "no node at $.missing");
+        assert!(doc["missing"].is_badvalue());
+
+        let old = doc.set("", Yaml::Integer(0)).unwrap();
+        assert!(old.unwrap().as_hash().is_some());
+        assert_eq!(doc, Yaml::Integer(0));
+    }
+
+    #[test]
+    fn test_path_syntax() {
+        let s = "list: [a, {b: [c]}]
+3: x
+map: {0: y}";
+        let mut doc = YamlLoader::load_from_str(s).unwrap().remove(0);
+        // every path taken as text finds the same node
+        for path in ["list.1.b.0", "list[1].b[0]", "list.1.b[0]", "3", "map.0"] {
+            let node = lookup_path(&doc, path).unwrap().clone();
+            assert_eq!(extract(s, path).unwrap().as_ref(), Some(&node), "{}", path);
+            if !path.contains('[') {
+                assert_eq!(doc.to_index().get(path).cloned(), Some(node.clone()));
+            }
+            let mut copy = doc.clone();
+            assert_eq!(copy.set(path, Yaml::Null), Ok(Some(node)), "{}", path);
+        }
+        assert_eq!(lookup_path(&doc, "list.x"), None);
+        let err = doc.set("list.x", Yaml::Null).unwrap_err();
+        assert_eq!(err.to_string(), "expected hash at $.list, found array");
+        let err = doc.set("list.5", Yaml::Null);
+        assert_eq!(err, Ok(None));
+        assert_eq!(doc["list"].as_vec().unwrap().len(), 6);
+    }
+
+    #[test]
+    fn test_try_index() {
+        let doc = YamlLoader::load_from_str("a: {8080: http, list: [x, {b: true}]}\n1: one")
+            .unwrap()
+            .remove(0);
+        let b = doc
+            .try_index("a")
+            .and_then(|v| v.try_index("list")?.try_index(1)?.try_index("b"))
+            .unwrap();
+        assert_eq!(b.as_bool(), Some(true));
+        assert_eq!(b.path().to_string(), "$.a.list[1].b");
+        assert_eq!(doc.try_index(1).unwrap().as_str(), Some("one"));
+        let http = doc.try_index("a").and_then(|v| v.try_index("8080"));
+        assert_eq!(http.unwrap().node().as_str(), Some("http"));
+
+        let err = doc
+            .try_index("a")
+            .and_then(|v| v.try_index("list")?.try_index(0)?.try_index("c"))
+            .unwrap_err();
+        assert_eq!(
+            err,
+            IndexError::Conflict {
+                path: parse_path("a.list[0]"),
+                expected: "hash",
+                found: "string",
+            }
+        );
+        let err = doc.try_index("b").unwrap_err();
+        assert_eq!(err, IndexError::Missing(parse_path("b")));
+        assert_eq!(err.to_string(), "no node at $.b");
+        let err = Yaml::Integer(1).try_index(0).unwrap_err();
+        assert_eq!(err.to_string(), "expected array at $, found integer");
+    }
+
+    fn parse_path(path: &str) -> YamlPath {
+        let mut parsed = YamlPath::new();
+        for step in parse_flat_path(path, ".") {
+            parsed.push(step);
+        }
+        parsed
+    }
+
+    #[test]
+    fn test_deep_get_all() {
+        let doc = YamlLoader::load_from_str(
+            "
+a: {k: 1, b: [{k: {k: 2}}, {j: 3}]}
+k: !t {x: 4}
+80: {k: ~}
+[k]: 5
+",
+        )
+        .unwrap()
+        .remove(0);
+        let found: Vec<_> = doc
+            .deep_get_all("k")
+            .into_iter()
+            .map(|(path, value)| (path.to_string(), value))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("$.a.k".to_owned(), &Yaml::Integer(1)),
+                ("$.a.b[0].k".to_owned(), &doc["a"]["b"][0]["k"]),
+                ("$.a.b[0].k.k".to_owned(), &Yaml::Integer(2)),
+                ("$.k".to_owned(), &doc["k"]),
+                ("$[Integer(80)].k".to_owned(), &Yaml::Null),
+            ]
+        );
+        assert_eq!(doc.deep_get_all("80").len(), 1);
+        assert!(doc.deep_get_all("missing").is_empty());
+        assert!(Yaml::Integer(1).deep_get_all("k").is_empty());
+    }
+
+    #[test]
+    fn test_walk_mut() {
+        let mut doc = YamlLoader::load_from_str("[1, [2, 3], {a: 4}]")
+            .unwrap()
+            .remove(0);
+        doc.walk_mut(|path, node| {
+            if let Some(i) = node.as_i64() {
+                *node = Yaml::Integer(i * 10 + path.len() as i64);
+            } else if path.last() == Some(&PathElement::Index(1)) {
+                // replaced before its children are visited
+                *node = Yaml::Array(vec![Yaml::Integer(5)]);
+            }
+        });
+        assert_eq!(
+            doc,
+            YamlLoader::load_from_str("[11, [52], {a: 42}]").unwrap()[0]
+        );
+    }
+}
@@ -0,0 +1,196 @@
+//! Conversion between arrays of hashes and tables of rows, for exchanging
+//! lists of objects with tabular formats such as CSV.
+//!
+//! # Examples
+//!
+//! ```
+//! # extern crate yaml_rust_davvid as yaml_rust;
+//! use yaml_rust::{Yaml, YamlLoader};
+//!
+//! let s = "
+//! - {name: a, size: 1}
+//! - {name: b, size: 2}
+//! ";
+//! let doc = &YamlLoader::load_from_str(s).unwrap()[0];
+//! let records = doc.to_records().unwrap();
+//! assert_eq!(records.header, [Yaml::from_str("name"), Yaml::from_str("size")]);
+//! assert_eq!(records.rows[1], [Yaml::from_str("b"), Yaml::Integer(2)]);
+//! assert_eq!(&Yaml::from_records(records).unwrap(), doc);
+//! ```
+
+use crate::yaml::{Hash, Yaml};
+
+use std::error::Error;
+use std::fmt;
+
+/// An array of hashes with the same keys, as a header and rows of values
+/// in the order of the header.
+#[derive(Clone, PartialEq, Debug, Default, Eq)]
+pub struct Records {
+    pub header: Vec<Yaml>,
+    pub rows: Vec<Vec<Yaml>>,
+}
+
+/// Why a value couldn't be converted to or from records.
+#[derive(Clone, PartialEq, Debug, Eq)]
+pub enum RecordsError {
+    /// The value is not an array, but the kind of value given.
+    NotArray(&'static str),
+    /// The record at `row` is not a hash.
+    NotHash { row: usize, found: &'static str },
+    /// The record at `row` lacks keys of the header or has keys that are
+    /// not in it. The header is made of the keys of the first record.
+    Ragged {
+        row: usize,
+        missing: Vec<Yaml>,
+        extra: Vec<Yaml>,
+    },
+    /// The row at `row` has a different number of values than the header
+    /// has columns.
+    Width {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl fmt::Display for RecordsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RecordsError::NotArray(found) => write!(f, "expected array, found {}", found),
+            RecordsError::NotHash { row, found } => {
+                write!(f, "expected hash at row {}, found {}", row, found)
+            }
+            RecordsError::Ragged {
+                row,
+                ref missing,
+                ref extra,
+            } => {
+                write!(f, "row {} does not match the header", row)?;
+                if !missing.is_empty() {
+                    write!(f, ", missing {:?}", missing)?;
+                }
+                if !extra.is_empty() {
+                    write!(f, ", extra {:?}", extra)?;
+                }
+                Ok(())
+            }
+            RecordsError::Width {
+                row,
+                expected,
+                found,
+            } => write!(f, "row {} has {} values, expected {}", row, found, expected),
+        }
+    }
+}
+
+impl Error for RecordsError {}
+
+impl Yaml {
+    /// Convert an array of hashes to records. Every hash must have the keys
+    /// of the first one, in any order; values may be any node.
+    pub fn to_records(&self) -> Result<Records, RecordsError> {
+        let array = match *self {
+            Yaml::Array(ref v) => v,
+            _ => return Err(RecordsError::NotArray(self.type_str())),
+        };
+        let mut records = Records::default();
+        for (row, record) in array.iter().enumerate() {
+            let hash = match *record {
+                Yaml::Hash(ref h) => h,
+                _ => {
+                    return Err(RecordsError::NotHash {
+                        row,
+                        found: record.type_str(),
+                    })
+                }
+            };
+            if row == 0 {
+                records.header = hash.keys().cloned().collect();
+            }
+            let missing: Vec<Yaml> = records
+                .header
+                .iter()
+                .filter(|key| !hash.contains_key(*key))
+                .cloned()
+                .collect();
+            if !missing.is_empty() || hash.len() != records.header.len() {
+                let extra = hash
+                    .keys()
+                    .filter(|key| !records.header.contains(*key))
+                    .cloned()
+                    .collect();
+                return Err(RecordsError::Ragged {
+                    row,
+                    missing,
+                    extra,
+                });
+            }
+            records
+                .rows
+                .push(records.header.iter().map(|key| hash[key].clone()).collect());
+        }
+        Ok(records)
+    }
+
+    /// Convert records to an array of hashes, keyed by the header. Every
+    /// row must have a value for each column.
+    pub fn from_records(records: Records) -> Result<Yaml, RecordsError> {
+        let Records { header, rows } = records;
+        let mut array = Vec::with_capacity(rows.len());
+        for (row, values) in rows.into_iter().enumerate() {
+            if values.len() != header.len() {
+                return Err(RecordsError::Width {
+                    row,
+                    expected: header.len(),
+                    found: values.len(),
+                });
+            }
+            let mut hash = Hash::new();
+            for (key, value) in header.iter().zip(values) {
+                hash.insert(key.clone(), value);
+            }
+            array.push(Yaml::Hash(hash));
+        }
+        Ok(Yaml::Array(array))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::YamlLoader;
+
+    #[test]
+    fn test_records_errors() {
+        let load = |s| YamlLoader::load_from_str(s).unwrap().remove(0);
+        assert_eq!(
+            load("a: 1").to_records(),
+            Err(RecordsError::NotArray("hash"))
+        );
+        assert_eq!(
+            load("[{a: 1}, 2]").to_records(),
+            Err(RecordsError::NotHash {
+                row: 1,
+                found: "integer"
+            })
+        );
+        let err = load("[{a: 1, b: 2}, {a: 3, c: 4}]")
+            .to_records()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "row 1 does not match the header, missing [String(\"b\")], extra [String(\"c\")]"
+        );
+        assert_eq!(load("[]").to_records(), Ok(Records::default()));
+
+        let records = Records {
+            header: vec![Yaml::from_str("a"), Yaml::from_str("b")],
+            rows: vec![vec![Yaml::Integer(1), Yaml::Null], vec![Yaml::Integer(2)]],
+        };
+        assert_eq!(
+            Yaml::from_records(records).unwrap_err().to_string(),
+            "row 1 has 1 values, expected 2"
+        );
+    }
+}
@@ -0,0 +1,278 @@
+//! Masking of sensitive values before a document is logged or emitted.
+//!
+//! Values are selected by their path with a `PathMatcher`, such as a `Glob`
+//! or, with the `regex` feature, a `regex::Regex` matched against the
+//! displayed path (`$.db.password`).
+//!
+//! # Examples
+//!
+//! ```
+//! # extern crate yaml_rust_davvid as yaml_rust;
+//! use yaml_rust::redact::Glob;
+//! use yaml_rust::{Yaml, YamlLoader};
+//!
+//! let s = "
+//! db: {user: app, password: hunter2}
+//! services:
+//!   - {name: api, token: abc}
+//! ";
+//! let mut doc = YamlLoader::load_from_str(s).unwrap().remove(0);
+//! let patterns = [Glob::new("**.password"), Glob::new("**.token")];
+//! let redacted = doc.redact(&patterns[..], &Yaml::String("***".to_owned()));
+//! assert_eq!(redacted, 2);
+//! assert_eq!(doc["db"]["password"].as_str(), Some("***"));
+//! assert_eq!(doc["services"][0]["token"].as_str(), Some("***"));
+//! assert_eq!(doc["db"]["user"].as_str(), Some("app"));
+//! ```
+
+use crate::path::{key_text, PathElement, YamlPath};
+use crate::yaml::Yaml;
+
+use std::mem;
+
+/// Selects nodes by their path from the document root.
+pub trait PathMatcher {
+    fn matches(&self, path: &YamlPath) -> bool;
+}
+
+/// A pattern over the steps of a path, written as segments separated by
+/// dots. A segment matches a hash key or an array index, and may contain
+/// `*` to match any run of characters; a `**` segment matches any number of
+/// steps, including none. `db.*` matches the values of `db`, and
+/// `**.password` matches every value with the key `password`. A `\`
+/// makes the character after it literal, so `a\.b` matches the key `a.b`.
+#[derive(Clone, PartialEq, Debug, Eq)]
+pub struct Glob {
+    segments: Vec<Segment>,
+}
+
+#[derive(Clone, PartialEq, Debug, Eq)]
+enum Segment {
+    // `**`
+    Steps,
+    // the literal text between the `*`s of the segment
+    Pattern(Vec<String>),
+}
+
+impl Segment {
+    fn new(pieces: Vec<String>) -> Segment {
+        if pieces == ["", "", ""] {
+            Segment::Steps
+        } else {
+            Segment::Pattern(pieces)
+        }
+    }
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Glob {
+        let mut segments = Vec::new();
+        let mut pieces = vec![String::new()];
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => pieces
+                    .last_mut()
+                    .unwrap()
+                    .push(chars.next().unwrap_or('\\')),
+                '*' => pieces.push(String::new()),
+                '.' => segments.push(Segment::new(mem::replace(&mut pieces, vec![String::new()]))),
+                c => pieces.last_mut().unwrap().push(c),
+            }
+        }
+        segments.push(Segment::new(pieces));
+        Glob { segments }
+    }
+}
+
+impl PathMatcher for Glob {
+    fn matches(&self, path: &YamlPath) -> bool {
+        let steps: Vec<Option<String>> = path.elements().iter().map(step_text).collect();
+        match_steps(&self.segments, &steps)
+    }
+}
+
+impl<M: PathMatcher> PathMatcher for [M] {
+    /// Match if any of the matchers does.
+    fn matches(&self, path: &YamlPath) -> bool {
+        self.iter().any(|m| m.matches(path))
+    }
+}
+
+impl<M: PathMatcher> PathMatcher for Vec<M> {
+    fn matches(&self, path: &YamlPath) -> bool {
+        self[..].matches(path)
+    }
+}
+
+#[cfg(feature = "regex")]
+impl PathMatcher for regex::Regex {
+    /// Search the displayed path, such as `$.servers[0].token`.
+    fn matches(&self, path: &YamlPath) -> bool {
+        self.is_match(&path.to_string())
+    }
+}
+
+impl Yaml {
+    /// Replace every node whose path is matched by `matcher` with
+    /// `replacement`, returning the number of nodes replaced. The children
+    /// of a replaced node are not visited.
+    pub fn redact<M: PathMatcher + ?Sized>(&mut self, matcher: &M, replacement: &Yaml) -> usize {
+        redact_node(self, &mut YamlPath::new(), matcher, replacement)
+    }
+}
+
+fn redact_node<M: PathMatcher + ?Sized>(
+    node: &mut Yaml,
+    path: &mut YamlPath,
+    matcher: &M,
+    replacement: &Yaml,
+) -> usize {
+    if matcher.matches(path) {
+        *node = replacement.clone();
+        return 1;
+    }
+    let node = match *node {
+        Yaml::Tagged(_, ref mut v) => v,
+        ref mut node => node,
+    };
+    let mut count = 0;
+    match *node {
+        Yaml::Array(ref mut v) => {
+            for (i, child) in v.iter_mut().enumerate() {
+                path.push(PathElement::Index(i));
+                count += redact_node(child, path, matcher, replacement);
+                path.pop();
+            }
+        }
+        Yaml::Hash(ref mut h) => {
+            for (key, child) in h.iter_mut() {
+                path.push(PathElement::Key(key.clone()));
+                count += redact_node(child, path, matcher, replacement);
+                path.pop();
+            }
+        }
+        _ => {}
+    }
+    count
+}
+
+// The text a glob segment is matched against, if the step has one.
+fn step_text(element: &PathElement) -> Option<String> {
+    match *element {
+        PathElement::Index(i) => Some(i.to_string()),
+        PathElement::Key(ref key) => key_text(key),
+    }
+}
+
+fn match_steps(segments: &[Segment], steps: &[Option<String>]) -> bool {
+    match segments.split_first() {
+        None => steps.is_empty(),
+        Some((Segment::Steps, rest)) => {
+            (0..=steps.len()).any(|skip| match_steps(rest, &steps[skip..]))
+        }
+        Some((Segment::Pattern(pieces), rest)) => match steps.split_first() {
+            Some((Some(step), steps)) => match_segment(pieces, step) && match_steps(rest, steps),
+            // keys that aren't scalars only match a wildcard
+            Some((None, steps)) => *pieces == ["", ""] && match_steps(rest, steps),
+            None => false,
+        },
+    }
+}
+
+// Match the pieces of a pattern, which are separated by `*`s, against `text`.
+fn match_segment(pieces: &[String], text: &str) -> bool {
+    match pieces.split_first() {
+        None => false,
+        Some((piece, [])) => piece == text,
+        Some((prefix, rest)) => {
+            text.starts_with(prefix.as_str())
+                && (prefix.len()..=text.len())
+                    .filter(|&i| text.is_char_boundary(i))
+                    .any(|i| match_segment(rest, &text[i..]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::YamlLoader;
+
+    fn paths(pattern: &str, s: &str) -> Vec<String> {
+        let glob = Glob::new(pattern);
+        let mut matched = Vec::new();
+        YamlLoader::load_from_str(s).unwrap()[0].walk(|path, _| {
+            if glob.matches(path) {
+                matched.push(path.to_string());
+            }
+        });
+        matched
+    }
+
+    #[test]
+    fn test_glob() {
+        let s = "
+password: a
+db: {password: b, api_token: c, 1: d}
+list: [{password: e}]
+";
+        assert_eq!(
+            paths("**.password", s),
+            vec!["$.password", "$.db.password", "$.list[0].password"]
+        );
+        assert_eq!(paths("db.*", s).len(), 3);
+        assert_eq!(paths("*.*_token", s), vec!["$.db.api_token"]);
+        assert_eq!(paths("db.1", s), vec!["$.db[Integer(1)]"]);
+        assert_eq!(paths("list.0.password", s), vec!["$.list[0].password"]);
+        assert_eq!(paths("**", s).len(), 9);
+        assert!(paths("password.*", s).is_empty());
+
+        // the key `a.b` and the key `b` of `a` are both displayed as `$.a.b`
+        let mut doc = YamlLoader::load_from_str("{a.b: 1, a: {b: 2}, '*': 3, c: 4}")
+            .unwrap()
+            .remove(0);
+        assert_eq!(doc.redact(&Glob::new(r"a\.b"), &Yaml::Null), 1);
+        assert_eq!(doc["a.b"], Yaml::Null);
+        assert_eq!(doc["a"]["b"].as_i64(), Some(2));
+        assert_eq!(doc.redact(&Glob::new(r"\*"), &Yaml::Null), 1);
+        assert_eq!(doc["*"], Yaml::Null);
+        assert_eq!(doc["c"].as_i64(), Some(4));
+    }
+
+    #[test]
+    fn test_redact_subtree() {
+        let mut doc = YamlLoader::load_from_str("secrets: {a: 1, b: 2}\nc: 3")
+            .unwrap()
+            .remove(0);
+        assert_eq!(doc.redact(&Glob::new("secrets"), &Yaml::Null), 1);
+        assert_eq!(
+            doc,
+            YamlLoader::load_from_str("secrets: ~\nc: 3").unwrap()[0]
+        );
+    }
+
+    #[test]
+    fn test_redact_skips_replacement() {
+        let mut doc = YamlLoader::load_from_str("password: a\nlist: [{password: b}]")
+            .unwrap()
+            .remove(0);
+        let replacement = YamlLoader::load_from_str("{password: masked}")
+            .unwrap()
+            .remove(0);
+        assert_eq!(doc.redact(&Glob::new("**.password"), &replacement), 2);
+        assert_eq!(doc["password"], replacement);
+        assert_eq!(doc["list"][0]["password"], replacement);
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_redact_regex() {
+        let mut doc = YamlLoader::load_from_str("{db_password: a, token: b, user: c}")
+            .unwrap()
+            .remove(0);
+        let re = regex::Regex::new(r"(password|token)$").unwrap();
+        assert_eq!(doc.redact(&re, &Yaml::Null), 2);
+        assert_eq!(doc["user"].as_str(), Some("c"));
+    }
+}
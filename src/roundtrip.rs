@@ -0,0 +1,272 @@
+//! Checks that documents survive being emitted and loaded again.
+//!
+//! ```
+//! # extern crate yaml_rust_davvid as yaml_rust;
+//! use yaml_rust::roundtrip;
+//!
+//! let report = roundtrip::check("a: [1, 2]\nb: {c: d}").unwrap();
+//! assert!(report.is_ok());
+//! ```
+
+use crate::emitter::{EmitError, YamlEmitter};
+use crate::path::key_text;
+use crate::scanner::ScanError;
+use crate::yaml::{Yaml, YamlLoader};
+
+use std::fmt;
+
+pub use crate::path::PathElement;
+
+/// How a round trip went wrong.
+#[derive(Clone, PartialEq, Debug)]
+pub enum RoundTripFailure {
+    /// The documents could not be emitted.
+    Emit(EmitError),
+    /// The emitted text could not be loaded.
+    Reload(ScanError),
+    /// The emitted text loaded as a different number of documents.
+    DocumentCount { expected: usize, found: usize },
+    /// A document changed. `path` leads from the document root to the first
+    /// node that differs, and `expected` and `found` are that node before and
+    /// after the round trip. A hash entry that is missing on one side is
+    /// `Yaml::BadValue` on that side, and hashes whose keys are only in a
+    /// different order differ as a whole.
+    Mismatch {
+        document: usize,
+        path: Vec<PathElement>,
+        expected: Yaml,
+        found: Yaml,
+    },
+}
+
+impl fmt::Display for RoundTripFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RoundTripFailure::Emit(ref err) => write!(f, "failed to emit: {}", err),
+            RoundTripFailure::Reload(ref err) => write!(f, "failed to reload: {}", err),
+            RoundTripFailure::DocumentCount { expected, found } => {
+                write!(f, "expected {} documents, found {}", expected, found)
+            }
+            RoundTripFailure::Mismatch {
+                document,
+                ref path,
+                ref expected,
+                ref found,
+            } => {
+                write!(f, "document {} differs at $", document)?;
+                for element in path {
+                    // scalar keys are written as they are in YAML
+                    match *element {
+                        PathElement::Key(ref key) => match key_text(key) {
+                            Some(text) => write!(f, ".{}", text)?,
+                            None => write!(f, "{}", element)?,
+                        },
+                        PathElement::Index(_) => write!(f, "{}", element)?,
+                    }
+                }
+                write!(f, ": expected {:?}, found {:?}", expected, found)
+            }
+        }
+    }
+}
+
+/// The outcome of `check`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct RoundTripReport {
+    /// The documents loaded from the source.
+    pub documents: Vec<Yaml>,
+    /// The text the documents were emitted as.
+    pub emitted: String,
+    /// The first difference found, if any.
+    pub failure: Option<RoundTripFailure>,
+}
+
+impl RoundTripReport {
+    pub fn is_ok(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+/// Load `source`, emit the documents, load them again and compare the
+/// results. Only fails if `source` itself cannot be loaded.
+pub fn check(source: &str) -> Result<RoundTripReport, ScanError> {
+    let documents = YamlLoader::load_from_str(source)?;
+    let mut emitted = String::new();
+    let failure = emit(&documents, &mut emitted)
+        .err()
+        .map(RoundTripFailure::Emit)
+        .or_else(|| match YamlLoader::load_from_str(&emitted) {
+            Err(err) => Some(RoundTripFailure::Reload(err)),
+            Ok(reloaded) => compare(&documents, &reloaded),
+        });
+    Ok(RoundTripReport {
+        documents,
+        emitted,
+        failure,
+    })
+}
+
+fn emit(documents: &[Yaml], out: &mut String) -> Result<(), EmitError> {
+    for doc in documents {
+        YamlEmitter::new(out).dump(doc)?;
+        out.push('\n');
+    }
+    Ok(())
+}
+
+fn compare(expected: &[Yaml], found: &[Yaml]) -> Option<RoundTripFailure> {
+    if expected.len() != found.len() {
+        return Some(RoundTripFailure::DocumentCount {
+            expected: expected.len(),
+            found: found.len(),
+        });
+    }
+    let mut path = Vec::new();
+    for (document, (a, b)) in expected.iter().zip(found).enumerate() {
+        if let Some((expected, found)) = first_difference(a, b, &mut path) {
+            return Some(RoundTripFailure::Mismatch {
+                document,
+                path,
+                expected: expected.clone(),
+                found: found.clone(),
+            });
+        }
+    }
+    None
+}
+
+// On a difference, `path` is left pointing at the differing nodes.
+fn first_difference<'a>(
+    a: &'a Yaml,
+    b: &'a Yaml,
+    path: &mut Vec<PathElement>,
+) -> Option<(&'a Yaml, &'a Yaml)> {
+    match (a, b) {
+        (Yaml::Array(x), Yaml::Array(y)) if x.len() == y.len() => {
+            for (i, (x, y)) in x.iter().zip(y).enumerate() {
+                path.push(PathElement::Index(i));
+                if let Some(diff) = first_difference(x, y, path) {
+                    return Some(diff);
+                }
+                path.pop();
+            }
+            None
+        }
+        (Yaml::Hash(x), Yaml::Hash(y)) => {
+            // entries on one side only are compared with a missing value
+            let missing = &Yaml::BadValue;
+            let entries = x
+                .iter()
+                .map(|(k, v)| (k, v, y.get(k).unwrap_or(missing)))
+                .chain(
+                    y.iter()
+                        .filter(|&(k, _)| !x.contains_key(k))
+                        .map(|(k, v)| (k, missing, v)),
+                );
+            for (k, x, y) in entries {
+                path.push(PathElement::Key(k.clone()));
+                if let Some(diff) = first_difference(x, y, path) {
+                    return Some(diff);
+                }
+                path.pop();
+            }
+            // the entries are the same, in a different order
+            if a != b {
+                return Some((a, b));
+            }
+            None
+        }
+        _ if a == b => None,
+        _ => Some((a, b)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let report = check("--- [1, {a: b}]\n--- x\n---\n").unwrap();
+        assert!(report.is_ok());
+        assert_eq!(report.documents.len(), 3);
+        assert_eq!(report.emitted, "---\n- 1\n- a: b\n---\nx\n---\n~\n");
+
+        assert!(check("[").is_err());
+    }
+
+    #[test]
+    fn test_mismatch() {
+        // BadValue is emitted as `~`, which reloads as null
+        let a = Yaml::Array(vec![Yaml::Integer(1), Yaml::BadValue]);
+        let b = Yaml::Array(vec![Yaml::Integer(1), Yaml::Null]);
+        let failure = compare(&[a], &[b]).unwrap();
+        assert_eq!(
+            failure,
+            RoundTripFailure::Mismatch {
+                document: 0,
+                path: vec![PathElement::Index(1)],
+                expected: Yaml::BadValue,
+                found: Yaml::Null,
+            }
+        );
+        assert_eq!(
+            failure.to_string(),
+            "document 0 differs at $[1]: expected BadValue, found Null"
+        );
+
+        let x = YamlLoader::load_from_str("{a: {1: [x]}}").unwrap();
+        let y = YamlLoader::load_from_str("{a: {1: [y]}}").unwrap();
+        let failure = compare(&x, &y).unwrap();
+        assert_eq!(
+            failure.to_string(),
+            "document 0 differs at $.a.1[0]: expected String(\"x\"), found String(\"y\")"
+        );
+
+        // keys on one side only are reported on their own
+        let z = YamlLoader::load_from_str("{b: {1: [x]}}").unwrap();
+        let failure = compare(&x, &z).unwrap();
+        assert_eq!(
+            failure,
+            RoundTripFailure::Mismatch {
+                document: 0,
+                path: vec![PathElement::from("a")],
+                expected: x[0]["a"].clone(),
+                found: Yaml::BadValue,
+            }
+        );
+        let z = YamlLoader::load_from_str("{a: {1: [x]}, b: 2}").unwrap();
+        let failure = compare(&x, &z).unwrap();
+        assert_eq!(
+            failure.to_string(),
+            "document 0 differs at $.b: expected BadValue, found Integer(2)"
+        );
+        let z = YamlLoader::load_from_str("{a: {1: [x], 2.5: y}}").unwrap();
+        let failure = compare(&z, &x).unwrap();
+        assert_eq!(
+            failure.to_string(),
+            "document 0 differs at $.a.2.5: expected String(\"y\"), found BadValue"
+        );
+
+        // a different order is reported at the hash
+        let x = YamlLoader::load_from_str("{a: {b: 1, c: 2}}").unwrap();
+        let y = YamlLoader::load_from_str("{a: {c: 2, b: 1}}").unwrap();
+        let failure = compare(&x, &y).unwrap();
+        assert_eq!(
+            failure,
+            RoundTripFailure::Mismatch {
+                document: 0,
+                path: vec![PathElement::from("a")],
+                expected: x[0]["a"].clone(),
+                found: y[0]["a"].clone(),
+            }
+        );
+        assert_eq!(
+            compare(&x, &[]),
+            Some(RoundTripFailure::DocumentCount {
+                expected: 1,
+                found: 0
+            })
+        );
+    }
+}
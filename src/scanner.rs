@@ -1,6 +1,10 @@
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
 use std::error::Error;
-use std::{char, fmt};
+use std::{char, fmt, mem};
+
+// How far past a `[` or `{` to look for the commas that estimate how many
+// entries the collection has.
+const FLOW_HINT_LOOKAHEAD: usize = 1024;
 
 #[derive(Clone, Copy, PartialEq, Debug, Eq)]
 pub enum TEncoding {
@@ -21,19 +25,31 @@ pub enum TScalarStyle {
 #[derive(Clone, Copy, PartialEq, Debug, Eq)]
 pub struct Marker {
     index: usize,
+    byte: usize,
     line: usize,
     col: usize,
 }
 
 impl Marker {
-    fn new(index: usize, line: usize, col: usize) -> Marker {
-        Marker { index, line, col }
+    pub(crate) fn new(index: usize, byte: usize, line: usize, col: usize) -> Marker {
+        Marker {
+            index,
+            byte,
+            line,
+            col,
+        }
     }
 
     pub fn index(&self) -> usize {
         self.index
     }
 
+    /// The offset of the marker in the source in UTF-8 bytes, where
+    /// `index` counts characters.
+    pub fn byte(&self) -> usize {
+        self.byte
+    }
+
     pub fn line(&self) -> usize {
         self.line
     }
@@ -43,10 +59,37 @@ impl Marker {
     }
 }
 
+/// A region of the source between two markers. `end` is exclusive.
+#[derive(Clone, Copy, PartialEq, Debug, Eq)]
+pub struct Span {
+    pub start: Marker,
+    pub end: Marker,
+}
+
+impl Span {
+    /// The text of the span in `source`, which must be the string the
+    /// markers were produced from.
+    pub fn slice<'s>(&self, source: &'s str) -> &'s str {
+        &source[self.start.byte..self.end.byte]
+    }
+}
+
 #[derive(Clone, PartialEq, Debug, Eq)]
 pub struct ScanError {
     mark: Marker,
     info: String,
+    kind: ScanErrorKind,
+}
+
+/// The kinds of `ScanError` that callers may want to tell apart.
+#[derive(Clone, Copy, PartialEq, Debug, Eq, Default)]
+pub enum ScanErrorKind {
+    /// Any error without a kind of its own.
+    #[default]
+    Other,
+    /// A tab in the indentation of a line, where YAML only allows spaces.
+    /// `ScannerConfig::tab_width` reads such tabs as spaces instead.
+    TabIndentation,
 }
 
 impl ScanError {
@@ -54,6 +97,14 @@ impl ScanError {
         ScanError {
             mark: loc,
             info: info.to_owned(),
+            kind: ScanErrorKind::Other,
+        }
+    }
+
+    fn tab_indentation(loc: Marker) -> ScanError {
+        ScanError {
+            kind: ScanErrorKind::TabIndentation,
+            ..ScanError::new(loc, "tab used for indentation, YAML requires spaces")
         }
     }
 
@@ -64,6 +115,10 @@ impl ScanError {
     pub fn info(&self) -> &str {
         self.info.as_ref()
     }
+
+    pub fn kind(&self) -> ScanErrorKind {
+        self.kind
+    }
 }
 
 impl Error for ScanError {
@@ -91,6 +146,7 @@ impl fmt::Display for ScanError {
 }
 
 #[derive(Clone, PartialEq, Debug, Eq)]
+#[non_exhaustive]
 pub enum TokenType {
     NoToken,
     StreamStart(TEncoding),
@@ -117,6 +173,9 @@ pub enum TokenType {
     /// handle, suffix
     Tag(String, String),
     Scalar(TScalarStyle, String),
+    /// Part of the content of a block scalar, see
+    /// `ScannerConfig::block_scalar_chunk_bytes`.
+    ScalarChunk(String),
 }
 
 #[derive(Clone, PartialEq, Debug, Eq)]
@@ -141,9 +200,164 @@ impl SimpleKey {
     }
 }
 
+/// Limits and restrictions applied while scanning and parsing a stream.
+#[derive(Clone, Copy, PartialEq, Debug, Eq)]
+pub struct ScannerConfig {
+    /// The maximum length in characters of an implicit key. The spec sets
+    /// this to 1024.
+    pub max_implicit_key_len: usize,
+    /// The maximum number of anchors defined in a single document.
+    pub max_anchors: Option<usize>,
+    /// The maximum number of documents in the stream, so a stream of many
+    /// small documents can't make a loader build an unbounded list.
+    pub max_documents: Option<usize>,
+    /// The maximum number of events the whole stream produces, counting
+    /// the start and end of the stream and of each document.
+    pub max_events: Option<usize>,
+    /// The maximum size in UTF-8 bytes of the content of a single scalar.
+    /// Scanning fails as soon as a scalar grows past it, so a huge scalar
+    /// is never held in memory.
+    pub max_scalar_bytes: Option<usize>,
+    /// The maximum size in UTF-8 bytes of the source of a single document,
+    /// checked while scanning each token and scalar, so a document fails
+    /// as soon as it grows past it.
+    pub max_document_bytes: Option<usize>,
+    /// Deliver the content of a block scalar in chunks of at least this
+    /// many UTF-8 bytes, so a large scalar is never held in memory whole.
+    /// Each chunk ends at a line break, and the scalar token that follows
+    /// the chunks holds the rest of the content. Only a `Parser` read event
+    /// by event, or a receiver that takes chunks, gets this bound:
+    /// `YamlLoader` joins the chunks to build whole strings.
+    pub block_scalar_chunk_bytes: Option<usize>,
+    /// Accept only JSON text as defined by RFC 8259: a single value, with
+    /// JSON's number and string syntax and no comments, tags, anchors or
+    /// block style. Numbers too large for an `f64` are errors. Loading it
+    /// gives the value a JSON parser would, so `-0` loads as a real.
+    pub json: bool,
+    /// Which characters end a line, see `LineBreaks`.
+    pub line_breaks: LineBreaks,
+    /// Read a tab in the indentation of a line as the spaces up to the next
+    /// multiple of this many columns, rather than failing with
+    /// `ScanErrorKind::TabIndentation`. The columns of markers then count
+    /// those spaces. In a block scalar, the content of a line starts after
+    /// its last tab of indentation.
+    pub tab_width: Option<usize>,
+}
+
+/// The characters the scanner treats as line breaks.
+///
+/// Every form of break advances `Marker::line` exactly once, with "\r\n"
+/// counted as a single break.
+#[derive(Clone, Copy, PartialEq, Debug, Eq, Default)]
+pub enum LineBreaks {
+    /// Only CR, LF and CRLF end a line, as in YAML 1.2. NEL, LS and PS are
+    /// ordinary content characters.
+    #[default]
+    Yaml12,
+    /// NEL (U+0085), LS (U+2028) and PS (U+2029) also end a line, as in
+    /// YAML 1.1. A NEL is read as "\n" in scalar content, while LS and PS
+    /// are kept as they are.
+    Yaml11,
+}
+
+impl Default for ScannerConfig {
+    fn default() -> Self {
+        ScannerConfig {
+            max_implicit_key_len: 1024,
+            max_anchors: None,
+            max_documents: None,
+            max_events: None,
+            max_scalar_bytes: None,
+            max_document_bytes: None,
+            block_scalar_chunk_bytes: None,
+            json: false,
+            line_breaks: LineBreaks::default(),
+            tab_width: None,
+        }
+    }
+}
+
+// A block scalar scanned up to the start of a line, kept between the
+// chunks of its content.
+#[derive(Clone, PartialEq, Debug, Eq)]
+struct BlockScalar {
+    literal: bool,
+    chomping: i32,
+    indent: usize,
+    leading_blank: bool,
+    string: String,
+    leading_break: String,
+    trailing_breaks: String,
+    // bytes of content already delivered in chunks
+    delivered: usize,
+    start_mark: Marker,
+    header_mark: Marker,
+    end_mark: Marker,
+}
+
+// What JSON text allows next, tracked in JSON mode.
+#[derive(Clone, Copy, PartialEq, Debug, Eq)]
+enum JsonExpect {
+    Value,
+    // the first element of an array, or its end
+    FirstValue,
+    // the first key of an object, or its end
+    FirstKey,
+    Key,
+    // the string of a key
+    KeyString,
+    Colon,
+    // a comma or the end of the enclosing array or object
+    Next,
+    End,
+}
+
+impl JsonExpect {
+    fn describe(self) -> &'static str {
+        match self {
+            JsonExpect::Value | JsonExpect::FirstValue => "a value",
+            JsonExpect::FirstKey | JsonExpect::Key | JsonExpect::KeyString => "a string key",
+            JsonExpect::Colon => "':'",
+            JsonExpect::Next => "',' or a closing bracket",
+            JsonExpect::End => "the end of the input",
+        }
+    }
+}
+
+// Whether a plain scalar is a JSON number, boolean or null.
+fn is_json_literal(v: &str) -> bool {
+    if matches!(v, "true" | "false" | "null") {
+        return true;
+    }
+    let digits = |s: &str| s.bytes().take_while(u8::is_ascii_digit).count();
+    let v = v.strip_prefix('-').unwrap_or(v);
+    let int = digits(v);
+    if int == 0 || (int > 1 && v.starts_with('0')) {
+        return false;
+    }
+    let mut rest = &v[int..];
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let n = digits(fraction);
+        if n == 0 {
+            return false;
+        }
+        rest = &fraction[n..];
+    }
+    if let Some(exponent) = rest.strip_prefix(['e', 'E']) {
+        let exponent = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+        let n = digits(exponent);
+        if n == 0 {
+            return false;
+        }
+        rest = &exponent[n..];
+    }
+    rest.is_empty()
+}
+
 #[derive(Debug)]
 pub struct Scanner<T> {
     rdr: T,
+    config: ScannerConfig,
     mark: Marker,
     tokens: VecDeque<Token>,
     buffer: VecDeque<char>,
@@ -159,6 +373,21 @@ pub struct Scanner<T> {
     flow_level: u8,
     tokens_parsed: usize,
     token_available: bool,
+    after_cr: bool,
+    // where the current document started, in UTF-8 bytes
+    document_bytes: usize,
+    // the block scalar being delivered in chunks
+    block_scalar: Option<BlockScalar>,
+    // spans of scalar, anchor, alias and tag tokens by the index of their
+    // token's marker, when recorded
+    token_spans: Option<BTreeMap<usize, Span>>,
+    // estimated entries of flow collections by the index of their start
+    // token's marker, when recorded
+    capacity_hints: Option<BTreeMap<usize, usize>>,
+    // in JSON mode, what comes next and whether each open collection is
+    // an object
+    json_expect: JsonExpect,
+    json_stack: Vec<bool>,
 }
 
 impl<T: Iterator<Item = char>> Iterator for Scanner<T> {
@@ -186,18 +415,10 @@ fn is_break(c: char) -> bool {
     c == '\n' || c == '\r'
 }
 #[inline]
-fn is_breakz(c: char) -> bool {
-    is_break(c) || is_z(c)
-}
-#[inline]
 fn is_blank(c: char) -> bool {
     c == ' ' || c == '\t'
 }
 #[inline]
-fn is_blankz(c: char) -> bool {
-    is_blank(c) || is_breakz(c)
-}
-#[inline]
 fn is_digit(c: char) -> bool {
     c.is_ascii_digit()
 }
@@ -228,10 +449,16 @@ pub type ScanResult = Result<(), ScanError>;
 impl<T: Iterator<Item = char>> Scanner<T> {
     /// Creates the YAML tokenizer.
     pub fn new(rdr: T) -> Scanner<T> {
+        Scanner::new_with_config(rdr, ScannerConfig::default())
+    }
+
+    /// Creates the YAML tokenizer with the given limits.
+    pub fn new_with_config(rdr: T, config: ScannerConfig) -> Scanner<T> {
         Scanner {
             rdr,
+            config,
             buffer: VecDeque::new(),
-            mark: Marker::new(0, 1, 0),
+            mark: Marker::new(0, 0, 1, 0),
             tokens: VecDeque::new(),
             error: None,
 
@@ -245,6 +472,88 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             flow_level: 0,
             tokens_parsed: 0,
             token_available: false,
+            after_cr: false,
+            document_bytes: 0,
+            block_scalar: None,
+            token_spans: None,
+            capacity_hints: None,
+            json_expect: JsonExpect::Value,
+            json_stack: Vec::new(),
+        }
+    }
+
+    /// Record where scalar, anchor, alias and tag tokens end, to be taken
+    /// with `take_token_span`.
+    pub(crate) fn record_token_spans(&mut self) {
+        self.token_spans = Some(BTreeMap::new());
+    }
+
+    /// The source a recorded token occupies, by the index of the token's
+    /// marker. For block scalars this includes the header.
+    pub(crate) fn take_token_span(&mut self, index: usize) -> Option<Span> {
+        self.token_spans.as_mut()?.remove(&index)
+    }
+
+    /// Estimate the number of entries of each flow collection from the
+    /// commas that follow its start, to be taken with `take_capacity_hint`.
+    pub(crate) fn record_capacity_hints(&mut self) {
+        self.capacity_hints = Some(BTreeMap::new());
+    }
+
+    /// The estimated number of entries of the flow collection whose start
+    /// token has a marker at `index`.
+    pub(crate) fn take_capacity_hint(&mut self, index: usize) -> Option<usize> {
+        self.capacity_hints.as_mut()?.remove(&index)
+    }
+
+    // Count the entries of the flow collection starting at the next
+    // character, looking no further than `FLOW_HINT_LOOKAHEAD` characters.
+    // Quotes are skipped over, so an apostrophe in a plain scalar can hide
+    // the rest of the collection, and a collection that doesn't end in
+    // time is counted as far as it was seen.
+    fn flow_capacity_hint(&mut self) -> usize {
+        let mut depth = 0;
+        let mut entries = 0;
+        let mut pending = false;
+        let mut quote = None;
+        let mut escaped = false;
+        for i in 1..FLOW_HINT_LOOKAHEAD {
+            self.lookahead(i + 1);
+            let c = self.buffer[i];
+            if let Some(q) = quote {
+                if escaped {
+                    escaped = false;
+                } else if q == '"' && c == '\\' {
+                    escaped = true;
+                } else if c == q {
+                    quote = None;
+                }
+                if c != '\0' {
+                    continue;
+                }
+            }
+            match c {
+                '\0' => break,
+                '\'' | '"' => quote = Some(c),
+                '[' | '{' => depth += 1,
+                ']' | '}' if depth == 0 => break,
+                ']' | '}' => depth -= 1,
+                ',' if depth == 0 => {
+                    entries += 1;
+                    pending = false;
+                    continue;
+                }
+                _ if c.is_whitespace() => continue,
+                _ => {}
+            }
+            pending = true;
+        }
+        entries + pending as usize
+    }
+
+    fn record_span(&mut self, token: Marker, start: Marker, end: Marker) {
+        if let Some(ref mut spans) = self.token_spans {
+            spans.insert(token.index, Span { start, end });
         }
     }
     #[inline]
@@ -266,23 +575,67 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         let c = self.buffer.pop_front().unwrap();
 
         self.mark.index += 1;
-        if c == '\n' {
-            self.mark.line += 1;
-            self.mark.col = 0;
-        } else {
-            self.mark.col += 1;
+        self.mark.byte += c.len_utf8();
+        match c {
+            // the line was already counted at the '\r' of a "\r\n"
+            '\n' if self.after_cr => {}
+            c if self.is_break(c) => {
+                self.mark.line += 1;
+                self.mark.col = 0;
+            }
+            _ => self.mark.col += 1,
+        }
+        self.after_cr = c == '\r';
+    }
+    // Skip a tab in the indentation of a line, failing unless `tab_width`
+    // allows it.
+    fn skip_indentation_tab(&mut self) -> ScanResult {
+        match self.config.tab_width {
+            Some(width) if width > 0 => {
+                self.skip();
+                self.mark.col = (self.mark.col - 1) / width * width + width;
+                Ok(())
+            }
+            _ => Err(ScanError::tab_indentation(self.mark)),
         }
     }
+
+    // Whether the rest of the line holds nothing but blanks and a comment.
+    fn rest_of_line_blank(&mut self) -> bool {
+        let mut n = 0;
+        loop {
+            self.lookahead(n + 1);
+            match self.buffer[n] {
+                ' ' | '\t' => n += 1,
+                c => return c == '#' || self.is_breakz(c),
+            }
+        }
+    }
+
     #[inline]
     fn skip_line(&mut self) {
         if self.buffer[0] == '\r' && self.buffer[1] == '\n' {
             self.skip();
             self.skip();
-        } else if is_break(self.buffer[0]) {
+        } else if self.is_break(self.buffer[0]) {
             self.skip();
         }
     }
     #[inline]
+    fn is_break(&self, c: char) -> bool {
+        is_break(c)
+            || (self.config.line_breaks == LineBreaks::Yaml11
+                && matches!(c, '\u{85}' | '\u{2028}' | '\u{2029}'))
+    }
+    #[inline]
+    fn is_breakz(&self, c: char) -> bool {
+        self.is_break(c) || is_z(c)
+    }
+    #[inline]
+    fn is_blankz(&self, c: char) -> bool {
+        is_blank(c) || self.is_breakz(c)
+    }
+    #[inline]
     fn ch(&self) -> char {
         self.buffer[0]
     }
@@ -307,14 +660,69 @@ impl<T: Iterator<Item = char>> Scanner<T> {
     pub fn mark(&self) -> Marker {
         self.mark
     }
+    /// The number of flow collections open at the current position.
+    #[inline]
+    pub fn flow_level(&self) -> usize {
+        self.flow_level as usize
+    }
+    /// The column of the innermost open block collection, or -1 outside of
+    /// any.
+    #[inline]
+    pub fn indent(&self) -> isize {
+        self.indent
+    }
+    /// Whether a simple key, one without a `?`, may start at the current
+    /// position.
+    #[inline]
+    pub fn simple_key_allowed(&self) -> bool {
+        self.simple_key_allowed
+    }
+    /// The number of bytes the characters consumed so far take in UTF-8.
+    #[inline]
+    pub fn bytes(&self) -> usize {
+        self.mark.byte
+    }
+
+    // Count `max_document_bytes` from the current position.
+    pub(crate) fn start_document(&mut self) {
+        self.document_bytes = self.mark.byte;
+    }
+
+    fn check_document_size(&self) -> Result<(), ScanError> {
+        match self.config.max_document_bytes {
+            Some(max) if self.mark.byte - self.document_bytes > max => Err(ScanError::new(
+                self.mark,
+                "document exceeds the maximum size in bytes",
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    // Fail if a scalar starting at `mark` with `len` bytes of content so far
+    // is too large, or its document is.
+    fn check_scalar_size(&self, len: usize, mark: Marker) -> Result<(), ScanError> {
+        match self.config.max_scalar_bytes {
+            Some(max) if len > max => Err(ScanError::new(
+                mark,
+                "scalar exceeds the maximum size in bytes",
+            )),
+            _ => self.check_document_size(),
+        }
+    }
     #[inline]
     fn read_break(&mut self, s: &mut String) {
         if self.buffer[0] == '\r' && self.buffer[1] == '\n' {
             s.push('\n');
             self.skip();
             self.skip();
-        } else if self.buffer[0] == '\r' || self.buffer[0] == '\n' {
-            s.push('\n');
+        } else if self.is_break(self.buffer[0]) {
+            // a NEL reads as "\n", while YAML 1.1 keeps LS and PS in content
+            let c = self.buffer[0];
+            s.push(if matches!(c, '\u{2028}' | '\u{2029}') {
+                c
+            } else {
+                '\n'
+            });
             self.skip();
         } else {
             unreachable!();
@@ -337,13 +745,19 @@ impl<T: Iterator<Item = char>> Scanner<T> {
 
     pub fn fetch_next_token(&mut self) -> ScanResult {
         self.lookahead(1);
+        if let Some(block) = self.block_scalar.take() {
+            let tok = self.scan_block_scalar_content(block)?;
+            self.tokens.push_back(tok);
+            return Ok(());
+        }
         // println!("--> fetch_next_token Cur {:?} {:?}", self.mark, self.ch());
 
         if !self.stream_start_produced {
             self.fetch_stream_start();
             return Ok(());
         }
-        self.skip_to_next_token();
+        self.skip_to_next_token()?;
+        self.check_document_size()?;
 
         self.stale_simple_keys()?;
 
@@ -357,6 +771,10 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             return Ok(());
         }
 
+        if self.config.json {
+            return self.fetch_json_token();
+        }
+
         // Is it a directive?
         if self.mark.col == 0 && self.ch_is('%') {
             return self.fetch_directive();
@@ -366,7 +784,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             && self.buffer[0] == '-'
             && self.buffer[1] == '-'
             && self.buffer[2] == '-'
-            && is_blankz(self.buffer[3])
+            && self.is_blankz(self.buffer[3])
         {
             self.fetch_document_indicator(TokenType::DocumentStart)?;
             return Ok(());
@@ -376,7 +794,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             && self.buffer[0] == '.'
             && self.buffer[1] == '.'
             && self.buffer[2] == '.'
-            && is_blankz(self.buffer[3])
+            && self.is_blankz(self.buffer[3])
         {
             self.fetch_document_indicator(TokenType::DocumentEnd)?;
             return Ok(());
@@ -390,9 +808,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             ']' => self.fetch_flow_collection_end(TokenType::FlowSequenceEnd),
             '}' => self.fetch_flow_collection_end(TokenType::FlowMappingEnd),
             ',' => self.fetch_flow_entry(),
-            '-' if is_blankz(nc) => self.fetch_block_entry(),
-            '?' if is_blankz(nc) => self.fetch_key(),
-            ':' if is_blankz(nc)
+            '-' if self.is_blankz(nc) => self.fetch_block_entry(),
+            '?' if self.is_blankz(nc) => self.fetch_key(),
+            ':' if self.is_blankz(nc)
                 || (self.flow_level > 0
                     && (is_flow(nc) || self.mark.index == self.adjacent_value_allowed_at)) =>
             {
@@ -410,8 +828,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             '\'' => self.fetch_flow_scalar(true),
             '"' => self.fetch_flow_scalar(false),
             // plain scalar
-            '-' if !is_blankz(nc) => self.fetch_plain_scalar(),
-            ':' | '?' if !is_blankz(nc) && self.flow_level == 0 => self.fetch_plain_scalar(),
+            '-' if !self.is_blankz(nc) => self.fetch_plain_scalar(),
+            ':' | '?' if !self.is_blankz(nc) && self.flow_level == 0 => self.fetch_plain_scalar(),
             '%' | '@' | '`' => Err(ScanError::new(
                 self.mark,
                 &format!("unexpected character: `{}'", c),
@@ -420,6 +838,101 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         }
     }
 
+    // Fetch a token of JSON text, in JSON mode.
+    fn fetch_json_token(&mut self) -> ScanResult {
+        match self.ch() {
+            '[' => self.fetch_flow_collection_start(TokenType::FlowSequenceStart),
+            '{' => self.fetch_flow_collection_start(TokenType::FlowMappingStart),
+            ']' => self.fetch_flow_collection_end(TokenType::FlowSequenceEnd),
+            '}' => self.fetch_flow_collection_end(TokenType::FlowMappingEnd),
+            ',' => self.fetch_flow_entry(),
+            ':' => self.fetch_value(),
+            '"' => self.fetch_flow_scalar(false),
+            '-' | '0'..='9' | 't' | 'f' | 'n' => {
+                let mark = self.mark;
+                self.fetch_plain_scalar()?;
+                match self.tokens.back() {
+                    Some(Token(_, TokenType::Scalar(_, ref v))) if !is_json_literal(v) => {
+                        Err(ScanError::new(mark, "invalid JSON number or literal"))
+                    }
+                    Some(Token(_, TokenType::Scalar(_, ref v)))
+                        if v.parse::<f64>().is_ok_and(f64::is_infinite) =>
+                    {
+                        Err(ScanError::new(mark, "JSON number out of range"))
+                    }
+                    Some(Token(_, TokenType::Scalar(..))) => Ok(()),
+                    _ => Err(ScanError::new(mark, "invalid JSON number or literal")),
+                }
+            }
+            c => Err(ScanError::new(
+                self.mark,
+                &format!("unexpected character in JSON: `{}'", c),
+            )),
+        }
+    }
+
+    // Check that a token may come next in JSON text.
+    fn check_json_token(&mut self, tok: &Token) -> ScanResult {
+        let expect = self.json_expect;
+        let next = match (expect, &tok.1) {
+            (_, TokenType::StreamStart(_)) => expect,
+            (JsonExpect::End, TokenType::StreamEnd) => expect,
+            (JsonExpect::Value, TokenType::Scalar(..))
+            | (JsonExpect::FirstValue, TokenType::Scalar(..)) => self.json_value_done(),
+            (JsonExpect::Value, TokenType::FlowSequenceStart)
+            | (JsonExpect::FirstValue, TokenType::FlowSequenceStart) => {
+                self.json_stack.push(false);
+                JsonExpect::FirstValue
+            }
+            (JsonExpect::Value, TokenType::FlowMappingStart)
+            | (JsonExpect::FirstValue, TokenType::FlowMappingStart) => {
+                self.json_stack.push(true);
+                JsonExpect::FirstKey
+            }
+            (JsonExpect::FirstKey, TokenType::Key) | (JsonExpect::Key, TokenType::Key) => {
+                JsonExpect::KeyString
+            }
+            (JsonExpect::KeyString, TokenType::Scalar(TScalarStyle::DoubleQuoted, _)) => {
+                JsonExpect::Colon
+            }
+            (JsonExpect::Colon, TokenType::Value) => JsonExpect::Value,
+            (JsonExpect::Next, TokenType::FlowEntry) => match self.json_stack.last() {
+                Some(true) => JsonExpect::Key,
+                _ => JsonExpect::Value,
+            },
+            (JsonExpect::FirstValue, TokenType::FlowSequenceEnd)
+            | (JsonExpect::Next, TokenType::FlowSequenceEnd)
+                if self.json_stack.last() == Some(&false) =>
+            {
+                self.json_stack.pop();
+                self.json_value_done()
+            }
+            (JsonExpect::FirstKey, TokenType::FlowMappingEnd)
+            | (JsonExpect::Next, TokenType::FlowMappingEnd)
+                if self.json_stack.last() == Some(&true) =>
+            {
+                self.json_stack.pop();
+                self.json_value_done()
+            }
+            _ => {
+                return Err(ScanError::new(
+                    tok.0,
+                    &format!("invalid JSON, expected {}", expect.describe()),
+                ))
+            }
+        };
+        self.json_expect = next;
+        Ok(())
+    }
+
+    fn json_value_done(&self) -> JsonExpect {
+        if self.json_stack.is_empty() {
+            JsonExpect::End
+        } else {
+            JsonExpect::Next
+        }
+    }
+
     pub fn next_token(&mut self) -> Result<Option<Token>, ScanError> {
         if self.stream_end_produced {
             return Ok(None);
@@ -431,6 +944,9 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         let t = self.tokens.pop_front().unwrap();
         self.token_available = false;
         self.tokens_parsed += 1;
+        if self.config.json {
+            self.check_json_token(&t)?;
+        }
 
         if let TokenType::StreamEnd = t.1 {
             self.stream_end_produced = true;
@@ -467,7 +983,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
     fn stale_simple_keys(&mut self) -> ScanResult {
         for sk in &mut self.simple_keys {
             if sk.possible
-                && (sk.mark.line < self.mark.line || sk.mark.index + 1024 < self.mark.index)
+                && (sk.mark.line < self.mark.line
+                    || sk.mark.index + self.config.max_implicit_key_len < self.mark.index)
             {
                 if sk.required {
                     return Err(ScanError::new(self.mark, "simple key expect ':'"));
@@ -478,22 +995,35 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         Ok(())
     }
 
-    fn skip_to_next_token(&mut self) {
+    fn skip_to_next_token(&mut self) -> ScanResult {
+        // whether only blanks precede on the line
+        let mut indentation = self.mark.col == 0;
         loop {
             self.lookahead(1);
             // TODO(chenyh) BOM
             match self.ch() {
                 ' ' => self.skip(),
-                '\t' if self.flow_level > 0 || !self.simple_key_allowed => self.skip(),
+                '\t' if indentation && self.flow_level == 0 && !self.config.json => {
+                    if self.rest_of_line_blank() {
+                        self.skip();
+                    } else {
+                        self.skip_indentation_tab()?;
+                    }
+                }
+                '\t' if self.flow_level > 0 || !self.simple_key_allowed || self.config.json => {
+                    self.skip()
+                }
                 '\n' | '\r' => {
                     self.lookahead(2);
                     self.skip_line();
+                    indentation = true;
                     if self.flow_level == 0 {
                         self.allow_simple_key();
                     }
                 }
-                '#' => {
-                    while !is_breakz(self.ch()) {
+                // comments are an error in JSON mode
+                '#' if !self.config.json => {
+                    while !self.is_breakz(self.ch()) {
                         self.skip();
                         self.lookahead(1);
                     }
@@ -501,6 +1031,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 _ => break,
             }
         }
+        Ok(())
     }
 
     fn fetch_stream_start(&mut self) {
@@ -510,7 +1041,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         self.allow_simple_key();
         self.tokens
             .push_back(Token(mark, TokenType::StreamStart(TEncoding::Utf8)));
-        self.simple_keys.push(SimpleKey::new(Marker::new(0, 0, 0)));
+        self.simple_keys
+            .push(SimpleKey::new(Marker::new(0, 0, 0, 0)));
     }
 
     fn fetch_stream_end(&mut self) -> ScanResult {
@@ -554,7 +1086,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             _ => {
                 // skip current line
                 self.lookahead(1);
-                while !is_breakz(self.ch()) {
+                while !self.is_breakz(self.ch()) {
                     self.skip();
                     self.lookahead(1);
                 }
@@ -575,13 +1107,13 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         }
 
         if self.ch() == '#' {
-            while !is_breakz(self.ch()) {
+            while !self.is_breakz(self.ch()) {
                 self.skip();
                 self.lookahead(1);
             }
         }
 
-        if !is_breakz(self.ch()) {
+        if !self.is_breakz(self.ch()) {
             return Err(ScanError::new(
                 start_mark,
                 "while scanning a directive, did not find expected comment or line break",
@@ -589,7 +1121,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         }
 
         // Eat a line break
-        if is_break(self.ch()) {
+        if self.is_break(self.ch()) {
             self.lookahead(2);
             self.skip_line();
         }
@@ -638,7 +1170,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             ));
         }
 
-        if !is_blankz(self.ch()) {
+        if !self.is_blankz(self.ch()) {
             return Err(ScanError::new(
                 start_mark,
                 "while scanning a directive, found unexpected non-alphabetical character",
@@ -696,7 +1228,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
 
         self.lookahead(1);
 
-        if is_blankz(self.ch()) {
+        if self.is_blankz(self.ch()) {
             Ok(Token(*mark, TokenType::TagDirective(handle, prefix)))
         } else {
             Err(ScanError::new(
@@ -711,6 +1243,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         self.disallow_simple_key();
 
         let tok = self.scan_tag()?;
+        self.record_span(tok.0, tok.0, self.mark);
         self.tokens.push_back(tok);
         Ok(())
     }
@@ -760,7 +1293,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         }
 
         self.lookahead(1);
-        if is_blankz(self.ch()) {
+        if self.is_blankz(self.ch()) {
             // XXX: ex 7.2, an empty scalar can follow a secondary tag
             Ok(Token(start_mark, TokenType::Tag(handle, suffix)))
         } else {
@@ -922,6 +1455,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         self.disallow_simple_key();
 
         let tok = self.scan_anchor(alias)?;
+        self.record_span(tok.0, tok.0, self.mark);
 
         self.tokens.push_back(tok);
 
@@ -942,7 +1476,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
 
         if string.is_empty()
             || match self.ch() {
-                c if is_blankz(c) => false,
+                c if self.is_blankz(c) => false,
                 '?' | ':' | ',' | ']' | '}' | '%' | '@' | '`' => false,
                 _ => true,
             }
@@ -966,6 +1500,12 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         self.allow_simple_key();
 
         let start_mark = self.mark;
+        if self.capacity_hints.is_some() {
+            let hint = self.flow_capacity_hint();
+            if let Some(ref mut hints) = self.capacity_hints {
+                hints.insert(start_mark.index, hint);
+            }
+        }
         self.skip();
 
         self.tokens.push_back(Token(start_mark, tok));
@@ -998,7 +1538,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
     }
 
     fn increase_flow_level(&mut self) -> ScanResult {
-        self.simple_keys.push(SimpleKey::new(Marker::new(0, 0, 0)));
+        self.simple_keys
+            .push(SimpleKey::new(Marker::new(0, 0, 0, 0)));
         self.flow_level = self
             .flow_level
             .checked_add(1)
@@ -1069,14 +1610,10 @@ impl<T: Iterator<Item = char>> Scanner<T> {
 
     fn scan_block_scalar(&mut self, literal: bool) -> Result<Token, ScanError> {
         let start_mark = self.mark;
+        let header_mark = self.mark;
         let mut chomping: i32 = 0;
         let mut increment: usize = 0;
         let mut indent: usize = 0;
-        let mut trailing_blank: bool;
-        let mut leading_blank: bool = false;
-
-        let mut string = String::new();
-        let mut leading_break = String::new();
         let mut trailing_breaks = String::new();
 
         // skip '|' or '>'
@@ -1124,6 +1661,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
 
         // Eat whitespaces and comments to the end of the line.
         self.lookahead(1);
+        let end_mark = self.mark;
 
         while is_blank(self.ch()) {
             self.skip();
@@ -1131,21 +1669,21 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         }
 
         if self.ch() == '#' {
-            while !is_breakz(self.ch()) {
+            while !self.is_breakz(self.ch()) {
                 self.skip();
                 self.lookahead(1);
             }
         }
 
         // Check if we are at the end of the line.
-        if !is_breakz(self.ch()) {
+        if !self.is_breakz(self.ch()) {
             return Err(ScanError::new(
                 start_mark,
                 "while scanning a block scalar, did not find expected comment or line break",
             ));
         }
 
-        if is_break(self.ch()) {
+        if self.is_break(self.ch()) {
             self.lookahead(2);
             self.skip_line();
         }
@@ -1162,62 +1700,95 @@ impl<T: Iterator<Item = char>> Scanner<T> {
 
         self.lookahead(1);
 
-        let start_mark = self.mark;
+        let block = BlockScalar {
+            literal,
+            chomping,
+            indent,
+            leading_blank: false,
+            string: String::new(),
+            leading_break: String::new(),
+            trailing_breaks,
+            delivered: 0,
+            start_mark: self.mark,
+            header_mark,
+            end_mark,
+        };
+        self.scan_block_scalar_content(block)
+    }
 
+    // Scan the lines of a block scalar from the start of one, stopping
+    // after a chunk of content if they are delivered in chunks.
+    fn scan_block_scalar_content(&mut self, mut block: BlockScalar) -> Result<Token, ScanError> {
+        let start_mark = block.start_mark;
+        let indent = block.indent;
         while self.mark.col == indent && !is_z(self.ch()) {
             // We are at the beginning of a non-empty line.
-            trailing_blank = is_blank(self.ch());
-            if !literal && !leading_break.is_empty() && !leading_blank && !trailing_blank {
-                if trailing_breaks.is_empty() {
-                    string.push(' ');
+            let trailing_blank = is_blank(self.ch());
+            if !block.literal
+                && block.leading_break == "\n"
+                && !block.leading_blank
+                && !trailing_blank
+            {
+                if block.trailing_breaks.is_empty() {
+                    block.string.push(' ');
                 }
             } else {
-                string.push_str(&leading_break);
+                block.string.push_str(&block.leading_break);
             }
-            leading_break.clear();
+            block.leading_break.clear();
 
-            string.push_str(&trailing_breaks);
-            trailing_breaks.clear();
+            block.string.push_str(&block.trailing_breaks);
+            block.trailing_breaks.clear();
 
-            leading_blank = is_blank(self.ch());
+            block.leading_blank = is_blank(self.ch());
 
-            while !is_breakz(self.ch()) {
-                string.push(self.ch());
+            while !self.is_breakz(self.ch()) {
+                block.string.push(self.ch());
                 self.skip();
                 self.lookahead(1);
+                self.check_scalar_size(block.delivered + block.string.len(), start_mark)?;
             }
+            block.end_mark = self.mark;
             // break on EOF
             if is_z(self.ch()) {
                 break;
             }
 
             self.lookahead(2);
-            self.read_break(&mut leading_break);
+            self.read_break(&mut block.leading_break);
 
             // Eat the following indentation spaces and line breaks.
-            self.block_scalar_breaks(&mut indent, &mut trailing_breaks)?;
+            let mut indent = indent;
+            self.block_scalar_breaks(&mut indent, &mut block.trailing_breaks)?;
+            self.check_document_size()?;
+
+            if let Some(size) = self.config.block_scalar_chunk_bytes {
+                if block.string.len() >= size && self.mark.col == indent && !is_z(self.ch()) {
+                    let chunk = mem::take(&mut block.string);
+                    block.delivered += chunk.len();
+                    self.block_scalar = Some(block);
+                    return Ok(Token(start_mark, TokenType::ScalarChunk(chunk)));
+                }
+            }
         }
 
         // Chomp the tail.
-        if chomping != -1 {
-            string.push_str(&leading_break);
+        let mut string = block.string;
+        if block.chomping != -1 {
+            string.push_str(&block.leading_break);
         }
 
-        if chomping == 1 {
-            string.push_str(&trailing_breaks);
+        if block.chomping == 1 {
+            string.push_str(&block.trailing_breaks);
         }
+        self.record_span(start_mark, block.header_mark, block.end_mark);
 
-        if literal {
-            Ok(Token(
-                start_mark,
-                TokenType::Scalar(TScalarStyle::Literal, string),
-            ))
+        let style = if block.literal {
+            TScalarStyle::Literal
         } else {
-            Ok(Token(
-                start_mark,
-                TokenType::Scalar(TScalarStyle::Foled, string),
-            ))
-        }
+            TScalarStyle::Foled
+        };
+        Ok(Token(start_mark, TokenType::Scalar(style, string)))
     }
 
     fn block_scalar_breaks(&mut self, indent: &mut usize, breaks: &mut String) -> ScanResult {
@@ -1235,11 +1806,11 @@ impl<T: Iterator<Item = char>> Scanner<T> {
 
             // Check for a tab character messing the indentation.
             if (*indent == 0 || self.mark.col < *indent) && self.buffer[0] == '\t' {
-                return Err(ScanError::new(self.mark,
-                        "while scanning a block scalar, found a tab character where an indentation space is expected"));
+                self.skip_indentation_tab()?;
+                continue;
             }
 
-            if !is_break(self.ch()) {
+            if !self.is_break(self.ch()) {
                 break;
             }
 
@@ -1264,7 +1835,12 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         self.save_simple_key()?;
         self.disallow_simple_key();
 
-        let tok = self.scan_flow_scalar(single)?;
+        let tok = if self.config.json {
+            self.scan_json_string()?
+        } else {
+            self.scan_flow_scalar(single)?
+        };
+        self.record_span(tok.0, tok.0, self.mark);
 
         // From spec: To ensure JSON compatibility, if a key inside a flow mapping is JSON-like,
         // YAML allows the following value to be specified adjacent to the “:”.
@@ -1295,7 +1871,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                     || ((self.buffer[0] == '.')
                         && (self.buffer[1] == '.')
                         && (self.buffer[2] == '.')))
-                && is_blankz(self.buffer[3])
+                && self.is_blankz(self.buffer[3])
             {
                 return Err(ScanError::new(
                     start_mark,
@@ -1315,7 +1891,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             leading_blanks = false;
             // Consume non-blank characters.
 
-            while !is_blankz(self.ch()) {
+            while !self.is_blankz(self.ch()) {
                 match self.ch() {
                     // Check for an escaped single quote.
                     '\'' if self.buffer[1] == '\'' && single => {
@@ -1327,7 +1903,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                     '\'' if single => break,
                     '"' if !single => break,
                     // Check for an escaped line break.
-                    '\\' if !single && is_break(self.buffer[1]) => {
+                    '\\' if !single && self.is_break(self.buffer[1]) => {
                         self.lookahead(3);
                         self.skip();
                         self.skip_line();
@@ -1403,6 +1979,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                     }
                 }
                 self.lookahead(2);
+                self.check_scalar_size(string.len(), start_mark)?;
             }
             self.lookahead(1);
             match self.ch() {
@@ -1412,7 +1989,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             }
 
             // Consume blank characters.
-            while is_blank(self.ch()) || is_break(self.ch()) {
+            while is_blank(self.ch()) || self.is_break(self.ch()) {
                 if is_blank(self.ch()) {
                     // Consume a space or a tab character.
                     if leading_blanks {
@@ -1433,9 +2010,11 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 }
                 self.lookahead(1);
             }
+            self.check_document_size()?;
             // Join the whitespaces or fold line breaks.
             if leading_blanks {
-                if leading_break.is_empty() {
+                // only a "\n" folds, an LS or PS is kept as it is
+                if leading_break != "\n" {
                     string.push_str(&leading_break);
                     string.push_str(&trailing_breaks);
                     trailing_breaks.clear();
@@ -1468,6 +2047,119 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         }
     }
 
+    // Scan a string with JSON's syntax, where line breaks and control
+    // characters must be escaped and an escaped UTF-16 surrogate pair
+    // stands for one character.
+    fn scan_json_string(&mut self) -> Result<Token, ScanError> {
+        let start_mark = self.mark;
+        let mut string = String::new();
+        self.skip();
+        loop {
+            self.lookahead(2);
+            match self.ch() {
+                '"' => break,
+                '\\' => {
+                    let escaped = match self.buffer[1] {
+                        '"' => '"',
+                        '\\' => '\\',
+                        '/' => '/',
+                        'b' => '\x08',
+                        'f' => '\x0c',
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        'u' => {
+                            self.skip();
+                            self.skip();
+                            let unit = self.scan_json_code_unit(&start_mark)?;
+                            let code = if (0xD800..0xDC00).contains(&unit) {
+                                self.lookahead(2);
+                                if self.buffer[0] != '\\' || self.buffer[1] != 'u' {
+                                    return Err(ScanError::new(
+                                        start_mark,
+                                        "while parsing a JSON string, found a lone surrogate",
+                                    ));
+                                }
+                                self.skip();
+                                self.skip();
+                                let low = self.scan_json_code_unit(&start_mark)?;
+                                if !(0xDC00..0xE000).contains(&low) {
+                                    return Err(ScanError::new(
+                                        start_mark,
+                                        "while parsing a JSON string, found a lone surrogate",
+                                    ));
+                                }
+                                0x10000 + ((unit - 0xD800) << 10) + (low - 0xDC00)
+                            } else {
+                                unit
+                            };
+                            match char::from_u32(code) {
+                                Some(c) => string.push(c),
+                                None => {
+                                    return Err(ScanError::new(
+                                        start_mark,
+                                        "while parsing a JSON string, found a lone surrogate",
+                                    ))
+                                }
+                            }
+                            continue;
+                        }
+                        _ => {
+                            return Err(ScanError::new(
+                                start_mark,
+                                "while parsing a JSON string, found unknown escape character",
+                            ))
+                        }
+                    };
+                    string.push(escaped);
+                    self.skip();
+                    self.skip();
+                }
+                c if is_z(c) => {
+                    return Err(ScanError::new(
+                        start_mark,
+                        "while scanning a JSON string, found unexpected end of stream",
+                    ))
+                }
+                c if c < ' ' => {
+                    return Err(ScanError::new(
+                        self.mark,
+                        "while scanning a JSON string, found an unescaped control character",
+                    ))
+                }
+                c => {
+                    string.push(c);
+                    self.skip();
+                }
+            }
+        }
+        // Eat the right quote.
+        self.skip();
+        Ok(Token(
+            start_mark,
+            TokenType::Scalar(TScalarStyle::DoubleQuoted, string),
+        ))
+    }
+
+    // Consume the four hexadecimal digits of a `\u` escape.
+    fn scan_json_code_unit(&mut self, start_mark: &Marker) -> Result<u32, ScanError> {
+        self.lookahead(4);
+        let mut value = 0;
+        for i in 0..4 {
+            if !is_hex(self.buffer[i]) {
+                return Err(ScanError::new(
+                    *start_mark,
+                    "while parsing a JSON string, did not find expected hexadecimal number",
+                ));
+            }
+            value = (value << 4) + as_hex(self.buffer[i]);
+        }
+        for _ in 0..4 {
+            self.skip();
+        }
+        Ok(value)
+    }
+
     fn fetch_plain_scalar(&mut self) -> ScanResult {
         self.save_simple_key()?;
         self.disallow_simple_key();
@@ -1487,6 +2179,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         let mut trailing_breaks = String::new();
         let mut whitespaces = String::new();
         let mut leading_blanks = false;
+        let mut end_mark = start_mark;
 
         loop {
             /* Check for a document indicator. */
@@ -1497,7 +2190,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                     || ((self.buffer[0] == '.')
                         && (self.buffer[1] == '.')
                         && (self.buffer[2] == '.')))
-                && is_blankz(self.buffer[3])
+                && self.is_blankz(self.buffer[3])
             {
                 break;
             }
@@ -1505,10 +2198,10 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             if self.ch() == '#' {
                 break;
             }
-            while !is_blankz(self.ch()) {
+            while !self.is_blankz(self.ch()) {
                 // indicators can end a plain scalar, see 7.3.3. Plain Style
                 match self.ch() {
-                    ':' if is_blankz(self.buffer[1])
+                    ':' if self.is_blankz(self.buffer[1])
                         || (self.flow_level > 0 && is_flow(self.buffer[1])) =>
                     {
                         break;
@@ -1519,7 +2212,8 @@ impl<T: Iterator<Item = char>> Scanner<T> {
 
                 if leading_blanks || !whitespaces.is_empty() {
                     if leading_blanks {
-                        if leading_break.is_empty() {
+                        // only a "\n" folds, an LS or PS is kept as it is
+                        if leading_break != "\n" {
                             string.push_str(&leading_break);
                             string.push_str(&trailing_breaks);
                             trailing_breaks.clear();
@@ -1540,20 +2234,24 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 string.push(self.ch());
                 self.skip();
                 self.lookahead(2);
+                end_mark = self.mark;
+                self.check_scalar_size(string.len(), start_mark)?;
             }
             // is the end?
-            if !(is_blank(self.ch()) || is_break(self.ch())) {
+            if !(is_blank(self.ch()) || self.is_break(self.ch())) {
                 break;
             }
             self.lookahead(1);
 
-            while is_blank(self.ch()) || is_break(self.ch()) {
+            while is_blank(self.ch()) || self.is_break(self.ch()) {
                 if is_blank(self.ch()) {
-                    if leading_blanks && (self.mark.col as isize) < indent && self.ch() == '\t' {
-                        return Err(ScanError::new(
-                            start_mark,
-                            "while scanning a plain scalar, found a tab",
-                        ));
+                    if leading_blanks
+                        && (self.mark.col as isize) < indent
+                        && self.ch() == '\t'
+                        && !self.rest_of_line_blank()
+                    {
+                        self.skip_indentation_tab()?;
+                        continue;
                     }
 
                     if leading_blanks {
@@ -1574,6 +2272,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
                 }
                 self.lookahead(1);
             }
+            self.check_document_size()?;
 
             // check indentation level
             if self.flow_level == 0 && (self.mark.col as isize) < indent {
@@ -1584,6 +2283,7 @@ impl<T: Iterator<Item = char>> Scanner<T> {
         if leading_blanks {
             self.allow_simple_key();
         }
+        self.record_span(start_mark, start_mark, end_mark);
 
         Ok(Token(
             start_mark,
@@ -1631,12 +2331,15 @@ impl<T: Iterator<Item = char>> Scanner<T> {
             let tokens_parsed = self.tokens_parsed;
             self.insert_token(sk.token_number - tokens_parsed, tok);
 
-            // Add the BLOCK-MAPPING-START token if needed.
+            // Add the BLOCK-MAPPING-START token if needed. The mapping
+            // starts where its first key does, not at the `:` found after
+            // it, so the spans of documents and nodes cover the whole
+            // mapping.
             self.roll_indent(
                 sk.mark.col,
                 Some(sk.token_number),
                 TokenType::BlockMappingStart,
-                start_mark,
+                sk.mark,
             );
 
             self.simple_keys.last_mut().unwrap().possible = false;
@@ -2161,6 +2864,128 @@ key:
         end!(p);
     }
 
+    #[test]
+    fn test_scanner_cr_only() {
+        let s = "---\r- tok1\r- |\r  a\r  b\r";
+        let mut p = Scanner::new(s.chars());
+        next!(p, StreamStart(..));
+        next!(p, DocumentStart);
+        next!(p, BlockSequenceStart);
+        next!(p, BlockEntry);
+        next_scalar!(p, TScalarStyle::Plain, "tok1");
+        next!(p, BlockEntry);
+        next_scalar!(p, TScalarStyle::Literal, "a\nb\n");
+        next!(p, BlockEnd);
+        next!(p, StreamEnd);
+        end!(p);
+        assert_eq!(p.mark().line(), 6);
+    }
+
+    #[test]
+    fn test_line_numbers_crlf() {
+        let s = "a: 1\r\n\r\nb: c\r";
+        let mut p = Scanner::new(s.chars());
+        while p.next().is_some() {}
+        assert_eq!(p.mark().line(), 4);
+        assert_eq!(p.mark().col(), 0);
+    }
+
+    #[test]
+    fn test_block_mapping_start_mark() {
+        let s = "- x
+-   key 1: a
+    b: c
+";
+        let marks: Vec<(usize, usize)> = Scanner::new(s.chars())
+            .filter(|tok| tok.1 == BlockMappingStart)
+            .map(|tok| (tok.0.line(), tok.0.col()))
+            .collect();
+        assert_eq!(marks, [(2, 4)]);
+    }
+
+    #[test]
+    fn test_line_breaks_yaml11() {
+        let s = "- a\u{85}- |\r\n  b\u{2028}  c\r- d\u{2029}  e\n";
+        let config = ScannerConfig {
+            line_breaks: LineBreaks::Yaml11,
+            ..ScannerConfig::default()
+        };
+        let mut p = Scanner::new_with_config(s.chars(), config);
+        next!(p, StreamStart(..));
+        next!(p, BlockSequenceStart);
+        next!(p, BlockEntry);
+        next_scalar!(p, TScalarStyle::Plain, "a");
+        next!(p, BlockEntry);
+        next_scalar!(p, TScalarStyle::Literal, "b\u{2028}c\n");
+        next!(p, BlockEntry);
+        next_scalar!(p, TScalarStyle::Plain, "d\u{2029}e");
+        next!(p, BlockEnd);
+        next!(p, StreamEnd);
+        end!(p);
+        assert_eq!(p.mark().line(), 7);
+    }
+
+    #[test]
+    fn test_line_breaks_yaml12() {
+        // YAML 1.2 reads the same characters as content
+        let mut p = Scanner::new("- a\u{85}b\u{2028}c\n".chars());
+        next!(p, StreamStart(..));
+        next!(p, BlockSequenceStart);
+        next!(p, BlockEntry);
+        next_scalar!(p, TScalarStyle::Plain, "a\u{85}b\u{2028}c");
+        next!(p, BlockEnd);
+        next!(p, StreamEnd);
+        end!(p);
+        assert_eq!(p.mark().line(), 2);
+    }
+
+    #[test]
+    fn test_tab_indentation() {
+        for (s, line, col) in [
+            ("a:\n\tb: 1\n", 2, 0),
+            ("a:\n  b:\n  \tc: 1\n", 3, 2),
+            ("a: |\n  x\n\ty\n", 3, 0),
+            ("a: b\n\tc\n", 2, 0),
+        ] {
+            let mut p = Scanner::new(s.chars());
+            while p.next().is_some() {}
+            let err = p.get_error().unwrap();
+            assert_eq!(err.kind(), ScanErrorKind::TabIndentation, "{:?}", s);
+            assert_eq!((err.marker().line(), err.marker().col()), (line, col));
+        }
+        // tabs on lines without content, in flow context and between
+        // tokens are not indentation
+        for s in ["a:\n  - b\n\t\n\t# c\n  - c\n", "a: [1,\n\t2]\n", "a:\t1\n"] {
+            let mut p = Scanner::new(s.chars());
+            while p.next().is_some() {}
+            assert_eq!(p.get_error(), None, "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn test_tab_width() {
+        let config = ScannerConfig {
+            tab_width: Some(4),
+            ..ScannerConfig::default()
+        };
+        let mut p = Scanner::new_with_config("a:\n  \tb: |\n\t\tx\n".chars(), config);
+        next!(p, StreamStart(..));
+        next!(p, BlockMappingStart);
+        next!(p, Key);
+        next_scalar!(p, TScalarStyle::Plain, "a");
+        next!(p, Value);
+        next!(p, BlockMappingStart);
+        next!(p, Key);
+        let tok = p.next().unwrap();
+        assert_eq!((tok.0.line(), tok.0.col(), tok.0.index()), (2, 4, 6));
+        next!(p, Value);
+        next_scalar!(p, TScalarStyle::Literal, "x\n");
+        next!(p, BlockEnd);
+        next!(p, BlockEnd);
+        next!(p, StreamEnd);
+        end!(p);
+    }
+
     #[test]
     fn test_uri() {
         // TODO
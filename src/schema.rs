@@ -0,0 +1,214 @@
+//! Inference of a schema from example documents, such as to describe the
+//! configuration files that already exist.
+//!
+//! The schema is a document in the style of JSON Schema. Each node has the
+//! `type` of the values seen at its place, or a list of types if there are
+//! several. Hashes have `properties` for their keys and `required` for the
+//! keys every example has, and arrays have `items` for all their items.
+//! Strings and integers that repeat and take few values have those values
+//! as an `enum`.
+//!
+//! # Examples
+//!
+//! ```
+//! # extern crate yaml_rust_davvid as yaml_rust;
+//! use yaml_rust::{schema, YamlLoader};
+//!
+//! let examples = YamlLoader::load_from_str("
+//! name: web
+//! env: prod
+//! ---
+//! name: db
+//! env: prod
+//! replicas: 3
+//! ").unwrap();
+//! let schema = schema::infer(&examples);
+//! let expected = YamlLoader::load_from_str("
+//! type: object
+//! properties:
+//!   name: {type: string}
+//!   env: {type: string, enum: [prod]}
+//!   replicas: {type: integer}
+//! required: [name, env]
+//! ").unwrap();
+//! assert_eq!(schema, expected[0]);
+//! ```
+
+use crate::yaml::{Hash, Yaml};
+
+use hashlink::LinkedHashMap;
+
+// The most values a node may take to be given as an enum.
+const MAX_ENUM: usize = 8;
+
+// What the examples hold at one place.
+#[derive(Debug, Default)]
+struct Shape {
+    // the number of values seen
+    count: usize,
+    types: Vec<&'static str>,
+    // distinct strings and integers, until there are too many
+    values: Vec<Yaml>,
+    values_seen: usize,
+    too_many_values: bool,
+    hashes: usize,
+    properties: LinkedHashMap<Yaml, Shape>,
+    items: Option<Box<Shape>>,
+}
+
+impl Shape {
+    fn add(&mut self, node: &Yaml) {
+        let kind = match *node {
+            Yaml::Null => "null",
+            Yaml::Boolean(_) => "boolean",
+            Yaml::Integer(_) => "integer",
+            Yaml::Real(_) => "number",
+            Yaml::String(_) | Yaml::Interned(_) => "string",
+            Yaml::Array(_) => "array",
+            Yaml::Hash(_) => "object",
+            Yaml::Tagged(_, ref v) => return self.add(v),
+            Yaml::Alias(_) | Yaml::BadValue => return,
+        };
+        self.count += 1;
+        if !self.types.contains(&kind) {
+            self.types.push(kind);
+        }
+        match *node {
+            Yaml::String(_) | Yaml::Interned(_) | Yaml::Integer(_) => self.add_value(node),
+            Yaml::Array(ref v) => {
+                let items = self.items.get_or_insert_with(Box::default);
+                for item in v {
+                    items.add(item);
+                }
+            }
+            Yaml::Hash(ref h) => {
+                self.hashes += 1;
+                for (key, value) in h {
+                    // `entry` would move the key to the end
+                    if !self.properties.contains_key(key) {
+                        self.properties.insert(key.clone(), Shape::default());
+                    }
+                    self.properties.get_mut(key).unwrap().add(value);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn add_value(&mut self, value: &Yaml) {
+        self.values_seen += 1;
+        if self.too_many_values || self.values.contains(value) {
+            return;
+        }
+        if self.values.len() == MAX_ENUM {
+            self.too_many_values = true;
+            self.values = Vec::new();
+        } else {
+            self.values.push(value.clone());
+        }
+    }
+
+    fn to_yaml(&self) -> Yaml {
+        let mut schema = Hash::new();
+        let string = |s: &str| Yaml::String(s.to_owned());
+        match self.types[..] {
+            [] => {}
+            [kind] => {
+                schema.insert(string("type"), string(kind));
+            }
+            ref kinds => {
+                let kinds = kinds.iter().map(|kind| string(kind)).collect();
+                schema.insert(string("type"), Yaml::Array(kinds));
+            }
+        }
+        // a value seen once is not a choice among values
+        if !self.too_many_values && self.values_seen > self.values.len() {
+            schema.insert(string("enum"), Yaml::Array(self.values.clone()));
+        }
+        if self.hashes > 0 {
+            let properties = self
+                .properties
+                .iter()
+                .map(|(key, shape)| (key.clone(), shape.to_yaml()))
+                .collect();
+            schema.insert(string("properties"), Yaml::Hash(properties));
+            let required: Vec<Yaml> = self
+                .properties
+                .iter()
+                .filter(|(_, shape)| shape.count == self.hashes)
+                .map(|(key, _)| key.clone())
+                .collect();
+            if !required.is_empty() {
+                schema.insert(string("required"), Yaml::Array(required));
+            }
+        }
+        if let Some(ref items) = self.items {
+            if !items.types.is_empty() {
+                schema.insert(string("items"), items.to_yaml());
+            }
+        }
+        Yaml::Hash(schema)
+    }
+}
+
+/// Infer a schema that the `examples` follow, see the module documentation.
+/// Without examples the schema is an empty hash, which allows anything.
+pub fn infer(examples: &[Yaml]) -> Yaml {
+    let mut shape = Shape::default();
+    for example in examples {
+        shape.add(example);
+    }
+    shape.to_yaml()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::YamlLoader;
+
+    #[test]
+    fn test_infer() {
+        let examples = YamlLoader::load_from_str(
+            "
+ports: [{port: 80, proto: tcp}, {port: 443}]
+level: 1
+tags: !t [a]
+---
+ports: [{port: 53, proto: udp}]
+level: high
+extra: ~
+---
+ports: []
+level: 2.5
+",
+        )
+        .unwrap();
+        let expected = YamlLoader::load_from_str(
+            "
+type: object
+properties:
+  ports:
+    type: array
+    items:
+      type: object
+      properties:
+        port: {type: integer}
+        proto: {type: string}
+      required: [port]
+  level: {type: [integer, string, number]}
+  tags: {type: array, items: {type: string}}
+  extra: {type: 'null'}
+required: [ports, level]
+",
+        )
+        .unwrap();
+        assert_eq!(infer(&examples), expected[0]);
+        assert_eq!(infer(&[]), Yaml::Hash(Hash::new()));
+
+        // repeated values up to a limit are an enum
+        let many: Vec<Yaml> = (0..20).map(|i| Yaml::Integer(i % 9)).collect();
+        assert_eq!(infer(&many)["enum"], Yaml::BadValue);
+        let few: Vec<Yaml> = (0..20).map(|i| Yaml::Integer(i % 8)).collect();
+        assert_eq!(infer(&few)["enum"].as_vec().map(Vec::len), Some(8));
+    }
+}
@@ -0,0 +1,571 @@
+//! Documents shared between threads, such as configuration that many
+//! threads read and that is sometimes replaced.
+//!
+//! Every loaded value is `Send + Sync`, which this module checks when it
+//! is compiled, so documents can be sent to other threads or shared with
+//! an `Arc`. The exception is `lazy::LazyNode`, whose nodes share the
+//! children they load through an `Rc`.
+//!
+//! A `SharedYaml` is a document behind an `Arc` that is cheap to clone and
+//! is copied when it is changed while other clones still read it, so that
+//! readers never see a change part way through.
+//!
+//! # Examples
+//!
+//! ```
+//! # extern crate yaml_rust_davvid as yaml_rust;
+//! use std::thread;
+//! use yaml_rust::shared::SharedYaml;
+//! use yaml_rust::{Yaml, YamlLoader};
+//!
+//! let mut config = SharedYaml::new(YamlLoader::load_from_str("workers: 4").unwrap().remove(0));
+//! let reader = config.clone();
+//! let handle = thread::spawn(move || reader["workers"].as_i64());
+//!
+//! config.make_mut().set("workers", Yaml::Integer(8)).unwrap();
+//! assert_eq!(handle.join().unwrap(), Some(4));
+//! assert_eq!(config["workers"].as_i64(), Some(8));
+//! ```
+//!
+//! An `Overlay` is a view of a shared document with changes of its own,
+//! which copies only the nodes on the way to what it changes.
+
+use crate::path::{self, parse_flat_path, Intermediates, PathElement, SetError, YamlPath};
+use crate::yaml::{Hash, Yaml};
+
+use std::borrow::Cow;
+use std::mem;
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// A document that clones share until one of them is changed, see the
+/// `shared` module.
+#[derive(Clone, PartialEq, Debug, Eq)]
+pub struct SharedYaml(Arc<Yaml>);
+
+impl SharedYaml {
+    pub fn new(doc: Yaml) -> SharedYaml {
+        SharedYaml(Arc::new(doc))
+    }
+
+    /// Change the document, copying it first if other clones share it.
+    pub fn make_mut(&mut self) -> &mut Yaml {
+        Arc::make_mut(&mut self.0)
+    }
+
+    /// Whether both share the same document, without comparing it.
+    pub fn ptr_eq(&self, other: &SharedYaml) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+
+    /// The document, copied if other clones share it.
+    pub fn into_inner(self) -> Yaml {
+        Arc::try_unwrap(self.0).unwrap_or_else(|doc| (*doc).clone())
+    }
+
+    /// A view of the document to change without copying it.
+    pub fn overlay(&self) -> Overlay {
+        Overlay::new(self.0.clone())
+    }
+}
+
+impl Deref for SharedYaml {
+    type Target = Yaml;
+
+    fn deref(&self) -> &Yaml {
+        &self.0
+    }
+}
+
+impl AsRef<Yaml> for SharedYaml {
+    fn as_ref(&self) -> &Yaml {
+        &self.0
+    }
+}
+
+impl From<Yaml> for SharedYaml {
+    fn from(doc: Yaml) -> SharedYaml {
+        SharedYaml::new(doc)
+    }
+}
+
+impl From<Arc<Yaml>> for SharedYaml {
+    fn from(doc: Arc<Yaml>) -> SharedYaml {
+        SharedYaml(doc)
+    }
+}
+
+/// Changes layered over a shared document, such as the settings of one
+/// request over the configuration of a server. The document is not copied:
+/// reading the overlay borrows the nodes it doesn't change, and `to_yaml`
+/// copies the document with the changes made.
+///
+/// Paths are written as the `path` module describes, as for `Yaml::set`.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate yaml_rust_davvid as yaml_rust;
+/// use std::sync::Arc;
+/// use yaml_rust::shared::Overlay;
+/// use yaml_rust::{Yaml, YamlLoader};
+///
+/// let base = Arc::new(YamlLoader::load_from_str("db: {host: a, pool: 4}\nlog: info").unwrap().remove(0));
+/// let mut overlay = Overlay::new(base.clone());
+/// overlay.set("db.pool", Yaml::Integer(1)).unwrap();
+/// assert!(overlay.remove("log"));
+///
+/// assert_eq!(overlay.get("db.pool").unwrap().as_i64(), Some(1));
+/// assert_eq!(overlay.get("db.host").unwrap().as_str(), Some("a"));
+/// assert_eq!(overlay.get("log"), None);
+/// assert_eq!(base["db"]["pool"].as_i64(), Some(4));
+/// assert_eq!(overlay.to_yaml(), YamlLoader::load_from_str("db: {host: a, pool: 1}").unwrap()[0]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Overlay {
+    base: Arc<Yaml>,
+    root: Layer,
+}
+
+// What an overlay makes of a node of the base document.
+#[derive(Clone, Debug)]
+enum Layer {
+    // the node as it is
+    Base,
+    // a node that replaces it, or that the base doesn't have
+    Owned(Yaml),
+    Removed,
+    // a hash or array with some children changed, and any added after them
+    Patched(Vec<(PathElement, Layer)>),
+}
+
+impl Layer {
+    fn children(&mut self) -> &mut Vec<(PathElement, Layer)> {
+        if let Layer::Base = *self {
+            *self = Layer::Patched(Vec::new());
+        }
+        match *self {
+            Layer::Patched(ref mut children) => children,
+            _ => unreachable!("only base nodes have changed children"),
+        }
+    }
+}
+
+impl Overlay {
+    pub fn new(base: Arc<Yaml>) -> Overlay {
+        Overlay {
+            base,
+            root: Layer::Base,
+        }
+    }
+
+    /// The document under the changes.
+    pub fn base(&self) -> &Arc<Yaml> {
+        &self.base
+    }
+
+    /// The node at `path`, borrowed unless it contains a change.
+    pub fn get(&self, path: &str) -> Option<Cow<'_, Yaml>> {
+        let steps = parse_flat_path(path, ".");
+        get(&self.root, Some(&self.base), &steps)
+    }
+
+    /// Set the node at `path`, creating the hashes and arrays on the way as
+    /// `Yaml::set` does. A removed key that is set again keeps its place.
+    pub fn set(&mut self, path: &str, value: Yaml) -> Result<(), SetError> {
+        let steps = parse_flat_path(path, ".");
+        set(
+            &mut self.root,
+            Some(&self.base),
+            &steps,
+            value,
+            &mut YamlPath::new(),
+        )
+    }
+
+    /// Remove the node at `path` from its hash or array. Returns whether
+    /// there was one. Removing an array item copies the array, as the items
+    /// after it move.
+    pub fn remove(&mut self, path: &str) -> bool {
+        let steps = parse_flat_path(path, ".");
+        remove(&mut self.root, Some(&self.base), &steps)
+    }
+
+    /// The document with the changes made.
+    pub fn to_yaml(&self) -> Yaml {
+        apply(&self.root, Some(&self.base)).unwrap_or(Yaml::Null)
+    }
+}
+
+impl From<SharedYaml> for Overlay {
+    fn from(doc: SharedYaml) -> Overlay {
+        Overlay::new(doc.0)
+    }
+}
+
+fn find_child<'a>(
+    children: &'a [(PathElement, Layer)],
+    element: &PathElement,
+) -> Option<&'a Layer> {
+    children
+        .iter()
+        .find(|(e, _)| e == element)
+        .map(|(_, layer)| layer)
+}
+
+fn child_mut<'a>(
+    children: &'a mut Vec<(PathElement, Layer)>,
+    element: &PathElement,
+    layer: Layer,
+) -> &'a mut Layer {
+    let i = match children.iter().position(|(e, _)| e == element) {
+        Some(i) => i,
+        None => {
+            children.push((element.clone(), layer));
+            children.len() - 1
+        }
+    };
+    &mut children[i].1
+}
+
+fn get<'a>(
+    layer: &'a Layer,
+    base: Option<&'a Yaml>,
+    steps: &[PathElement],
+) -> Option<Cow<'a, Yaml>> {
+    let children = match *layer {
+        Layer::Base => return path::lookup(base?, steps).map(Cow::Borrowed),
+        Layer::Owned(ref node) => return path::lookup(node, steps).map(Cow::Borrowed),
+        Layer::Removed => return None,
+        Layer::Patched(ref children) => children,
+    };
+    let base = base?;
+    let (step, rest) = match steps.split_first() {
+        Some(split) => split,
+        None => return apply(layer, Some(base)).map(Cow::Owned),
+    };
+    let step = &*path::resolve_step(base, step);
+    match path::child(base, step) {
+        Some((element, child)) => match find_child(children, &element) {
+            Some(layer) => get(layer, Some(child), rest),
+            None => path::lookup(child, rest).map(Cow::Borrowed),
+        },
+        None => find_child(children, step).and_then(|layer| get(layer, None, rest)),
+    }
+}
+
+fn set(
+    layer: &mut Layer,
+    base: Option<&Yaml>,
+    steps: &[PathElement],
+    value: Yaml,
+    path: &mut YamlPath,
+) -> Result<(), SetError> {
+    let (step, rest) = match steps.split_first() {
+        Some(split) => split,
+        None => {
+            *layer = Layer::Owned(value);
+            return Ok(());
+        }
+    };
+    if let Layer::Owned(ref mut node) = *layer {
+        return path::set(node, steps, value, Intermediates::Create, path).map(|_| ());
+    }
+    let base = match base {
+        Some(base) if !matches!(*layer, Layer::Removed) && !base.is_null() => base,
+        // set as if there was no node
+        _ => {
+            *layer = Layer::Owned(Yaml::Null);
+            return set(layer, None, steps, value, path);
+        }
+    };
+    let step = &*path::resolve_step(base, step);
+    if let Some((element, child)) = path::child(base, step) {
+        path.push(element.clone());
+        let layer = child_mut(layer.children(), &element, Layer::Base);
+        return set(layer, Some(child), rest, value, path);
+    }
+    match (step, base) {
+        (PathElement::Key(_), Yaml::Hash(_)) | (PathElement::Index(_), Yaml::Array(_)) => {
+            path.push(step.clone());
+            let layer = child_mut(layer.children(), step, Layer::Owned(Yaml::Null));
+            set(layer, None, rest, value, path)
+        }
+        _ => Err(SetError::Conflict {
+            path: path.clone(),
+            expected: match *step {
+                PathElement::Key(_) => "hash",
+                PathElement::Index(_) => "array",
+            },
+            found: base.type_str(),
+        }),
+    }
+}
+
+fn remove(layer: &mut Layer, base: Option<&Yaml>, steps: &[PathElement]) -> bool {
+    let (step, rest) = match steps.split_first() {
+        Some(split) => split,
+        None => return false,
+    };
+    if let Layer::Owned(ref mut node) = *layer {
+        return remove_node(node, steps);
+    }
+    let base = match base {
+        Some(base) if !matches!(*layer, Layer::Removed) => base,
+        _ => return false,
+    };
+    let step = &*path::resolve_step(base, step);
+    if rest.is_empty() && base.as_vec().is_some() {
+        let mut node = apply(layer, Some(base)).unwrap_or(Yaml::Null);
+        let removed = remove_node(&mut node, steps);
+        *layer = Layer::Owned(node);
+        return removed;
+    }
+    if let Some((element, child)) = path::child(base, step) {
+        let layer = child_mut(layer.children(), &element, Layer::Base);
+        return if rest.is_empty() {
+            !matches!(mem::replace(layer, Layer::Removed), Layer::Removed)
+        } else {
+            remove(layer, Some(child), rest)
+        };
+    }
+    let children = match *layer {
+        Layer::Patched(ref mut children) => children,
+        _ => return false,
+    };
+    match children.iter().position(|(e, _)| e == step) {
+        Some(i) if rest.is_empty() => {
+            children.remove(i);
+            true
+        }
+        Some(i) => remove(&mut children[i].1, None, rest),
+        None => false,
+    }
+}
+
+fn remove_node(node: &mut Yaml, steps: &[PathElement]) -> bool {
+    let (step, rest) = match steps.split_first() {
+        Some(split) => split,
+        None => return false,
+    };
+    let element = match path::child(node, step) {
+        Some((element, _)) => element,
+        None => return false,
+    };
+    match (element, node) {
+        (PathElement::Key(key), Yaml::Hash(h)) if rest.is_empty() => h.remove(&key).is_some(),
+        (PathElement::Key(key), Yaml::Hash(h)) => h
+            .get_mut(&key)
+            .is_some_and(|child| remove_node(child, rest)),
+        (PathElement::Index(i), Yaml::Array(v)) if rest.is_empty() => {
+            v.remove(i);
+            true
+        }
+        (PathElement::Index(i), Yaml::Array(v)) => remove_node(&mut v[i], rest),
+        _ => false,
+    }
+}
+
+// The node `layer` makes of `base`, or `None` if it removes it.
+fn apply(layer: &Layer, base: Option<&Yaml>) -> Option<Yaml> {
+    let children = match *layer {
+        Layer::Base => return base.cloned(),
+        Layer::Owned(ref node) => return Some(node.clone()),
+        Layer::Removed => return None,
+        Layer::Patched(ref children) => children,
+    };
+    Some(match *base? {
+        Yaml::Hash(ref h) => {
+            let mut out = Hash::with_capacity(h.len());
+            for (key, child) in h {
+                let layer = children
+                    .iter()
+                    .find(|(e, _)| matches!(*e, PathElement::Key(ref k) if k == key));
+                let child = match layer {
+                    Some((_, layer)) => apply(layer, Some(child)),
+                    None => Some(child.clone()),
+                };
+                if let Some(child) = child {
+                    out.insert(key.clone(), child);
+                }
+            }
+            for (element, layer) in children {
+                if let PathElement::Key(ref key) = *element {
+                    if !h.contains_key(key) {
+                        if let Some(child) = apply(layer, None) {
+                            out.insert(key.clone(), child);
+                        }
+                    }
+                }
+            }
+            Yaml::Hash(out)
+        }
+        Yaml::Array(ref v) => {
+            let mut out: Vec<Yaml> = v.clone();
+            for (element, layer) in children {
+                if let PathElement::Index(i) = *element {
+                    if i >= out.len() {
+                        out.resize(i + 1, Yaml::Null);
+                    }
+                    out[i] = apply(layer, v.get(i)).unwrap_or(Yaml::Null);
+                }
+            }
+            Yaml::Array(out)
+        }
+        ref node => node.clone(),
+    })
+}
+
+// Loaded values must stay `Send + Sync`, which fails to compile here if a
+// change makes one of them lose it.
+const _: () = {
+    fn send_sync<T: Send + Sync>() {}
+
+    #[allow(dead_code)]
+    fn check() {
+        use crate::config::Config;
+        use crate::dedup::Deduplicated;
+        use crate::graph::YamlGraph;
+        use crate::incremental::IncrementalLoader;
+        use crate::index::YamlIndex;
+        use crate::marked::MarkedNode;
+        use crate::parser::{Event, EventLog, ParserStats};
+        use crate::path::YamlPath;
+        use crate::scanner::{Marker, ScanError, Span};
+        use crate::stats::Stats;
+        use crate::yaml::{Anchor, LoadError, Tag, Warning, YamlConversionError};
+
+        send_sync::<Yaml>();
+        send_sync::<Tag>();
+        send_sync::<Event>();
+        send_sync::<Marker>();
+        send_sync::<Span>();
+        send_sync::<ScanError>();
+        send_sync::<LoadError>();
+        send_sync::<Warning>();
+        send_sync::<Anchor>();
+        send_sync::<YamlConversionError>();
+        send_sync::<YamlPath>();
+        send_sync::<YamlIndex<'static>>();
+        send_sync::<MarkedNode>();
+        send_sync::<YamlGraph>();
+        send_sync::<Deduplicated>();
+        send_sync::<Config>();
+        send_sync::<Stats>();
+        send_sync::<ParserStats>();
+        send_sync::<EventLog>();
+        send_sync::<IncrementalLoader>();
+        send_sync::<SharedYaml>();
+        send_sync::<Overlay>();
+    }
+};
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::YamlLoader;
+
+    use std::thread;
+
+    #[test]
+    fn test_shared_yaml() {
+        let doc = YamlLoader::load_from_str("a: [1, 2]").unwrap().remove(0);
+        let mut shared = SharedYaml::from(doc.clone());
+        let readers: Vec<SharedYaml> = (0..4).map(|_| shared.clone()).collect();
+        assert!(readers.iter().all(|r| r.ptr_eq(&shared)));
+
+        shared.make_mut().set("a[0]", Yaml::Integer(3)).unwrap();
+        assert!(!readers[0].ptr_eq(&shared));
+        let handles: Vec<_> = readers
+            .into_iter()
+            .map(|r| thread::spawn(move || r.into_inner()))
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), doc);
+        }
+
+        // the only clone changes in place
+        let before: *const Yaml = &*shared;
+        shared.make_mut().set("a[1]", Yaml::Integer(4)).unwrap();
+        assert!(std::ptr::eq(before, &*shared));
+        assert_eq!(shared.into_inner()["a"][1], Yaml::Integer(4));
+    }
+
+    #[test]
+    fn test_overlay() {
+        let s = "
+a: {b: 1, c: [x, y, z]}
+ports: {8080: http}
+d: 2
+";
+        let base = Arc::new(YamlLoader::load_from_str(s).unwrap().remove(0));
+        let mut overlay = SharedYaml::from(base.clone()).overlay();
+        let mut expected = (*base).clone();
+        let int = Yaml::Integer;
+        let changes: Vec<(&str, Option<Yaml>)> = vec![
+            ("a.b", Some(int(3))),
+            ("ports.8080", Some(Yaml::from_str("https"))),
+            ("a.c[1]", Some(int(5))),
+            ("a.c[5]", Some(int(6))),
+            ("e.f[1].g", Some(int(7))),
+            ("e.f[1].h", Some(int(8))),
+            ("a.c.2", Some(int(9))),
+            ("a.c.7", Some(int(10))),
+            ("e.f.1.i", Some(int(11))),
+            ("a.c.x", Some(int(12))),
+            ("a.c[0]", None),
+            ("a.c.6", None),
+            ("e.f.1.h", None),
+            ("e.f[1].g", None),
+            ("d", None),
+            ("d", None),
+            ("a.b", None),
+            ("z", None),
+            ("e", None),
+        ];
+        for (path, value) in changes {
+            match value {
+                Some(value) => {
+                    assert_eq!(
+                        overlay.set(path, value.clone()).is_ok(),
+                        expected.set(path, value).is_ok()
+                    );
+                }
+                None => {
+                    let steps = parse_flat_path(path, ".");
+                    assert_eq!(overlay.remove(path), remove_node(&mut expected, &steps));
+                }
+            }
+            assert_eq!(overlay.to_yaml(), expected, "after {}", path);
+            for path in [
+                "a",
+                "a.c",
+                "a.c[2]",
+                "a.c.2",
+                "ports.8080",
+                "d",
+                "d.x",
+                "e.f.1",
+            ] {
+                let node = path::lookup(&expected, &parse_flat_path(path, "."));
+                assert_eq!(overlay.get(path).as_deref(), node, "{}", path);
+            }
+        }
+        assert_eq!(*base, YamlLoader::load_from_str(s).unwrap()[0]);
+        assert!(matches!(overlay.get("ports"), Some(Cow::Owned(_))));
+        overlay.set("d.x", int(9)).unwrap();
+        assert_eq!(
+            overlay.to_yaml().as_hash().unwrap().keys().nth(2),
+            Some(&Yaml::from_str("d"))
+        );
+
+        let mut overlay = Overlay::new(base.clone());
+        overlay.set("a.b", int(2)).unwrap();
+        assert!(matches!(overlay.get("a.c[0]"), Some(Cow::Borrowed(_))));
+        assert!(matches!(overlay.get("a"), Some(Cow::Owned(_))));
+        assert_eq!(
+            overlay.set("d.x", int(1)).unwrap_err().to_string(),
+            "expected hash at $.d, found integer"
+        );
+    }
+}
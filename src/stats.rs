@@ -0,0 +1,267 @@
+//! Size and shape statistics of documents, computed from a loaded tree with
+//! `Yaml::stats` or straight from source with `measure`.
+//!
+//! `measure` expands aliases the way loading does, so it can reject
+//! documents that would grow too large before any of them is built:
+//!
+//! ```
+//! # extern crate yaml_rust_davvid as yaml_rust;
+//! use yaml_rust::stats::measure;
+//!
+//! let s = "
+//! a: &a [x, x, x, x]
+//! b: &b [*a, *a, *a, *a]
+//! c: [*b, *b, *b, *b]
+//! ";
+//! let stats = &measure(s).unwrap()[0];
+//! assert_eq!(stats.counts.strings, 4 + 16 + 64 + 3);
+//! assert_eq!(stats.max_depth, 4);
+//! assert!(stats.nodes > 100);
+//! ```
+
+use crate::parser::{Event, Parser};
+use crate::scanner::{ScanError, TokenType};
+use crate::yaml::{application_tag, resolve_scalar, Hash, Yaml};
+
+use std::collections::HashMap;
+
+/// The number of nodes of each kind.
+#[derive(Clone, Copy, PartialEq, Debug, Default, Eq)]
+pub struct VariantCounts {
+    pub reals: usize,
+    pub integers: usize,
+    pub strings: usize,
+    pub booleans: usize,
+    pub arrays: usize,
+    pub hashes: usize,
+    pub aliases: usize,
+    pub nulls: usize,
+    pub bad_values: usize,
+    /// Nodes with an application tag, which are also counted by their
+    /// content.
+    pub tagged: usize,
+}
+
+impl VariantCounts {
+    fn add(&mut self, other: &VariantCounts) {
+        self.reals = self.reals.saturating_add(other.reals);
+        self.integers = self.integers.saturating_add(other.integers);
+        self.strings = self.strings.saturating_add(other.strings);
+        self.booleans = self.booleans.saturating_add(other.booleans);
+        self.arrays = self.arrays.saturating_add(other.arrays);
+        self.hashes = self.hashes.saturating_add(other.hashes);
+        self.aliases = self.aliases.saturating_add(other.aliases);
+        self.nulls = self.nulls.saturating_add(other.nulls);
+        self.bad_values = self.bad_values.saturating_add(other.bad_values);
+        self.tagged = self.tagged.saturating_add(other.tagged);
+    }
+}
+
+/// Statistics of a node and everything below it, hash keys included.
+/// Counts saturate rather than overflow.
+#[derive(Clone, Copy, PartialEq, Debug, Default, Eq)]
+pub struct Stats {
+    /// The number of nodes. A tagged node counts once.
+    pub nodes: usize,
+    /// The number of nested collections, so a scalar has depth 0 and
+    /// `[[1]]` has depth 2.
+    pub max_depth: usize,
+    /// The total length in bytes of string and real scalars.
+    pub scalar_bytes: usize,
+    pub counts: VariantCounts,
+}
+
+impl Stats {
+    fn node(node: &Yaml) -> Stats {
+        let mut stats = Stats {
+            nodes: 1,
+            ..Stats::default()
+        };
+        let counts = &mut stats.counts;
+        match *node {
+            Yaml::Real(ref v) => {
+                counts.reals = 1;
+                stats.scalar_bytes = v.len();
+            }
+            Yaml::String(_) | Yaml::Interned(_) => {
+                counts.strings = 1;
+                stats.scalar_bytes = node.string_text().unwrap().len();
+            }
+            Yaml::Integer(_) => counts.integers = 1,
+            Yaml::Boolean(_) => counts.booleans = 1,
+            Yaml::Array(_) => {
+                counts.arrays = 1;
+                stats.max_depth = 1;
+            }
+            Yaml::Hash(_) => {
+                counts.hashes = 1;
+                stats.max_depth = 1;
+            }
+            Yaml::Tagged(_, ref v) => {
+                stats = Stats::node(v);
+                stats.counts.tagged = 1;
+            }
+            Yaml::Alias(_) => counts.aliases = 1,
+            Yaml::Null => counts.nulls = 1,
+            Yaml::BadValue => counts.bad_values = 1,
+        }
+        stats
+    }
+
+    fn add_child(&mut self, child: &Stats) {
+        self.nodes = self.nodes.saturating_add(child.nodes);
+        self.max_depth = self.max_depth.max(child.max_depth + 1);
+        self.scalar_bytes = self.scalar_bytes.saturating_add(child.scalar_bytes);
+        self.counts.add(&child.counts);
+    }
+}
+
+impl Yaml {
+    /// Compute the statistics of this node and everything below it.
+    pub fn stats(&self) -> Stats {
+        let mut stats = Stats::node(self);
+        let node = match *self {
+            Yaml::Tagged(_, ref v) => v,
+            _ => self,
+        };
+        match *node {
+            Yaml::Array(ref v) => {
+                for child in v {
+                    stats.add_child(&child.stats());
+                }
+            }
+            Yaml::Hash(ref h) => {
+                for (key, value) in h {
+                    stats.add_child(&key.stats());
+                    stats.add_child(&value.stats());
+                }
+            }
+            _ => {}
+        }
+        stats
+    }
+}
+
+/// Compute the statistics of each document in `source` without loading it.
+/// The result for a document equals `stats()` of the document as
+/// `YamlLoader` loads it, with aliases expanded into copies of their nodes.
+pub fn measure(source: &str) -> Result<Vec<Stats>, ScanError> {
+    let mut parser = Parser::new(source.chars());
+    let mut docs = Vec::new();
+    // (stats, anchor_id) of open collections
+    let mut stack: Vec<(Stats, usize)> = Vec::new();
+    let mut anchors: HashMap<usize, Stats> = HashMap::new();
+    let mut root = None;
+    loop {
+        let (ev, mark) = parser.next_event()?;
+        let (node, aid) = match ev {
+            Event::StreamEnd => return Ok(docs),
+            Event::DocumentEnd => {
+                docs.push(root.take().unwrap_or_else(|| Stats::node(&Yaml::BadValue)));
+                continue;
+            }
+            Event::SequenceStart(aid, tag) => {
+                stack.push((collection(Yaml::Array(Vec::new()), &tag), aid));
+                continue;
+            }
+            Event::MappingStart(aid, tag) => {
+                stack.push((collection(Yaml::Hash(Hash::new()), &tag), aid));
+                continue;
+            }
+            Event::SequenceEnd | Event::MappingEnd => stack.pop().unwrap(),
+            Event::Scalar(v, style, aid, tag) => {
+                (Stats::node(&resolve_scalar(v, style, &tag)), aid)
+            }
+            Event::Alias(id) => {
+                if stack.iter().any(|&(_, aid)| aid == id) {
+                    return Err(ScanError::new(mark, "found recursive alias"));
+                }
+                let stats = anchors
+                    .get(&id)
+                    .copied()
+                    .unwrap_or_else(|| Stats::node(&Yaml::BadValue));
+                (stats, 0)
+            }
+            _ => continue,
+        };
+        if aid > 0 {
+            anchors.insert(aid, node);
+        }
+        match stack.last_mut() {
+            Some((parent, _)) => parent.add_child(&node),
+            None => root = Some(node),
+        }
+    }
+}
+
+fn collection(node: Yaml, tag: &Option<TokenType>) -> Stats {
+    let mut stats = Stats::node(&node);
+    if application_tag(tag).is_some() {
+        stats.counts.tagged = 1;
+    }
+    stats
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::YamlLoader;
+
+    #[test]
+    fn test_stats() {
+        let s = "
+name: web
+ports: [80, 443]
+ratio: 0.5
+env: !vault {key: [~, true]}
+bad: !!int x
+";
+        let doc = &YamlLoader::load_from_str(s).unwrap()[0];
+        let stats = doc.stats();
+        assert_eq!(stats.nodes, 17);
+        assert_eq!(stats.max_depth, 3);
+        assert_eq!(
+            stats.scalar_bytes,
+            "name".len()
+                + "web".len()
+                + "ports".len()
+                + "ratio".len()
+                + "0.5".len()
+                + "env".len()
+                + "key".len()
+                + "bad".len()
+        );
+        assert_eq!(
+            stats.counts,
+            VariantCounts {
+                reals: 1,
+                integers: 2,
+                strings: 7,
+                booleans: 1,
+                arrays: 2,
+                hashes: 2,
+                nulls: 1,
+                bad_values: 1,
+                tagged: 1,
+                ..VariantCounts::default()
+            }
+        );
+        assert_eq!(Yaml::Integer(1).stats().max_depth, 0);
+    }
+
+    #[test]
+    fn test_measure_matches_stats() {
+        let s = "
+a: &x {b: [1, 'two', 3.0]}
+c: [*x, *x]
+d: !t [!u e]
+---
+---
+plain
+";
+        let docs = YamlLoader::load_from_str(s).unwrap();
+        let expected: Vec<_> = docs.iter().map(Yaml::stats).collect();
+        assert_eq!(measure(s).unwrap(), expected);
+        assert!(measure("&a [*a]").is_err());
+    }
+}
@@ -0,0 +1,285 @@
+//! Substitution of `{{ expr }}` placeholders in the strings of a document,
+//! with values given by a `Resolver`.
+//!
+//! A string that is a single placeholder is replaced by the value itself,
+//! so `"{{ port }}"` can become an integer and `"{{ hosts }}"` an array.
+//! Placeholders within other text are replaced by the text of a scalar
+//! value, see `Yaml::as_str_lossy`. The expression is the text between the
+//! braces without surrounding whitespace. Only values are rendered, not
+//! hash keys, and rendered values are not rendered again.
+//!
+//! # Examples
+//!
+//! ```
+//! # extern crate yaml_rust_davvid as yaml_rust;
+//! use yaml_rust::template::{self, Missing, Resolver};
+//! use yaml_rust::{Yaml, YamlLoader};
+//!
+//! let values = YamlLoader::load_from_str("app: {name: web, port: 8080}").unwrap().remove(0);
+//! let doc = YamlLoader::load_from_str("
+//! image: 'registry/{{ app.name }}:{{ tag }}'
+//! port: '{{ app.port }}'
+//! ").unwrap().remove(0);
+//!
+//! assert!(template::render(&doc, &values, Missing::Error).is_err());
+//! let out = template::render(&doc, &values, Missing::Keep).unwrap();
+//! assert_eq!(out["image"].as_str(), Some("registry/web:{{ tag }}"));
+//! assert_eq!(out["port"], Yaml::Integer(8080));
+//!
+//! let tag = |expr: &str| match expr {
+//!     "tag" => Some(Yaml::String("v2".to_owned())),
+//!     _ => values.resolve(expr),
+//! };
+//! let out = template::render(&doc, &tag, Missing::Error).unwrap();
+//! assert_eq!(out["image"].as_str(), Some("registry/web:v2"));
+//! ```
+
+use crate::path::{PathElement, YamlPath};
+use crate::yaml::{lookup_path, Hash, Yaml};
+
+use std::env;
+use std::error::Error;
+use std::fmt;
+
+/// Gives the values of placeholder expressions.
+pub trait Resolver {
+    /// The value of `expr`, or `None` if it has none.
+    fn resolve(&self, expr: &str) -> Option<Yaml>;
+}
+
+/// A document resolves dotted paths to its nodes, as in
+/// `config::Config::lookup`.
+impl Resolver for Yaml {
+    fn resolve(&self, expr: &str) -> Option<Yaml> {
+        lookup_path(self, expr).cloned()
+    }
+}
+
+impl<F: Fn(&str) -> Option<Yaml>> Resolver for F {
+    fn resolve(&self, expr: &str) -> Option<Yaml> {
+        self(expr)
+    }
+}
+
+/// Resolves the names of environment variables to their values, as
+/// strings. Variables that are not valid unicode have no value.
+#[derive(Clone, Copy, PartialEq, Debug, Default, Eq)]
+pub struct Env;
+
+impl Resolver for Env {
+    fn resolve(&self, expr: &str) -> Option<Yaml> {
+        env::var(expr).ok().map(Yaml::String)
+    }
+}
+
+/// What `render` does with a placeholder that its resolver has no value
+/// for.
+#[derive(Clone, Copy, PartialEq, Debug, Default, Eq)]
+pub enum Missing {
+    /// Fail with `TemplateError::Missing`.
+    #[default]
+    Error,
+    /// Leave the placeholder as it is written.
+    Keep,
+}
+
+/// Why `render` failed.
+#[derive(Clone, PartialEq, Debug, Eq)]
+pub enum TemplateError {
+    /// A placeholder with no value, with `Missing::Error`.
+    Missing { path: YamlPath, expr: String },
+    /// A `{{` with no `}}` after it.
+    Unclosed { path: YamlPath },
+    /// A placeholder within other text whose value is not a scalar, but the
+    /// kind of value given.
+    NotScalar {
+        path: YamlPath,
+        expr: String,
+        found: &'static str,
+    },
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TemplateError::Missing { ref path, ref expr } => {
+                write!(f, "no value for {{{{ {} }}}} at {}", expr, path)
+            }
+            TemplateError::Unclosed { ref path } => write!(f, "unclosed {{{{ at {}", path),
+            TemplateError::NotScalar {
+                ref path,
+                ref expr,
+                found,
+            } => write!(
+                f,
+                "{{{{ {} }}}} is {} {}, which can't be part of a string at {}",
+                expr,
+                if found.starts_with('a') { "an" } else { "a" },
+                found,
+                path
+            ),
+        }
+    }
+}
+
+impl Error for TemplateError {}
+
+/// Render the placeholders in the strings of `doc` with values from
+/// `resolver`.
+pub fn render<R: Resolver + ?Sized>(
+    doc: &Yaml,
+    resolver: &R,
+    missing: Missing,
+) -> Result<Yaml, TemplateError> {
+    let mut path = YamlPath::new();
+    render_node(doc, &mut path, resolver, missing)
+}
+
+fn render_node<R: Resolver + ?Sized>(
+    node: &Yaml,
+    path: &mut YamlPath,
+    resolver: &R,
+    missing: Missing,
+) -> Result<Yaml, TemplateError> {
+    Ok(match *node {
+        Yaml::String(ref s) => render_str(s, path, resolver, missing)?,
+        Yaml::Array(ref v) => {
+            let mut out = Vec::with_capacity(v.len());
+            for (i, child) in v.iter().enumerate() {
+                path.push(PathElement::Index(i));
+                out.push(render_node(child, path, resolver, missing)?);
+                path.pop();
+            }
+            Yaml::Array(out)
+        }
+        Yaml::Hash(ref h) => {
+            let mut out = Hash::with_capacity(h.len());
+            for (key, child) in h {
+                path.push(PathElement::Key(key.clone()));
+                out.insert(key.clone(), render_node(child, path, resolver, missing)?);
+                path.pop();
+            }
+            Yaml::Hash(out)
+        }
+        Yaml::Tagged(ref tag, ref v) => Yaml::Tagged(
+            tag.clone(),
+            Box::new(render_node(v, path, resolver, missing)?),
+        ),
+        _ => node.clone(),
+    })
+}
+
+fn render_str<R: Resolver + ?Sized>(
+    s: &str,
+    path: &YamlPath,
+    resolver: &R,
+    missing: Missing,
+) -> Result<Yaml, TemplateError> {
+    let missing_value = |expr: &str| TemplateError::Missing {
+        path: path.clone(),
+        expr: expr.to_owned(),
+    };
+    // a string that is a single placeholder keeps the type of its value
+    if let Some(inner) = s.strip_prefix("{{").and_then(|s| s.strip_suffix("}}")) {
+        if !inner.contains("{{") && !inner.contains("}}") {
+            let expr = inner.trim();
+            return match (resolver.resolve(expr), missing) {
+                (Some(value), _) => Ok(value),
+                (None, Missing::Keep) => Ok(Yaml::String(s.to_owned())),
+                (None, Missing::Error) => Err(missing_value(expr)),
+            };
+        }
+    }
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after
+            .find("}}")
+            .ok_or_else(|| TemplateError::Unclosed { path: path.clone() })?;
+        let expr = after[..end].trim();
+        match (resolver.resolve(expr), missing) {
+            (Some(value), _) => match value.as_str_lossy() {
+                Some(text) => out.push_str(&text),
+                None => {
+                    return Err(TemplateError::NotScalar {
+                        path: path.clone(),
+                        expr: expr.to_owned(),
+                        found: value.type_str(),
+                    })
+                }
+            },
+            (None, Missing::Keep) => out.push_str(&rest[start..start + end + 4]),
+            (None, Missing::Error) => return Err(missing_value(expr)),
+        }
+        rest = &after[end + 2..];
+    }
+    out.push_str(rest);
+    Ok(Yaml::String(out))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::YamlLoader;
+
+    #[test]
+    fn test_render() {
+        let values = YamlLoader::load_from_str("{a: 1, b: [x, y], c: ~, d: {e: f}}")
+            .unwrap()
+            .remove(0);
+        let render = |s: &str| {
+            let doc = Yaml::String(s.to_owned());
+            render(&doc, &values, Missing::Error)
+        };
+        assert_eq!(render("{{a}}"), Ok(Yaml::Integer(1)));
+        assert_eq!(render("{{ b }}").unwrap()[1].as_str(), Some("y"));
+        assert_eq!(
+            render("{{ b.0 }}-{{c}}!"),
+            Ok(Yaml::String("x-null!".to_owned()))
+        );
+        assert_eq!(
+            render("no {placeholders}"),
+            Ok(Yaml::String("no {placeholders}".to_owned()))
+        );
+        assert_eq!(
+            render("{{ a }} and {{ d }}").unwrap_err().to_string(),
+            "{{ d }} is a hash, which can't be part of a string at $"
+        );
+        assert_eq!(render("{{ a").unwrap_err().to_string(), "unclosed {{ at $");
+        assert_eq!(render("{{ a }}}}"), Ok(Yaml::String("1}}".to_owned())));
+        assert_eq!(
+            render("{{ z }}").unwrap_err().to_string(),
+            "no value for {{ z }} at $"
+        );
+    }
+
+    #[test]
+    fn test_render_document() {
+        let s = "
+list: ['{{ x }}', 'a {{x}} b {{ y }}']
+!t tagged: !t '{{ x }}'
+'{{ x }}': key
+";
+        let doc = YamlLoader::load_from_str(s).unwrap().remove(0);
+        let resolver = |expr: &str| (expr == "x").then(|| Yaml::Real("1.5".to_owned()));
+        let err = render(&doc, &resolver, Missing::Error).unwrap_err();
+        assert_eq!(err.to_string(), "no value for {{ y }} at $.list[1]");
+
+        let out = render(&doc, &resolver, Missing::Keep).unwrap();
+        assert_eq!(out["list"][0], Yaml::Real("1.5".to_owned()));
+        assert_eq!(out["list"][1].as_str(), Some("a 1.5 b {{ y }}"));
+        let tagged = out.as_hash().unwrap().values().nth(1).unwrap();
+        assert!(matches!(*tagged, Yaml::Tagged(_, ref v) if v.as_f64() == Some(1.5)));
+        assert_eq!(out["{{ x }}"].as_str(), Some("key"));
+    }
+
+    #[test]
+    fn test_env() {
+        env::set_var("YAML_RUST_TEMPLATE_TEST", "8080");
+        let doc = Yaml::String("port {{ YAML_RUST_TEMPLATE_TEST }}".to_owned());
+        let out = render(&doc, &Env, Missing::Error).unwrap();
+        assert_eq!(out.as_str(), Some("port 8080"));
+    }
+}
@@ -0,0 +1,102 @@
+//! Loading YAML from a `tokio::io::AsyncRead`, enabled by the `tokio`
+//! feature.
+//!
+//! `YamlDecoder::read_async` reads the whole source without blocking, then
+//! detects its encoding and parses it on tokio's blocking thread pool, so a
+//! large or slow document does not hold up other tasks on the executor.
+//!
+//! # Examples
+//!
+//! ```
+//! # extern crate yaml_rust_davvid as yaml_rust;
+//! # extern crate tokio;
+//! use yaml_rust::yaml::YamlDecoder;
+//!
+//! # let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+//! # runtime.block_on(async {
+//! let body: &[u8] = b"name: web\nport: 8080\n";
+//! let docs = YamlDecoder::read_async(body).decode().await.unwrap();
+//! assert_eq!(docs[0]["port"].as_i64(), Some(8080));
+//! # });
+//! ```
+
+use crate::yaml::{LoadError, Yaml, YamlDecoder, YamlEncoding};
+
+use ::tokio::io::{AsyncRead, AsyncReadExt};
+use ::tokio::task;
+use std::io;
+use std::panic;
+
+impl YamlDecoder<io::Cursor<Vec<u8>>> {
+    /// Create a decoder for an asynchronous source, see `AsyncYamlDecoder`.
+    pub fn read_async<R: AsyncRead + Unpin>(source: R) -> AsyncYamlDecoder<R> {
+        AsyncYamlDecoder {
+            source,
+            trap: encoding::DecoderTrap::Strict,
+            encoding: None,
+        }
+    }
+}
+
+/// The asynchronous counterpart of `YamlDecoder`, created with
+/// `YamlDecoder::read_async`.
+pub struct AsyncYamlDecoder<R> {
+    source: R,
+    trap: encoding::types::DecoderTrap,
+    encoding: Option<YamlEncoding>,
+}
+
+impl<R: AsyncRead + Unpin> AsyncYamlDecoder<R> {
+    pub fn encoding_trap(&mut self, trap: encoding::types::DecoderTrap) -> &mut Self {
+        self.trap = trap;
+        self
+    }
+
+    /// The encoding detected by the last call to `decode`.
+    pub fn encoding(&self) -> Option<YamlEncoding> {
+        self.encoding
+    }
+
+    /// Read the source to its end, then decode and load it on the blocking
+    /// thread pool. Must be called from within a tokio runtime.
+    ///
+    /// Unlike `YamlDecoder::decode`, the source is held in memory as a
+    /// whole, since the parser cannot wait for more input.
+    pub async fn decode(&mut self) -> Result<Vec<Yaml>, LoadError> {
+        let mut bytes = Vec::new();
+        self.source.read_to_end(&mut bytes).await?;
+        let trap = self.trap;
+        let handle = task::spawn_blocking(move || {
+            let mut decoder = YamlDecoder::read(io::Cursor::new(bytes));
+            decoder.encoding_trap(trap);
+            let result = decoder.decode();
+            (result, decoder.encoding())
+        });
+        match handle.await {
+            Ok((result, encoding)) => {
+                self.encoding = encoding;
+                result
+            }
+            Err(err) if err.is_panic() => panic::resume_unwind(err.into_panic()),
+            Err(err) => Err(LoadError::IO(io::Error::other(err))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[::tokio::test]
+    async fn test_read_async() {
+        let source: &[u8] = b"\xff\xfea\x00:\x00 \x00[\x001\x00]\x00";
+        let mut decoder = YamlDecoder::read_async(source);
+        let docs = decoder.decode().await.unwrap();
+        assert_eq!(docs[0]["a"][0].as_i64(), Some(1));
+        assert_eq!(decoder.encoding(), Some(YamlEncoding::Utf16Le));
+
+        let source: &[u8] = b"a: [1";
+        let err = YamlDecoder::read_async(source).decode().await.unwrap_err();
+        assert!(matches!(err, LoadError::Scan(_)));
+    }
+}
@@ -0,0 +1,190 @@
+//! Conversion between `Yaml` and `toml::Value`, enabled by the `toml`
+//! feature.
+//!
+//! `Yaml` converts from any TOML value with `From`, and to one with
+//! `Yaml::to_toml`, which fails for YAML that has no TOML equivalent:
+//!
+//! - TOML has no null. A hash entry whose value is null is left out, and a
+//!   null anywhere else is an error.
+//! - Arrays may mix types, as TOML 1.0 allows.
+//! - A TOML datetime becomes a string in RFC 3339 form, and a string that
+//!   is a valid TOML datetime becomes a datetime again.
+//! - Hash keys that are numbers or booleans become strings; other keys are
+//!   an error.
+//! - Tags are dropped, and aliases and bad values are an error.
+//!
+//! # Examples
+//!
+//! ```
+//! # extern crate yaml_rust_davvid as yaml_rust;
+//! # extern crate toml;
+//! use yaml_rust::{Yaml, YamlLoader};
+//!
+//! let doc = YamlLoader::load_from_str("name: web\nport: 8080\nbackup: ~").unwrap().remove(0);
+//! let value = doc.to_toml().unwrap();
+//! assert_eq!(toml::to_string(&value).unwrap(), "name = \"web\"\nport = 8080\n");
+//!
+//! let back = Yaml::from(value);
+//! assert_eq!(back["port"], Yaml::Integer(8080));
+//! assert!(back["backup"].is_badvalue());
+//! ```
+
+use crate::path::{PathElement, YamlPath};
+use crate::yaml::{format_real, Hash, Yaml};
+
+use std::error::Error;
+use std::fmt;
+
+/// A `Yaml` node that has no TOML equivalent.
+#[derive(Clone, PartialEq, Debug, Eq)]
+pub enum TomlError {
+    /// A null that is not the value of a hash entry.
+    Null(YamlPath),
+    /// A hash key that is not a string, number or boolean.
+    Key(YamlPath),
+    /// An alias or bad value.
+    Unsupported(YamlPath),
+}
+
+impl TomlError {
+    /// The path to the node.
+    pub fn path(&self) -> &YamlPath {
+        match *self {
+            TomlError::Null(ref path)
+            | TomlError::Key(ref path)
+            | TomlError::Unsupported(ref path) => path,
+        }
+    }
+}
+
+impl fmt::Display for TomlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let what = match *self {
+            TomlError::Null(_) => "null",
+            TomlError::Key(_) => "hash key",
+            TomlError::Unsupported(_) => "value",
+        };
+        write!(f, "{} at {} can't be converted to TOML", what, self.path())
+    }
+}
+
+impl Error for TomlError {}
+
+impl From<::toml::Value> for Yaml {
+    fn from(value: ::toml::Value) -> Yaml {
+        match value {
+            ::toml::Value::String(s) => Yaml::String(s),
+            ::toml::Value::Integer(i) => Yaml::Integer(i),
+            ::toml::Value::Float(f) => Yaml::Real(format_real(f)),
+            ::toml::Value::Boolean(b) => Yaml::Boolean(b),
+            ::toml::Value::Datetime(dt) => Yaml::String(dt.to_string()),
+            ::toml::Value::Array(v) => Yaml::Array(v.into_iter().map(Yaml::from).collect()),
+            ::toml::Value::Table(t) => {
+                let mut hash = Hash::new();
+                for (k, v) in t {
+                    hash.insert(Yaml::String(k), Yaml::from(v));
+                }
+                Yaml::Hash(hash)
+            }
+        }
+    }
+}
+
+impl Yaml {
+    /// Convert to a TOML value, as described in the module documentation.
+    pub fn to_toml(&self) -> Result<::toml::Value, TomlError> {
+        to_toml(self, &mut YamlPath::new())
+    }
+}
+
+fn to_toml(node: &Yaml, path: &mut YamlPath) -> Result<::toml::Value, TomlError> {
+    Ok(match *node {
+        Yaml::String(_) | Yaml::Interned(_) => {
+            let s = node.string_text().unwrap();
+            match s.parse::<::toml::value::Datetime>() {
+                Ok(dt) => ::toml::Value::Datetime(dt),
+                Err(_) => ::toml::Value::String(s.to_owned()),
+            }
+        }
+        Yaml::Integer(i) => ::toml::Value::Integer(i),
+        Yaml::Real(_) => match node.as_f64() {
+            Some(f) => ::toml::Value::Float(f),
+            None => return Err(TomlError::Unsupported(path.clone())),
+        },
+        Yaml::Boolean(b) => ::toml::Value::Boolean(b),
+        Yaml::Array(ref v) => {
+            let mut array = Vec::with_capacity(v.len());
+            for (i, x) in v.iter().enumerate() {
+                path.push(PathElement::Index(i));
+                array.push(to_toml(x, path)?);
+                path.pop();
+            }
+            ::toml::Value::Array(array)
+        }
+        Yaml::Hash(ref h) => {
+            let mut table = ::toml::value::Table::new();
+            for (k, v) in h {
+                path.push(PathElement::Key(k.clone()));
+                let key = match *k {
+                    Yaml::String(ref s) | Yaml::Real(ref s) => s.clone(),
+                    Yaml::Interned(ref s) => s.to_string(),
+                    Yaml::Integer(i) => i.to_string(),
+                    Yaml::Boolean(b) => b.to_string(),
+                    _ => return Err(TomlError::Key(path.clone())),
+                };
+                if !v.is_null() {
+                    table.insert(key, to_toml(v, path)?);
+                }
+                path.pop();
+            }
+            ::toml::Value::Table(table)
+        }
+        Yaml::Tagged(_, ref v) => to_toml(v, path)?,
+        Yaml::Null => return Err(TomlError::Null(path.clone())),
+        Yaml::Alias(_) | Yaml::BadValue => return Err(TomlError::Unsupported(path.clone())),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::YamlLoader;
+
+    #[test]
+    fn test_toml_round_trip() {
+        let s = r#"
+title = "example"
+ratio = 0.5
+big = inf
+created = 1979-05-27T07:32:00Z
+mixed = [1, "two", [3.0]]
+
+[server]
+ports = [80, 443]
+enabled = true
+"#;
+        let value: ::toml::Value = s.parse().unwrap();
+        let yaml = Yaml::from(value.clone());
+        assert_eq!(yaml["created"].as_str(), Some("1979-05-27T07:32:00Z"));
+        assert_eq!(yaml["big"], Yaml::Real(".inf".to_owned()));
+        assert_eq!(yaml["mixed"][2][0], Yaml::Real("3.0".to_owned()));
+        assert_eq!(yaml["server"]["ports"][1], Yaml::Integer(443));
+        assert_eq!(yaml.to_toml(), Ok(value));
+    }
+
+    #[test]
+    fn test_toml_errors() {
+        let doc = YamlLoader::load_from_str("a: [1, ~]\n").unwrap().remove(0);
+        let err = doc.to_toml().unwrap_err();
+        assert_eq!(err.to_string(), "null at $.a[1] can't be converted to TOML");
+
+        let doc = YamlLoader::load_from_str("? [k]\n: v\n").unwrap().remove(0);
+        assert!(matches!(doc.to_toml(), Err(TomlError::Key(_))));
+
+        let doc = YamlLoader::load_from_str("1: !t x\ntrue: [{b: ~}]")
+            .unwrap()
+            .remove(0);
+        let value = doc.to_toml().unwrap();
+        assert_eq!(value, "1 = 'x'\n[[true]]".parse().unwrap());
+    }
+}
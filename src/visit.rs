@@ -0,0 +1,466 @@
+//! Visiting the nodes of documents with a `Visitor` that can skip the
+//! children of a collection or stop the visit, so an analysis of a large
+//! document only looks at the parts it needs.
+//!
+//! The same visitor can walk a loaded `Yaml` tree with `visit`, or events
+//! with `visit_events` and `visit_str` without building a tree. Each node is
+//! visited with its path, and hash keys are part of the paths of their
+//! values rather than visited themselves. Tags are looked through.
+//!
+//! # Examples
+//!
+//! ```
+//! # extern crate yaml_rust_davvid as yaml_rust;
+//! use yaml_rust::path::YamlPath;
+//! use yaml_rust::visit::{visit_str, VisitFlow, Visitor};
+//! use yaml_rust::Yaml;
+//!
+//! // the first port, without looking into `env`
+//! #[derive(Default)]
+//! struct FirstPort(Option<i64>);
+//!
+//! impl Visitor for FirstPort {
+//!     fn enter_map(&mut self, path: &YamlPath) -> VisitFlow {
+//!         if path.to_string().ends_with(".env") {
+//!             VisitFlow::SkipChildren
+//!         } else {
+//!             VisitFlow::Continue
+//!         }
+//!     }
+//!
+//!     fn scalar(&mut self, path: &YamlPath, value: &Yaml) -> VisitFlow {
+//!         if path.to_string().ends_with(".port") {
+//!             self.0 = value.as_i64();
+//!             return VisitFlow::Stop;
+//!         }
+//!         VisitFlow::Continue
+//!     }
+//! }
+//!
+//! let mut visitor = FirstPort::default();
+//! let s = "env: {port: 1}\nweb: {port: 80}\ndb: {port: 5432}\n[unclosed";
+//! // parsing stops with the visit, before the error
+//! assert_eq!(visit_str(s, &mut visitor), Ok(false));
+//! assert_eq!(visitor.0, Some(80));
+//! ```
+
+use crate::parser::{Event, Parser};
+use crate::path::{PathElement, YamlPath};
+use crate::scanner::ScanError;
+use crate::yaml::{resolve_scalar, Hash, Yaml};
+
+use std::mem;
+
+/// What a visit does after a `Visitor` callback.
+#[derive(Clone, Copy, PartialEq, Debug, Default, Eq)]
+pub enum VisitFlow {
+    #[default]
+    Continue,
+    /// Don't visit the children of the collection just entered. Its exit
+    /// callback is still called. After other callbacks this is the same as
+    /// `Continue`.
+    SkipChildren,
+    /// End the visit.
+    Stop,
+}
+
+/// Callbacks for the nodes of a document, see the module documentation.
+/// Every callback continues the visit by default.
+pub trait Visitor {
+    fn enter_map(&mut self, _path: &YamlPath) -> VisitFlow {
+        VisitFlow::Continue
+    }
+
+    fn exit_map(&mut self, _path: &YamlPath) -> VisitFlow {
+        VisitFlow::Continue
+    }
+
+    fn enter_seq(&mut self, _path: &YamlPath) -> VisitFlow {
+        VisitFlow::Continue
+    }
+
+    fn exit_seq(&mut self, _path: &YamlPath) -> VisitFlow {
+        VisitFlow::Continue
+    }
+
+    /// A node that is not a collection, including `Yaml::Alias` and
+    /// `Yaml::BadValue`.
+    fn scalar(&mut self, _path: &YamlPath, _value: &Yaml) -> VisitFlow {
+        VisitFlow::Continue
+    }
+}
+
+/// Visit `doc` depth first, in document order. Returns false if the
+/// visitor stopped the visit.
+pub fn visit<V: Visitor + ?Sized>(doc: &Yaml, visitor: &mut V) -> bool {
+    visit_node(doc, &mut YamlPath::new(), visitor)
+}
+
+// Visit `node` and its children, returning whether to go on.
+fn visit_node<V: Visitor + ?Sized>(node: &Yaml, path: &mut YamlPath, visitor: &mut V) -> bool {
+    let node = match *node {
+        Yaml::Tagged(_, ref v) => v,
+        _ => node,
+    };
+    match *node {
+        Yaml::Array(ref v) => {
+            match visitor.enter_seq(path) {
+                VisitFlow::Stop => return false,
+                VisitFlow::SkipChildren => {}
+                VisitFlow::Continue => {
+                    for (i, child) in v.iter().enumerate() {
+                        path.push(PathElement::Index(i));
+                        let go_on = visit_node(child, path, visitor);
+                        path.pop();
+                        if !go_on {
+                            return false;
+                        }
+                    }
+                }
+            }
+            visitor.exit_seq(path) != VisitFlow::Stop
+        }
+        Yaml::Hash(ref h) => {
+            match visitor.enter_map(path) {
+                VisitFlow::Stop => return false,
+                VisitFlow::SkipChildren => {}
+                VisitFlow::Continue => {
+                    for (key, child) in h {
+                        path.push(PathElement::Key(key.clone()));
+                        let go_on = visit_node(child, path, visitor);
+                        path.pop();
+                        if !go_on {
+                            return false;
+                        }
+                    }
+                }
+            }
+            visitor.exit_map(path) != VisitFlow::Stop
+        }
+        _ => visitor.scalar(path, node) != VisitFlow::Stop,
+    }
+}
+
+/// Visit the documents of a stream of events as they come, as `visit`
+/// would visit the loaded documents. Aliases are not resolved and are
+/// visited as `Yaml::Alias` with their anchor id. Returns false if the
+/// visitor stopped the visit, after which no more events are taken.
+pub fn visit_events<I, V>(events: I, visitor: &mut V) -> bool
+where
+    I: IntoIterator<Item = Event>,
+    V: Visitor + ?Sized,
+{
+    let mut walker = EventWalker::new(visitor);
+    events.into_iter().all(|ev| walker.event(ev))
+}
+
+/// Parse `source` and visit its documents with `visit_events`. Parsing
+/// stops when the visitor stops the visit, so errors after that point are
+/// not found.
+pub fn visit_str<V: Visitor + ?Sized>(source: &str, visitor: &mut V) -> Result<bool, ScanError> {
+    let mut parser = Parser::new(source.chars());
+    let mut walker = EventWalker::new(visitor);
+    loop {
+        let (ev, _) = parser.next_event()?;
+        let end = ev == Event::StreamEnd;
+        if !walker.event(ev) {
+            return Ok(false);
+        }
+        if end {
+            return Ok(true);
+        }
+    }
+}
+
+// A collection open in the event stream.
+struct Frame {
+    map: bool,
+    entries: usize,
+    // for hashes, the key of the current entry once it has been read
+    key: Option<Yaml>,
+}
+
+struct EventWalker<'v, V: ?Sized> {
+    visitor: &'v mut V,
+    path: YamlPath,
+    frames: Vec<Frame>,
+    // collections open inside one whose children are skipped, counting it
+    skipped: usize,
+    // collections of a hash key being built, with their pending keys
+    key_nodes: Vec<(Yaml, Option<Yaml>)>,
+    // the chunks of the next scalar so far
+    chunks: String,
+}
+
+impl<'v, V: Visitor + ?Sized> EventWalker<'v, V> {
+    fn new(visitor: &'v mut V) -> EventWalker<'v, V> {
+        EventWalker {
+            visitor,
+            path: YamlPath::new(),
+            frames: Vec::new(),
+            skipped: 0,
+            key_nodes: Vec::new(),
+            chunks: String::new(),
+        }
+    }
+
+    // Handle one event, returning whether to go on.
+    fn event(&mut self, ev: Event) -> bool {
+        let ev = match ev {
+            Event::ScalarChunk(v) => {
+                self.chunks.push_str(&v);
+                return true;
+            }
+            Event::Scalar(v, style, aid, tag) if !self.chunks.is_empty() => {
+                Event::Scalar(mem::take(&mut self.chunks) + &v, style, aid, tag)
+            }
+            ev => ev,
+        };
+        if self.skipped > 0 {
+            match ev {
+                Event::SequenceStart(..) | Event::MappingStart(..) => self.skipped += 1,
+                Event::SequenceEnd | Event::MappingEnd => {
+                    self.skipped -= 1;
+                    if self.skipped == 0 {
+                        return self.end();
+                    }
+                }
+                _ => {}
+            }
+            return true;
+        }
+        let at_key = self.frames.last().is_some_and(|f| f.map && f.key.is_none());
+        if !self.key_nodes.is_empty() || (at_key && ev != Event::MappingEnd) {
+            self.key_event(ev);
+            return true;
+        }
+        match ev {
+            Event::Scalar(v, style, _, tag) => {
+                let value = match resolve_scalar(v, style, &tag) {
+                    Yaml::Tagged(_, v) => *v,
+                    value => value,
+                };
+                self.scalar(&value)
+            }
+            Event::Alias(id) => self.scalar(&Yaml::Alias(id)),
+            Event::SequenceStart(..) | Event::MappingStart(..) => {
+                let map = matches!(ev, Event::MappingStart(..));
+                self.begin_child();
+                let flow = if map {
+                    self.visitor.enter_map(&self.path)
+                } else {
+                    self.visitor.enter_seq(&self.path)
+                };
+                self.frames.push(Frame {
+                    map,
+                    entries: 0,
+                    key: None,
+                });
+                match flow {
+                    VisitFlow::Stop => return false,
+                    VisitFlow::SkipChildren => self.skipped = 1,
+                    VisitFlow::Continue => {}
+                }
+                true
+            }
+            Event::SequenceEnd | Event::MappingEnd => self.end(),
+            _ => true,
+        }
+    }
+
+    fn scalar(&mut self, value: &Yaml) -> bool {
+        self.begin_child();
+        let go_on = self.visitor.scalar(&self.path, value) != VisitFlow::Stop;
+        self.end_child();
+        go_on
+    }
+
+    // End the innermost collection.
+    fn end(&mut self) -> bool {
+        let frame = self.frames.pop().unwrap();
+        let flow = if frame.map {
+            self.visitor.exit_map(&self.path)
+        } else {
+            self.visitor.exit_seq(&self.path)
+        };
+        self.end_child();
+        flow != VisitFlow::Stop
+    }
+
+    // Add the step to a node that starts to the path.
+    fn begin_child(&mut self) {
+        if let Some(frame) = self.frames.last_mut() {
+            let step = match frame.key.take() {
+                Some(key) => PathElement::Key(key),
+                None => PathElement::Index(frame.entries),
+            };
+            self.path.push(step);
+        }
+    }
+
+    fn end_child(&mut self) {
+        if let Some(frame) = self.frames.last_mut() {
+            self.path.pop();
+            frame.entries += 1;
+        }
+    }
+
+    // Build the key of a hash entry, which may be a collection.
+    fn key_event(&mut self, ev: Event) {
+        let node = match ev {
+            Event::SequenceStart(..) => {
+                self.key_nodes.push((Yaml::Array(Vec::new()), None));
+                return;
+            }
+            Event::MappingStart(..) => {
+                self.key_nodes.push((Yaml::Hash(Hash::new()), None));
+                return;
+            }
+            Event::SequenceEnd | Event::MappingEnd => self.key_nodes.pop().unwrap().0,
+            Event::Scalar(v, style, _, tag) => resolve_scalar(v, style, &tag),
+            Event::Alias(id) => Yaml::Alias(id),
+            _ => return,
+        };
+        match self.key_nodes.last_mut() {
+            None => self.frames.last_mut().unwrap().key = Some(node),
+            Some((Yaml::Array(v), _)) => v.push(node),
+            Some((Yaml::Hash(h), key)) => match key.take() {
+                Some(key) => {
+                    h.insert(key, node);
+                }
+                None => *key = Some(node),
+            },
+            Some(_) => unreachable!(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::YamlLoader;
+
+    // Records the callbacks, skipping and stopping at given paths.
+    #[derive(Default)]
+    struct Recorder {
+        calls: Vec<String>,
+        skip: &'static str,
+        stop: &'static str,
+    }
+
+    impl Recorder {
+        fn call(&mut self, name: &str, path: &YamlPath) -> VisitFlow {
+            let path = path.to_string();
+            self.calls.push(format!("{} {}", name, path));
+            if path == self.stop {
+                VisitFlow::Stop
+            } else if path == self.skip && name.starts_with("enter") {
+                VisitFlow::SkipChildren
+            } else {
+                VisitFlow::Continue
+            }
+        }
+    }
+
+    impl Visitor for Recorder {
+        fn enter_map(&mut self, path: &YamlPath) -> VisitFlow {
+            self.call("enter_map", path)
+        }
+
+        fn exit_map(&mut self, path: &YamlPath) -> VisitFlow {
+            self.call("exit_map", path)
+        }
+
+        fn enter_seq(&mut self, path: &YamlPath) -> VisitFlow {
+            self.call("enter_seq", path)
+        }
+
+        fn exit_seq(&mut self, path: &YamlPath) -> VisitFlow {
+            self.call("exit_seq", path)
+        }
+
+        fn scalar(&mut self, path: &YamlPath, value: &Yaml) -> VisitFlow {
+            self.call(&format!("{:?}", value), path)
+        }
+    }
+
+    // The calls of a visit of the tree, checked against those of a visit of
+    // the events.
+    fn calls(s: &str, skip: &'static str, stop: &'static str) -> (Vec<String>, bool) {
+        let mut tree = Recorder {
+            skip,
+            stop,
+            ..Recorder::default()
+        };
+        let mut done = true;
+        for doc in YamlLoader::load_from_str(s).unwrap() {
+            done = visit(&doc, &mut tree);
+            if !done {
+                break;
+            }
+        }
+        let mut events = Recorder {
+            skip,
+            stop,
+            ..Recorder::default()
+        };
+        assert_eq!(visit_str(s, &mut events), Ok(done));
+        assert_eq!(tree.calls, events.calls);
+        (tree.calls, done)
+    }
+
+    #[test]
+    fn test_visit() {
+        let s = "a: [1, {b: !t x}]\n[k, {c: d}]: ~\n";
+        let (all, done) = calls(s, "", "");
+        assert!(done);
+        assert_eq!(
+            all,
+            [
+                "enter_map $",
+                "enter_seq $.a",
+                "Integer(1) $.a[0]",
+                "enter_map $.a[1]",
+                "String(\"x\") $.a[1].b",
+                "exit_map $.a[1]",
+                "exit_seq $.a",
+                "Null $[Array([String(\"k\"), Hash({String(\"c\"): String(\"d\")})])]",
+                "exit_map $",
+            ]
+        );
+
+        let (skipped, done) = calls(s, "$.a", "");
+        assert!(done);
+        assert_eq!(
+            skipped[..3],
+            ["enter_map $", "enter_seq $.a", "exit_seq $.a"]
+        );
+        assert_eq!(skipped.len(), 5);
+
+        let (stopped, done) = calls(s, "", "$.a[1]");
+        assert!(!done);
+        assert_eq!(stopped.last().unwrap(), "enter_map $.a[1]");
+        assert_eq!(stopped.len(), 4);
+
+        let (docs, _) = calls("--- 1\n--- [2]\n", "", "");
+        assert_eq!(
+            docs,
+            [
+                "Integer(1) $",
+                "enter_seq $",
+                "Integer(2) $[0]",
+                "exit_seq $"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_visit_events_aliases() {
+        let mut recorder = Recorder::default();
+        assert_eq!(visit_str("- &x [1]\n- *x\n", &mut recorder), Ok(true));
+        assert_eq!(recorder.calls[4], "Alias(1) $[1]");
+
+        let mut recorder = Recorder::default();
+        assert!(visit_str("[1, 2", &mut recorder).is_err());
+    }
+}
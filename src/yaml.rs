@@ -1,13 +1,25 @@
+use crate::emitter::{escape_str, CommentedYaml, EmitError, KeyOrder, YamlEmitter};
 use crate::parser::*;
-use crate::scanner::{Marker, ScanError, TScalarStyle, TokenType};
+use crate::path::{self, key_text, parse_flat_path, PathElement, YamlPath};
+use crate::scanner::{Marker, ScanError, ScannerConfig, Span, TScalarStyle, TokenType};
 
+use encoding::types::{ByteWriter, CodecError, Encoding, RawDecoder, RawEncoder, StringWriter};
 use hashlink::LinkedHashMap;
-use std::collections::BTreeMap;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::convert::TryFrom;
+use std::error::Error;
 use std::f64;
-use std::i64;
+use std::fmt;
+use std::fs::File;
+use std::hash::{self, BuildHasher, Hash as _, Hasher};
+use std::io;
 use std::mem;
 use std::ops::Index;
+use std::path::Path;
 use std::string;
+use std::sync::{Arc, Mutex, PoisonError};
 use std::vec;
 
 /// A YAML node is stored as this `Yaml` enumeration, which provides an easy way to
@@ -27,10 +39,17 @@ use std::vec;
 ///     assert!(v.as_i64().is_some());
 /// }
 /// ```
-#[derive(Clone, PartialEq, PartialOrd, Debug, Eq, Ord, Hash)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum Yaml {
     /// Float types are stored as String and parsed on demand.
     /// Note that f64 does NOT implement Eq trait and can NOT be stored in BTreeMap.
+    ///
+    /// Equality, ordering and hashing compare the text, so `1.0` and `1.00`
+    /// are different hash keys while `.nan` equals itself. Use
+    /// `Yaml::normalize_numbers` to make equal numbers equal keys, or
+    /// `Yaml::semantic_eq` to compare values numerically.
     Real(string::String),
     /// YAML int is stored as i64.
     Integer(i64),
@@ -43,7 +62,12 @@ pub enum Yaml {
     /// YAML hash, can be accessed as a `LinkedHashMap`.
     ///
     /// Insertion order will match the order of insertion into the map.
+    #[cfg_attr(feature = "serde", serde(with = "hash_pairs"))]
     Hash(self::Hash),
+    /// A node with an application tag, such as `!Ref name`. Tagged scalars
+    /// hold their content as a `Yaml::String`. Tags from the core schema,
+    /// such as `!!int`, are resolved rather than kept.
+    Tagged(Tag, Box<Yaml>),
     /// Alias, not fully supported yet.
     Alias(usize),
     /// YAML null, e.g. `null` or `~`.
@@ -52,14 +76,262 @@ pub enum Yaml {
     /// simplifies error handling in the calling code. Invalid type conversion also
     /// returns `BadValue`.
     BadValue,
+    /// A string shared with the equal strings of other nodes, as hash keys
+    /// are loaded with `YamlLoader::intern_keys`. It equals, hashes and
+    /// orders as the `Yaml::String` of the same text, and reads as one with
+    /// `as_str` and `into_string`.
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            rename = "String",
+            serialize_with = "serialize_str",
+            skip_deserializing
+        )
+    )]
+    Interned(Arc<str>),
 }
 
 pub type Array = Vec<Yaml>;
 pub type Hash = LinkedHashMap<Yaml, Yaml>;
 
+impl Yaml {
+    // The text of a `String` or `Interned` node, without looking through
+    // tags.
+    pub(crate) fn string_text(&self) -> Option<&str> {
+        match *self {
+            Yaml::String(ref v) => Some(v),
+            Yaml::Interned(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    // The position of the variant in the order of nodes, where `Interned`
+    // is a `String`.
+    fn rank(&self) -> u8 {
+        match *self {
+            Yaml::Real(_) => 0,
+            Yaml::Integer(_) => 1,
+            Yaml::String(_) | Yaml::Interned(_) => 2,
+            Yaml::Boolean(_) => 3,
+            Yaml::Array(_) => 4,
+            Yaml::Hash(_) => 5,
+            Yaml::Tagged(..) => 6,
+            Yaml::Alias(_) => 7,
+            Yaml::Null => 8,
+            Yaml::BadValue => 9,
+        }
+    }
+}
+
+// Equality, ordering and hashing are written out rather than derived so
+// that `Interned` is the same as `String`, and so that a string key can be
+// hashed from a `&str`, see `get_str_key`.
+impl PartialEq for Yaml {
+    fn eq(&self, other: &Yaml) -> bool {
+        match (self, other) {
+            (Yaml::String(a), Yaml::String(b)) | (Yaml::Real(a), Yaml::Real(b)) => a == b,
+            (Yaml::Integer(a), Yaml::Integer(b)) => a == b,
+            (Yaml::Boolean(a), Yaml::Boolean(b)) => a == b,
+            (Yaml::Array(a), Yaml::Array(b)) => a == b,
+            (Yaml::Hash(a), Yaml::Hash(b)) => a == b,
+            (Yaml::Tagged(ta, a), Yaml::Tagged(tb, b)) => ta == tb && a == b,
+            (Yaml::Alias(a), Yaml::Alias(b)) => a == b,
+            (Yaml::Null, Yaml::Null) | (Yaml::BadValue, Yaml::BadValue) => true,
+            _ => match (self.string_text(), other.string_text()) {
+                (Some(a), Some(b)) => a == b,
+                _ => false,
+            },
+        }
+    }
+}
+
+impl Eq for Yaml {}
+
+impl PartialOrd for Yaml {
+    fn partial_cmp(&self, other: &Yaml) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Yaml {
+    fn cmp(&self, other: &Yaml) -> Ordering {
+        match (self, other) {
+            (Yaml::Real(a), Yaml::Real(b)) => a.cmp(b),
+            (Yaml::Integer(a), Yaml::Integer(b)) => a.cmp(b),
+            (Yaml::Boolean(a), Yaml::Boolean(b)) => a.cmp(b),
+            (Yaml::Array(a), Yaml::Array(b)) => a.cmp(b),
+            (Yaml::Hash(a), Yaml::Hash(b)) => a.cmp(b),
+            (Yaml::Tagged(ta, a), Yaml::Tagged(tb, b)) => ta.cmp(tb).then_with(|| a.cmp(b)),
+            (Yaml::Alias(a), Yaml::Alias(b)) => a.cmp(b),
+            _ => match (self.string_text(), other.string_text()) {
+                (Some(a), Some(b)) => a.cmp(b),
+                _ => self.rank().cmp(&other.rank()),
+            },
+        }
+    }
+}
+
+impl hash::Hash for Yaml {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        if let Yaml::Interned(ref v) = *self {
+            return hash_str_key(v, state);
+        }
+        mem::discriminant(self).hash(state);
+        match *self {
+            Yaml::Real(ref v) | Yaml::String(ref v) => v.hash(state),
+            Yaml::Integer(v) => v.hash(state),
+            Yaml::Boolean(v) => v.hash(state),
+            Yaml::Array(ref v) => v.hash(state),
+            Yaml::Hash(ref v) => v.hash(state),
+            Yaml::Tagged(ref tag, ref v) => {
+                tag.hash(state);
+                v.hash(state);
+            }
+            Yaml::Alias(id) => id.hash(state),
+            Yaml::Null | Yaml::BadValue | Yaml::Interned(_) => {}
+        }
+    }
+}
+
+// Hash `key` the way `Yaml::String` of it is hashed, as `String` hashes
+// like `str`.
+fn hash_str_key<H: Hasher>(key: &str, state: &mut H) {
+    mem::discriminant(&Yaml::String(String::new())).hash(state);
+    key.hash(state);
+}
+
+// Look up a string key without allocating a `Yaml::String` for it.
+pub(crate) fn get_str_key<'h>(hash: &'h Hash, key: &str) -> Option<&'h Yaml> {
+    let mut state = hash.hasher().build_hasher();
+    hash_str_key(key, &mut state);
+    hash.raw_entry()
+        .from_hash(state.finish(), |k| k.string_text() == Some(key))
+        .map(|(_, v)| v)
+}
+
+/// A tag as written on a node, with any `%TAG` directive applied to the
+/// handle. Verbatim tags such as `!<tag:example.com,2000:app/foo>` have an
+/// empty handle.
+///
+/// Tags compare and hash by the full tag, the handle followed by the
+/// suffix, so a tag equals its verbatim form however it was split.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Tag {
+    pub handle: String,
+    pub suffix: String,
+}
+
+impl Tag {
+    pub fn new<H: Into<String>, S: Into<String>>(handle: H, suffix: S) -> Tag {
+        Tag {
+            handle: handle.into(),
+            suffix: suffix.into(),
+        }
+    }
+
+    // Whether the tag belongs to the core schema, which the loader resolves
+    // instead of keeping.
+    pub(crate) fn is_core(&self) -> bool {
+        self.handle == "!!"
+            || self.handle.starts_with("tag:yaml.org,2002:")
+            || (self.handle.is_empty() && self.suffix.starts_with("tag:yaml.org,2002:"))
+    }
+}
+
+impl fmt::Display for Tag {
+    /// Write the tag as it would appear in a document.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let named = self.handle.len() > 1 && self.handle.ends_with('!');
+        if self.handle == "!" || (named && self.handle.starts_with('!')) {
+            write!(f, "{}{}", self.handle, self.suffix)
+        } else if self.handle.is_empty() && self.suffix == "!" {
+            f.write_str("!")
+        } else {
+            write!(f, "!<{}{}>", self.handle, self.suffix)
+        }
+    }
+}
+
+impl Tag {
+    // The bytes of the full tag.
+    fn bytes(&self) -> impl Iterator<Item = u8> + '_ {
+        self.handle.bytes().chain(self.suffix.bytes())
+    }
+}
+
+impl PartialEq for Tag {
+    fn eq(&self, other: &Tag) -> bool {
+        self.handle.len() + self.suffix.len() == other.handle.len() + other.suffix.len()
+            && self.bytes().eq(other.bytes())
+    }
+}
+
+impl Eq for Tag {}
+
+impl PartialOrd for Tag {
+    fn partial_cmp(&self, other: &Tag) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Tag {
+    fn cmp(&self, other: &Tag) -> Ordering {
+        self.bytes().cmp(other.bytes())
+    }
+}
+
+impl hash::Hash for Tag {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for b in self.bytes() {
+            state.write_u8(b);
+        }
+        // as `str` ends its hash, so that prefixes hash differently
+        state.write_u8(0xff);
+    }
+}
+
+// The application tag of a node, if it has one.
+pub(crate) fn application_tag(tag: &Option<TokenType>) -> Option<Tag> {
+    match *tag {
+        Some(TokenType::Tag(ref handle, ref suffix)) => {
+            Some(Tag::new(handle.as_str(), suffix.as_str())).filter(|tag| !tag.is_core())
+        }
+        _ => None,
+    }
+}
+
+// `Interned` strings are serialized as `String`s.
+#[cfg(feature = "serde")]
+fn serialize_str<S: serde::Serializer>(v: &Arc<str>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(v)
+}
+
+// Hashes are serialized as a sequence of key and value pairs, which keeps
+// their order and works with formats that only allow string keys.
+#[cfg(feature = "serde")]
+mod hash_pairs {
+    use super::{Hash, Yaml};
+    use serde::ser::SerializeSeq;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(hash: &Hash, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(hash.len()))?;
+        for entry in hash.iter() {
+            seq.serialize_element(&entry)?;
+        }
+        seq.end()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Hash, D::Error> {
+        let pairs = Vec::<(Yaml, Yaml)>::deserialize(deserializer)?;
+        Ok(pairs.into_iter().collect())
+    }
+}
+
 // parse f64 as Core schema
 // See: https://github.com/chyh1990/yaml-rust/issues/51
-fn parse_f64(v: &str) -> Option<f64> {
+pub(crate) fn parse_f64(v: &str) -> Option<f64> {
     match v {
         ".inf" | ".Inf" | ".INF" | "+.inf" | "+.Inf" | "+.INF" => Some(f64::INFINITY),
         "-.inf" | "-.Inf" | "-.INF" => Some(f64::NEG_INFINITY),
@@ -68,6 +340,18 @@ fn parse_f64(v: &str) -> Option<f64> {
     }
 }
 
+// The text of a `Yaml::Real` holding `v`, which loads back as the same
+// value.
+pub(crate) fn format_real(v: f64) -> String {
+    if v.is_nan() {
+        ".nan".to_owned()
+    } else if v.is_infinite() {
+        if v > 0.0 { ".inf" } else { "-.inf" }.to_owned()
+    } else {
+        format!("{:?}", v)
+    }
+}
+
 /// A `YamlScalarParser` is a parser that change the parsing of a yaml scalar value
 /// like a tag
 ///
@@ -101,19 +385,713 @@ pub struct YamlLoader<'a> {
     // states
     // (current node, anchor_id) tuple
     doc_stack: Vec<(Yaml, usize)>,
-    key_stack: Vec<Yaml>,
+    // (current key, marker of the key) of the hashes being loaded
+    key_stack: Vec<(Yaml, Marker)>,
     anchor_map: BTreeMap<usize, Yaml>,
+    // (anchor_id, marker of the anchor) of anchored collections being
+    // loaded
+    open_anchors: Vec<(usize, Marker)>,
+    // application tags of the collections being loaded
+    tag_stack: Vec<Option<Tag>>,
     scalar_parser: Vec<&'a dyn YamlScalarParser>,
+    // handle -> suffix -> parser
+    tag_parsers: HashMap<String, HashMap<String, &'a dyn YamlScalarParser>>,
+    error: Option<ScanError>,
+    // start of the current document, and the spans of finished ones
+    doc_start: Option<Marker>,
+    spans: Vec<Span>,
+    // options
+    shrink_to_fit: bool,
+    intern_keys: bool,
+    structure_only: bool,
+    bad_value_mode: BadValueMode,
+    key_coercion: KeyCoercion,
+    number_format: NumberFormat,
+    schema: Schema,
+    scanner_config: ScannerConfig,
+    empty_stream_mode: EmptyStreamMode,
+    key_rules: Option<KeyRules>,
+    anchor_redefinition: AnchorRedefinition,
+    version_policy: VersionPolicy,
+    warning_handler: Option<&'a dyn Fn(&Warning)>,
+    // (anchor_id, anchor) of the anchors defined in each document, when
+    // they are being recorded
+    anchors: Option<Vec<Vec<(usize, Anchor)>>>,
+    // the `%YAML` version of each document, when it is being recorded
+    versions: Option<Vec<Option<YamlVersion>>>,
+    // the layout of each document's root, when it is being recorded
+    roots: Option<Vec<RootLayout>>,
+    // estimated entries of the collection the next event starts
+    capacity_hint: usize,
+    // whether the parse is timed, for `parse_from_str_with_stats`
+    record_time: bool,
+    // where the anchor of the collection the next event starts is, when
+    // it is not where the collection starts
+    anchor_mark: Option<Marker>,
+    // the strings of interned keys, and the cache shared with other loaders
+    // that is used instead
+    key_cache: StringCache,
+    string_cache: Option<&'a StringCache>,
+    // (value, marker of the first chunk) of a block scalar whose chunks
+    // are being joined
+    scalar_chunks: Option<(String, Marker)>,
+}
+
+/// Strings that the interned hash keys of several loaders share, see
+/// `YamlLoader::string_cache`. A cache can be used from many threads at
+/// once, and keeps its strings until it is dropped.
+#[derive(Debug, Default)]
+pub struct StringCache {
+    strings: Mutex<HashSet<Arc<str>>>,
+}
+
+impl StringCache {
+    /// An empty cache.
+    pub fn new() -> StringCache {
+        StringCache::default()
+    }
+
+    /// The string in the cache equal to `s`, which is added if there is none.
+    pub fn intern(&self, s: &str) -> Arc<str> {
+        let mut strings = self.strings.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(v) = strings.get(s) {
+            return v.clone();
+        }
+        let v: Arc<str> = s.into();
+        strings.insert(v.clone());
+        v
+    }
+
+    /// The number of strings in the cache.
+    pub fn len(&self) -> usize {
+        self.strings
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// Where the root of a document and the keys of a root hash start, and
+// whether the document has anchors or aliases, for re-parsing parts of it.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub(crate) struct RootLayout {
+    pub(crate) start: Option<Marker>,
+    pub(crate) keys: Vec<Marker>,
+    pub(crate) anchors: bool,
+}
+
+/// An anchored node of a loaded document, see
+/// `YamlLoader::parse_from_str_with_anchors`.
+#[derive(Clone, PartialEq, Debug, Eq)]
+pub struct Anchor {
+    /// The anchor's name, without the `&`.
+    pub name: String,
+    /// Where the node is in its document, or `None` for a node that is a
+    /// hash key.
+    pub path: Option<YamlPath>,
+    /// Where the node starts.
+    pub mark: Marker,
+    /// The node as loaded, which is what aliases to it resolve to.
+    pub value: Yaml,
+}
+
+/// A problem found while loading that does not stop it, see
+/// `YamlLoader::on_warning`.
+#[derive(Clone, PartialEq, Debug, Eq)]
+pub struct Warning {
+    /// Where the node with the problem starts.
+    pub mark: Marker,
+    /// Where the key starts, if the node is the value of a hash entry.
+    pub key_mark: Option<Marker>,
+    pub kind: WarningKind,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} at line {} column {}",
+            self.kind,
+            self.mark.line(),
+            self.mark.col() + 1
+        )
+    }
+}
+
+/// The kinds of `Warning`.
+#[derive(Clone, PartialEq, Debug, Eq)]
+#[non_exhaustive]
+pub enum WarningKind {
+    /// A core schema tag the loader does not resolve, such as `!!binary`.
+    /// The value is loaded as a string.
+    UnknownTag(String),
+    /// A value that is invalid for its tag, such as `!!int x`, loaded as
+    /// `Yaml::BadValue`. With `BadValueMode::Error` this is an error
+    /// instead.
+    InvalidValue { tag: String, value: String },
+    /// A plain scalar written as an integer that does not fit in an
+    /// `i64`, with what it was loaded as instead.
+    IntegerOverflow(Yaml),
+    /// A key that appears again in the same hash. The later value replaces
+    /// the earlier one.
+    DuplicateKey(Yaml),
+    /// An alias to a node of at least `LARGE_ALIAS_NODES` nodes, which is
+    /// copied in full wherever the alias appears.
+    LargeAlias { nodes: usize },
+    /// A `%YAML` directive declaring a version other than 1.1 or 1.2, with
+    /// `VersionPolicy::Warn`.
+    UnsupportedVersion { major: u32, minor: u32 },
+}
+
+/// The size from which an alias is reported as `WarningKind::LargeAlias`.
+pub const LARGE_ALIAS_NODES: usize = 1000;
+
+impl fmt::Display for WarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            WarningKind::UnknownTag(ref tag) => {
+                write!(f, "unknown tag {}, loaded as a string", tag)
+            }
+            WarningKind::InvalidValue { ref tag, ref value } => {
+                write!(f, "invalid value {:?} for tag {}", value, tag)
+            }
+            WarningKind::IntegerOverflow(ref node) => {
+                write!(f, "integer out of range, loaded as a {}", node.type_str())
+            }
+            WarningKind::DuplicateKey(ref key) => write!(f, "duplicate key {:?}", key),
+            WarningKind::LargeAlias { nodes } => {
+                write!(f, "alias copies a node of {} nodes", nodes)
+            }
+            WarningKind::UnsupportedVersion { major, minor } => {
+                write!(f, "unsupported YAML version {}.{}", major, minor)
+            }
+        }
+    }
+}
+
+/// The rules that give plain scalars without a tag their type, used by
+/// `YamlLoader`, `Yaml::from_str_with_schema` and `parse_scalar`.
+#[derive(Clone, Copy, PartialEq, Debug, Default, Eq)]
+pub enum Schema {
+    /// Every scalar is a string.
+    Failsafe,
+    /// Only `null`, `true`, `false` and numbers written as JSON writes them
+    /// have other types.
+    Json,
+    /// `~` and `null`, `true` and `false`, and integers and reals as read
+    /// by `NumberFormat`, as `Yaml::from_str` resolves them.
+    #[default]
+    Core,
+}
+
+/// Resolve a scalar without a tag as `YamlLoader` does: scalars that are
+/// quoted or written as block scalars are strings, and plain scalars are
+/// resolved by `schema`.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate yaml_rust_davvid as yaml_rust;
+/// use yaml_rust::scanner::TScalarStyle;
+/// use yaml_rust::yaml::{parse_scalar, Schema};
+/// use yaml_rust::Yaml;
+///
+/// assert_eq!(parse_scalar("0x10", TScalarStyle::Plain, Schema::Core), Yaml::Integer(16));
+/// assert_eq!(parse_scalar("0x10", TScalarStyle::Plain, Schema::Json), Yaml::String("0x10".to_owned()));
+/// assert_eq!(parse_scalar("10", TScalarStyle::SingleQuoted, Schema::Core), Yaml::String("10".to_owned()));
+/// ```
+pub fn parse_scalar(value: &str, style: TScalarStyle, schema: Schema) -> Yaml {
+    if style == TScalarStyle::Plain {
+        plain_node(value, schema, &NumberFormat::default())
+    } else {
+        Yaml::String(value.to_owned())
+    }
+}
+
+/// The type of a node in a `Yaml` tree that holds a scalar, see
+/// `resolve_plain`.
+#[derive(Clone, Copy, PartialEq, Debug, Eq, Hash)]
+pub enum ScalarType {
+    Null,
+    Boolean,
+    Integer,
+    Real,
+    String,
+}
+
+/// The type a plain scalar `value` is loaded as with `schema`, without
+/// building the node, such as to find the strings that must be quoted to
+/// stay strings.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate yaml_rust_davvid as yaml_rust;
+/// use yaml_rust::yaml::{resolve_plain, ScalarType, Schema};
+///
+/// // `no` is a boolean in YAML 1.1, but not in the core schema
+/// assert_eq!(resolve_plain("no", Schema::Core), ScalarType::String);
+/// assert_eq!(resolve_plain("false", Schema::Core), ScalarType::Boolean);
+/// assert_eq!(resolve_plain("0o17", Schema::Core), ScalarType::Integer);
+/// assert_eq!(resolve_plain("0o17", Schema::Json), ScalarType::String);
+/// assert_eq!(resolve_plain("99999999999999999999", Schema::Core), ScalarType::Real);
+/// ```
+pub fn resolve_plain(value: &str, schema: Schema) -> ScalarType {
+    plain_type(value, schema, &NumberFormat::default())
+}
+
+fn plain_type(v: &str, schema: Schema, format: &NumberFormat) -> ScalarType {
+    match schema {
+        Schema::Failsafe => ScalarType::String,
+        Schema::Json => match v {
+            "null" => ScalarType::Null,
+            "true" | "false" => ScalarType::Boolean,
+            _ if is_json_number(v) => match v.parse::<i64>() {
+                Ok(_) => ScalarType::Integer,
+                Err(_) => ScalarType::Real,
+            },
+            _ => ScalarType::String,
+        },
+        Schema::Core => match v {
+            "~" | "null" => ScalarType::Null,
+            "true" | "false" => ScalarType::Boolean,
+            _ if format.parse_int(v).is_some() => ScalarType::Integer,
+            _ if format.real_text(v).is_some() => ScalarType::Real,
+            _ => ScalarType::String,
+        },
+    }
+}
+
+fn plain_node(v: &str, schema: Schema, format: &NumberFormat) -> Yaml {
+    match schema {
+        Schema::Failsafe => Yaml::String(v.to_owned()),
+        Schema::Json => match v {
+            "null" => Yaml::Null,
+            "true" => Yaml::Boolean(true),
+            "false" => Yaml::Boolean(false),
+            _ if is_json_number(v) => match v.parse::<i64>() {
+                Ok(i) => Yaml::Integer(i),
+                Err(_) => Yaml::Real(v.to_owned()),
+            },
+            _ => Yaml::String(v.to_owned()),
+        },
+        Schema::Core => Yaml::from_str_with(v, format),
+    }
+}
+
+// Whether `v` is a number as JSON writes it.
+fn is_json_number(v: &str) -> bool {
+    fn digits(s: &str) -> (usize, &str) {
+        let n = s.bytes().take_while(u8::is_ascii_digit).count();
+        (n, &s[n..])
+    }
+    let int = v.strip_prefix('-').unwrap_or(v);
+    let rest = match digits(int) {
+        (0, _) => return false,
+        // no leading zeros
+        (n, _) if n > 1 && int.starts_with('0') => return false,
+        (_, rest) => rest,
+    };
+    let rest = match rest.strip_prefix('.') {
+        Some(fraction) => match digits(fraction) {
+            (0, _) => return false,
+            (_, rest) => rest,
+        },
+        None => rest,
+    };
+    match rest.strip_prefix(['e', 'E']) {
+        Some(exp) => matches!(
+            digits(exp.strip_prefix(['-', '+']).unwrap_or(exp)),
+            (1.., "")
+        ),
+        None => rest.is_empty(),
+    }
+}
+
+/// How plain scalars that look like numbers are read, by `YamlLoader` and
+/// `Yaml::from_str_with`. The default reads them as the core schema does,
+/// like `Yaml::from_str`, and the other settings accept forms of YAML 1.1.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate yaml_rust_davvid as yaml_rust;
+/// use yaml_rust::yaml::{LeadingZero, NumberFormat};
+/// use yaml_rust::Yaml;
+///
+/// let yaml11 = NumberFormat {
+///     underscores: true,
+///     sexagesimal: true,
+///     leading_zero: LeadingZero::Octal,
+/// };
+/// assert_eq!(Yaml::from_str_with("1_000_000", &yaml11), Yaml::Integer(1_000_000));
+/// assert_eq!(Yaml::from_str_with("1:30", &yaml11), Yaml::Integer(90));
+/// assert_eq!(Yaml::from_str_with("012", &yaml11), Yaml::Integer(10));
+/// assert_eq!(Yaml::from_str_with("1_000.5", &yaml11), Yaml::Real("1000.5".to_owned()));
+///
+/// let core = NumberFormat::default();
+/// assert_eq!(Yaml::from_str_with("1_000_000", &core), Yaml::from_str("1_000_000"));
+/// assert_eq!(Yaml::from_str_with("012", &core), Yaml::Integer(12));
+/// ```
+#[derive(Clone, Copy, PartialEq, Debug, Default, Eq)]
+pub struct NumberFormat {
+    /// Allow `_` after the first digit, as in `1_000_000`.
+    pub underscores: bool,
+    /// Read decimal integers in base 60 written with `:`, as in `1:30`.
+    pub sexagesimal: bool,
+    pub leading_zero: LeadingZero,
+}
+
+/// How `NumberFormat` reads a decimal integer with a leading zero, such as
+/// `012`.
+#[derive(Clone, Copy, PartialEq, Debug, Default, Eq)]
+pub enum LeadingZero {
+    /// As decimal, so `012` is 12.
+    #[default]
+    Decimal,
+    /// As octal, as YAML 1.1 does, so `012` is 10.
+    Octal,
+    /// As a string.
+    String,
+}
+
+impl NumberFormat {
+    /// The value of `v` if it is written as an integer in the range of an
+    /// `i64`.
+    pub fn parse_int(&self, v: &str) -> Option<i64> {
+        self.int_value(v).flatten()
+    }
+
+    /// The text of `v` as a real of the core schema, if it is written as a
+    /// real, or as an integer out of the range of `parse_int`.
+    pub fn parse_real(&self, v: &str) -> Option<String> {
+        self.real_text(v).map(Cow::into_owned)
+    }
+
+    fn real_text<'v>(&self, v: &'v str) -> Option<Cow<'v, str>> {
+        if self.leading_zero == LeadingZero::String && self.int_value(v).is_none() {
+            let body = v.strip_prefix(['-', '+']).unwrap_or(v);
+            if body.len() > 1 && body.starts_with('0') && body.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+        }
+        let text = if self.underscores && v.contains('_') {
+            // underscores may only follow digits
+            let mut prev = None;
+            for c in v.chars() {
+                if c == '_' && !prev.is_some_and(|p: char| p.is_ascii_digit() || p == '_') {
+                    return None;
+                }
+                prev = Some(c);
+            }
+            Cow::Owned(v.replace('_', ""))
+        } else {
+            Cow::Borrowed(v)
+        };
+        parse_f64(&text).map(|_| text)
+    }
+
+    // `None` if `v` isn't written as an integer, and `Some(None)` if it is
+    // out of range.
+    fn int_value(&self, v: &str) -> Option<Option<i64>> {
+        if let Some(hex) = v.strip_prefix("0x") {
+            return self.digits(hex, 16, false);
+        }
+        if let Some(oct) = v.strip_prefix("0o") {
+            return self.digits(oct, 8, false);
+        }
+        let (negative, body) = match v.strip_prefix('-') {
+            Some(body) => (true, body),
+            None => (false, v.strip_prefix('+').unwrap_or(v)),
+        };
+        if self.sexagesimal && body.contains(':') {
+            return self.sexagesimal_value(body, negative);
+        }
+        if body.len() > 1 && body.starts_with('0') {
+            return match self.leading_zero {
+                LeadingZero::Decimal => self.digits(body, 10, negative),
+                LeadingZero::Octal => self.digits(&body[1..], 8, negative),
+                LeadingZero::String => None,
+            };
+        }
+        self.digits(body, 10, negative)
+    }
+
+    fn digits(&self, s: &str, radix: u32, negative: bool) -> Option<Option<i64>> {
+        if !s.starts_with(|c: char| c.is_digit(radix)) {
+            return None;
+        }
+        let mut value = Some(0i64);
+        for c in s.chars() {
+            if c == '_' && self.underscores {
+                continue;
+            }
+            let digit = i64::from(c.to_digit(radix)?);
+            value = value.and_then(|v| accumulate(v, i64::from(radix), digit, negative));
+        }
+        Some(value)
+    }
+
+    // `1:30` is 1 * 60 + 30, and each part after the first is below 60.
+    fn sexagesimal_value(&self, s: &str, negative: bool) -> Option<Option<i64>> {
+        let mut parts = s.split(':');
+        let mut value = self.digits(parts.next()?, 10, negative)?;
+        for part in parts {
+            if part.is_empty() || part.len() > 2 || !part.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            let digit = part.parse::<i64>().ok().filter(|&d| d < 60)?;
+            value = value.and_then(|v| accumulate(v, 60, digit, negative));
+        }
+        Some(value)
+    }
+}
+
+// `v * radix + digit`, with the digit subtracted for negative numbers so
+// that `i64::MIN` can be read, or `None` on overflow.
+fn accumulate(v: i64, radix: i64, digit: i64, negative: bool) -> Option<i64> {
+    let v = v.checked_mul(radix)?;
+    if negative {
+        v.checked_sub(digit)
+    } else {
+        v.checked_add(digit)
+    }
+}
+
+/// Rules that hash keys must follow, checked as documents are loaded so
+/// that a document with an offending key fails with the key's position.
+/// Scalar keys are checked as written, before resolution, so `8080` is the
+/// text `8080`.
+#[derive(Clone, Copy, PartialEq, Debug, Default, Eq)]
+pub struct KeyRules {
+    /// The characters keys may contain. Unless this is `KeyCharset::Any`,
+    /// keys that are collections or aliases are rejected.
+    pub charset: KeyCharset,
+    /// The maximum length of a key in characters.
+    pub max_len: Option<usize>,
+}
+
+/// The characters allowed in keys by `KeyRules`.
+#[derive(Clone, Copy, PartialEq, Debug, Default, Eq)]
+pub enum KeyCharset {
+    /// Any characters.
+    #[default]
+    Any,
+    /// Anything but whitespace and control characters.
+    NoWhitespace,
+    /// ASCII letters, digits and `_`, not starting with a digit.
+    Identifier,
+}
+
+impl KeyRules {
+    // Check the text of a scalar key, describing the problem if any.
+    fn check(&self, key: &str) -> Result<(), String> {
+        if let Some(max) = self.max_len {
+            if key.chars().count() > max {
+                return Err(format!("key {:?} is longer than {} characters", key, max));
+            }
+        }
+        let valid = match self.charset {
+            KeyCharset::Any => true,
+            KeyCharset::NoWhitespace => !key.chars().any(|c| c.is_whitespace() || c.is_control()),
+            KeyCharset::Identifier => {
+                key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                    && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            }
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(match self.charset {
+                KeyCharset::Identifier => format!("key {:?} is not an identifier", key),
+                _ => format!("key {:?} contains whitespace or control characters", key),
+            })
+        }
+    }
+}
+
+/// What `YamlLoader` does when an anchor name is defined again later in the
+/// same document. YAML allows it and aliases then refer to the latest
+/// definition, but implementations disagree, so documents that rely on it
+/// may load differently elsewhere.
+#[derive(Clone, Copy, PartialEq, Debug, Default, Eq)]
+pub enum AnchorRedefinition {
+    /// Aliases after the redefinition refer to the new node.
+    #[default]
+    Shadow,
+    /// Aliases keep referring to the first node with that name.
+    FirstWins,
+    /// Return a `ScanError` at the node with the redefined anchor that also
+    /// gives the position of the first node with that anchor.
+    Error,
+}
+
+/// What `YamlLoader` does with a document whose `%YAML` directive declares
+/// a version other than 1.1 or 1.2, which may use syntax this crate reads
+/// differently. Documents without the directive are always loaded.
+#[derive(Clone, Copy, PartialEq, Debug, Default, Eq)]
+pub enum VersionPolicy {
+    /// Load the document as YAML 1.2.
+    #[default]
+    Accept,
+    /// Load the document as YAML 1.2 and report a
+    /// `WarningKind::UnsupportedVersion`.
+    Warn,
+    /// Return a `ScanError` at the `%YAML` directive.
+    Error,
+}
+
+/// A version declared by a `%YAML` directive, as `(major, minor)`.
+pub type YamlVersion = (u32, u32);
+
+// Whether the loader supports documents declared as `major.minor`.
+fn is_supported_version(major: u32, minor: u32) -> bool {
+    major == 1 && (minor == 1 || minor == 2)
+}
+
+/// What `YamlLoader` returns for a stream without documents, which is input
+/// that is empty or only holds whitespace and comments. A document that is
+/// started but has no content, such as `---` on its own, is not empty and
+/// loads as `Yaml::Null`.
+#[derive(Clone, Copy, PartialEq, Debug, Default, Eq)]
+pub enum EmptyStreamMode {
+    /// Return no documents.
+    #[default]
+    NoDocuments,
+    /// Return a single `Yaml::Null` document, as if the input were `---`.
+    Null,
+    /// Return a `ScanError` pointing at the end of the input.
+    Error,
+}
+
+/// How `YamlLoader` normalizes the scalar keys of hashes, so that keys
+/// written differently can be looked up the same way.
+#[derive(Clone, Copy, PartialEq, Debug, Default, Eq)]
+pub enum KeyCoercion {
+    /// Load keys as they are written.
+    #[default]
+    None,
+    /// Load integer, real and boolean keys as strings, so `8080` and
+    /// `"8080"` both become `Yaml::String("8080")`.
+    String,
+    /// Resolve string keys with the loader's `Schema` and `NumberFormat`,
+    /// as if they were plain scalars, so `"8080"` and `8080` both become
+    /// `Yaml::Integer(8080)`.
+    Schema,
+}
+
+impl KeyCoercion {
+    fn coerce(self, key: Yaml, schema: Schema, format: &NumberFormat) -> Yaml {
+        match (self, key) {
+            (KeyCoercion::String, Yaml::Integer(i)) => Yaml::String(i.to_string()),
+            (KeyCoercion::String, Yaml::Real(v)) => Yaml::String(v),
+            (KeyCoercion::String, Yaml::Boolean(b)) => Yaml::String(b.to_string()),
+            (KeyCoercion::Schema, Yaml::String(v)) => plain_node(&v, schema, format),
+            (_, key) => key,
+        }
+    }
+}
+
+/// What `YamlLoader` does with a scalar whose value is invalid for its tag,
+/// such as `!!int string`.
+#[derive(Clone, Copy, PartialEq, Debug, Default, Eq)]
+pub enum BadValueMode {
+    /// Load the scalar as `Yaml::BadValue`.
+    #[default]
+    BadValue,
+    /// Panic.
+    Panic,
+    /// Stop loading and return a `ScanError` pointing at the scalar.
+    Error,
+}
+
+// Resolve a scalar without registered parsers, loading values that are
+// invalid for their tag as `BadValue`.
+pub(crate) fn resolve_scalar(v: String, style: TScalarStyle, tag: &Option<TokenType>) -> Yaml {
+    let format = NumberFormat::default();
+    if let Some(tag) = application_tag(tag) {
+        return Yaml::Tagged(tag, Box::new(Yaml::String(v)));
+    }
+    if style != TScalarStyle::Plain {
+        return Yaml::String(v);
+    }
+    match *tag {
+        Some(TokenType::Tag(ref handle, ref suffix)) if handle == "!!" => {
+            resolve_core_tag(suffix, v, &format).unwrap_or(Yaml::BadValue)
+        }
+        Some(_) => Yaml::String(v),
+        None => Yaml::from_str(&v),
+    }
+}
+
+// Resolve a scalar with a tag from the core schema, handing the value back if
+// it is invalid for the tag.
+pub(crate) fn resolve_core_tag(
+    suffix: &str,
+    v: String,
+    format: &NumberFormat,
+) -> Result<Yaml, String> {
+    match suffix {
+        // "true" or "false"
+        "bool" => match v.parse::<bool>() {
+            Ok(b) => Ok(Yaml::Boolean(b)),
+            Err(_) => Err(v),
+        },
+        "int" => match format.parse_int(&v) {
+            Some(i) => Ok(Yaml::Integer(i)),
+            None => Err(v),
+        },
+        "float" => match parse_f64(&v) {
+            Some(_) => Ok(Yaml::Real(v)),
+            None => Err(v),
+        },
+        "null" => match v.as_ref() {
+            "~" | "null" => Ok(Yaml::Null),
+            _ => Err(v),
+        },
+        _ => Ok(Yaml::String(v)),
+    }
 }
 
 impl<'a> MarkedEventReceiver for YamlLoader<'a> {
-    fn on_event(&mut self, ev: Event, _: Marker) {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
         // println!("EV {:?}", ev);
+        if self.error.is_some() {
+            return;
+        }
+        let (ev, mark) = match self.join_chunks(ev, mark) {
+            Some(next) => next,
+            None => return,
+        };
+        if let Some(rules) = self.key_rules {
+            if self.at_key() {
+                let result = match ev {
+                    Event::Scalar(ref v, ..) => rules.check(v),
+                    Event::SequenceStart(..) | Event::MappingStart(..) | Event::Alias(_)
+                        if rules.charset != KeyCharset::Any =>
+                    {
+                        Err("key is not a scalar".to_owned())
+                    }
+                    _ => Ok(()),
+                };
+                if let Err(info) = result {
+                    self.error = Some(ScanError::new(mark, &info));
+                    return;
+                }
+            }
+        }
         match ev {
             Event::DocumentStart => {
-                // do nothing
+                self.doc_start = Some(mark);
             }
             Event::DocumentEnd => {
+                if let Some(start) = self.doc_start.take() {
+                    self.spans.push(Span { start, end: mark });
+                }
                 match self.doc_stack.len() {
                     // empty document
                     0 => self.docs.push(Yaml::BadValue),
@@ -121,77 +1099,111 @@ impl<'a> MarkedEventReceiver for YamlLoader<'a> {
                     _ => unreachable!(),
                 }
             }
-            Event::SequenceStart(aid) => {
-                self.doc_stack.push((Yaml::Array(Vec::new()), aid));
+            Event::SequenceStart(aid, tag) => {
+                self.open_anchor(aid, mark);
+                self.tag_stack.push(application_tag(&tag));
+                let capacity = mem::take(&mut self.capacity_hint);
+                self.doc_stack
+                    .push((Yaml::Array(Vec::with_capacity(capacity)), aid));
             }
             Event::SequenceEnd => {
-                let node = self.doc_stack.pop().unwrap();
-                self.insert_new_node(node);
+                let node = self.pop_collection();
+                self.close_anchor(node.1);
+                self.insert_new_node(node, mark);
             }
-            Event::MappingStart(aid) => {
-                self.doc_stack.push((Yaml::Hash(Hash::new()), aid));
-                self.key_stack.push(Yaml::BadValue);
+            Event::MappingStart(aid, tag) => {
+                self.open_anchor(aid, mark);
+                self.tag_stack.push(application_tag(&tag));
+                let capacity = mem::take(&mut self.capacity_hint);
+                self.doc_stack
+                    .push((Yaml::Hash(Hash::with_capacity(capacity)), aid));
+                self.key_stack.push((Yaml::BadValue, mark));
             }
             Event::MappingEnd => {
                 self.key_stack.pop().unwrap();
-                let node = self.doc_stack.pop().unwrap();
-                self.insert_new_node(node);
+                let node = self.pop_collection();
+                self.close_anchor(node.1);
+                self.insert_new_node(node, mark);
             }
             Event::Scalar(v, style, aid, tag) => {
-                if let Some(ref tag) = tag {
-                    let mut yaml = None;
-                    for parser in &self.scalar_parser {
-                        yaml = parser.parse_scalar(tag, &v);
-                    }
-                    if let Some(yaml) = yaml {
-                        self.insert_new_node((yaml, aid));
-                        return;
-                    }
+                if self.structure_only && !self.in_key() {
+                    self.insert_new_node((Yaml::Null, aid), mark);
+                    return;
+                }
+                if let Some(yaml) = tag.as_ref().and_then(|tag| self.parse_tagged(tag, &v)) {
+                    self.insert_new_node((yaml, aid), mark);
+                    return;
                 }
 
-                let node = if style != TScalarStyle::Plain {
+                let node = if let Some(tag) = application_tag(&tag) {
+                    Yaml::Tagged(tag, Box::new(Yaml::String(v)))
+                } else if style != TScalarStyle::Plain {
                     Yaml::String(v)
                 } else if let Some(TokenType::Tag(ref handle, ref suffix)) = tag {
                     // XXX tag:yaml.org,2002:
                     if handle == "!!" {
-                        match suffix.as_ref() {
-                            "bool" => {
-                                // "true" or "false"
-                                match v.parse::<bool>() {
-                                    Err(_) => Yaml::BadValue,
-                                    Ok(v) => Yaml::Boolean(v),
+                        if !matches!(suffix.as_str(), "bool" | "int" | "float" | "null" | "str") {
+                            self.warn(mark, WarningKind::UnknownTag(format!("!!{}", suffix)));
+                        }
+                        match resolve_core_tag(suffix, v, &self.number_format) {
+                            Ok(node) => node,
+                            Err(v) => {
+                                let info = format!("invalid value {:?} for tag !!{}", v, suffix);
+                                match self.bad_value_mode {
+                                    BadValueMode::BadValue => {
+                                        let tag = format!("!!{}", suffix);
+                                        self.warn(
+                                            mark,
+                                            WarningKind::InvalidValue { tag, value: v },
+                                        );
+                                        Yaml::BadValue
+                                    }
+                                    BadValueMode::Panic => panic!("{} at {:?}", info, mark),
+                                    BadValueMode::Error => {
+                                        self.error = Some(ScanError::new(mark, &info));
+                                        return;
+                                    }
                                 }
                             }
-                            "int" => match v.parse::<i64>() {
-                                Err(_) => Yaml::BadValue,
-                                Ok(v) => Yaml::Integer(v),
-                            },
-                            "float" => match parse_f64(&v) {
-                                Some(_) => Yaml::Real(v),
-                                None => Yaml::BadValue,
-                            },
-                            "null" => match v.as_ref() {
-                                "~" | "null" => Yaml::Null,
-                                _ => Yaml::BadValue,
-                            },
-                            _ => Yaml::String(v),
                         }
                     } else {
+                        if Tag::new(handle.as_str(), suffix.as_str()).is_core() {
+                            let tag = format!("!<{}{}>", handle, suffix);
+                            self.warn(mark, WarningKind::UnknownTag(tag));
+                        }
                         Yaml::String(v)
                     }
                 } else {
                     // Datatype is not specified, or unrecognized
-                    Yaml::from_str(&v)
+                    let node = if self.scanner_config.json && v == "-0" {
+                        // JSON text keeps the sign of `-0`, which an integer would lose
+                        Yaml::Real(v.clone())
+                    } else {
+                        plain_node(&v, self.schema, &self.number_format)
+                    };
+                    if self.schema == Schema::Core
+                        && !matches!(node, Yaml::Integer(_))
+                        && self.number_format.int_value(&v) == Some(None)
+                    {
+                        self.warn(mark, WarningKind::IntegerOverflow(node.clone()));
+                    }
+                    node
                 };
 
-                self.insert_new_node((node, aid));
+                self.insert_new_node((node, aid), mark);
             }
             Event::Alias(id) => {
                 let n = match self.anchor_map.get(&id) {
                     Some(v) => v.clone(),
                     None => Yaml::BadValue,
                 };
-                self.insert_new_node((n, 0));
+                if self.warning_handler.is_some() {
+                    let nodes = n.stats().nodes;
+                    if nodes >= LARGE_ALIAS_NODES {
+                        self.warn(mark, WarningKind::LargeAlias { nodes });
+                    }
+                }
+                self.insert_new_node((n, 0), mark);
             }
             _ => { /* ignore */ }
         }
@@ -200,20 +1212,228 @@ impl<'a> MarkedEventReceiver for YamlLoader<'a> {
 }
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum LoadError {
     IO(std::io::Error),
     Scan(ScanError),
-    Decode(std::borrow::Cow<'static, str>),
+    Decode(Cow<'static, str>),
+    /// An error annotated with the name of the input it occurred in, see
+    /// `LoadError::with_source_name`.
+    WithSource {
+        name: String,
+        error: Box<LoadError>,
+    },
 }
 
-impl From<std::io::Error> for LoadError {
+impl LoadError {
+    /// Annotate the error with the name of the input that failed, such as a
+    /// file path. The name is included when the error is displayed:
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::yaml::{LoadError, YamlDecoder};
+    ///
+    /// let err = YamlDecoder::read(b"a: b: c" as &[u8]).decode().unwrap_err();
+    /// let err = err.with_source_name("config/app.yaml");
+    /// assert_eq!(err.source_name(), Some("config/app.yaml"));
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "config/app.yaml:1:5: mapping values are not allowed in this context"
+    /// );
+    /// ```
+    pub fn with_source_name<S: Into<String>>(self, name: S) -> LoadError {
+        let error = match self {
+            LoadError::WithSource { error, .. } => error,
+            error => Box::new(error),
+        };
+        LoadError::WithSource {
+            name: name.into(),
+            error,
+        }
+    }
+
+    /// The name of the input the error occurred in, if known.
+    pub fn source_name(&self) -> Option<&str> {
+        match *self {
+            LoadError::WithSource { ref name, .. } => Some(name),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LoadError::IO(ref err) => fmt::Display::fmt(err, f),
+            LoadError::Scan(ref err) => fmt::Display::fmt(err, f),
+            LoadError::Decode(ref cause) => write!(f, "failed to decode input: {}", cause),
+            LoadError::WithSource {
+                ref name,
+                ref error,
+            } => match **error {
+                LoadError::Scan(ref err) => write!(
+                    f,
+                    "{}:{}:{}: {}",
+                    name,
+                    err.marker().line(),
+                    err.marker().col() + 1,
+                    err.info()
+                ),
+                ref err => write!(f, "{}: {}", name, err),
+            },
+        }
+    }
+}
+
+// The message of the wrapped error is part of the message of a `LoadError`,
+// so its source is the wrapped error's source rather than the error itself.
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            LoadError::IO(ref err) => err.source(),
+            LoadError::Scan(ref err) => err.source(),
+            LoadError::Decode(_) => None,
+            LoadError::WithSource { ref error, .. } => error.source(),
+        }
+    }
+}
+
+impl From<ScanError> for LoadError {
+    fn from(error: ScanError) -> Self {
+        LoadError::Scan(error)
+    }
+}
+
+impl From<std::io::Error> for LoadError {
     fn from(error: std::io::Error) -> Self {
         LoadError::IO(error)
     }
 }
 
 impl<'a> YamlLoader<'a> {
-    fn insert_new_node(&mut self, node: (Yaml, usize)) {
+    fn open_anchor(&mut self, aid: usize, mark: Marker) {
+        let anchor_mark = self.anchor_mark.take().unwrap_or(mark);
+        if aid > 0 {
+            self.open_anchors.push((aid, anchor_mark));
+        }
+    }
+
+    // Pop a finished collection, wrapping it in its application tag.
+    fn pop_collection(&mut self) -> (Yaml, usize) {
+        let (node, aid) = self.doc_stack.pop().unwrap();
+        match self.tag_stack.pop().unwrap() {
+            Some(tag) => (Yaml::Tagged(tag, Box::new(node)), aid),
+            None => (node, aid),
+        }
+    }
+
+    fn close_anchor(&mut self, aid: usize) {
+        if aid > 0 {
+            self.open_anchors.pop();
+        }
+    }
+
+    // The path to the next node, or `None` if it is a hash key or inside
+    // one.
+    fn next_path(&self) -> Option<YamlPath> {
+        let mut path = YamlPath::new();
+        let mut keys = self.key_stack.iter();
+        for (node, _) in &self.doc_stack {
+            match *node {
+                Yaml::Array(ref v) => path.push(PathElement::Index(v.len())),
+                Yaml::Hash(_) => {
+                    let (key, _) = keys.next().unwrap();
+                    if key.is_badvalue() {
+                        return None;
+                    }
+                    path.push(PathElement::Key(key.clone()));
+                }
+                _ => unreachable!(),
+            }
+        }
+        Some(path)
+    }
+
+    // Join the chunks of a block scalar with the `Scalar` event that ends
+    // it, which then starts where its first chunk does. Returns `None` for
+    // a chunk, which is kept until the scalar ends.
+    fn join_chunks(&mut self, ev: Event, mark: Marker) -> Option<(Event, Marker)> {
+        match ev {
+            Event::ScalarChunk(chunk) => {
+                match self.scalar_chunks {
+                    Some((ref mut value, _)) => value.push_str(&chunk),
+                    None => self.scalar_chunks = Some((chunk, mark)),
+                }
+                None
+            }
+            Event::Scalar(v, style, aid, tag) => match self.scalar_chunks.take() {
+                Some((mut value, start)) => {
+                    value.push_str(&v);
+                    Some((Event::Scalar(value, style, aid, tag), start))
+                }
+                None => Some((Event::Scalar(v, style, aid, tag), mark)),
+            },
+            ev => Some((ev, mark)),
+        }
+    }
+
+    // Where the key starts, if the node being loaded is the value of a hash
+    // entry.
+    fn value_key_mark(&self) -> Option<Marker> {
+        match (self.doc_stack.last(), self.key_stack.last()) {
+            (Some((Yaml::Hash(_), _)), Some((key, mark))) if !key.is_badvalue() => Some(*mark),
+            _ => None,
+        }
+    }
+
+    // Whether the next node is a hash key.
+    fn at_key(&self) -> bool {
+        matches!(self.doc_stack.last(), Some((Yaml::Hash(_), _)))
+            && self
+                .key_stack
+                .last()
+                .is_some_and(|(key, _)| key.is_badvalue())
+    }
+
+    // Whether the next node is a hash key or inside one.
+    fn in_key(&self) -> bool {
+        let mut keys = self.key_stack.iter();
+        self.doc_stack.iter().any(|(node, _)| {
+            node.is_hash() && keys.next().is_some_and(|(key, _)| key.is_badvalue())
+        })
+    }
+
+    // Parse a tagged scalar with the registered parsers.
+    fn parse_tagged(&self, tag: &TokenType, v: &str) -> Option<Yaml> {
+        if let TokenType::Tag(ref handle, ref suffix) = *tag {
+            let parser = self.tag_parsers.get(handle).and_then(|p| p.get(suffix));
+            if let Some(yaml) = parser.and_then(|p| p.parse_scalar(tag, v)) {
+                return Some(yaml);
+            }
+        }
+        let mut yaml = None;
+        for parser in &self.scalar_parser {
+            yaml = parser.parse_scalar(tag, v);
+        }
+        yaml
+    }
+
+    fn warn(&self, mark: Marker, kind: WarningKind) {
+        if let Some(handler) = self.warning_handler {
+            handler(&Warning {
+                mark,
+                key_mark: self.value_key_mark(),
+                kind,
+            });
+        }
+    }
+
+    // Add a finished node to its parent. `mark` is where the node starts,
+    // or for a collection where it ends.
+    fn insert_new_node(&mut self, mut node: (Yaml, usize), mark: Marker) {
+        if self.shrink_to_fit {
+            shrink_node(&mut node.0);
+        }
         // valid anchor id starts from 1
         if node.1 > 0 {
             self.anchor_map.insert(node.1, node.0.clone());
@@ -222,22 +1442,39 @@ impl<'a> YamlLoader<'a> {
             self.doc_stack.push(node);
         } else {
             let parent = self.doc_stack.last_mut().unwrap();
+            let mut duplicate = None;
             match *parent {
                 (Yaml::Array(ref mut v), _) => v.push(node.0),
                 (Yaml::Hash(ref mut h), _) => {
-                    let cur_key = self.key_stack.last_mut().unwrap();
+                    let (cur_key, key_mark) = self.key_stack.last_mut().unwrap();
                     // current node is a key
                     if cur_key.is_badvalue() {
-                        *cur_key = node.0;
+                        let key =
+                            self.key_coercion
+                                .coerce(node.0, self.schema, &self.number_format);
+                        *cur_key = match key {
+                            Yaml::String(v) if self.intern_keys => {
+                                let cache = self.string_cache.unwrap_or(&self.key_cache);
+                                Yaml::Interned(cache.intern(&v))
+                            }
+                            key => key,
+                        };
+                        *key_mark = mark;
                     // current node is a value
                     } else {
                         let mut newkey = Yaml::BadValue;
                         mem::swap(&mut newkey, cur_key);
+                        if self.warning_handler.is_some() && h.contains_key(&newkey) {
+                            duplicate = Some((newkey.clone(), *key_mark));
+                        }
                         h.insert(newkey, node.0);
                     }
                 }
                 _ => unreachable!(),
             }
+            if let Some((key, mark)) = duplicate {
+                self.warn(mark, WarningKind::DuplicateKey(key));
+            }
         }
     }
 
@@ -245,543 +1482,4294 @@ impl<'a> YamlLoader<'a> {
         self.scalar_parser.push(parser);
     }
 
-    pub fn load_from_str(source: &str) -> Result<Vec<Yaml>, ScanError> {
-        YamlLoader::new().parse_from_str(source)
+    /// Parse scalars tagged with `handle` and `suffix` using `parser`, which
+    /// then doesn't have to check the tag itself. The handle is the one the
+    /// scanner resolves, so `!`, `!!` or the prefix of a `%TAG` directive.
+    ///
+    /// Parsers registered for a tag are tried before those added with
+    /// `register_scalar_parser`, and registering a tag again replaces its
+    /// parser. If `parser` returns `None` the scalar is loaded as usual.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::scanner::TokenType;
+    /// use yaml_rust::yaml::YamlScalarParser;
+    /// use yaml_rust::{Yaml, YamlLoader};
+    ///
+    /// struct Upper;
+    ///
+    /// impl YamlScalarParser for Upper {
+    ///     fn parse_scalar(&self, _: &TokenType, value: &str) -> Option<Yaml> {
+    ///         Some(Yaml::String(value.to_uppercase()))
+    ///     }
+    /// }
+    ///
+    /// let mut loader = YamlLoader::new();
+    /// loader.register_tag_parser("!", "upper", &Upper);
+    /// let docs = loader.parse_from_str("[!upper a, !lower b]").unwrap();
+    /// assert_eq!(docs[0][0].as_str(), Some("A"));
+    /// assert!(docs[0][1].is_tagged());
+    /// ```
+    pub fn register_tag_parser(
+        &mut self,
+        handle: &str,
+        suffix: &str,
+        parser: &'a dyn YamlScalarParser,
+    ) -> &mut Self {
+        self.tag_parsers
+            .entry(handle.to_owned())
+            .or_default()
+            .insert(suffix.to_owned(), parser);
+        self
     }
 
-    pub fn new() -> YamlLoader<'a> {
-        YamlLoader {
-            docs: Vec::new(),
-            doc_stack: Vec::new(),
-            key_stack: Vec::new(),
-            anchor_map: BTreeMap::new(),
-            scalar_parser: Vec::new(),
-        }
+    /// Release the excess capacity of every string and collection as it is
+    /// loaded.
+    ///
+    /// Scalars produced by the scanner and collections grown during parsing
+    /// typically over-allocate. Shrinking costs a reallocation per node, so it
+    /// is off by default; enable it when loaded documents are kept around for
+    /// a long time.
+    pub fn shrink_to_fit(&mut self, shrink_to_fit: bool) -> &mut Self {
+        self.shrink_to_fit = shrink_to_fit;
+        self
     }
 
-    pub fn parse_from_str(mut self, source: &str) -> Result<Vec<Yaml>, ScanError> {
-        let mut parser = Parser::new(source.chars());
-        parser.load(&mut self, true)?;
-        Ok(self.docs)
+    /// Load string hash keys as `Yaml::Interned`, so that equal keys in the
+    /// loaded documents share one string. This saves memory on documents
+    /// that repeat their keys, such as lists of records. Interned keys
+    /// equal, and read as, `Yaml::String` keys.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::{Yaml, YamlLoader};
+    ///
+    /// let mut loader = YamlLoader::new();
+    /// loader.intern_keys(true);
+    /// let docs = loader.parse_from_str("[{name: a}, {name: b}]").unwrap();
+    /// let keys: Vec<_> = docs[0].as_vec().unwrap().iter().map(|item| {
+    ///     match item.as_hash().unwrap().keys().next() {
+    ///         Some(Yaml::Interned(key)) => key.clone(),
+    ///         _ => unreachable!(),
+    ///     }
+    /// }).collect();
+    /// assert!(std::sync::Arc::ptr_eq(&keys[0], &keys[1]));
+    /// assert_eq!(docs[0][1]["name"].as_str(), Some("b"));
+    /// ```
+    pub fn intern_keys(&mut self, intern_keys: bool) -> &mut Self {
+        self.intern_keys = intern_keys;
+        self
     }
-}
-
-pub struct YamlDecoder<T: std::io::Read> {
-    source: T,
-    trap: encoding::types::DecoderTrap,
-}
 
-impl<T: std::io::Read> YamlDecoder<T> {
-    pub fn read(source: T) -> YamlDecoder<T> {
-        YamlDecoder {
-            source,
-            trap: encoding::DecoderTrap::Strict,
-        }
+    /// Intern hash keys as `intern_keys` does, taking their strings from
+    /// `cache` and adding new ones to it. Loaders that share a cache share
+    /// their keys, which saves memory when many similar files are loaded
+    /// and kept.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::yaml::StringCache;
+    /// use yaml_rust::YamlLoader;
+    ///
+    /// let cache = StringCache::new();
+    /// for source in ["name: a\nvalue: 1", "name: b\nkind: c"] {
+    ///     let mut loader = YamlLoader::new();
+    ///     loader.string_cache(&cache);
+    ///     let docs = loader.parse_from_str(source).unwrap();
+    ///     assert_eq!(docs.len(), 1);
+    /// }
+    /// assert_eq!(cache.len(), 3);
+    /// ```
+    pub fn string_cache(&mut self, cache: &'a StringCache) -> &mut Self {
+        self.intern_keys = true;
+        self.string_cache = Some(cache);
+        self
     }
 
-    pub fn encoding_trap(&mut self, trap: encoding::types::DecoderTrap) -> &mut Self {
-        self.trap = trap;
+    /// Load only the structure of documents: hashes with their keys, and
+    /// arrays, with every scalar that is not part of a key loaded as
+    /// `Yaml::Null`, such as to find which fields many files have. Keys that
+    /// are collections are loaded whole, so that they stay distinct. Values
+    /// are dropped as they are parsed and are not resolved or checked
+    /// against their tags.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::{Yaml, YamlLoader};
+    ///
+    /// let mut loader = YamlLoader::new();
+    /// loader.structure_only(true);
+    /// let doc = loader.parse_from_str("name: web\nports: [80, !!int x]").unwrap().remove(0);
+    /// assert_eq!(doc, YamlLoader::load_from_str("{name: ~, ports: [~, ~]}").unwrap()[0]);
+    /// ```
+    pub fn structure_only(&mut self, structure_only: bool) -> &mut Self {
+        self.structure_only = structure_only;
         self
     }
 
-    pub fn decode(&mut self) -> Result<Vec<Yaml>, LoadError> {
-        let mut buffer = Vec::new();
-        self.source.read_to_end(&mut buffer)?;
-
-        // Decodes the input buffer using either UTF-8, UTF-16LE or UTF-16BE depending on the BOM codepoint.
-        // If the buffer doesn't start with a BOM codepoint, it will use a fallback encoding obtained by
-        // detect_utf16_endianness.
-        let (res, _) =
-            encoding::types::decode(&buffer, self.trap, detect_utf16_endianness(&buffer));
-        let s = res.map_err(LoadError::Decode)?;
-        YamlLoader::load_from_str(&s).map_err(LoadError::Scan)
+    /// Choose how scalars with a value that is invalid for their tag are
+    /// loaded. Defaults to `BadValueMode::BadValue`.
+    pub fn bad_value_mode(&mut self, mode: BadValueMode) -> &mut Self {
+        self.bad_value_mode = mode;
+        self
     }
-}
 
-/// The encoding crate knows how to tell apart UTF-8 from UTF-16LE and utf-16BE, when the
-/// bytestream starts with BOM codepoint.
-/// However, it doesn't even attempt to guess the UTF-16 endianness of the input bytestream since
-/// in the general case the bytestream could start with a codepoint that uses both bytes.
-///
-/// The YAML-1.2 spec mandates that the first character of a YAML document is an ASCII character.
-/// This allows the encoding to be deduced by the pattern of null (#x00) characters.
-//
-/// See spec at https://yaml.org/spec/1.2/spec.html#id2771184
-fn detect_utf16_endianness(b: &[u8]) -> encoding::types::EncodingRef {
-    if b.len() > 1 && (b[0] != b[1]) {
-        if b[0] == 0 {
-            return encoding::all::UTF_16BE;
-        } else if b[1] == 0 {
-            return encoding::all::UTF_16LE;
-        }
+    /// Choose how hash keys are normalized. Defaults to `KeyCoercion::None`.
+    pub fn key_coercion(&mut self, coercion: KeyCoercion) -> &mut Self {
+        self.key_coercion = coercion;
+        self
     }
-    encoding::all::UTF_8
-}
 
-macro_rules! define_as (
-    ($name:ident, $t:ident, $yt:ident) => (
-pub fn $name(&self) -> Option<$t> {
-    match *self {
-        Yaml::$yt(v) => Some(v),
-        _ => None
+    /// Choose how plain scalars and `!!int` values that look like numbers
+    /// are read with `Schema::Core`. Defaults to the core schema.
+    pub fn number_format(&mut self, format: NumberFormat) -> &mut Self {
+        self.number_format = format;
+        self
     }
-}
-    );
-);
 
-macro_rules! define_as_ref (
-    ($name:ident, $t:ty, $yt:ident) => (
-pub fn $name(&self) -> Option<$t> {
-    match *self {
-        Yaml::$yt(ref v) => Some(v),
-        _ => None
+    /// Choose how plain scalars without a tag are resolved. Defaults to
+    /// `Schema::Core`.
+    pub fn schema(&mut self, schema: Schema) -> &mut Self {
+        self.schema = schema;
+        self
     }
-}
-    );
-);
 
-macro_rules! define_into (
-    ($name:ident, $t:ty, $yt:ident) => (
-pub fn $name(self) -> Option<$t> {
-    match self {
-        Yaml::$yt(v) => Some(v),
-        _ => None
+    /// Set the limits enforced while parsing, see `ScannerConfig`.
+    pub fn scanner_config(&mut self, config: ScannerConfig) -> &mut Self {
+        self.scanner_config = config;
+        self
     }
-}
-    );
-);
-
-impl Yaml {
-    define_as!(as_bool, bool, Boolean);
-    define_as!(as_i64, i64, Integer);
 
-    define_as_ref!(as_str, &str, String);
-    define_as_ref!(as_hash, &Hash, Hash);
-    define_as_ref!(as_vec, &Array, Array);
+    /// Choose what a stream without documents loads as. Defaults to
+    /// `EmptyStreamMode::NoDocuments`.
+    pub fn empty_stream_mode(&mut self, mode: EmptyStreamMode) -> &mut Self {
+        self.empty_stream_mode = mode;
+        self
+    }
 
-    define_into!(into_bool, bool, Boolean);
-    define_into!(into_i64, i64, Integer);
-    define_into!(into_string, String, String);
-    define_into!(into_hash, Hash, Hash);
-    define_into!(into_vec, Array, Array);
+    /// Reject documents with keys that break `rules`. Keys are not checked
+    /// by default.
+    pub fn key_rules(&mut self, rules: KeyRules) -> &mut Self {
+        self.key_rules = Some(rules);
+        self
+    }
 
-    pub fn is_null(&self) -> bool {
-        matches!(*self, Yaml::Null)
+    /// Choose how anchors that are defined twice in a document are
+    /// handled. Defaults to `AnchorRedefinition::Shadow`.
+    pub fn anchor_redefinition(&mut self, policy: AnchorRedefinition) -> &mut Self {
+        self.anchor_redefinition = policy;
+        self
     }
 
-    pub fn is_badvalue(&self) -> bool {
-        matches!(*self, Yaml::BadValue)
+    /// Choose what happens to documents declared with a `%YAML` version
+    /// other than 1.1 or 1.2. Defaults to `VersionPolicy::Accept`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::yaml::VersionPolicy;
+    /// use yaml_rust::YamlLoader;
+    ///
+    /// let s = "%YAML 1.3\n--- a";
+    /// assert!(YamlLoader::load_from_str(s).is_ok());
+    /// let mut loader = YamlLoader::new();
+    /// loader.version_policy(VersionPolicy::Error);
+    /// let err = loader.parse_from_str(s).unwrap_err();
+    /// assert_eq!(err.info(), "found unsupported YAML version 1.3");
+    /// ```
+    pub fn version_policy(&mut self, policy: VersionPolicy) -> &mut Self {
+        self.version_policy = policy;
+        self
     }
 
-    pub fn is_array(&self) -> bool {
-        matches!(*self, Yaml::Array(_))
+    /// Call `handler` with every `Warning` found while loading, for
+    /// problems that do not stop it. No warnings are reported by default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use std::cell::RefCell;
+    /// use yaml_rust::yaml::Warning;
+    /// use yaml_rust::YamlLoader;
+    ///
+    /// let warnings = RefCell::new(Vec::new());
+    /// let handler = |w: &Warning| warnings.borrow_mut().push(w.to_string());
+    /// let mut loader = YamlLoader::new();
+    /// loader.on_warning(&handler);
+    /// loader.parse_from_str("a: 1\nb: !!int x\na: 2").unwrap();
+    /// assert_eq!(
+    ///     *warnings.borrow(),
+    ///     [
+    ///         "invalid value \"x\" for tag !!int at line 2 column 10",
+    ///         "duplicate key String(\"a\") at line 3 column 1",
+    ///     ]
+    /// );
+    /// ```
+    pub fn on_warning(&mut self, handler: &'a dyn Fn(&Warning)) -> &mut Self {
+        self.warning_handler = Some(handler);
+        self
     }
 
-    pub fn as_f64(&self) -> Option<f64> {
-        match *self {
-            Yaml::Real(ref v) => parse_f64(v),
-            _ => None,
-        }
+    /// Start configuring a loader, see `YamlLoaderBuilder`.
+    pub fn builder() -> YamlLoaderBuilder<'a> {
+        YamlLoaderBuilder::new()
     }
 
-    pub fn into_f64(self) -> Option<f64> {
-        match self {
-            Yaml::Real(ref v) => parse_f64(v),
-            _ => None,
-        }
+    pub fn load_from_str(source: &str) -> Result<Vec<Yaml>, ScanError> {
+        YamlLoader::new().parse_from_str(source)
     }
 
-    /// If a value is null or otherwise bad (see variants), consume it and
-    /// replace it with a given value `other`. Otherwise, return self unchanged.
+    /// Load JSON text, rejecting anything that is not JSON, see
+    /// `ScannerConfig::json`.
+    ///
+    /// # Examples
     ///
     /// ```
     /// # extern crate yaml_rust_davvid as yaml_rust;
-    /// use yaml_rust::yaml::Yaml;
+    /// use yaml_rust::{Yaml, YamlLoader};
     ///
-    /// assert_eq!(Yaml::BadValue.or(Yaml::Integer(3)),  Yaml::Integer(3));
-    /// assert_eq!(Yaml::Integer(3).or(Yaml::BadValue),  Yaml::Integer(3));
+    /// let doc = YamlLoader::load_from_json_str(r#"{"a": [1, "\ud83d\ude00"]}"#).unwrap();
+    /// assert_eq!(doc["a"][1].as_str(), Some("😀"));
+    /// assert!(YamlLoader::load_from_json_str("{a: 1}").is_err());
     /// ```
-    pub fn or(self, other: Self) -> Self {
-        match self {
-            Yaml::BadValue | Yaml::Null => other,
-            this => this,
-        }
+    pub fn load_from_json_str(source: &str) -> Result<Yaml, ScanError> {
+        let mut loader = YamlLoader::new();
+        loader
+            .scanner_config(ScannerConfig {
+                json: true,
+                ..ScannerConfig::default()
+            })
+            .schema(Schema::Json);
+        let docs = loader.parse_from_str(source)?;
+        Ok(docs.into_iter().next().unwrap_or(Yaml::BadValue))
     }
 
-    /// See `or` for behavior. This performs the same operations, but with
-    /// borrowed values for less linear pipelines.
-    pub fn borrowed_or<'a>(&'a self, other: &'a Self) -> &'a Self {
-        match self {
-            Yaml::BadValue | Yaml::Null => other,
-            this => this,
-        }
+    /// Load all documents from a file, detecting its encoding with
+    /// `YamlDecoder`. Errors are annotated with the path.
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Vec<Yaml>, LoadError> {
+        let path = path.as_ref();
+        File::open(path)
+            .map_err(LoadError::from)
+            .and_then(|file| YamlDecoder::read(file).decode())
+            .map_err(|err| err.with_source_name(path.display().to_string()))
     }
-}
 
-#[cfg_attr(feature = "cargo-clippy", allow(should_implement_trait))]
-impl Yaml {
-    // Not implementing FromStr because there is no possibility of Error.
-    // This function falls back to Yaml::String if nothing else matches.
-    pub fn from_str(v: &str) -> Yaml {
-        if let Some(value) = v.strip_prefix("0x") {
-            if let Ok(i) = i64::from_str_radix(value, 16) {
-                return Yaml::Integer(i);
-            }
-        }
-        if let Some(value) = v.strip_prefix("0o") {
-            if let Ok(i) = i64::from_str_radix(value, 8) {
-                return Yaml::Integer(i);
-            }
-        }
-        if let Some(value) = v.strip_prefix('+') {
-            if let Ok(i) = value.parse::<i64>() {
-                return Yaml::Integer(i);
-            }
-        }
-        match v {
-            "~" | "null" => Yaml::Null,
-            "true" => Yaml::Boolean(true),
-            "false" => Yaml::Boolean(false),
-            _ if v.parse::<i64>().is_ok() => Yaml::Integer(v.parse::<i64>().unwrap()),
-            // try parsing as f64
-            _ if parse_f64(v).is_some() => Yaml::Real(v.to_owned()),
-            _ => Yaml::String(v.to_owned()),
+    pub fn new() -> YamlLoader<'a> {
+        YamlLoader {
+            docs: Vec::new(),
+            doc_stack: Vec::new(),
+            key_stack: Vec::new(),
+            anchor_map: BTreeMap::new(),
+            open_anchors: Vec::new(),
+            tag_stack: Vec::new(),
+            scalar_parser: Vec::new(),
+            tag_parsers: HashMap::new(),
+            error: None,
+            doc_start: None,
+            spans: Vec::new(),
+            shrink_to_fit: false,
+            intern_keys: false,
+            structure_only: false,
+            bad_value_mode: BadValueMode::BadValue,
+            key_coercion: KeyCoercion::None,
+            number_format: NumberFormat::default(),
+            schema: Schema::Core,
+            scanner_config: ScannerConfig::default(),
+            empty_stream_mode: EmptyStreamMode::NoDocuments,
+            key_rules: None,
+            anchor_redefinition: AnchorRedefinition::Shadow,
+            version_policy: VersionPolicy::Accept,
+            warning_handler: None,
+            anchors: None,
+            versions: None,
+            roots: None,
+            capacity_hint: 0,
+            record_time: false,
+            anchor_mark: None,
+            key_cache: StringCache::default(),
+            string_cache: None,
+            scalar_chunks: None,
         }
     }
-}
 
-static BAD_VALUE: Yaml = Yaml::BadValue;
-impl<'a> Index<&'a str> for Yaml {
-    type Output = Yaml;
+    pub fn parse_from_str(self, source: &str) -> Result<Vec<Yaml>, ScanError> {
+        self.parse_from_chars(source.chars())
+    }
 
-    fn index(&self, idx: &'a str) -> &Yaml {
-        let key = Yaml::String(idx.to_owned());
-        match self.as_hash() {
-            Some(h) => h.get(&key).unwrap_or(&BAD_VALUE),
-            None => &BAD_VALUE,
+    /// Load all documents along with the span each one occupies in `source`,
+    /// so a single document can be extracted or replaced while leaving the
+    /// rest of the stream untouched. A span starts at the document's `---`
+    /// or first token, and ends at its `...` or the token following it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::YamlLoader;
+    ///
+    /// let s = "a: 1\n---\nb: 2\n";
+    /// let docs = YamlLoader::load_from_str_with_spans(s).unwrap();
+    /// assert_eq!(docs[0].1.slice(s), "a: 1\n");
+    /// assert_eq!(docs[1].1.slice(s), "---\nb: 2\n");
+    /// ```
+    pub fn load_from_str_with_spans(source: &str) -> Result<Vec<(Yaml, Span)>, ScanError> {
+        YamlLoader::new().parse_from_str_with_spans(source)
+    }
+
+    /// Like `load_from_str_with_spans`, with this loader's options.
+    pub fn parse_from_str_with_spans(
+        mut self,
+        source: &str,
+    ) -> Result<Vec<(Yaml, Span)>, ScanError> {
+        self.load_chars(source.chars())?;
+        Ok(self.docs.into_iter().zip(self.spans).collect())
+    }
+
+    /// Like `parse_from_str`, also returning what the parser did, to help
+    /// find out why an input is slow to load. The parse is timed, see
+    /// `Parser::record_time`.
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::YamlLoader;
+    ///
+    /// let (docs, stats) = YamlLoader::new().parse_from_str_with_stats("[[[x]]]").unwrap();
+    /// assert_eq!(docs.len(), 1);
+    /// assert_eq!(stats.max_depth, 3);
+    /// ```
+    pub fn parse_from_str_with_stats(
+        mut self,
+        source: &str,
+    ) -> Result<(Vec<Yaml>, ParserStats), ScanError> {
+        self.record_time = true;
+        let stats = self.load_chars(source.chars())?;
+        Ok((self.docs, stats))
+    }
+
+    /// Load all documents along with the anchors defined in each one, in
+    /// the order they appear, for tools that explain or preserve how nodes
+    /// are reused. A name that is defined twice in a document is listed
+    /// twice.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::{Yaml, YamlLoader};
+    ///
+    /// let s = "base: &base {port: 80}\nweb: *base\nports: [&http 8080]";
+    /// let docs = YamlLoader::new().parse_from_str_with_anchors(s).unwrap();
+    /// let anchors = &docs[0].1;
+    /// assert_eq!(anchors[0].name, "base");
+    /// assert_eq!(anchors[0].path.as_ref().unwrap().to_string(), "$.base");
+    /// assert_eq!(anchors[0].value["port"], Yaml::Integer(80));
+    /// assert_eq!(anchors[1].path.as_ref().unwrap().to_string(), "$.ports[0]");
+    /// ```
+    pub fn parse_from_str_with_anchors(
+        mut self,
+        source: &str,
+    ) -> Result<Vec<(Yaml, Vec<Anchor>)>, ScanError> {
+        self.anchors = Some(Vec::new());
+        self.load_chars(source.chars())?;
+        let mut anchors = self.anchors.take().unwrap();
+        anchors.resize_with(self.docs.len(), Vec::new);
+        let mut values = mem::take(&mut self.anchor_map);
+        let anchors = anchors.into_iter().map(|doc| {
+            doc.into_iter()
+                .map(|(aid, anchor)| Anchor {
+                    value: values.remove(&aid).unwrap_or(Yaml::BadValue),
+                    ..anchor
+                })
+                .collect()
+        });
+        Ok(self.docs.into_iter().zip(anchors).collect())
+    }
+
+    /// Load all documents along with the version each one declares with a
+    /// `%YAML` directive.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::YamlLoader;
+    ///
+    /// let s = "%YAML 1.1\n--- on\n...\n--- off";
+    /// let docs = YamlLoader::new().parse_from_str_with_versions(s).unwrap();
+    /// let versions: Vec<_> = docs.iter().map(|doc| doc.1).collect();
+    /// assert_eq!(versions, [Some((1, 1)), None]);
+    /// ```
+    pub fn parse_from_str_with_versions(
+        mut self,
+        source: &str,
+    ) -> Result<Vec<(Yaml, Option<YamlVersion>)>, ScanError> {
+        self.versions = Some(Vec::new());
+        self.load_chars(source.chars())?;
+        let mut versions = self.versions.take().unwrap();
+        versions.resize(self.docs.len(), None);
+        Ok(self.docs.into_iter().zip(versions).collect())
+    }
+
+    // Like `parse_from_str_with_spans`, also returning the layout of each
+    // document's root.
+    pub(crate) fn parse_from_str_with_roots(
+        mut self,
+        source: &str,
+    ) -> Result<Vec<(Yaml, Span, RootLayout)>, ScanError> {
+        self.roots = Some(Vec::new());
+        self.load_chars(source.chars())?;
+        let roots = self.roots.take().unwrap();
+        Ok(self
+            .docs
+            .into_iter()
+            .zip(self.spans)
+            .zip(roots)
+            .map(|((doc, span), root)| (doc, span, root))
+            .collect())
+    }
+
+    fn record_root(&mut self, ev: &Event, mark: Marker) {
+        let roots = match self.roots {
+            Some(ref mut roots) => roots,
+            None => return,
+        };
+        let aid = match *ev {
+            Event::DocumentStart => {
+                roots.push(RootLayout::default());
+                return;
+            }
+            Event::Scalar(_, _, aid, _)
+            | Event::SequenceStart(aid, _)
+            | Event::MappingStart(aid, _) => aid,
+            Event::Alias(_) => 1,
+            _ => return,
+        };
+        let root = match roots.last_mut() {
+            Some(root) => root,
+            None => return,
+        };
+        root.anchors |= aid > 0;
+        match self.doc_stack.len() {
+            0 => root.start = Some(mark),
+            1 if self.doc_stack[0].0.is_hash()
+                && self.key_stack.last().is_some_and(|k| k.0.is_badvalue()) =>
+            {
+                root.keys.push(mark)
+            }
+            _ => {}
+        }
+    }
+
+    /// Load all documents from a stream of characters.
+    pub fn parse_from_chars<T: Iterator<Item = char>>(
+        mut self,
+        source: T,
+    ) -> Result<Vec<Yaml>, ScanError> {
+        self.load_chars(source)?;
+        Ok(self.docs)
+    }
+
+    /// Load all documents from recorded events, such as those of a cached
+    /// parse that have been transformed.
+    ///
+    /// Events are checked as a parse is: recursive aliases and redefined
+    /// anchors are found, and the empty stream mode applies. Recorded events
+    /// don't carry directives or anchor names, so the version policy does
+    /// not apply and anchors are never redefinitions.
+    pub fn parse_from_events(mut self, log: &EventLog) -> Result<Vec<Yaml>, ScanError> {
+        self.load_events(&mut RecordedEvents {
+            events: log.events().iter(),
+            end: Marker::new(0, 0, 1, 0),
+        })?;
+        Ok(self.docs)
+    }
+
+    fn load_chars<T: Iterator<Item = char>>(
+        &mut self,
+        source: T,
+    ) -> Result<ParserStats, ScanError> {
+        let mut parser = Parser::new_with_config(source, self.scanner_config);
+        parser.record_time(self.record_time);
+        self.load_events(&mut parser)
+    }
+
+    // Load the events of `source`, as every way of loading does.
+    fn load_events<S: EventSource>(&mut self, source: &mut S) -> Result<ParserStats, ScanError> {
+        let mut names = AnchorNames::default();
+        // where the block scalar whose chunks are being loaded starts
+        let mut chunks_start = None;
+        loop {
+            let (ev, mut mark) = source.next_event()?;
+            match ev {
+                Event::ScalarChunk(_) => {
+                    chunks_start.get_or_insert(mark);
+                    self.on_event(ev, mark);
+                    continue;
+                }
+                Event::Scalar(..) => mark = chunks_start.take().unwrap_or(mark),
+                _ => {}
+            }
+            let end = ev == Event::StreamEnd;
+            self.load_event(ev, mark, source, &mut names)?;
+            if end {
+                if self.docs.is_empty() {
+                    match self.empty_stream_mode {
+                        EmptyStreamMode::NoDocuments => {}
+                        EmptyStreamMode::Null => {
+                            self.docs.push(Yaml::Null);
+                            self.spans.push(Span {
+                                start: mark,
+                                end: mark,
+                            });
+                        }
+                        EmptyStreamMode::Error => {
+                            return Err(ScanError::new(mark, "found no documents in the stream"))
+                        }
+                    }
+                }
+                return Ok(source.stats());
+            }
+        }
+    }
+
+    // Check and load one event of `source`.
+    fn load_event<S: EventSource>(
+        &mut self,
+        mut ev: Event,
+        mark: Marker,
+        source: &S,
+        names: &mut AnchorNames,
+    ) -> Result<(), ScanError> {
+        self.check_event(&mut ev, mark, source, names)?;
+        self.record_root(&ev, mark);
+        self.capacity_hint = source.capacity_hint().unwrap_or(0);
+        self.anchor_mark = match ev {
+            Event::SequenceStart(aid, _) | Event::MappingStart(aid, _) if aid > 0 => {
+                source.anchor_mark(aid)
+            }
+            _ => None,
+        };
+        self.on_event(ev, mark);
+        match self.error.take() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    // Apply the version policy and anchor redefinition policy to the next
+    // event, record its anchor, and fail on a recursive alias.
+    fn check_event<S: EventSource>(
+        &mut self,
+        ev: &mut Event,
+        mark: Marker,
+        source: &S,
+        names: &mut AnchorNames,
+    ) -> Result<(), ScanError> {
+        match *ev {
+            Event::DocumentStart => {
+                names.defined.clear();
+                names.redirects.clear();
+                let version = source.document_version();
+                if let Some(ref mut versions) = self.versions {
+                    versions.push(version);
+                }
+                match version {
+                    Some((major, minor)) if !is_supported_version(major, minor) => {
+                        let mark = source.document_version_mark().unwrap_or(mark);
+                        match self.version_policy {
+                            VersionPolicy::Accept => {}
+                            VersionPolicy::Warn => {
+                                self.warn(mark, WarningKind::UnsupportedVersion { major, minor })
+                            }
+                            VersionPolicy::Error => {
+                                let info =
+                                    format!("found unsupported YAML version {}.{}", major, minor);
+                                return Err(ScanError::new(mark, &info));
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+                if let Some(ref mut anchors) = self.anchors {
+                    anchors.push(Vec::new());
+                }
+            }
+            Event::Scalar(_, _, aid, _)
+            | Event::SequenceStart(aid, _)
+            | Event::MappingStart(aid, _)
+                if aid > 0 =>
+            {
+                let name = source.anchor_name(aid).map(str::to_owned);
+                let path = self.anchors.as_ref().map(|_| self.next_path());
+                if let (Some(anchors), Some(path)) = (self.anchors.as_mut(), path) {
+                    let anchor = Anchor {
+                        name: name.clone().unwrap_or_default(),
+                        path,
+                        mark,
+                        value: Yaml::BadValue,
+                    };
+                    anchors.last_mut().unwrap().push((aid, anchor));
+                }
+                // recorded events have no names to find redefinitions by
+                if let Some(name) = name {
+                    self.define_anchor(name, aid, mark, names)?;
+                }
+            }
+            Event::Alias(ref mut id) => {
+                if let Some(&first_id) = names.redirects.get(id) {
+                    *id = first_id;
+                }
+                // an alias to a node that is still being loaded can't be
+                // represented
+                if let Some(&(_, anchor_mark)) = self.open_anchors.iter().find(|a| a.0 == *id) {
+                    let name = names
+                        .defined
+                        .iter()
+                        .find(|&(_, &(first_id, _))| first_id == *id)
+                        .map(|(name, _)| name.as_str())
+                        .or_else(|| source.anchor_name(*id));
+                    let info = match name {
+                        Some(name) => format!(
+                            "found recursive alias *{}, anchor &{} is at line {} column {}",
+                            name,
+                            name,
+                            anchor_mark.line(),
+                            anchor_mark.col() + 1
+                        ),
+                        None => format!(
+                            "found recursive alias, its anchor is at line {} column {}",
+                            anchor_mark.line(),
+                            anchor_mark.col() + 1
+                        ),
+                    };
+                    return Err(ScanError::new(mark, &info));
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // Record the definition of an anchor, applying the redefinition policy
+    // if it was defined before.
+    fn define_anchor(
+        &self,
+        name: String,
+        aid: usize,
+        mark: Marker,
+        names: &mut AnchorNames,
+    ) -> Result<(), ScanError> {
+        match names.defined.get(&name) {
+            None => {
+                names.defined.insert(name, (aid, mark));
+            }
+            Some(&(first_id, first_mark)) => match self.anchor_redefinition {
+                AnchorRedefinition::Shadow => {}
+                AnchorRedefinition::FirstWins => {
+                    names.redirects.insert(aid, first_id);
+                }
+                AnchorRedefinition::Error => {
+                    let info = format!(
+                        "anchor &{} is redefined, its first node is at line {} column {}",
+                        name,
+                        first_mark.line(),
+                        first_mark.col() + 1
+                    );
+                    return Err(ScanError::new(mark, &info));
+                }
+            },
+        }
+        Ok(())
+    }
+}
+
+// The anchors defined in the document being loaded: name -> (anchor_id,
+// marker) of the first definition, and the ids of later definitions that
+// aliases resolve through instead.
+#[derive(Default)]
+struct AnchorNames {
+    defined: BTreeMap<String, (usize, Marker)>,
+    redirects: BTreeMap<usize, usize>,
+}
+
+// Where `YamlLoader` takes the events it loads from, and what is known about
+// them besides the events.
+trait EventSource {
+    fn next_event(&mut self) -> ParseResult;
+
+    fn anchor_name(&self, _id: usize) -> Option<&str> {
+        None
+    }
+
+    fn anchor_mark(&self, _id: usize) -> Option<Marker> {
+        None
+    }
+
+    fn document_version(&self) -> Option<(u32, u32)> {
+        None
+    }
+
+    fn document_version_mark(&self) -> Option<Marker> {
+        None
+    }
+
+    fn capacity_hint(&self) -> Option<usize> {
+        None
+    }
+
+    fn stats(&self) -> ParserStats {
+        ParserStats::default()
+    }
+}
+
+impl<T: Iterator<Item = char>> EventSource for Parser<T> {
+    fn next_event(&mut self) -> ParseResult {
+        Parser::next_event(self)
+    }
+
+    fn anchor_name(&self, id: usize) -> Option<&str> {
+        Parser::anchor_name(self, id)
+    }
+
+    fn anchor_mark(&self, id: usize) -> Option<Marker> {
+        Parser::anchor_mark(self, id)
+    }
+
+    fn document_version(&self) -> Option<(u32, u32)> {
+        Parser::document_version(self)
+    }
+
+    fn document_version_mark(&self) -> Option<Marker> {
+        Parser::document_version_mark(self)
+    }
+
+    fn capacity_hint(&self) -> Option<usize> {
+        Parser::capacity_hint(self)
+    }
+
+    fn stats(&self) -> ParserStats {
+        Parser::stats(self)
+    }
+}
+
+// The events of an `EventLog`, ending with `StreamEnd` if the log does not.
+struct RecordedEvents<'l> {
+    events: std::slice::Iter<'l, (Event, Marker)>,
+    end: Marker,
+}
+
+impl EventSource for RecordedEvents<'_> {
+    fn next_event(&mut self) -> ParseResult {
+        match self.events.next() {
+            Some(&(ref ev, mark)) => {
+                self.end = mark;
+                Ok((ev.clone(), mark))
+            }
+            None => Ok((Event::StreamEnd, self.end)),
+        }
+    }
+}
+
+/// Collects `YamlLoader` options so one configuration can load many
+/// sources. `YamlLoader::load_from_str` remains the way to load with the
+/// defaults.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate yaml_rust_davvid as yaml_rust;
+/// use yaml_rust::yaml::{BadValueMode, KeyCoercion, YamlLoaderBuilder};
+/// use yaml_rust::Yaml;
+///
+/// let mut builder = YamlLoaderBuilder::new();
+/// builder
+///     .bad_value_mode(BadValueMode::Error)
+///     .key_coercion(KeyCoercion::String);
+///
+/// let docs = builder.load("8080: http").unwrap();
+/// assert_eq!(docs[0]["8080"], Yaml::String("http".to_owned()));
+/// assert!(builder.load("port: !!int http").is_err());
+/// ```
+#[derive(Clone, Default)]
+pub struct YamlLoaderBuilder<'a> {
+    scalar_parser: Vec<&'a dyn YamlScalarParser>,
+    // (handle, suffix, parser)
+    tag_parsers: Vec<(String, String, &'a dyn YamlScalarParser)>,
+    shrink_to_fit: bool,
+    intern_keys: bool,
+    string_cache: Option<&'a StringCache>,
+    structure_only: bool,
+    bad_value_mode: BadValueMode,
+    key_coercion: KeyCoercion,
+    number_format: NumberFormat,
+    schema: Schema,
+    scanner_config: ScannerConfig,
+    empty_stream_mode: EmptyStreamMode,
+    key_rules: Option<KeyRules>,
+    anchor_redefinition: AnchorRedefinition,
+    version_policy: VersionPolicy,
+    warning_handler: Option<&'a dyn Fn(&Warning)>,
+}
+
+impl<'a> YamlLoaderBuilder<'a> {
+    pub fn new() -> YamlLoaderBuilder<'a> {
+        YamlLoaderBuilder::default()
+    }
+
+    /// See `YamlLoader::register_scalar_parser`.
+    pub fn scalar_parser(&mut self, parser: &'a dyn YamlScalarParser) -> &mut Self {
+        self.scalar_parser.push(parser);
+        self
+    }
+
+    /// See `YamlLoader::register_tag_parser`.
+    pub fn tag_parser(
+        &mut self,
+        handle: &str,
+        suffix: &str,
+        parser: &'a dyn YamlScalarParser,
+    ) -> &mut Self {
+        self.tag_parsers
+            .push((handle.to_owned(), suffix.to_owned(), parser));
+        self
+    }
+
+    /// See `YamlLoader::shrink_to_fit`.
+    pub fn shrink_to_fit(&mut self, shrink_to_fit: bool) -> &mut Self {
+        self.shrink_to_fit = shrink_to_fit;
+        self
+    }
+
+    /// See `YamlLoader::intern_keys`.
+    pub fn intern_keys(&mut self, intern_keys: bool) -> &mut Self {
+        self.intern_keys = intern_keys;
+        self
+    }
+
+    /// See `YamlLoader::string_cache`.
+    pub fn string_cache(&mut self, cache: &'a StringCache) -> &mut Self {
+        self.intern_keys = true;
+        self.string_cache = Some(cache);
+        self
+    }
+
+    /// See `YamlLoader::structure_only`.
+    pub fn structure_only(&mut self, structure_only: bool) -> &mut Self {
+        self.structure_only = structure_only;
+        self
+    }
+
+    /// See `YamlLoader::bad_value_mode`.
+    pub fn bad_value_mode(&mut self, mode: BadValueMode) -> &mut Self {
+        self.bad_value_mode = mode;
+        self
+    }
+
+    /// See `YamlLoader::key_coercion`.
+    pub fn key_coercion(&mut self, coercion: KeyCoercion) -> &mut Self {
+        self.key_coercion = coercion;
+        self
+    }
+
+    /// See `YamlLoader::number_format`.
+    pub fn number_format(&mut self, format: NumberFormat) -> &mut Self {
+        self.number_format = format;
+        self
+    }
+
+    /// See `YamlLoader::schema`.
+    pub fn schema(&mut self, schema: Schema) -> &mut Self {
+        self.schema = schema;
+        self
+    }
+
+    /// See `YamlLoader::scanner_config`.
+    pub fn scanner_config(&mut self, config: ScannerConfig) -> &mut Self {
+        self.scanner_config = config;
+        self
+    }
+
+    /// See `YamlLoader::empty_stream_mode`.
+    pub fn empty_stream_mode(&mut self, mode: EmptyStreamMode) -> &mut Self {
+        self.empty_stream_mode = mode;
+        self
+    }
+
+    /// See `YamlLoader::key_rules`.
+    pub fn key_rules(&mut self, rules: KeyRules) -> &mut Self {
+        self.key_rules = Some(rules);
+        self
+    }
+
+    /// See `YamlLoader::anchor_redefinition`.
+    pub fn anchor_redefinition(&mut self, policy: AnchorRedefinition) -> &mut Self {
+        self.anchor_redefinition = policy;
+        self
+    }
+
+    /// See `YamlLoader::version_policy`.
+    pub fn version_policy(&mut self, policy: VersionPolicy) -> &mut Self {
+        self.version_policy = policy;
+        self
+    }
+
+    /// See `YamlLoader::on_warning`.
+    pub fn on_warning(&mut self, handler: &'a dyn Fn(&Warning)) -> &mut Self {
+        self.warning_handler = Some(handler);
+        self
+    }
+
+    /// Create a loader with the collected options.
+    pub fn build(&self) -> YamlLoader<'a> {
+        let mut loader = YamlLoader::new();
+        for parser in &self.scalar_parser {
+            loader.register_scalar_parser(*parser);
+        }
+        for (handle, suffix, parser) in &self.tag_parsers {
+            loader.register_tag_parser(handle, suffix, *parser);
+        }
+        loader
+            .shrink_to_fit(self.shrink_to_fit)
+            .intern_keys(self.intern_keys)
+            .structure_only(self.structure_only)
+            .bad_value_mode(self.bad_value_mode)
+            .key_coercion(self.key_coercion)
+            .number_format(self.number_format)
+            .schema(self.schema)
+            .scanner_config(self.scanner_config)
+            .empty_stream_mode(self.empty_stream_mode)
+            .anchor_redefinition(self.anchor_redefinition)
+            .version_policy(self.version_policy);
+        if let Some(rules) = self.key_rules {
+            loader.key_rules(rules);
+        }
+        if let Some(handler) = self.warning_handler {
+            loader.on_warning(handler);
+        }
+        if let Some(cache) = self.string_cache {
+            loader.string_cache(cache);
+        }
+        loader
+    }
+
+    /// Load all documents in `source` with the collected options.
+    pub fn load(&self, source: &str) -> Result<Vec<Yaml>, ScanError> {
+        self.build().parse_from_str(source)
+    }
+
+    /// See `YamlLoader::load_from_str_with_spans`.
+    pub fn load_with_spans(&self, source: &str) -> Result<Vec<(Yaml, Span)>, ScanError> {
+        self.build().parse_from_str_with_spans(source)
+    }
+
+    /// See `YamlLoader::parse_from_str_with_anchors`.
+    pub fn load_with_anchors(&self, source: &str) -> Result<Vec<(Yaml, Vec<Anchor>)>, ScanError> {
+        self.build().parse_from_str_with_anchors(source)
+    }
+
+    /// See `YamlLoader::parse_from_str_with_versions`.
+    pub fn load_with_versions(
+        &self,
+        source: &str,
+    ) -> Result<Vec<(Yaml, Option<YamlVersion>)>, ScanError> {
+        self.build().parse_from_str_with_versions(source)
+    }
+
+    /// See `YamlLoader::parse_from_str_with_stats`.
+    pub fn load_with_stats(&self, source: &str) -> Result<(Vec<Yaml>, ParserStats), ScanError> {
+        self.build().parse_from_str_with_stats(source)
+    }
+}
+
+// The node at a path written as the `path` module describes.
+pub(crate) fn lookup_path<'a>(doc: &'a Yaml, path: &str) -> Option<&'a Yaml> {
+    path::lookup(doc, &parse_flat_path(path, "."))
+}
+
+/// Load only the node at `path` in the first document of `source`, without
+/// building the rest of the tree. Paths are written as the `path` module
+/// describes; an empty path is the whole document. As when loading, the
+/// last of duplicate keys wins. Parsing stops at the end of the document,
+/// so later documents are not checked for errors.
+///
+/// Anchored nodes are loaded wherever they are so that aliases resolve,
+/// and aliases are checked as `YamlLoader` checks them.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate yaml_rust_davvid as yaml_rust;
+/// use yaml_rust::yaml;
+///
+/// let s = "
+/// kind: Deployment
+/// metadata: {name: web, labels: {app: web}}
+/// spec:
+///   replicas: 3
+/// ";
+/// let name = yaml::extract(s, "metadata.name").unwrap();
+/// assert_eq!(name.unwrap().as_str(), Some("web"));
+/// assert_eq!(yaml::extract(s, "spec.selector").unwrap(), None);
+/// ```
+pub fn extract(source: &str, path: &str) -> Result<Option<Yaml>, ScanError> {
+    let mut extractor = Extractor {
+        parser: Parser::new(source.chars()),
+        loader: YamlLoader::new(),
+        names: AnchorNames::default(),
+    };
+    loop {
+        match extractor.parser.next_event()?.0 {
+            Event::DocumentStart => break,
+            Event::StreamEnd => return Ok(None),
+            _ => {}
+        }
+    }
+    let steps = parse_flat_path(path, ".");
+    let (ev, mark) = extractor.parser.next_event()?;
+    extractor.extract(ev, mark, &steps)
+}
+
+struct Extractor<'a, T: Iterator<Item = char>> {
+    parser: Parser<T>,
+    // loads the requested node and anchored nodes along the way
+    loader: YamlLoader<'a>,
+    names: AnchorNames,
+}
+
+impl<'a, T: Iterator<Item = char>> Extractor<'a, T> {
+    fn extract(
+        &mut self,
+        ev: Event,
+        mark: Marker,
+        path: &[PathElement],
+    ) -> Result<Option<Yaml>, ScanError> {
+        let (step, rest) = match path.split_first() {
+            Some(split) => split,
+            None => return self.load(ev, mark).map(Some),
+        };
+        match ev {
+            // an alias to the node may follow, so it has to be loaded
+            Event::SequenceStart(aid, _) | Event::MappingStart(aid, _) if aid > 0 => {
+                let node = self.load(ev, mark)?;
+                Ok(path::lookup(&node, path).cloned())
+            }
+            Event::Alias(_) => {
+                let node = self.load(ev, mark)?;
+                Ok(path::lookup(&node, path).cloned())
+            }
+            Event::SequenceStart(..) => {
+                let index = path::step_index(step);
+                let mut found = None;
+                for i in 0.. {
+                    let (ev, mark) = self.parser.next_event()?;
+                    if ev == Event::SequenceEnd {
+                        break;
+                    }
+                    if index == Some(i) {
+                        found = self.extract(ev, mark, rest)?;
+                    } else {
+                        self.skip(ev, mark)?;
+                    }
+                }
+                Ok(found)
+            }
+            Event::MappingStart(..) => {
+                let mut found = None;
+                loop {
+                    let (ev, mark) = self.parser.next_event()?;
+                    if ev == Event::MappingEnd {
+                        return Ok(found);
+                    }
+                    let key = self.load(ev, mark)?;
+                    let (ev, mark) = self.parser.next_event()?;
+                    let matches = match *step {
+                        PathElement::Key(ref k) => {
+                            key == *k || key_text(&key).as_deref() == k.as_str()
+                        }
+                        PathElement::Index(_) => false,
+                    };
+                    // the value of a later duplicate key replaces this one
+                    if matches {
+                        found = self.extract(ev, mark, rest)?;
+                    } else {
+                        self.skip(ev, mark)?;
+                    }
+                }
+            }
+            _ => {
+                self.skip(ev, mark)?;
+                Ok(None)
+            }
+        }
+    }
+
+    // Consume a node, loading it only if it is anchored.
+    fn skip(&mut self, ev: Event, mark: Marker) -> Result<(), ScanError> {
+        match ev {
+            Event::Scalar(_, _, aid, _)
+            | Event::SequenceStart(aid, _)
+            | Event::MappingStart(aid, _)
+                if aid > 0 =>
+            {
+                self.load(ev, mark).map(|_| ())
+            }
+            Event::SequenceStart(..) | Event::MappingStart(..) => loop {
+                let (ev, mark) = self.parser.next_event()?;
+                if ev == Event::SequenceEnd || ev == Event::MappingEnd {
+                    return Ok(());
+                }
+                self.skip(ev, mark)?;
+            },
+            _ => Ok(()),
+        }
+    }
+
+    // Load the node starting with `ev`.
+    fn load(&mut self, ev: Event, mark: Marker) -> Result<Yaml, ScanError> {
+        let mut depth = 0;
+        let (mut ev, mut mark) = (ev, mark);
+        loop {
+            match ev {
+                Event::SequenceStart(..) | Event::MappingStart(..) => depth += 1,
+                Event::SequenceEnd | Event::MappingEnd => depth -= 1,
+                _ => {}
+            }
+            self.loader
+                .load_event(ev, mark, &self.parser, &mut self.names)?;
+            if depth == 0 {
+                return Ok(self.loader.doc_stack.pop().unwrap().0);
+            }
+            let next = self.parser.next_event()?;
+            ev = next.0;
+            mark = next.1;
+        }
+    }
+}
+
+// Prefix an IO error's message with the path it concerns.
+pub(crate) fn with_path(err: io::Error, path: &Path) -> io::Error {
+    io::Error::new(err.kind(), format!("{}: {}", path.display(), err))
+}
+
+/// Remove every hash entry of `doc` whose key matches `predicate`, at any
+/// depth, such as the `x-` extension keys of an OpenAPI document. Returns
+/// the removed values with the paths they were at, in document order. The
+/// entries inside a removed value are not checked.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate yaml_rust_davvid as yaml_rust;
+/// use yaml_rust::{yaml, YamlLoader};
+///
+/// let s = "
+/// openapi: 3.0.0
+/// x-owner: team-a
+/// paths:
+///   /pets: {get: {x-internal: true, summary: List pets}}
+/// ";
+/// let mut doc = YamlLoader::load_from_str(s).unwrap().remove(0);
+/// let removed = yaml::strip(&mut doc, |key| key.as_str().is_some_and(|k| k.starts_with("x-")));
+/// assert_eq!(removed[0].0.to_string(), "$.x-owner");
+/// assert_eq!(removed[1].0.to_string(), "$.paths./pets.get.x-internal");
+/// assert_eq!(doc["paths"]["/pets"]["get"].as_hash().unwrap().len(), 1);
+/// ```
+pub fn strip<F: FnMut(&Yaml) -> bool>(doc: &mut Yaml, mut predicate: F) -> Vec<(YamlPath, Yaml)> {
+    let mut removed = Vec::new();
+    strip_node(doc, &mut predicate, &mut YamlPath::new(), &mut removed);
+    removed
+}
+
+fn strip_node<F: FnMut(&Yaml) -> bool>(
+    node: &mut Yaml,
+    predicate: &mut F,
+    path: &mut YamlPath,
+    removed: &mut Vec<(YamlPath, Yaml)>,
+) {
+    match *node {
+        Yaml::Array(ref mut v) => {
+            for (i, child) in v.iter_mut().enumerate() {
+                path.push(PathElement::Index(i));
+                strip_node(child, predicate, path, removed);
+                path.pop();
+            }
+        }
+        Yaml::Hash(ref mut h) => h.retain_with_order(|key, value| {
+            path.push(PathElement::Key(key.clone()));
+            let keep = !predicate(key);
+            if keep {
+                strip_node(value, predicate, path, removed);
+            } else {
+                removed.push((path.clone(), mem::replace(value, Yaml::BadValue)));
+            }
+            path.pop();
+            keep
+        }),
+        Yaml::Tagged(_, ref mut v) => strip_node(v, predicate, path, removed),
+        _ => {}
+    }
+}
+
+/// An error of the conversion helpers `to_json_string`, `from_json_string`
+/// and `reformat`.
+#[derive(Clone, PartialEq, Debug, Eq)]
+pub enum ConvertError {
+    /// The source could not be loaded.
+    Scan(ScanError),
+    /// The result could not be written.
+    Emit(String),
+    /// A node that has no JSON equivalent, such as `.inf` or a hash key
+    /// that is a collection.
+    NotJson(YamlPath),
+}
+
+impl fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConvertError::Scan(ref err) => fmt::Display::fmt(err, f),
+            ConvertError::Emit(ref err) => write!(f, "can't write the result: {}", err),
+            ConvertError::NotJson(ref path) => {
+                write!(f, "value at {} can't be converted to JSON", path)
+            }
+        }
+    }
+}
+
+impl Error for ConvertError {}
+
+impl From<ScanError> for ConvertError {
+    fn from(err: ScanError) -> ConvertError {
+        ConvertError::Scan(err)
+    }
+}
+
+impl From<EmitError> for ConvertError {
+    fn from(err: EmitError) -> ConvertError {
+        ConvertError::Emit(err.to_string())
+    }
+}
+
+/// Convert every document of a YAML stream to a line of JSON. Tags are
+/// dropped, and hash keys that are numbers or booleans become strings.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate yaml_rust_davvid as yaml_rust;
+/// use yaml_rust::yaml;
+///
+/// let json = yaml::to_json_string("name: web\nports: [80, 443]\ntls: ~\n---\n1.5").unwrap();
+/// assert_eq!(json, "{\"name\":\"web\",\"ports\":[80,443],\"tls\":null}\n1.5\n");
+/// assert!(yaml::to_json_string("x: .nan").is_err());
+/// ```
+pub fn to_json_string(source: &str) -> Result<String, ConvertError> {
+    let mut out = String::new();
+    for doc in YamlLoader::load_from_str(source)? {
+        write_json(&doc, &mut out, &mut YamlPath::new())?;
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// Convert JSON text to a YAML document, see
+/// `YamlLoader::load_from_json_str`.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate yaml_rust_davvid as yaml_rust;
+/// use yaml_rust::yaml;
+///
+/// let yaml = yaml::from_json_string(r#"{"name": "web", "ports": [80]}"#).unwrap();
+/// assert_eq!(yaml, "---\nname: web\nports:\n  - 80\n");
+/// ```
+pub fn from_json_string(json: &str) -> Result<String, ConvertError> {
+    let doc = YamlLoader::load_from_json_str(json)?;
+    let mut out = String::new();
+    let mut emitter = YamlEmitter::new(&mut out);
+    emitter.trailing_newline(true);
+    emitter.dump(&doc)?;
+    Ok(out)
+}
+
+/// How `reformat` writes documents.
+#[derive(Clone, PartialEq, Debug, Eq)]
+pub struct ReformatOptions {
+    /// See `YamlEmitter::compact`. Defaults to true.
+    pub compact: bool,
+    /// See `YamlEmitter::multiline_strings`. Defaults to false.
+    pub multiline_strings: bool,
+    /// Write the keys of hashes sorted, see `KeyOrder::Alphabetical`.
+    /// Defaults to false.
+    pub sort_keys: bool,
+    /// See `YamlEmitter::best_width`. Defaults to 80.
+    pub width: usize,
+    /// Keep the comment lines and blank lines above the entries of hashes
+    /// at the roots of documents, see `CommentedYaml::load_from_str`.
+    /// Defaults to true.
+    pub keep_comments: bool,
+}
+
+impl Default for ReformatOptions {
+    fn default() -> ReformatOptions {
+        ReformatOptions {
+            compact: true,
+            multiline_strings: false,
+            sort_keys: false,
+            width: 80,
+            keep_comments: true,
+        }
+    }
+}
+
+/// Load a YAML stream and write it again as `opts` asks, ending every
+/// document with a line break.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate yaml_rust_davvid as yaml_rust;
+/// use yaml_rust::yaml::{self, ReformatOptions};
+///
+/// let source = "a: [1,2]\n\n# settings\nb: {y: 1,   x: 2}\n";
+/// let opts = ReformatOptions { sort_keys: true, ..ReformatOptions::default() };
+/// assert_eq!(
+///     yaml::reformat(source, &opts).unwrap(),
+///     "---\na:\n  - 1\n  - 2\n\n# settings\nb:\n  x: 2\n  y: 1\n"
+/// );
+/// ```
+pub fn reformat(source: &str, opts: &ReformatOptions) -> Result<String, ConvertError> {
+    let mut out = String::new();
+    let mut emitter = YamlEmitter::new(&mut out);
+    emitter.compact(opts.compact);
+    emitter.multiline_strings(opts.multiline_strings);
+    emitter.best_width(opts.width);
+    emitter.trailing_newline(true);
+    if opts.sort_keys {
+        emitter.key_order(KeyOrder::Alphabetical);
+    }
+    if opts.keep_comments {
+        for doc in CommentedYaml::load_from_str(source)? {
+            emitter.dump_commented(&doc)?;
+        }
+    } else {
+        for doc in YamlLoader::load_from_str(source)? {
+            emitter.dump(&doc)?;
+        }
+    }
+    Ok(out)
+}
+
+fn write_json(node: &Yaml, out: &mut String, path: &mut YamlPath) -> Result<(), ConvertError> {
+    match *node {
+        Yaml::Real(ref v) if is_json_number(v) => out.push_str(v),
+        Yaml::Real(ref v) => match parse_f64(v) {
+            Some(f) if f.is_finite() => out.push_str(&format_real(f)),
+            _ => return Err(ConvertError::NotJson(path.clone())),
+        },
+        Yaml::Integer(i) => out.push_str(&i.to_string()),
+        Yaml::String(ref s) => escape_str(out, s).expect("writing to a String"),
+        Yaml::Interned(ref s) => escape_str(out, s).expect("writing to a String"),
+        Yaml::Boolean(b) => out.push_str(if b { "true" } else { "false" }),
+        Yaml::Array(ref v) => {
+            out.push('[');
+            for (i, x) in v.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                path.push(PathElement::Index(i));
+                write_json(x, out, path)?;
+                path.pop();
+            }
+            out.push(']');
+        }
+        Yaml::Hash(ref h) => {
+            out.push('{');
+            for (i, (k, v)) in h.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                path.push(PathElement::Key(k.clone()));
+                let key = key_text(k).ok_or_else(|| ConvertError::NotJson(path.clone()))?;
+                escape_str(out, &key).expect("writing to a String");
+                out.push(':');
+                write_json(v, out, path)?;
+                path.pop();
+            }
+            out.push('}');
+        }
+        Yaml::Tagged(_, ref v) => write_json(v, out, path)?,
+        Yaml::Null => out.push_str("null"),
+        Yaml::Alias(_) | Yaml::BadValue => return Err(ConvertError::NotJson(path.clone())),
+    }
+    Ok(())
+}
+
+// Children are shrunk as they are inserted, so only the node itself needs it.
+fn shrink_node(node: &mut Yaml) {
+    match *node {
+        Yaml::Real(ref mut v) | Yaml::String(ref mut v) => v.shrink_to_fit(),
+        Yaml::Array(ref mut v) => v.shrink_to_fit(),
+        Yaml::Hash(ref mut h) => h.shrink_to_fit(),
+        Yaml::Tagged(_, ref mut v) => shrink_node(v),
+        _ => {}
+    }
+}
+
+/// The character encodings a YAML stream may use, see
+/// [section 5.2](https://yaml.org/spec/1.2/spec.html#id2771184) of the spec.
+#[derive(Clone, Copy, PartialEq, Debug, Eq)]
+pub enum YamlEncoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Utf32Le,
+    Utf32Be,
+}
+
+impl YamlEncoding {
+    /// Deduce the encoding of a stream from its first four bytes.
+    ///
+    /// Streams may start with a byte order mark. Without one, the spec
+    /// mandates that the first character is ASCII, so the encoding can be
+    /// deduced from the pattern of null bytes. Returns the encoding and the
+    /// length of the byte order mark, which is zero if there is none.
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::yaml::YamlEncoding;
+    ///
+    /// assert_eq!(YamlEncoding::detect(b"\xff\xfe\0\0"), (YamlEncoding::Utf32Le, 4));
+    /// assert_eq!(YamlEncoding::detect(b"\0a"), (YamlEncoding::Utf16Be, 0));
+    /// assert_eq!(YamlEncoding::detect(b"a: 1"), (YamlEncoding::Utf8, 0));
+    /// ```
+    pub fn detect(b: &[u8]) -> (YamlEncoding, usize) {
+        match *b {
+            [0, 0, 0xfe, 0xff, ..] => (YamlEncoding::Utf32Be, 4),
+            [0, 0, 0, _, ..] => (YamlEncoding::Utf32Be, 0),
+            [0xff, 0xfe, 0, 0, ..] => (YamlEncoding::Utf32Le, 4),
+            [_, 0, 0, 0, ..] => (YamlEncoding::Utf32Le, 0),
+            [0xfe, 0xff, ..] => (YamlEncoding::Utf16Be, 2),
+            [0, _, ..] => (YamlEncoding::Utf16Be, 0),
+            [0xff, 0xfe, ..] => (YamlEncoding::Utf16Le, 2),
+            [_, 0, ..] => (YamlEncoding::Utf16Le, 0),
+            [0xef, 0xbb, 0xbf, ..] => (YamlEncoding::Utf8, 3),
+            _ => (YamlEncoding::Utf8, 0),
+        }
+    }
+
+    fn raw_decoder(self) -> Box<dyn RawDecoder> {
+        match self {
+            YamlEncoding::Utf8 => encoding::all::UTF_8.raw_decoder(),
+            YamlEncoding::Utf16Le => encoding::all::UTF_16LE.raw_decoder(),
+            YamlEncoding::Utf16Be => encoding::all::UTF_16BE.raw_decoder(),
+            YamlEncoding::Utf32Le => Box::new(Utf32Decoder::new(false)),
+            YamlEncoding::Utf32Be => Box::new(Utf32Decoder::new(true)),
+        }
+    }
+
+    pub(crate) fn raw_encoder(self) -> Box<dyn RawEncoder> {
+        match self {
+            YamlEncoding::Utf8 => encoding::all::UTF_8.raw_encoder(),
+            YamlEncoding::Utf16Le => encoding::all::UTF_16LE.raw_encoder(),
+            YamlEncoding::Utf16Be => encoding::all::UTF_16BE.raw_encoder(),
+            YamlEncoding::Utf32Le => Box::new(Utf32Encoder { big_endian: false }),
+            YamlEncoding::Utf32Be => Box::new(Utf32Encoder { big_endian: true }),
+        }
+    }
+}
+
+struct Utf32Encoder {
+    big_endian: bool,
+}
+
+impl RawEncoder for Utf32Encoder {
+    fn from_self(&self) -> Box<dyn RawEncoder> {
+        Box::new(Utf32Encoder {
+            big_endian: self.big_endian,
+        })
+    }
+
+    fn raw_feed(
+        &mut self,
+        input: &str,
+        output: &mut dyn ByteWriter,
+    ) -> (usize, Option<CodecError>) {
+        output.writer_hint(input.len() * 4);
+        for c in input.chars() {
+            if self.big_endian {
+                output.write_bytes(&(c as u32).to_be_bytes());
+            } else {
+                output.write_bytes(&(c as u32).to_le_bytes());
+            }
+        }
+        (input.len(), None)
+    }
+
+    fn raw_finish(&mut self, _output: &mut dyn ByteWriter) -> Option<CodecError> {
+        None
+    }
+}
+
+/// The encoding crate has no UTF-32 support, so provide a decoder for it.
+struct Utf32Decoder {
+    big_endian: bool,
+    unit: [u8; 4],
+    len: usize,
+}
+
+impl Utf32Decoder {
+    fn new(big_endian: bool) -> Utf32Decoder {
+        Utf32Decoder {
+            big_endian,
+            unit: [0; 4],
+            len: 0,
+        }
+    }
+}
+
+impl RawDecoder for Utf32Decoder {
+    fn from_self(&self) -> Box<dyn RawDecoder> {
+        Box::new(Utf32Decoder::new(self.big_endian))
+    }
+
+    fn raw_feed(
+        &mut self,
+        input: &[u8],
+        output: &mut dyn StringWriter,
+    ) -> (usize, Option<CodecError>) {
+        output.writer_hint(input.len() / 4);
+        for (i, byte) in input.iter().enumerate() {
+            self.unit[self.len] = *byte;
+            self.len += 1;
+            if self.len < 4 {
+                continue;
+            }
+            self.len = 0;
+            let code = if self.big_endian {
+                u32::from_be_bytes(self.unit)
+            } else {
+                u32::from_le_bytes(self.unit)
+            };
+            match char::from_u32(code) {
+                Some(c) => output.write_char(c),
+                None => {
+                    // The invalid unit may have started in a previous feed.
+                    let start = (i + 1).saturating_sub(4);
+                    return (
+                        start,
+                        Some(CodecError {
+                            upto: i as isize + 1,
+                            cause: "invalid code point".into(),
+                        }),
+                    );
+                }
+            }
+        }
+        (input.len().saturating_sub(self.len), None)
+    }
+
+    fn raw_finish(&mut self, _output: &mut dyn StringWriter) -> Option<CodecError> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len = 0;
+        Some(CodecError {
+            upto: 0,
+            cause: "incomplete sequence".into(),
+        })
+    }
+}
+
+pub struct YamlDecoder<T: std::io::Read> {
+    source: T,
+    trap: encoding::types::DecoderTrap,
+    encoding: Option<YamlEncoding>,
+}
+
+impl<T: std::io::Read> YamlDecoder<T> {
+    pub fn read(source: T) -> YamlDecoder<T> {
+        YamlDecoder {
+            source,
+            trap: encoding::DecoderTrap::Strict,
+            encoding: None,
+        }
+    }
+
+    pub fn encoding_trap(&mut self, trap: encoding::types::DecoderTrap) -> &mut Self {
+        self.trap = trap;
+        self
+    }
+
+    /// The encoding detected by the last call to `decode`.
+    pub fn encoding(&self) -> Option<YamlEncoding> {
+        self.encoding
+    }
+
+    /// Decode and load the source.
+    ///
+    /// The source is decoded incrementally while it is parsed, so it is never
+    /// held in memory as a whole. The encoding is detected from the byte order
+    /// mark, or from the pattern of null bytes if there is none.
+    pub fn decode(&mut self) -> Result<Vec<Yaml>, LoadError> {
+        let mut head = [0u8; 4];
+        let mut len = 0;
+        while len < head.len() {
+            match self.source.read(&mut head[len..])? {
+                0 => break,
+                n => len += n,
+            }
+        }
+        let (encoding, bom_len) = YamlEncoding::detect(&head[..len]);
+        self.encoding = Some(encoding);
+
+        let mut chars = DecodeChars {
+            source: &mut self.source,
+            decoder: encoding.raw_decoder(),
+            trap: self.trap,
+            input: head[bom_len..len].to_vec(),
+            output: String::new(),
+            pos: 0,
+            finished: false,
+            error: None,
+        };
+        let result = YamlLoader::new().parse_from_chars(&mut chars);
+        // A decoding error ends the character stream early, which is likely
+        // to surface as a scan error. Report the root cause instead.
+        if let Some(error) = chars.error {
+            return Err(error);
+        }
+        result.map_err(LoadError::Scan)
+    }
+}
+
+// Size of the chunks read from the source of a `YamlDecoder`.
+const DECODE_CHUNK_SIZE: usize = 8192;
+
+/// Iterates over the characters of a byte source, decoding it chunk by chunk.
+struct DecodeChars<'r, T> {
+    source: &'r mut T,
+    decoder: Box<dyn RawDecoder>,
+    trap: encoding::types::DecoderTrap,
+    // bytes to feed to the decoder before reading from the source
+    input: Vec<u8>,
+    output: String,
+    pos: usize,
+    finished: bool,
+    error: Option<LoadError>,
+}
+
+impl<'r, T: std::io::Read> DecodeChars<'r, T> {
+    fn fill(&mut self) -> Result<(), LoadError> {
+        self.output.clear();
+        self.pos = 0;
+        while self.output.is_empty() && !self.finished {
+            if self.input.is_empty() {
+                self.input.resize(DECODE_CHUNK_SIZE, 0);
+                let n = self.source.read(&mut self.input)?;
+                self.input.truncate(n);
+            }
+            if self.input.is_empty() {
+                self.finished = true;
+                if let Some(err) = self.decoder.raw_finish(&mut self.output) {
+                    if !self.trap.trap(&mut *self.decoder, &[], &mut self.output) {
+                        return Err(LoadError::Decode(err.cause));
+                    }
+                }
+            } else {
+                self.feed()?;
+            }
+        }
+        Ok(())
+    }
+
+    // Mirrors `encoding::types::Encoding::decode_to`, applying the trap to
+    // every error in the pending input.
+    fn feed(&mut self) -> Result<(), LoadError> {
+        let input = mem::take(&mut self.input);
+        let mut remaining = 0;
+        loop {
+            let (offset, err) = self.decoder.raw_feed(&input[remaining..], &mut self.output);
+            let unprocessed = remaining + offset;
+            match err {
+                Some(err) => {
+                    remaining =
+                        ((remaining as isize + err.upto).max(unprocessed as isize)) as usize;
+                    let problem = &input[unprocessed..remaining];
+                    if !self
+                        .trap
+                        .trap(&mut *self.decoder, problem, &mut self.output)
+                    {
+                        return Err(LoadError::Decode(err.cause));
+                    }
+                }
+                None => return Ok(()),
+            }
+        }
+    }
+}
+
+impl<'r, T: std::io::Read> Iterator for DecodeChars<'r, T> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        if self.pos == self.output.len() {
+            if self.error.is_some() {
+                return None;
+            }
+            if let Err(error) = self.fill() {
+                self.error = Some(error);
+                return None;
+            }
+        }
+        let c = self.output[self.pos..].chars().next()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+}
+
+macro_rules! define_as (
+    ($name:ident, $t:ident, $yt:ident) => (
+pub fn $name(&self) -> Option<$t> {
+    match *self.untagged() {
+        Yaml::$yt(v) => Some(v),
+        _ => None
+    }
+}
+    );
+);
+
+macro_rules! define_as_ref (
+    ($name:ident, $t:ty, $yt:ident) => (
+pub fn $name(&self) -> Option<$t> {
+    match *self.untagged() {
+        Yaml::$yt(ref v) => Some(v),
+        _ => None
+    }
+}
+    );
+);
+
+macro_rules! define_into (
+    ($name:ident, $t:ty, $yt:ident) => (
+pub fn $name(self) -> Option<$t> {
+    match self {
+        Yaml::$yt(v) => Some(v),
+        Yaml::Tagged(_, v) => v.$name(),
+        _ => None
+    }
+}
+    );
+);
+
+impl Yaml {
+    define_as!(as_bool, bool, Boolean);
+    define_as!(as_i64, i64, Integer);
+
+    define_as_ref!(as_hash, &Hash, Hash);
+    define_as_ref!(as_vec, &Array, Array);
+
+    define_into!(into_bool, bool, Boolean);
+    define_into!(into_i64, i64, Integer);
+    define_into!(into_hash, Hash, Hash);
+    define_into!(into_vec, Array, Array);
+
+    pub fn is_null(&self) -> bool {
+        matches!(*self.untagged(), Yaml::Null)
+    }
+
+    pub fn is_badvalue(&self) -> bool {
+        matches!(*self, Yaml::BadValue)
+    }
+
+    pub fn is_array(&self) -> bool {
+        matches!(*self.untagged(), Yaml::Array(_))
+    }
+
+    pub fn is_hash(&self) -> bool {
+        matches!(*self.untagged(), Yaml::Hash(_))
+    }
+
+    pub fn is_string(&self) -> bool {
+        matches!(*self.untagged(), Yaml::String(_) | Yaml::Interned(_))
+    }
+
+    pub fn is_integer(&self) -> bool {
+        matches!(*self.untagged(), Yaml::Integer(_))
+    }
+
+    pub fn is_real(&self) -> bool {
+        matches!(*self.untagged(), Yaml::Real(_))
+    }
+
+    pub fn is_boolean(&self) -> bool {
+        matches!(*self.untagged(), Yaml::Boolean(_))
+    }
+
+    pub fn is_alias(&self) -> bool {
+        matches!(*self, Yaml::Alias(_))
+    }
+
+    pub fn is_tagged(&self) -> bool {
+        matches!(*self, Yaml::Tagged(..))
+    }
+
+    /// The value under any application tags. The `as_*`, `into_*` and `is_*`
+    /// accessors, `get_*` and indexing all look through tags, so `!Ref name`
+    /// reads as the string `name` and `!Sub [a]` as an array.
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::YamlLoader;
+    ///
+    /// let doc = YamlLoader::load_from_str("a: !Ref name\nb: !Sub [x]").unwrap().remove(0);
+    /// assert!(doc["a"].is_tagged());
+    /// assert_eq!(doc["a"].as_str(), Some("name"));
+    /// assert_eq!(doc["b"][0].as_str(), Some("x"));
+    /// ```
+    pub fn untagged(&self) -> &Yaml {
+        match *self {
+            Yaml::Tagged(_, ref v) => v.untagged(),
+            _ => self,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        self.untagged().string_text()
+    }
+
+    pub fn into_string(self) -> Option<String> {
+        match self {
+            Yaml::String(v) => Some(v),
+            Yaml::Interned(v) => Some(String::from(&*v)),
+            Yaml::Tagged(_, v) => v.into_string(),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self.untagged() {
+            Yaml::Real(ref v) => parse_f64(v),
+            _ => None,
+        }
+    }
+
+    pub fn into_f64(self) -> Option<f64> {
+        self.as_f64()
+    }
+
+    /// The character of a string that holds exactly one.
+    pub fn as_char(&self) -> Option<char> {
+        let mut chars = self.as_str()?.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None,
+        }
+    }
+
+    /// The text of any scalar: strings as they are, reals as written in the
+    /// document, integers and booleans formatted, and null as `null`. A
+    /// tagged scalar gives the text of its value. Returns `None` for
+    /// collections, aliases and bad values.
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::YamlLoader;
+    ///
+    /// let doc = YamlLoader::load_from_str("[web, 8080, 0.50, true, ~, [x]]").unwrap().remove(0);
+    /// let text: Vec<_> = doc.as_vec().unwrap().iter().map(|v| v.as_str_lossy()).collect();
+    /// assert_eq!(text, [Some("web".into()), Some("8080".into()), Some("0.50".into()),
+    ///                   Some("true".into()), Some("null".into()), None]);
+    /// ```
+    pub fn as_str_lossy(&self) -> Option<Cow<'_, str>> {
+        match *self {
+            Yaml::String(ref v) | Yaml::Real(ref v) => Some(Cow::Borrowed(v)),
+            Yaml::Interned(ref v) => Some(Cow::Borrowed(v)),
+            Yaml::Integer(v) => Some(Cow::Owned(v.to_string())),
+            Yaml::Boolean(v) => Some(Cow::Borrowed(if v { "true" } else { "false" })),
+            Yaml::Null => Some(Cow::Borrowed("null")),
+            Yaml::Tagged(_, ref v) => v.as_str_lossy(),
+            Yaml::Array(_) | Yaml::Hash(_) | Yaml::Alias(_) | Yaml::BadValue => None,
+        }
+    }
+
+    /// If a value is null or otherwise bad (see variants), consume it and
+    /// replace it with a given value `other`. Otherwise, return self unchanged.
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::yaml::Yaml;
+    ///
+    /// assert_eq!(Yaml::BadValue.or(Yaml::Integer(3)),  Yaml::Integer(3));
+    /// assert_eq!(Yaml::Integer(3).or(Yaml::BadValue),  Yaml::Integer(3));
+    /// ```
+    pub fn or(self, other: Self) -> Self {
+        match self {
+            Yaml::BadValue | Yaml::Null => other,
+            this => this,
+        }
+    }
+
+    /// See `or` for behavior. This performs the same operations, but with
+    /// borrowed values for less linear pipelines.
+    pub fn borrowed_or<'a>(&'a self, other: &'a Self) -> &'a Self {
+        match self {
+            Yaml::BadValue | Yaml::Null => other,
+            this => this,
+        }
+    }
+
+    /// Move the value out, leaving `Yaml::Null` in its place.
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::{Yaml, YamlLoader};
+    ///
+    /// let mut doc = YamlLoader::load_from_str("a: [1, 2]").unwrap().remove(0);
+    /// if let Yaml::Hash(ref mut h) = doc {
+    ///     let a = h.get_mut(&Yaml::from_str("a")).unwrap().take();
+    ///     assert_eq!(a.as_vec().map(Vec::len), Some(2));
+    /// }
+    /// assert!(doc["a"].is_null());
+    /// ```
+    pub fn take(&mut self) -> Yaml {
+        mem::replace(self, Yaml::Null)
+    }
+
+    /// Store `value` in place of this value, returning the old one.
+    pub fn replace(&mut self, value: Yaml) -> Yaml {
+        mem::replace(self, value)
+    }
+
+    /// Rewrite every real in the tree, hash keys included, in one canonical
+    /// form, so reals with the same value are equal and hash alike: `1.00`
+    /// and `1.0` become `1.0`, `1e3` becomes `1000.0`, `-0.0` becomes `0.0`
+    /// and the spellings of infinity and NaN become `.inf`, `-.inf` and
+    /// `.nan`. Reals that don't parse are left as they are.
+    ///
+    /// Hash keys that become equal are merged, keeping the position of the
+    /// first and the value of the last.
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::{Yaml, YamlLoader};
+    ///
+    /// let mut doc = YamlLoader::load_from_str("{1.0: a, 1.00: b, 2e0: c}").unwrap().remove(0);
+    /// assert_eq!(doc.as_hash().unwrap().len(), 3);
+    /// doc.normalize_numbers();
+    /// assert_eq!(doc.as_hash().unwrap().len(), 2);
+    /// let one = Yaml::Real("1.0".to_owned());
+    /// assert_eq!(doc.get_key(&one).and_then(Yaml::as_str), Some("b"));
+    /// let two = Yaml::Real("2.0".to_owned());
+    /// assert_eq!(doc.get_key(&two).and_then(Yaml::as_str), Some("c"));
+    /// ```
+    pub fn normalize_numbers(&mut self) {
+        match *self {
+            Yaml::Real(ref mut v) => {
+                if let Some(f) = parse_f64(v) {
+                    // -0.0 == 0.0, so they share a form
+                    *v = format_real(if f == 0.0 { 0.0 } else { f });
+                }
+            }
+            Yaml::Array(ref mut v) => v.iter_mut().for_each(Yaml::normalize_numbers),
+            Yaml::Hash(ref mut h) => {
+                for (mut key, mut value) in mem::take(h) {
+                    key.normalize_numbers();
+                    value.normalize_numbers();
+                    h.insert(key, value);
+                }
+            }
+            Yaml::Tagged(_, ref mut v) => v.normalize_numbers(),
+            _ => {}
+        }
+    }
+
+    /// Append `value` to an array.
+    ///
+    /// Like the other array methods, this leaves any value that is not an
+    /// array unchanged and returns an error, which can be ignored where a
+    /// non-array should simply be skipped.
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::{Yaml, YamlLoader};
+    ///
+    /// let mut doc = YamlLoader::load_from_str("[1, 2, 3]").unwrap().remove(0);
+    /// doc.push(Yaml::Integer(4)).unwrap();
+    /// doc.retain(|x| x.as_i64().map_or(false, |i| i % 2 == 0)).unwrap();
+    /// assert_eq!(doc, Yaml::Array(vec![Yaml::Integer(2), Yaml::Integer(4)]));
+    ///
+    /// let err = Yaml::Integer(1).push(Yaml::Null).unwrap_err();
+    /// assert_eq!(err.to_string(), "expected array, found integer");
+    /// ```
+    pub fn push(&mut self, value: Yaml) -> Result<(), YamlConversionError> {
+        self.array_mut()?.push(value);
+        Ok(())
+    }
+
+    /// Insert `value` into an array at `idx`, shifting later elements.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is greater than the array's length.
+    pub fn insert(&mut self, idx: usize, value: Yaml) -> Result<(), YamlConversionError> {
+        self.array_mut()?.insert(idx, value);
+        Ok(())
+    }
+
+    /// Remove and return the element at `idx` of an array, shifting later
+    /// elements.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is out of bounds.
+    pub fn remove(&mut self, idx: usize) -> Result<Yaml, YamlConversionError> {
+        Ok(self.array_mut()?.remove(idx))
+    }
+
+    /// Keep only the elements of an array for which `f` returns `true`.
+    pub fn retain<F: FnMut(&Yaml) -> bool>(&mut self, f: F) -> Result<(), YamlConversionError> {
+        self.array_mut()?.retain(f);
+        Ok(())
+    }
+
+    fn array_mut(&mut self) -> Result<&mut Array, YamlConversionError> {
+        let found = self.type_str();
+        match *self {
+            Yaml::Array(ref mut v) => Ok(v),
+            _ => Err(YamlConversionError {
+                expected: "array",
+                found,
+            }),
+        }
+    }
+
+    /// Look up `key` in a hash without any type coercion.
+    ///
+    /// Unlike indexing, the key is matched exactly, so `Yaml::Integer(0)` and
+    /// `Yaml::String("0")` are distinct keys. Returns `None` if `self` is not
+    /// a hash or the key is missing.
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::{Yaml, YamlLoader};
+    ///
+    /// let docs = YamlLoader::load_from_str("{0: int, '0': str}").unwrap();
+    /// let doc = &docs[0];
+    /// assert_eq!(doc.get_key(&Yaml::Integer(0)).unwrap().as_str(), Some("int"));
+    /// assert_eq!(doc.get_str("0").unwrap().as_str(), Some("str"));
+    /// ```
+    pub fn get_key(&self, key: &Yaml) -> Option<&Yaml> {
+        self.as_hash().and_then(|h| h.get(key))
+    }
+
+    /// Look up a key that is a collection, such as `[x, y]` in
+    /// `{[x, y]: 1}`.
+    ///
+    /// An exact match is tried first. Otherwise keys are compared with
+    /// `semantic_eq`, ignoring the order of hash entries, so `key` can be
+    /// built or loaded separately from the document.
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::YamlLoader;
+    ///
+    /// let doc = &YamlLoader::load_from_str("? {x: 1, y: 2}\n: point").unwrap()[0];
+    /// let key = &YamlLoader::load_from_str("{y: 2, x: 1}").unwrap()[0];
+    /// assert!(doc.get_key(key).is_none());
+    /// assert_eq!(doc.get_complex_key(key).unwrap().as_str(), Some("point"));
+    /// ```
+    pub fn get_complex_key(&self, key: &Yaml) -> Option<&Yaml> {
+        let h = self.as_hash()?;
+        let options = SemanticEqOptions {
+            ignore_map_order: true,
+        };
+        h.get(key).or_else(|| {
+            h.iter()
+                .find(|&(k, _)| k.semantic_eq(key, options))
+                .map(|(_, v)| v)
+        })
+    }
+
+    /// Whether this is a collection, possibly tagged, which as a hash key
+    /// has to be written after an explicit `?`.
+    pub fn is_complex_key(&self) -> bool {
+        match *self {
+            Yaml::Array(_) | Yaml::Hash(_) => true,
+            Yaml::Tagged(_, ref v) => v.is_complex_key(),
+            _ => false,
+        }
+    }
+
+    /// Iterate over the entries of a hash, flagging those with a complex
+    /// key. Yields nothing if `self` is not a hash.
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::YamlLoader;
+    ///
+    /// let doc = &YamlLoader::load_from_str("{a: 1, [b, c]: 2}").unwrap()[0];
+    /// let complex: Vec<bool> = doc.entries().map(|e| e.complex).collect();
+    /// assert_eq!(complex, [false, true]);
+    /// ```
+    pub fn entries(&self) -> HashEntries<'_> {
+        HashEntries {
+            entries: self.as_hash().map(|h| h.iter()),
+        }
+    }
+
+    /// Look up a string key in a hash. See `get_key`.
+    pub fn get_str(&self, key: &str) -> Option<&Yaml> {
+        self.as_hash().and_then(|h| get_str_key(h, key))
+    }
+
+    /// Return the element at `idx` of an array.
+    ///
+    /// Unlike indexing with `usize`, this never falls back to looking up an
+    /// `Integer` key in a hash. Returns `None` if `self` is not an array or
+    /// `idx` is out of bounds.
+    pub fn get_index(&self, idx: usize) -> Option<&Yaml> {
+        self.as_vec().and_then(|v| v.get(idx))
+    }
+
+    /// Return the first hash of an array whose `key` has the value `value`,
+    /// such as the container named `app` in a list of containers. Numbers
+    /// and booleans match their text.
+    ///
+    /// Returns `None` if `self` is not an array or no item matches.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::YamlLoader;
+    ///
+    /// let doc = &YamlLoader::load_from_str("
+    /// containers:
+    ///   - {name: app, image: web, port: 80}
+    ///   - {name: sidecar, image: proxy, port: 80}
+    /// ").unwrap()[0];
+    /// let app = doc["containers"].find_by("name", "app").unwrap();
+    /// assert_eq!(app["image"].as_str(), Some("web"));
+    /// assert_eq!(doc["containers"].find_all_by("port", "80").count(), 2);
+    /// ```
+    pub fn find_by(&self, key: &str, value: &str) -> Option<&Yaml> {
+        self.find_all_by(key, value).next()
+    }
+
+    /// Return every hash of an array whose `key` has the value `value`, in
+    /// order. See `find_by`.
+    pub fn find_all_by<'a: 'k, 'k>(
+        &'a self,
+        key: &'k str,
+        value: &'k str,
+    ) -> impl Iterator<Item = &'a Yaml> + 'k {
+        self.as_vec().into_iter().flatten().filter(move |item| {
+            item.get_str(key)
+                .and_then(key_text)
+                .is_some_and(|text| text == value)
+        })
+    }
+
+    /// Compare two values for equality, ignoring differences that only
+    /// affect how they are written.
+    ///
+    /// Reals are compared by numeric value, so `1.0` equals `1.00` and `.nan`
+    /// equals `.NaN`. Integers and reals are never equal to each other. Hashes
+    /// may optionally be compared without regard to key order.
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::yaml::SemanticEqOptions;
+    /// use yaml_rust::YamlLoader;
+    ///
+    /// let a = &YamlLoader::load_from_str("{x: 1.0, y: [.5]}").unwrap()[0];
+    /// let b = &YamlLoader::load_from_str("{y: [0.50], x: 1.00}").unwrap()[0];
+    /// assert!(!a.semantic_eq(b, SemanticEqOptions::default()));
+    ///
+    /// let options = SemanticEqOptions {
+    ///     ignore_map_order: true,
+    /// };
+    /// assert!(a.semantic_eq(b, options));
+    /// ```
+    pub fn semantic_eq(&self, other: &Yaml, options: SemanticEqOptions) -> bool {
+        match (self, other) {
+            (Yaml::Real(a), Yaml::Real(b)) => match (parse_f64(a), parse_f64(b)) {
+                (Some(x), Some(y)) => x == y || (x.is_nan() && y.is_nan()),
+                _ => a == b,
+            },
+            (Yaml::Array(a), Yaml::Array(b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(x, y)| x.semantic_eq(y, options))
+            }
+            (Yaml::Hash(a), Yaml::Hash(b)) => {
+                if a.len() != b.len() {
+                    return false;
+                }
+                if !options.ignore_map_order {
+                    return a.iter().zip(b).all(|((ka, va), (kb, vb))| {
+                        ka.semantic_eq(kb, options) && va.semantic_eq(vb, options)
+                    });
+                }
+                // each entry of `b` pairs with one entry of `a`, by the
+                // address of its key
+                let mut paired: HashSet<*const Yaml> = HashSet::new();
+                a.iter().all(|(ka, va)| {
+                    let unpaired = |kb: &Yaml, vb: &Yaml| {
+                        !paired.contains(&(kb as *const Yaml)) && va.semantic_eq(vb, options)
+                    };
+                    let entry = b
+                        .get_key_value(ka)
+                        .filter(|&(kb, vb)| unpaired(kb, vb))
+                        // the key may be written differently in `b`
+                        .or_else(|| {
+                            b.iter()
+                                .find(|&(kb, vb)| ka.semantic_eq(kb, options) && unpaired(kb, vb))
+                        });
+                    match entry {
+                        Some((kb, _)) => paired.insert(kb),
+                        None => false,
+                    }
+                })
+            }
+            (Yaml::Tagged(ta, a), Yaml::Tagged(tb, b)) => ta == tb && a.semantic_eq(b, options),
+            _ => self == other,
+        }
+    }
+
+    /// The name of the kind of value this is, such as `"hash"` or
+    /// `"integer"`, for error messages.
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::YamlLoader;
+    ///
+    /// let doc = &YamlLoader::load_from_str("[1, 2]").unwrap()[0];
+    /// assert_eq!(format!("expected hash, found {}", doc.type_str()), "expected hash, found array");
+    /// ```
+    pub fn type_str(&self) -> &'static str {
+        match *self {
+            Yaml::Real(_) => "real",
+            Yaml::Integer(_) => "integer",
+            Yaml::String(_) | Yaml::Interned(_) => "string",
+            Yaml::Boolean(_) => "boolean",
+            Yaml::Array(_) => "array",
+            Yaml::Hash(_) => "hash",
+            Yaml::Tagged(..) => "tagged value",
+            Yaml::Alias(_) => "alias",
+            Yaml::Null => "null",
+            Yaml::BadValue => "bad value",
+        }
+    }
+
+    /// Release the excess capacity of every string and collection in this
+    /// value, including hash keys and tags, for values kept for a long time
+    /// that were loaded without `YamlLoader::shrink_to_fit`. Hashes are
+    /// rebuilt, since their keys can't be changed in place.
+    pub fn shrink_to_fit(&mut self) {
+        match *self {
+            Yaml::Real(ref mut v) | Yaml::String(ref mut v) => v.shrink_to_fit(),
+            Yaml::Array(ref mut v) => {
+                v.iter_mut().for_each(Yaml::shrink_to_fit);
+                v.shrink_to_fit();
+            }
+            Yaml::Hash(ref mut h) => {
+                *h = mem::take(h)
+                    .into_iter()
+                    .map(|(mut key, mut value)| {
+                        key.shrink_to_fit();
+                        value.shrink_to_fit();
+                        (key, value)
+                    })
+                    .collect();
+                h.shrink_to_fit();
+            }
+            Yaml::Tagged(ref mut tag, ref mut v) => {
+                tag.handle.shrink_to_fit();
+                tag.suffix.shrink_to_fit();
+                v.shrink_to_fit();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Options for `Yaml::semantic_eq`.
+#[derive(Clone, Copy, PartialEq, Debug, Default, Eq)]
+pub struct SemanticEqOptions {
+    /// Treat hashes with the same entries in a different order as equal.
+    pub ignore_map_order: bool,
+}
+
+/// The error returned when a `Yaml` value cannot be converted with `TryFrom`.
+#[derive(Clone, Copy, PartialEq, Debug, Eq)]
+pub struct YamlConversionError {
+    /// The kind of value the conversion requires.
+    pub expected: &'static str,
+    /// The kind of value that was encountered.
+    pub found: &'static str,
+}
+
+impl fmt::Display for YamlConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "expected {}, found {}", self.expected, self.found)
+    }
+}
+
+impl Error for YamlConversionError {}
+
+macro_rules! define_try_from (
+    ($t:ty, $expected:expr, $into:ident) => (
+impl TryFrom<Yaml> for $t {
+    type Error = YamlConversionError;
+
+    fn try_from(value: Yaml) -> Result<$t, YamlConversionError> {
+        let found = value.type_str();
+        value.$into().ok_or(YamlConversionError {
+            expected: $expected,
+            found,
+        })
+    }
+}
+    );
+);
+
+define_try_from!(bool, "boolean", into_bool);
+define_try_from!(i64, "integer", into_i64);
+define_try_from!(f64, "real", into_f64);
+define_try_from!(String, "string", into_string);
+define_try_from!(Array, "array", into_vec);
+define_try_from!(Hash, "hash", into_hash);
+
+/// What `Yaml::into_string_map` does with hash keys that are not strings.
+#[derive(Clone, Copy, PartialEq, Debug, Default, Eq)]
+pub enum NonStringKeys {
+    /// Fail with a `YamlConversionError`.
+    #[default]
+    Error,
+    /// Leave the entry out.
+    Skip,
+}
+
+impl Yaml {
+    /// Convert a hash into a map with string keys, such as
+    /// `BTreeMap<String, Yaml>` or `HashMap<String, Yaml>`, for code that
+    /// doesn't care about the order of entries. `TryFrom` does the same
+    /// with `NonStringKeys::Error`.
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use std::collections::BTreeMap;
+    /// use yaml_rust::yaml::NonStringKeys;
+    /// use yaml_rust::{Yaml, YamlLoader};
+    ///
+    /// let doc = YamlLoader::load_from_str("{b: 1, a: 2, 3: c}").unwrap().remove(0);
+    /// assert!(doc.clone().into_string_map::<BTreeMap<_, _>>(NonStringKeys::Error).is_err());
+    /// let map: BTreeMap<String, Yaml> = doc.into_string_map(NonStringKeys::Skip).unwrap();
+    /// assert_eq!(map.keys().collect::<Vec<_>>(), ["a", "b"]);
+    /// assert_eq!(Yaml::from(map).as_hash().unwrap().len(), 2);
+    /// ```
+    pub fn into_string_map<M>(
+        self,
+        non_string_keys: NonStringKeys,
+    ) -> Result<M, YamlConversionError>
+    where
+        M: FromIterator<(String, Yaml)>,
+    {
+        let found = self.type_str();
+        let hash = self.into_hash().ok_or(YamlConversionError {
+            expected: "hash",
+            found,
+        })?;
+        hash.into_iter()
+            .filter_map(|(k, v)| match k {
+                Yaml::String(k) => Some(Ok((k, v))),
+                Yaml::Interned(k) => Some(Ok((String::from(&*k), v))),
+                _ => match non_string_keys {
+                    NonStringKeys::Error => Some(Err(YamlConversionError {
+                        expected: "string key",
+                        found: k.type_str(),
+                    })),
+                    NonStringKeys::Skip => None,
+                },
+            })
+            .collect()
+    }
+}
+
+impl TryFrom<Yaml> for BTreeMap<String, Yaml> {
+    type Error = YamlConversionError;
+
+    fn try_from(value: Yaml) -> Result<Self, YamlConversionError> {
+        value.into_string_map(NonStringKeys::Error)
+    }
+}
+
+impl<S: BuildHasher + Default> TryFrom<Yaml> for HashMap<String, Yaml, S> {
+    type Error = YamlConversionError;
+
+    fn try_from(value: Yaml) -> Result<Self, YamlConversionError> {
+        value.into_string_map(NonStringKeys::Error)
+    }
+}
+
+impl From<BTreeMap<String, Yaml>> for Yaml {
+    fn from(map: BTreeMap<String, Yaml>) -> Yaml {
+        Yaml::Hash(map.into_iter().map(|(k, v)| (Yaml::String(k), v)).collect())
+    }
+}
+
+/// Entries are added in the map's iteration order.
+impl<S> From<HashMap<String, Yaml, S>> for Yaml {
+    fn from(map: HashMap<String, Yaml, S>) -> Yaml {
+        Yaml::Hash(map.into_iter().map(|(k, v)| (Yaml::String(k), v)).collect())
+    }
+}
+
+impl Yaml {
+    /// Convert a hash into another kind of map with the same entries, such
+    /// as `BTreeMap<Yaml, Yaml>` for entries ordered by key, or an
+    /// `IndexMap<Yaml, Yaml>` with the `indexmap` feature. Nested hashes
+    /// are not converted. `TryFrom` does the same for those maps.
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use std::collections::BTreeMap;
+    /// use yaml_rust::{Yaml, YamlLoader};
+    ///
+    /// let doc = YamlLoader::load_from_str("{b: 1, a: {d: 2, c: 3}}").unwrap().remove(0);
+    /// let map: BTreeMap<Yaml, Yaml> = doc.into_map().unwrap();
+    /// let keys: Vec<_> = map.keys().filter_map(Yaml::as_str).collect();
+    /// assert_eq!(keys, ["a", "b"]);
+    /// assert_eq!(map[&Yaml::from_str("a")]["d"], Yaml::Integer(2));
+    /// ```
+    pub fn into_map<M>(self) -> Result<M, YamlConversionError>
+    where
+        M: FromIterator<(Yaml, Yaml)>,
+    {
+        let found = self.type_str();
+        self.into_hash()
+            .map(|hash| hash.into_iter().collect())
+            .ok_or(YamlConversionError {
+                expected: "hash",
+                found,
+            })
+    }
+}
+
+impl TryFrom<Yaml> for BTreeMap<Yaml, Yaml> {
+    type Error = YamlConversionError;
+
+    fn try_from(value: Yaml) -> Result<Self, YamlConversionError> {
+        value.into_map()
+    }
+}
+
+impl From<BTreeMap<Yaml, Yaml>> for Yaml {
+    fn from(map: BTreeMap<Yaml, Yaml>) -> Yaml {
+        Yaml::Hash(map.into_iter().collect())
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl<S: BuildHasher + Default> TryFrom<Yaml> for indexmap::IndexMap<Yaml, Yaml, S> {
+    type Error = YamlConversionError;
+
+    fn try_from(value: Yaml) -> Result<Self, YamlConversionError> {
+        value.into_map()
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl<S> From<indexmap::IndexMap<Yaml, Yaml, S>> for Yaml {
+    fn from(map: indexmap::IndexMap<Yaml, Yaml, S>) -> Yaml {
+        Yaml::Hash(map.into_iter().collect())
+    }
+}
+
+#[allow(clippy::should_implement_trait)]
+impl Yaml {
+    // Not implementing FromStr because there is no possibility of Error.
+    // This function falls back to Yaml::String if nothing else matches.
+    pub fn from_str(v: &str) -> Yaml {
+        Yaml::from_str_with(v, &NumberFormat::default())
+    }
+
+    /// Resolve `v` as a plain scalar with `schema`, as `YamlLoader` does.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::yaml::Schema;
+    /// use yaml_rust::Yaml;
+    ///
+    /// assert_eq!(Yaml::from_str_with_schema("~", Schema::Core), Yaml::Null);
+    /// assert_eq!(Yaml::from_str_with_schema("~", Schema::Json).as_str(), Some("~"));
+    /// assert_eq!(Yaml::from_str_with_schema("1.5", Schema::Failsafe).as_str(), Some("1.5"));
+    /// ```
+    pub fn from_str_with_schema(v: &str, schema: Schema) -> Yaml {
+        plain_node(v, schema, &NumberFormat::default())
+    }
+
+    /// Like `from_str`, reading numbers as `format` says.
+    pub fn from_str_with(v: &str, format: &NumberFormat) -> Yaml {
+        match v {
+            "~" | "null" => return Yaml::Null,
+            "true" => return Yaml::Boolean(true),
+            "false" => return Yaml::Boolean(false),
+            _ => {}
+        }
+        if let Some(i) = format.parse_int(v) {
+            return Yaml::Integer(i);
+        }
+        match format.parse_real(v) {
+            Some(text) => Yaml::Real(text),
+            None => Yaml::String(v.to_owned()),
+        }
+    }
+
+    /// A `Yaml::Real` holding `v`, written so that it loads back as a real
+    /// with the same value: `1.0` rather than `1`, and `.inf`, `-.inf` and
+    /// `.nan` for the values that are not finite.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::Yaml;
+    ///
+    /// assert_eq!(Yaml::from_f64(1.0), Yaml::Real("1.0".to_owned()));
+    /// assert_eq!(Yaml::from_f64(f64::NEG_INFINITY), Yaml::Real("-.inf".to_owned()));
+    /// ```
+    pub fn from_f64(v: f64) -> Yaml {
+        Yaml::Real(format_real(v))
+    }
+}
+
+static BAD_VALUE: Yaml = Yaml::BadValue;
+impl<'a> Index<&'a str> for Yaml {
+    type Output = Yaml;
+
+    fn index(&self, idx: &'a str) -> &Yaml {
+        self.get_str(idx).unwrap_or(&BAD_VALUE)
+    }
+}
+
+impl Index<usize> for Yaml {
+    type Output = Yaml;
+
+    fn index(&self, idx: usize) -> &Yaml {
+        if let Some(v) = self.as_vec() {
+            v.get(idx).unwrap_or(&BAD_VALUE)
+        } else if let Some(v) = self.as_hash() {
+            let key = Yaml::Integer(idx as i64);
+            v.get(&key).unwrap_or(&BAD_VALUE)
+        } else {
+            &BAD_VALUE
+        }
+    }
+}
+
+impl IntoIterator for Yaml {
+    type Item = Yaml;
+    type IntoIter = YamlIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        YamlIter {
+            yaml: self.into_vec().unwrap_or_default().into_iter(),
+        }
+    }
+}
+
+pub struct YamlIter {
+    yaml: vec::IntoIter<Yaml>,
+}
+
+impl Iterator for YamlIter {
+    type Item = Yaml;
+
+    fn next(&mut self) -> Option<Yaml> {
+        self.yaml.next()
+    }
+}
+
+/// An entry of a hash, see `Yaml::entries`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct HashEntry<'a> {
+    pub key: &'a Yaml,
+    pub value: &'a Yaml,
+    /// Whether the key is a collection, see `Yaml::is_complex_key`.
+    pub complex: bool,
+}
+
+impl<'a> HashEntry<'a> {
+    /// The key if it is a string, for the common hash of string keys.
+    ///
+    /// ```
+    /// # extern crate yaml_rust_davvid as yaml_rust;
+    /// use yaml_rust::YamlLoader;
+    ///
+    /// let doc = &YamlLoader::load_from_str("{name: web, 1: one, replicas: 3}").unwrap()[0];
+    /// let named: Vec<(&str, i64)> = doc
+    ///     .entries()
+    ///     .filter_map(|e| Some((e.key_str()?, e.value().as_i64()?)))
+    ///     .collect();
+    /// assert_eq!(named, [("replicas", 3)]);
+    /// ```
+    pub fn key_str(&self) -> Option<&'a str> {
+        self.key.as_str()
+    }
+
+    pub fn key(&self) -> &'a Yaml {
+        self.key
+    }
+
+    pub fn value(&self) -> &'a Yaml {
+        self.value
+    }
+}
+
+pub struct HashEntries<'a> {
+    entries: Option<hashlink::linked_hash_map::Iter<'a, Yaml, Yaml>>,
+}
+
+impl<'a> Iterator for HashEntries<'a> {
+    type Item = HashEntry<'a>;
+
+    fn next(&mut self) -> Option<HashEntry<'a>> {
+        let (key, value) = self.entries.as_mut()?.next()?;
+        Some(HashEntry {
+            key,
+            value,
+            complex: key.is_complex_key(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::scanner::*;
+    use crate::yaml::*;
+    use std::cell::RefCell;
+    use std::convert::TryInto;
+    use std::f64;
+
+    #[test]
+    fn test_coerce() {
+        let s = "---
+a: 1
+b: 2.2
+c: [1, 2]
+";
+        let out = YamlLoader::load_from_str(s).unwrap();
+        let doc = &out[0];
+        assert_eq!(doc["a"].as_i64().unwrap(), 1i64);
+        assert_eq!(doc["b"].as_f64().unwrap(), 2.2f64);
+        assert_eq!(doc["c"][1].as_i64().unwrap(), 2i64);
+        assert!(doc["d"][0].is_badvalue());
+    }
+
+    #[test]
+    fn test_empty_doc() {
+        let s: String = "".to_owned();
+        YamlLoader::load_from_str(&s).unwrap();
+        let s: String = "---".to_owned();
+        assert_eq!(YamlLoader::load_from_str(&s).unwrap()[0], Yaml::Null);
+    }
+
+    #[test]
+    fn test_parser() {
+        let s: String = "
+# comment
+a0 bb: val
+a1:
+    b1: 4
+    b2: d
+a2: 4 # i'm comment
+a3: [1, 2, 3]
+a4:
+    - - a1
+      - a2
+    - 2
+a5: 'single_quoted'
+a6: \"double_quoted\"
+a7: 你好
+"
+        .to_owned();
+        let out = YamlLoader::load_from_str(&s).unwrap();
+        let doc = &out[0];
+        assert_eq!(doc["a7"].as_str().unwrap(), "你好");
+    }
+
+    #[test]
+    fn test_multi_doc() {
+        let s = "
+'a scalar'
+---
+'a scalar'
+---
+'a scalar'
+";
+        let out = YamlLoader::load_from_str(s).unwrap();
+        assert_eq!(out.len(), 3);
+    }
+
+    #[test]
+    fn test_anchor() {
+        let s = "
+a1: &DEFAULT
+    b1: 4
+    b2: d
+a2: *DEFAULT
+";
+        let out = YamlLoader::load_from_str(s).unwrap();
+        let doc = &out[0];
+        assert_eq!(doc["a2"]["b1"].as_i64().unwrap(), 4);
+    }
+
+    #[test]
+    fn test_bad_anchor() {
+        let s = "
+a1: &DEFAULT
+    b1: 4
+    b2: *DEFAULT
+";
+        let err = YamlLoader::load_from_str(s).unwrap_err();
+        assert_eq!(
+            err.info().split(',').next(),
+            Some("found recursive alias *DEFAULT")
+        );
+    }
+
+    #[test]
+    fn test_recursive_alias() {
+        let s = "
+a: &x
+  b: [*x]
+";
+        let err = YamlLoader::load_from_str(s).unwrap_err();
+        assert_eq!(
+            err.info(),
+            "found recursive alias *x, anchor &x is at line 2 column 4"
+        );
+        assert_eq!(err.marker().line(), 3);
+        assert_eq!(err.marker().col(), 6);
+
+        let err = YamlLoader::load_from_str("&y [1, [*y]]").unwrap_err();
+        assert_eq!(
+            err.info(),
+            "found recursive alias *y, anchor &y is at line 1 column 1"
+        );
+
+        // the anchor is found after its tag, and the collection can start on
+        // a later line
+        let err = YamlLoader::load_from_str(
+            "- !!seq &z
+  - [*z]",
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.info(),
+            "found recursive alias *z, anchor &z is at line 1 column 9"
+        );
+
+        // sibling and nested anchors are fine
+        let s = "[&a [1], &b [*a, &c [2]], *b, *c]";
+        let out = YamlLoader::load_from_str(s).unwrap();
+        assert_eq!(out[0][2][0][0], Yaml::Integer(1));
+        assert_eq!(out[0][3][0], Yaml::Integer(2));
+    }
+
+    #[test]
+    fn test_anchor_scope() {
+        let s = "--- &a 1\n--- *a\n";
+        let err = YamlLoader::load_from_str(s).unwrap_err();
+        assert_eq!(err.info(), "while parsing node, found unknown anchor");
+    }
+
+    #[test]
+    fn test_github_27() {
+        // https://github.com/chyh1990/yaml-rust/issues/27
+        let s = "&a";
+        let out = YamlLoader::load_from_str(s).unwrap();
+        let doc = &out[0];
+        assert_eq!(doc.as_str().unwrap(), "");
+    }
+
+    #[test]
+    #[allow(clippy::cognitive_complexity)]
+    fn test_plain_datatype() {
+        let s = "
+- 'string'
+- \"string\"
+- string
+- 123
+- -321
+- 1.23
+- -1e4
+- ~
+- null
+- true
+- false
+- !!str 0
+- !!int 100
+- !!float 2
+- !!null ~
+- !!bool true
+- !!bool false
+- 0xFF
+# bad values
+- !!int string
+- !!float string
+- !!bool null
+- !!null val
+- 0o77
+- [ 0xF, 0xF ]
+- +12345
+- [ true, false ]
+";
+        let out = YamlLoader::load_from_str(s).unwrap();
+        let doc = &out[0];
+
+        assert_eq!(doc[0].as_str().unwrap(), "string");
+        assert_eq!(doc[1].as_str().unwrap(), "string");
+        assert_eq!(doc[2].as_str().unwrap(), "string");
+        assert_eq!(doc[3].as_i64().unwrap(), 123);
+        assert_eq!(doc[4].as_i64().unwrap(), -321);
+        assert_eq!(doc[5].as_f64().unwrap(), 1.23);
+        assert_eq!(doc[6].as_f64().unwrap(), -1e4);
+        assert!(doc[7].is_null());
+        assert!(doc[8].is_null());
+        assert!(doc[9].as_bool().unwrap());
+        assert!(!doc[10].as_bool().unwrap());
+        assert_eq!(doc[11].as_str().unwrap(), "0");
+        assert_eq!(doc[12].as_i64().unwrap(), 100);
+        assert_eq!(doc[13].as_f64().unwrap(), 2.0);
+        assert!(doc[14].is_null());
+        assert!(doc[15].as_bool().unwrap());
+        assert!(!doc[16].as_bool().unwrap());
+        assert_eq!(doc[17].as_i64().unwrap(), 255);
+        assert!(doc[18].is_badvalue());
+        assert!(doc[19].is_badvalue());
+        assert!(doc[20].is_badvalue());
+        assert!(doc[21].is_badvalue());
+        assert_eq!(doc[22].as_i64().unwrap(), 63);
+        assert_eq!(doc[23][0].as_i64().unwrap(), 15);
+        assert_eq!(doc[23][1].as_i64().unwrap(), 15);
+        assert_eq!(doc[24].as_i64().unwrap(), 12345);
+        assert!(doc[25][0].as_bool().unwrap());
+        assert!(!doc[25][1].as_bool().unwrap());
+    }
+
+    #[test]
+    fn test_bad_hyphen() {
+        // See: https://github.com/chyh1990/yaml-rust/issues/23
+        let s = "{-";
+        assert!(YamlLoader::load_from_str(s).is_err());
+    }
+
+    #[test]
+    fn test_issue_65() {
+        // See: https://github.com/chyh1990/yaml-rust/issues/65
+        let b = "\n\"ll\\\"ll\\\r\n\"ll\\\"ll\\\r\r\r\rU\r\r\rU";
+        assert!(YamlLoader::load_from_str(b).is_err());
+    }
+
+    #[test]
+    fn test_bad_docstart() {
+        assert!(YamlLoader::load_from_str("---This used to cause an infinite loop").is_ok());
+        assert_eq!(
+            YamlLoader::load_from_str("----"),
+            Ok(vec![Yaml::String(String::from("----"))])
+        );
+        assert_eq!(
+            YamlLoader::load_from_str("--- #here goes a comment"),
+            Ok(vec![Yaml::Null])
+        );
+        assert_eq!(
+            YamlLoader::load_from_str("---- #here goes a comment"),
+            Ok(vec![Yaml::String(String::from("----"))])
+        );
+    }
+
+    #[test]
+    fn test_plain_datatype_with_into_methods() {
+        let s = "
+- 'string'
+- \"string\"
+- string
+- 123
+- -321
+- 1.23
+- -1e4
+- true
+- false
+- !!str 0
+- !!int 100
+- !!float 2
+- !!bool true
+- !!bool false
+- 0xFF
+- 0o77
+- +12345
+- -.INF
+- .NAN
+- !!float .INF
+";
+        let mut out = YamlLoader::load_from_str(s).unwrap().into_iter();
+        let mut doc = out.next().unwrap().into_iter();
+
+        assert_eq!(doc.next().unwrap().into_string().unwrap(), "string");
+        assert_eq!(doc.next().unwrap().into_string().unwrap(), "string");
+        assert_eq!(doc.next().unwrap().into_string().unwrap(), "string");
+        assert_eq!(doc.next().unwrap().into_i64().unwrap(), 123);
+        assert_eq!(doc.next().unwrap().into_i64().unwrap(), -321);
+        assert_eq!(doc.next().unwrap().into_f64().unwrap(), 1.23);
+        assert_eq!(doc.next().unwrap().into_f64().unwrap(), -1e4);
+        assert!(doc.next().unwrap().into_bool().unwrap());
+        assert!(!doc.next().unwrap().into_bool().unwrap());
+        assert_eq!(doc.next().unwrap().into_string().unwrap(), "0");
+        assert_eq!(doc.next().unwrap().into_i64().unwrap(), 100);
+        assert_eq!(doc.next().unwrap().into_f64().unwrap(), 2.0);
+        assert!(doc.next().unwrap().into_bool().unwrap());
+        assert!(!doc.next().unwrap().into_bool().unwrap());
+        assert_eq!(doc.next().unwrap().into_i64().unwrap(), 255);
+        assert_eq!(doc.next().unwrap().into_i64().unwrap(), 63);
+        assert_eq!(doc.next().unwrap().into_i64().unwrap(), 12345);
+        assert_eq!(doc.next().unwrap().into_f64().unwrap(), f64::NEG_INFINITY);
+        assert!(doc.next().unwrap().into_f64().is_some());
+        assert_eq!(doc.next().unwrap().into_f64().unwrap(), f64::INFINITY);
+    }
+
+    #[test]
+    fn test_hash_order() {
+        let s = "---
+b: ~
+a: ~
+c: ~
+";
+        let out = YamlLoader::load_from_str(s).unwrap();
+        let first = out.into_iter().next().unwrap();
+        let mut iter = first.into_hash().unwrap().into_iter();
+        assert_eq!(
+            Some((Yaml::String("b".to_owned()), Yaml::Null)),
+            iter.next()
+        );
+        assert_eq!(
+            Some((Yaml::String("a".to_owned()), Yaml::Null)),
+            iter.next()
+        );
+        assert_eq!(
+            Some((Yaml::String("c".to_owned()), Yaml::Null)),
+            iter.next()
+        );
+        assert_eq!(None, iter.next());
+    }
+
+    #[test]
+    fn test_integer_key() {
+        let s = "
+0:
+    important: true
+1:
+    important: false
+";
+        let out = YamlLoader::load_from_str(s).unwrap();
+        let first = out.into_iter().next().unwrap();
+        assert!(first[0]["important"].as_bool().unwrap());
+    }
+
+    #[test]
+    fn test_shrink_to_fit() {
+        let s = "
+key: 'a quoted string that the scanner grew'
+list: [1, 2, 3, 4, 5]
+";
+        let mut loader = YamlLoader::new();
+        loader.shrink_to_fit(true);
+        let out = loader.parse_from_str(s).unwrap();
+        let doc = &out[0];
+        match doc["key"] {
+            Yaml::String(ref v) => assert_eq!(v.capacity(), v.len()),
+            _ => panic!("expected a string"),
+        }
+        let list = doc["list"].as_vec().unwrap();
+        assert_eq!(list.capacity(), list.len());
+        assert_eq!(out, YamlLoader::load_from_str(s).unwrap());
+    }
+
+    #[test]
+    fn test_intern_keys() {
+        let s = "
+- name: web
+  spec: {name: x, 8080: port}
+- name: name
+  'spec': ~
+";
+        let mut loader = YamlLoader::new();
+        loader.intern_keys(true);
+        let out = loader.parse_from_str(s).unwrap();
+        let key = |node: &Yaml, i: usize| match node.as_hash().unwrap().keys().nth(i) {
+            Some(Yaml::Interned(key)) => key.clone(),
+            key => panic!("expected an interned key, found {:?}", key),
+        };
+        let items = out[0].as_vec().unwrap();
+        let name = key(&items[0], 0);
+        assert!(Arc::ptr_eq(&name, &key(&items[1], 0)));
+        assert!(Arc::ptr_eq(&name, &key(&items[0]["spec"], 0)));
+        assert!(Arc::ptr_eq(&key(&items[0], 1), &key(&items[1], 1)));
+        // values and other keys are loaded as usual
+        assert_eq!(items[1]["name"], Yaml::String("name".to_owned()));
+        assert_eq!(
+            items[0]["spec"].as_hash().unwrap().keys().nth(1),
+            Some(&Yaml::Integer(8080))
+        );
+
+        // interned keys are the same as string keys
+        let plain = YamlLoader::load_from_str(s).unwrap();
+        assert_eq!(out, plain);
+        assert_eq!(Yaml::Interned(name.clone()), Yaml::from_str("name"));
+        assert_eq!(
+            Yaml::Interned(name.clone()).cmp(&Yaml::from_str("name")),
+            Ordering::Equal
+        );
+        assert!(Yaml::Interned(name.clone()) < Yaml::Boolean(false));
+        assert!(items[0]
+            .as_hash()
+            .unwrap()
+            .contains_key(&Yaml::from_str("spec")));
+        assert_eq!(items[0].get_str("name").and_then(Yaml::as_str), Some("web"));
+        assert_eq!(Yaml::Interned(name).into_string(), Some("name".to_owned()));
+        let mut emitted = String::new();
+        YamlEmitter::new(&mut emitted).dump(&out[0]).unwrap();
+        let mut expected = String::new();
+        YamlEmitter::new(&mut expected).dump(&plain[0]).unwrap();
+        assert_eq!(emitted, expected);
+    }
+
+    #[test]
+    fn test_string_cache() {
+        let name = |docs: &[Yaml]| match docs[0].as_hash().unwrap().keys().next() {
+            Some(Yaml::Interned(key)) => key.clone(),
+            key => panic!("expected an interned key, found {:?}", key),
+        };
+        let cache = StringCache::new();
+        let mut a = YamlLoader::new();
+        a.string_cache(&cache);
+        let mut builder = YamlLoader::builder();
+        builder.string_cache(&cache);
+        let b = builder.build();
+        let a = a.parse_from_str("{name: x, a: 1}").unwrap();
+        let b = b.parse_from_str("{name: y, b: 2}").unwrap();
+        assert!(Arc::ptr_eq(&name(&a), &name(&b)));
+        assert_eq!(cache.len(), 3);
+        assert!(Arc::ptr_eq(&name(&a), &cache.intern("name")));
+
+        // loaders without a shared cache don't share keys
+        let mut c = YamlLoader::new();
+        c.intern_keys(true);
+        let c = c.parse_from_str("{name: z}").unwrap();
+        assert!(!Arc::ptr_eq(&name(&a), &name(&c)));
+        assert_eq!(cache.len(), 3);
+    }
+
+    #[test]
+    fn test_scalar_chunk_events() {
+        let config = ScannerConfig {
+            block_scalar_chunk_bytes: Some(4),
+            ..ScannerConfig::default()
+        };
+        let s = "- |\n  line one\n  line two\n  line three\n- &x !t >\n  a b\n  c d\n- *x\n";
+        let log: EventLog = Parser::new_with_config(s.chars(), config)
+            .map(Result::unwrap)
+            .collect();
+        assert!(log
+            .events()
+            .iter()
+            .any(|(ev, _)| matches!(ev, Event::ScalarChunk(_))));
+        let docs = YamlLoader::new().parse_from_events(&log).unwrap();
+        assert_eq!(docs, YamlLoader::load_from_str(s).unwrap());
+        assert_eq!(
+            docs[0][0].as_str(),
+            Some("line one\nline two\nline three\n")
+        );
+        assert_eq!(docs[0][2].as_str(), Some("a b c d\n"));
+    }
+
+    #[test]
+    fn test_parse_from_events() {
+        let s = "a: &x [*x]";
+        let log = EventLog::record(s).unwrap();
+        let err = YamlLoader::new().parse_from_events(&log).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "found recursive alias, its anchor is at line 1 column 7 at line 1 column 8, i.e. byte index 7"
+        );
+        assert!(YamlLoader::load_from_str(s)
+            .unwrap_err()
+            .to_string()
+            .starts_with("found recursive alias *x, anchor &x is at line 1 column 4"));
+
+        // the options of the loader apply
+        let log = EventLog::record("# nothing").unwrap();
+        let mut loader = YamlLoader::new();
+        loader.empty_stream_mode(EmptyStreamMode::Null);
+        assert_eq!(loader.parse_from_events(&log).unwrap(), [Yaml::Null]);
+        let mut loader = YamlLoader::new();
+        loader.key_coercion(KeyCoercion::String);
+        let log = EventLog::record("1: a").unwrap();
+        assert_eq!(
+            loader.parse_from_events(&log).unwrap()[0]["1"].as_str(),
+            Some("a")
+        );
+
+        // a log without its end loads what it has
+        let log: EventLog = EventLog::record("[a]")
+            .unwrap()
+            .into_iter()
+            .filter(|(ev, _)| *ev != Event::StreamEnd)
+            .collect();
+        assert_eq!(YamlLoader::new().parse_from_events(&log).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_into_map() {
+        let doc = YamlLoader::load_from_str("{z: 1, 2: b, a: [c]}")
+            .unwrap()
+            .remove(0);
+        let map = BTreeMap::try_from(doc.clone()).unwrap();
+        let keys: Vec<&Yaml> = map.keys().collect();
+        assert_eq!(
+            keys,
+            [
+                &Yaml::Integer(2),
+                &Yaml::from_str("a"),
+                &Yaml::from_str("z")
+            ]
+        );
+        let back = Yaml::from(map);
+        assert_ne!(back, doc);
+        assert!(back.semantic_eq(
+            &doc,
+            SemanticEqOptions {
+                ignore_map_order: true
+            }
+        ));
+        assert_eq!(
+            Yaml::Integer(1)
+                .into_map::<BTreeMap<_, _>>()
+                .unwrap_err()
+                .to_string(),
+            "expected hash, found integer"
+        );
+    }
+
+    #[cfg(feature = "indexmap")]
+    #[test]
+    fn test_into_index_map() {
+        use indexmap::IndexMap;
+
+        let doc = YamlLoader::load_from_str("{z: 1, 2: b, a: [c]}")
+            .unwrap()
+            .remove(0);
+        let map: IndexMap<Yaml, Yaml> = IndexMap::try_from(doc.clone()).unwrap();
+        assert_eq!(
+            map.get_index(1),
+            Some((&Yaml::Integer(2), &Yaml::from_str("b")))
+        );
+        assert_eq!(Yaml::from(map), doc);
+    }
+
+    #[test]
+    fn test_flow_capacity() {
+        let s = "{a: [1, 2, 3, 4, 5], b: [x, [y], 'p, q', \"r\\\", s\",], c: []}";
+        let doc = &YamlLoader::load_from_str(s).unwrap()[0];
+        for key in ["a", "b", "c"] {
+            let v = doc[key].as_vec().unwrap();
+            assert_eq!(v.capacity(), v.len(), "{}", key);
+        }
+        assert_eq!(doc["b"][3].as_str(), Some("r\", s"));
+    }
+
+    #[test]
+    fn test_yaml_shrink_to_fit() {
+        let s = "
+'a quoted key': [1, 'a quoted string', !t {k: 'v'}]
+";
+        let mut doc = YamlLoader::load_from_str(s).unwrap().remove(0);
+        let loaded = doc.clone();
+        let mut key = String::with_capacity(64);
+        key.push('k');
+        if let Yaml::Hash(ref mut h) = doc {
+            h.insert(Yaml::String(key), Yaml::Array(Vec::with_capacity(64)));
+        }
+        doc.shrink_to_fit();
+
+        fn check(node: &Yaml) {
+            match *node {
+                Yaml::String(ref v) => assert_eq!(v.capacity(), v.len()),
+                Yaml::Array(ref v) => {
+                    assert_eq!(v.capacity(), v.len());
+                    v.iter().for_each(check);
+                }
+                Yaml::Hash(ref h) => h.iter().for_each(|(k, v)| {
+                    check(k);
+                    check(v);
+                }),
+                Yaml::Tagged(_, ref v) => check(v),
+                _ => {}
+            }
+        }
+        check(&doc);
+        if let Yaml::Hash(ref mut h) = doc {
+            assert_eq!(h.pop_back().unwrap().1, Yaml::Array(Vec::new()));
+        }
+        assert_eq!(doc, loaded);
+    }
+
+    #[test]
+    fn test_bad_value_mode() {
+        let s = "
+- !!int 1
+- !!int string
+- !!bool yes
+";
+        let out = YamlLoader::load_from_str(s).unwrap();
+        assert_eq!(out[0][1], Yaml::BadValue);
+        assert_eq!(out[0][2], Yaml::BadValue);
+
+        let mut loader = YamlLoader::new();
+        loader.bad_value_mode(BadValueMode::Error);
+        let err = loader.parse_from_str(s).unwrap_err();
+        assert_eq!(err.info(), "invalid value \"string\" for tag !!int");
+        assert_eq!(err.marker().line(), 3);
+        assert_eq!(err.marker().col(), 8);
+
+        let mut loader = YamlLoader::new();
+        loader.bad_value_mode(BadValueMode::Error);
+        let out = loader
+            .parse_from_str("[!!int 1, !!str x, !!null ~]")
+            .unwrap();
+        assert_eq!(out[0][0], Yaml::Integer(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid value \"string\" for tag !!int")]
+    fn test_bad_value_mode_panic() {
+        let mut loader = YamlLoader::new();
+        loader.bad_value_mode(BadValueMode::Panic);
+        let _ = loader.parse_from_str("!!int string");
+    }
+
+    #[test]
+    fn test_number_format() {
+        let core = NumberFormat::default();
+        assert_eq!(core.parse_int("-9223372036854775808"), Some(i64::MIN));
+        assert_eq!(core.parse_int("9223372036854775808"), None);
+        assert_eq!(core.parse_int("+0x10"), None);
+        assert_eq!(core.parse_int("+-1"), None);
+        assert_eq!(core.parse_int("1:30"), None);
+        assert_eq!(core.parse_real("1_0"), None);
+        assert_eq!(core.parse_real("1e3"), Some("1e3".to_owned()));
+
+        let format = NumberFormat {
+            underscores: true,
+            sexagesimal: true,
+            leading_zero: LeadingZero::String,
+        };
+        assert_eq!(format.parse_int("-1:00:00"), Some(-3600));
+        assert_eq!(format.parse_int("1:60"), None);
+        assert_eq!(format.parse_int("1::0"), None);
+        assert_eq!(format.parse_int("0x_f_f"), None);
+        assert_eq!(format.parse_int("0xf_f"), Some(255));
+        assert_eq!(format.parse_int("_1"), None);
+        assert_eq!(format.parse_int("012"), None);
+        assert_eq!(format.parse_real("012"), None);
+        assert_eq!(format.parse_real("0.5"), Some("0.5".to_owned()));
+        assert_eq!(format.parse_real("._inf"), None);
+        assert_eq!(format.parse_int("153722867280912930:08"), None);
+    }
+
+    #[test]
+    fn test_schema() {
+        let json = |v: &str| Yaml::from_str_with_schema(v, Schema::Json);
+        assert_eq!(json("-0"), Yaml::Integer(0));
+        assert_eq!(json("1.5e-3"), Yaml::Real("1.5e-3".to_owned()));
+        assert_eq!(json("1e400"), Yaml::Real("1e400".to_owned()));
+        assert_eq!(
+            json("99999999999999999999"),
+            Yaml::Real("99999999999999999999".to_owned())
+        );
+        for v in ["01", "1.", ".5", "+1", "1e", "-", "0x1", "True", ".inf"] {
+            assert_eq!(json(v), Yaml::String(v.to_owned()), "{}", v);
+        }
+
+        // the loader resolves scalars as `parse_scalar` does
+        let s = "- ~\n- 0o7\n- '1'\n- \"true\"\n- |\n  2\n";
+        for schema in [Schema::Failsafe, Schema::Json, Schema::Core] {
+            let mut loader = YamlLoader::new();
+            loader.schema(schema);
+            let doc = loader.parse_from_str(s).unwrap().remove(0);
+            let expected = vec![
+                parse_scalar("~", TScalarStyle::Plain, schema),
+                parse_scalar("0o7", TScalarStyle::Plain, schema),
+                parse_scalar("1", TScalarStyle::SingleQuoted, schema),
+                parse_scalar("true", TScalarStyle::DoubleQuoted, schema),
+                parse_scalar("2\n", TScalarStyle::Literal, schema),
+            ];
+            assert_eq!(doc, Yaml::Array(expected));
+        }
+        assert_eq!(
+            YamlLoader::load_from_str("0o7").unwrap()[0],
+            Yaml::Integer(7)
+        );
+    }
+
+    #[test]
+    fn test_resolve_plain() {
+        let values = [
+            "",
+            "~",
+            "null",
+            "Null",
+            "true",
+            "no",
+            "0",
+            "-12",
+            "+7",
+            "012",
+            "0x1F",
+            "0o8",
+            "1_000",
+            "1:30",
+            "1.5",
+            "-.5",
+            "1e3",
+            ".inf",
+            ".NaN",
+            "1.2.3",
+            "9223372036854775808",
+            "x",
+        ];
+        let formats = [
+            NumberFormat::default(),
+            NumberFormat {
+                underscores: true,
+                sexagesimal: true,
+                leading_zero: LeadingZero::String,
+            },
+        ];
+        for schema in [Schema::Failsafe, Schema::Json, Schema::Core] {
+            for format in &formats {
+                for v in values {
+                    let expected = match plain_node(v, schema, format) {
+                        Yaml::Null => ScalarType::Null,
+                        Yaml::Boolean(_) => ScalarType::Boolean,
+                        Yaml::Integer(_) => ScalarType::Integer,
+                        Yaml::Real(_) => ScalarType::Real,
+                        _ => ScalarType::String,
+                    };
+                    assert_eq!(plain_type(v, schema, format), expected, "{:?}", v);
+                }
+            }
         }
     }
-}
 
-impl Index<usize> for Yaml {
-    type Output = Yaml;
+    #[test]
+    fn test_load_number_format() {
+        let format = NumberFormat {
+            underscores: true,
+            sexagesimal: true,
+            leading_zero: LeadingZero::Octal,
+        };
+        let s = "
+a: 1_000
+b: !!int 1:30
+c: 9_223_372_036_854_775_808
+d: '1_000'
+e: 07
+";
+        let warnings = RefCell::new(Vec::new());
+        let handler = |warning: &Warning| warnings.borrow_mut().push(warning.kind.clone());
+        let mut loader = YamlLoader::new();
+        loader.number_format(format).on_warning(&handler);
+        let doc = loader.parse_from_str(s).unwrap().remove(0);
+        assert_eq!(doc["a"], Yaml::Integer(1000));
+        assert_eq!(doc["b"], Yaml::Integer(90));
+        assert_eq!(doc["c"].as_f64(), Some(9.223372036854776e18));
+        assert_eq!(doc["d"].as_str(), Some("1_000"));
+        assert_eq!(doc["e"], Yaml::Integer(7));
+        assert!(matches!(
+            warnings.borrow()[..],
+            [WarningKind::IntegerOverflow(_)]
+        ));
+
+        let doc = YamlLoader::load_from_str(s).unwrap().remove(0);
+        assert_eq!(doc["a"].as_str(), Some("1_000"));
+        assert_eq!(doc["b"], Yaml::BadValue);
+    }
+
+    #[test]
+    fn test_structure_only() {
+        let s = "
+name: web
+spec:
+  ports: &p [{port: 80, name: http}]
+  again: *p
+  8080: !!str x
+  ? [a, b]
+  : c
+  ? [a, c]
+  : d
+  ? {k: [1, {j: 2}]}
+  : e
+tagged: !t {x: 1}
+---
+plain
+";
+        let docs = YamlLoader::builder().structure_only(true).load(s).unwrap();
+        let expected = YamlLoader::load_from_str(
+            "
+name: ~
+spec:
+  ports: [{port: ~, name: ~}]
+  again: [{port: ~, name: ~}]
+  8080: ~
+  ? [a, b]
+  : ~
+  ? [a, c]
+  : ~
+  ? {k: [1, {j: 2}]}
+  : ~
+tagged: !t {x: ~}
+--- ~
+",
+        )
+        .unwrap();
+        assert_eq!(docs, expected);
+    }
 
-    fn index(&self, idx: usize) -> &Yaml {
-        if let Some(v) = self.as_vec() {
-            v.get(idx).unwrap_or(&BAD_VALUE)
-        } else if let Some(v) = self.as_hash() {
-            let key = Yaml::Integer(idx as i64);
-            v.get(&key).unwrap_or(&BAD_VALUE)
-        } else {
-            &BAD_VALUE
-        }
+    #[test]
+    fn test_key_coercion() {
+        let s = "
+8080: a
+'8081': b
+1.5: c
+true: d
+'x': e
+[1]: f
+";
+        let mut loader = YamlLoader::new();
+        loader.key_coercion(KeyCoercion::String);
+        let doc = loader.parse_from_str(s).unwrap().remove(0);
+        let keys: Vec<_> = doc.as_hash().unwrap().keys().cloned().collect();
+        assert_eq!(
+            keys,
+            vec![
+                Yaml::String("8080".to_owned()),
+                Yaml::String("8081".to_owned()),
+                Yaml::String("1.5".to_owned()),
+                Yaml::String("true".to_owned()),
+                Yaml::String("x".to_owned()),
+                Yaml::Array(vec![Yaml::Integer(1)]),
+            ]
+        );
+        assert_eq!(doc["8080"].as_str(), Some("a"));
+
+        let mut loader = YamlLoader::new();
+        loader.key_coercion(KeyCoercion::Schema);
+        let doc = loader.parse_from_str(s).unwrap().remove(0);
+        assert_eq!(doc[8081].as_str(), Some("b"));
+        assert_eq!(doc[8080].as_str(), Some("a"));
+        assert_eq!(doc["x"].as_str(), Some("e"));
+
+        // values are not affected
+        let mut loader = YamlLoader::new();
+        loader.key_coercion(KeyCoercion::Schema);
+        let doc = loader
+            .parse_from_str("{a: '1', '2': {'3': '4'}}")
+            .unwrap()
+            .remove(0);
+        assert_eq!(doc["a"].as_str(), Some("1"));
+        assert_eq!(doc[2][3].as_str(), Some("4"));
+
+        // keys are resolved by the loader's schema
+        let mut loader = YamlLoader::new();
+        loader
+            .key_coercion(KeyCoercion::Schema)
+            .schema(Schema::Json);
+        let doc = loader
+            .parse_from_str("{'~': a, 'null': b, '0x10': c, '16': d}")
+            .unwrap()
+            .remove(0);
+        let keys: Vec<_> = doc.as_hash().unwrap().keys().cloned().collect();
+        assert_eq!(
+            keys,
+            vec![
+                Yaml::String("~".to_owned()),
+                Yaml::Null,
+                Yaml::String("0x10".to_owned()),
+                Yaml::Integer(16),
+            ]
+        );
+        let mut loader = YamlLoader::new();
+        loader
+            .key_coercion(KeyCoercion::Schema)
+            .schema(Schema::Failsafe);
+        let doc = loader.parse_from_str("'1': a").unwrap().remove(0);
+        assert_eq!(doc["1"].as_str(), Some("a"));
     }
-}
 
-impl IntoIterator for Yaml {
-    type Item = Yaml;
-    type IntoIter = YamlIter;
+    #[test]
+    fn test_conversion_helpers() {
+        let source = "a: !x [1, -2.5e3, .5, +1.0]\n1: {true: \"\\u0001\\\"\"}\n---\n[]\n---\n";
+        let json = to_json_string(source).unwrap();
+        let lines: Vec<serde_json::Value> = json
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                serde_json::json!({"a": [1, -2500.0, 0.5, 1.0], "1": {"true": "\u{1}\""}}),
+                serde_json::json!([]),
+                serde_json::Value::Null,
+            ]
+        );
+        let mut path = YamlPath::new();
+        path.push(PathElement::Index(1));
+        assert_eq!(
+            to_json_string("[1, .inf]"),
+            Err(ConvertError::NotJson(path))
+        );
+        let mut path = YamlPath::new();
+        path.push(PathElement::Key(Yaml::from_str("a")));
+        path.push(PathElement::Key(Yaml::Array(vec![Yaml::Integer(1)])));
+        assert_eq!(
+            to_json_string("{a: {[1]: 2}}"),
+            Err(ConvertError::NotJson(path))
+        );
+        assert!(matches!(to_json_string("[1"), Err(ConvertError::Scan(_))));
 
-    fn into_iter(self) -> Self::IntoIter {
-        YamlIter {
-            yaml: self.into_vec().unwrap_or_default().into_iter(),
-        }
+        // JSON converts to YAML and back
+        let json = "{\"a\":[1,2.5,\"x\\ny\",null,true],\"b\":{}}\n";
+        let yaml = from_json_string(json).unwrap();
+        assert_eq!(to_json_string(&yaml).unwrap(), json);
+        assert!(from_json_string("{a: 1}").is_err());
+
+        let opts = ReformatOptions {
+            compact: false,
+            keep_comments: false,
+            ..ReformatOptions::default()
+        };
+        assert_eq!(
+            reformat("# gone\n- {a: 1}\n---\nx\n", &opts).unwrap(),
+            "---\n-\n  a: 1\n---\nx\n"
+        );
     }
-}
 
-pub struct YamlIter {
-    yaml: vec::IntoIter<Yaml>,
-}
+    #[test]
+    fn test_json_mode() {
+        fn same(yaml: &Yaml, json: &serde_json::Value) -> bool {
+            use serde_json::Value;
+            match (yaml, json) {
+                (Yaml::Null, Value::Null) => true,
+                (Yaml::Boolean(a), Value::Bool(b)) => a == b,
+                (Yaml::Integer(a), Value::Number(b)) => b.as_i64() == Some(*a),
+                (Yaml::Real(_), Value::Number(b)) => yaml.as_f64() == b.as_f64(),
+                (Yaml::String(a), Value::String(b)) => a == b,
+                (Yaml::Array(a), Value::Array(b)) => {
+                    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same(a, b))
+                }
+                (Yaml::Hash(a), Value::Object(b)) => {
+                    a.len() == b.len()
+                        && a.iter().all(|(k, v)| match k.as_str() {
+                            Some(k) => b.get(k).is_some_and(|b| same(v, b)),
+                            None => false,
+                        })
+                }
+                _ => false,
+            }
+        }
 
-impl Iterator for YamlIter {
-    type Item = Yaml;
+        let valid = [
+            "0",
+            " -1.5e+3 ",
+            "\t[1, 2.0, -0.25E-2, true, false, null]\r\n",
+            r#"{"a":{"b":[]},"c" : {}, "":"\"\\\/\b\f\n\r\t\u00e9\ud83d\ude00"}"#,
+            "[[],[{}]]",
+            r#""line\nbreak""#,
+        ];
+        for s in &valid {
+            let doc = YamlLoader::load_from_json_str(s).unwrap();
+            let json: serde_json::Value = serde_json::from_str(s).unwrap();
+            assert!(same(&doc, &json), "{}: {:?}", s, doc);
+        }
 
-    fn next(&mut self) -> Option<Yaml> {
-        self.yaml.next()
+        let invalid = [
+            "",
+            "a: 1",
+            "{a: 1}",
+            "[1, 2,]",
+            r#"{"a": 1,}"#,
+            r#"{"a"}"#,
+            r#"{"a": }"#,
+            r#"{1: 2}"#,
+            r#"["a": 1]"#,
+            "[1] [2]",
+            "&x [1]",
+            "!!str a",
+            "- 1",
+            "'a'",
+            "[1] # comment",
+            "---\n1",
+            "01",
+            "1.",
+            ".5",
+            "+1",
+            "0x10",
+            "NaN",
+            "[True]",
+            r#""a\x41""#,
+            r#""a\ud83d""#,
+            "\"a\nb\"",
+            "\"a\tb\"",
+            "{\"a\": 1, ? \"b\": 2}",
+            "1e400",
+            "[-1E400]",
+        ];
+        for s in &invalid {
+            assert!(
+                serde_json::from_str::<serde_json::Value>(s).is_err(),
+                "{}",
+                s
+            );
+            assert!(YamlLoader::load_from_json_str(s).is_err(), "{}", s);
+        }
+        assert_eq!(
+            YamlLoader::load_from_json_str("[1, 2,]")
+                .unwrap_err()
+                .to_string(),
+            "invalid JSON, expected a value at line 1 column 7, i.e. byte index 6"
+        );
+        assert_eq!(
+            YamlLoader::load_from_json_str("[1e400]")
+                .unwrap_err()
+                .to_string(),
+            "JSON number out of range at line 1 column 2, i.e. byte index 1"
+        );
+
+        // `-0` keeps its sign, like serde_json
+        let doc = YamlLoader::load_from_json_str("[-0, 0]").unwrap();
+        let json: serde_json::Value = serde_json::from_str("[-0, 0]").unwrap();
+        assert!(same(&doc, &json), "{:?}", doc);
+        assert_eq!(doc[0], Yaml::Real("-0".to_owned()));
+        assert!(doc[0].as_f64().unwrap().is_sign_negative());
+        assert_eq!(doc[1], Yaml::Integer(0));
     }
-}
 
-#[cfg(test)]
-mod test {
-    use crate::scanner::*;
-    use crate::yaml::*;
-    use std::f64;
+    #[test]
+    fn test_as_char_and_str_lossy() {
+        let doc = YamlLoader::load_from_str("[x, é, xy, '', 1, !t 2.5, {a: 1}]")
+            .unwrap()
+            .remove(0);
+        let chars: Vec<_> = doc.as_vec().unwrap().iter().map(Yaml::as_char).collect();
+        assert_eq!(chars, [Some('x'), Some('é'), None, None, None, None, None]);
+        assert_eq!(doc[5].as_str_lossy().as_deref(), Some("2.5"));
+        assert_eq!(doc[6].as_str_lossy(), None);
+        assert_eq!(Yaml::Integer(-3).as_str_lossy().as_deref(), Some("-3"));
+        assert_eq!(Yaml::BadValue.as_str_lossy(), None);
+    }
 
     #[test]
-    fn test_coerce() {
-        let s = "---
-a: 1
-b: 2.2
-c: [1, 2]
+    fn test_take_replace() {
+        let mut doc = YamlLoader::load_from_str("[a, [b]]").unwrap().remove(0);
+        if let Yaml::Array(ref mut v) = doc {
+            let inner = v[1].take();
+            assert_eq!(inner, Yaml::Array(vec![Yaml::from_str("b")]));
+            assert_eq!(v[0].replace(inner), Yaml::from_str("a"));
+        }
+        assert_eq!(
+            doc,
+            Yaml::Array(vec![Yaml::Array(vec![Yaml::from_str("b")]), Yaml::Null])
+        );
+    }
+
+    #[test]
+    fn test_normalize_numbers() {
+        let s = "
+- [1.50, 15e-1, -0.0, 0.0, .Inf, -.INF, .NAN, +2.5]
+- !t {0.10: x}
+- 1.0x
 ";
-        let out = YamlLoader::load_from_str(s).unwrap();
-        let doc = &out[0];
-        assert_eq!(doc["a"].as_i64().unwrap(), 1i64);
-        assert_eq!(doc["b"].as_f64().unwrap(), 2.2f64);
-        assert_eq!(doc["c"][1].as_i64().unwrap(), 2i64);
-        assert!(doc["d"][0].is_badvalue());
+        let mut doc = YamlLoader::load_from_str(s).unwrap().remove(0);
+        doc.normalize_numbers();
+        let reals: Vec<_> = doc[0]
+            .as_vec()
+            .unwrap()
+            .iter()
+            .map(|x| match *x {
+                Yaml::Real(ref v) => v.as_str(),
+                _ => panic!("{:?}", x),
+            })
+            .collect();
+        assert_eq!(
+            reals,
+            ["1.5", "1.5", "0.0", "0.0", ".inf", "-.inf", ".nan", "2.5"]
+        );
+        let mut hash = Hash::new();
+        hash.insert(Yaml::Real("0.1".to_owned()), Yaml::from_str("x"));
+        assert_eq!(
+            doc[1],
+            Yaml::Tagged(Tag::new("!", "t"), Box::new(Yaml::Hash(hash)))
+        );
+        assert_eq!(doc[2], Yaml::String("1.0x".to_owned()));
     }
 
     #[test]
-    fn test_empty_doc() {
-        let s: String = "".to_owned();
-        YamlLoader::load_from_str(&s).unwrap();
-        let s: String = "---".to_owned();
-        assert_eq!(YamlLoader::load_from_str(&s).unwrap()[0], Yaml::Null);
+    fn test_array_methods() {
+        let mut doc = YamlLoader::load_from_str("[b, d]").unwrap().remove(0);
+        doc.insert(0, Yaml::from_str("a")).unwrap();
+        doc.insert(2, Yaml::from_str("c")).unwrap();
+        assert_eq!(doc.remove(3), Ok(Yaml::from_str("d")));
+        assert_eq!(doc, YamlLoader::load_from_str("[a, b, c]").unwrap()[0]);
+
+        let mut hash = YamlLoader::load_from_str("a: 1").unwrap().remove(0);
+        let before = hash.clone();
+        assert!(hash.push(Yaml::Null).is_err());
+        assert!(hash.insert(0, Yaml::Null).is_err());
+        assert!(hash.remove(0).is_err());
+        assert_eq!(
+            hash.retain(|_| false),
+            Err(YamlConversionError {
+                expected: "array",
+                found: "hash"
+            })
+        );
+        assert_eq!(hash, before);
     }
 
     #[test]
-    fn test_parser() {
-        let s: String = "
-# comment
-a0 bb: val
-a1:
-    b1: 4
-    b2: d
-a2: 4 # i'm comment
-a3: [1, 2, 3]
-a4:
-    - - a1
-      - a2
-    - 2
-a5: 'single_quoted'
-a6: \"double_quoted\"
-a7: 你好
-"
-        .to_owned();
-        let out = YamlLoader::load_from_str(&s).unwrap();
-        let doc = &out[0];
-        assert_eq!(doc["a7"].as_str().unwrap(), "你好");
+    fn test_key_rules() {
+        let load = |rules: KeyRules, s: &str| {
+            let mut loader = YamlLoader::new();
+            loader.key_rules(rules);
+            loader.parse_from_str(s)
+        };
+        let identifiers = KeyRules {
+            charset: KeyCharset::Identifier,
+            max_len: Some(8),
+        };
+        assert!(load(identifiers, "a: {b_1: [x y], _c: {d: 1}}").is_ok());
+
+        let err = load(identifiers, "a:\n  b: 1\n  2c: 2").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "key \"2c\" is not an identifier at line 3 column 3, i.e. byte index 12"
+        );
+        let err = load(identifiers, "{abcdefghi: 1}").unwrap_err();
+        assert_eq!(err.info(), "key \"abcdefghi\" is longer than 8 characters");
+        let err = load(identifiers, "? [a]\n: 1").unwrap_err();
+        assert_eq!(err.info(), "key is not a scalar");
+
+        let no_whitespace = KeyRules {
+            charset: KeyCharset::NoWhitespace,
+            ..KeyRules::default()
+        };
+        assert!(load(no_whitespace, "a-b.c: 1\n8080: x").is_ok());
+        assert!(load(no_whitespace, "'a b': 1").is_err());
+        assert!(load(no_whitespace, "\"a\\tb\": 1").is_err());
+
+        let max_len = KeyRules {
+            max_len: Some(2),
+            ..KeyRules::default()
+        };
+        assert!(load(max_len, "? [long, list]\n: 1").is_ok());
+        assert!(YamlLoader::builder()
+            .key_rules(max_len)
+            .load("abc: 1")
+            .is_err());
     }
 
     #[test]
-    fn test_multi_doc() {
-        let s = "
-'a scalar'
----
-'a scalar'
----
-'a scalar'
-";
-        let out = YamlLoader::load_from_str(s).unwrap();
-        assert_eq!(out.len(), 3);
+    fn test_get_str_key() {
+        let doc = YamlLoader::load_from_str("{0: int, '0': str, a: 1, [a]: 2, '': 3}")
+            .unwrap()
+            .remove(0);
+        let hash = doc.as_hash().unwrap();
+        assert_eq!(get_str_key(hash, "0").and_then(Yaml::as_str), Some("str"));
+        assert_eq!(get_str_key(hash, "a"), Some(&Yaml::Integer(1)));
+        assert_eq!(get_str_key(hash, ""), Some(&Yaml::Integer(3)));
+        assert_eq!(get_str_key(hash, "b"), None);
+        assert_eq!(doc["a"], Yaml::Integer(1));
+        for key in ["0", "a", "", "[a]"] {
+            let yaml = hash.get(&Yaml::String(key.to_owned()));
+            assert_eq!(get_str_key(hash, key), yaml);
+        }
+
+        // only string keys match, not tagged ones
+        let doc = YamlLoader::load_from_str("{!t a: 1, !!str b: 2}")
+            .unwrap()
+            .remove(0);
+        let hash = doc.as_hash().unwrap();
+        assert_eq!(get_str_key(hash, "a"), None);
+        assert_eq!(get_str_key(hash, "b"), Some(&Yaml::Integer(2)));
+        let mut interned = Hash::new();
+        interned.insert(Yaml::Interned("c".into()), Yaml::Integer(3));
+        assert_eq!(get_str_key(&interned, "c"), Some(&Yaml::Integer(3)));
     }
 
     #[test]
-    fn test_anchor() {
-        let s = "
-a1: &DEFAULT
-    b1: 4
-    b2: d
-a2: *DEFAULT
-";
-        let out = YamlLoader::load_from_str(s).unwrap();
-        let doc = &out[0];
-        assert_eq!(doc["a2"]["b1"].as_i64().unwrap(), 4);
+    fn test_find_by() {
+        let doc = YamlLoader::load_from_str(
+            "[{name: a, on: true}, 1, {name: b, id: 2}, {name: a, id: 3}, {id: [2]}]",
+        )
+        .unwrap()
+        .remove(0);
+        assert_eq!(doc.find_by("name", "a"), Some(&doc[0]));
+        assert_eq!(doc.find_by("id", "2"), Some(&doc[2]));
+        assert_eq!(doc.find_by("on", "true"), Some(&doc[0]));
+        assert_eq!(doc.find_by("name", "c"), None);
+        let all: Vec<_> = doc.find_all_by("name", "a").collect();
+        assert_eq!(all, [&doc[0], &doc[3]]);
+        assert_eq!(doc[0].find_by("name", "a"), None);
+        assert_eq!(doc["missing"].find_all_by("name", "a").count(), 0);
     }
 
     #[test]
-    fn test_bad_anchor() {
+    fn test_warnings() {
         let s = "
-a1: &DEFAULT
-    b1: 4
-    b2: *DEFAULT
+big: 99999999999999999999
+hex: 0xffffffffffffffffff
+fine: 123
+bin: !!binary aGk=
+verbatim: !<tag:yaml.org,2002:int> 5
+list: &l [x]
+copy: *l
 ";
-        let out = YamlLoader::load_from_str(s).unwrap();
-        let doc = &out[0];
-        assert_eq!(doc["a1"]["b2"], Yaml::BadValue);
+        let warnings = RefCell::new(Vec::new());
+        let handler = |w: &Warning| warnings.borrow_mut().push(w.clone());
+        let docs = YamlLoader::builder().on_warning(&handler).load(s).unwrap();
+        let kinds: Vec<_> = warnings.borrow().iter().map(|w| w.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            [
+                WarningKind::IntegerOverflow(Yaml::Real("99999999999999999999".to_owned())),
+                WarningKind::IntegerOverflow(Yaml::String("0xffffffffffffffffff".to_owned())),
+                WarningKind::UnknownTag("!!binary".to_owned()),
+                WarningKind::UnknownTag("!<tag:yaml.org,2002:int>".to_owned()),
+            ]
+        );
+        assert_eq!(warnings.borrow()[0].mark.line(), 2);
+        let bin = warnings.borrow()[2].clone();
+        assert_eq!((bin.mark.line(), bin.mark.col()), (5, 14));
+        let key_mark = bin.key_mark.unwrap();
+        assert_eq!((key_mark.line(), key_mark.col()), (5, 0));
+        assert_eq!(docs[0]["fine"], Yaml::Integer(123));
+
+        warnings.borrow_mut().clear();
+        let big = (0..LARGE_ALIAS_NODES)
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let s = format!("a: &a [{}]\nb: *a\nc: [*a]\n{{x: 1, x: 2}}: 1", big);
+        YamlLoader::builder().on_warning(&handler).load(&s).unwrap();
+        let warnings = warnings.into_inner();
+        assert_eq!(warnings.len(), 3);
+        assert_eq!(
+            warnings[0].kind,
+            WarningKind::LargeAlias {
+                nodes: LARGE_ALIAS_NODES + 1
+            }
+        );
+        assert_eq!(
+            warnings[1].to_string(),
+            "alias copies a node of 1001 nodes at line 3 column 5"
+        );
+        assert_eq!(
+            warnings[2].to_string(),
+            "duplicate key String(\"x\") at line 4 column 8"
+        );
+        let key_marks: Vec<_> = warnings
+            .iter()
+            .map(|w| w.key_mark.map(|m| m.line()))
+            .collect();
+        assert_eq!(key_marks, [Some(2), None, None]);
     }
 
     #[test]
-    fn test_github_27() {
-        // https://github.com/chyh1990/yaml-rust/issues/27
-        let s = "&a";
-        let out = YamlLoader::load_from_str(s).unwrap();
-        let doc = &out[0];
-        assert_eq!(doc.as_str().unwrap(), "");
+    fn test_anchor_redefinition() {
+        let s = "a: &x 1\nb: *x\nc: &x 2\nd: *x\n---\ne: &x 3\nf: *x\n";
+        let load = |policy| YamlLoader::builder().anchor_redefinition(policy).load(s);
+        let docs = load(AnchorRedefinition::Shadow).unwrap();
+        assert_eq!(docs[0]["b"].as_i64(), Some(1));
+        assert_eq!(docs[0]["d"].as_i64(), Some(2));
+        let docs = load(AnchorRedefinition::FirstWins).unwrap();
+        assert_eq!(docs[0]["c"].as_i64(), Some(2));
+        assert_eq!(docs[0]["d"].as_i64(), Some(1));
+        // anchors are local to a document
+        assert_eq!(docs[1]["f"].as_i64(), Some(3));
+        let err = load(AnchorRedefinition::Error).unwrap_err();
+        assert_eq!(
+            err.info(),
+            "anchor &x is redefined, its first node is at line 1 column 7"
+        );
+        assert_eq!(err.marker().line(), 3);
+
+        // an alias to the first definition from inside the second is recursive
+        let mut loader = YamlLoader::new();
+        loader.anchor_redefinition(AnchorRedefinition::FirstWins);
+        let err = loader.parse_from_str("&x [&x [*x]]").unwrap_err();
+        assert!(err.info().starts_with("found recursive alias *x"));
     }
 
     #[test]
-    #[allow(clippy::cognitive_complexity)]
-    fn test_plain_datatype() {
-        let s = "
-- 'string'
-- \"string\"
-- string
-- 123
-- -321
-- 1.23
-- -1e4
-- ~
-- null
-- true
-- false
-- !!str 0
-- !!int 100
-- !!float 2
-- !!null ~
-- !!bool true
-- !!bool false
-- 0xFF
-# bad values
-- !!int string
-- !!float string
-- !!bool null
-- !!null val
-- 0o77
-- [ 0xF, 0xF ]
-- +12345
-- [ true, false ]
-";
-        let out = YamlLoader::load_from_str(s).unwrap();
-        let doc = &out[0];
+    fn test_version_policy() {
+        let s = "%YAML 1.2\n--- a\n...\n%YAML 1.3\n--- b\n...\n--- c\n...\n%YAML 2.0\n--- d\n";
+        let docs = YamlLoader::builder().load_with_versions(s).unwrap();
+        let versions: Vec<_> = docs.iter().map(|doc| doc.1).collect();
+        assert_eq!(versions, [Some((1, 2)), Some((1, 3)), None, Some((2, 0))]);
 
-        assert_eq!(doc[0].as_str().unwrap(), "string");
-        assert_eq!(doc[1].as_str().unwrap(), "string");
-        assert_eq!(doc[2].as_str().unwrap(), "string");
-        assert_eq!(doc[3].as_i64().unwrap(), 123);
-        assert_eq!(doc[4].as_i64().unwrap(), -321);
-        assert_eq!(doc[5].as_f64().unwrap(), 1.23);
-        assert_eq!(doc[6].as_f64().unwrap(), -1e4);
-        assert!(doc[7].is_null());
-        assert!(doc[8].is_null());
-        assert!(doc[9].as_bool().unwrap());
-        assert!(!doc[10].as_bool().unwrap());
-        assert_eq!(doc[11].as_str().unwrap(), "0");
-        assert_eq!(doc[12].as_i64().unwrap(), 100);
-        assert_eq!(doc[13].as_f64().unwrap(), 2.0);
-        assert!(doc[14].is_null());
-        assert!(doc[15].as_bool().unwrap());
-        assert!(!doc[16].as_bool().unwrap());
-        assert_eq!(doc[17].as_i64().unwrap(), 255);
-        assert!(doc[18].is_badvalue());
-        assert!(doc[19].is_badvalue());
-        assert!(doc[20].is_badvalue());
-        assert!(doc[21].is_badvalue());
-        assert_eq!(doc[22].as_i64().unwrap(), 63);
-        assert_eq!(doc[23][0].as_i64().unwrap(), 15);
-        assert_eq!(doc[23][1].as_i64().unwrap(), 15);
-        assert_eq!(doc[24].as_i64().unwrap(), 12345);
-        assert!(doc[25][0].as_bool().unwrap());
-        assert!(!doc[25][1].as_bool().unwrap());
+        let warnings = RefCell::new(Vec::new());
+        let handler = |w: &Warning| warnings.borrow_mut().push(w.to_string());
+        let docs = YamlLoader::builder()
+            .version_policy(VersionPolicy::Warn)
+            .on_warning(&handler)
+            .load(s)
+            .unwrap();
+        assert_eq!(docs.len(), 4);
+        assert_eq!(
+            *warnings.borrow(),
+            [
+                "unsupported YAML version 1.3 at line 4 column 1",
+                "unsupported YAML version 2.0 at line 9 column 1"
+            ]
+        );
+
+        let err = YamlLoader::builder()
+            .version_policy(VersionPolicy::Error)
+            .load(s)
+            .unwrap_err();
+        assert_eq!(err.info(), "found unsupported YAML version 1.3");
+        assert_eq!(err.marker().line(), 4);
+        let docs = YamlLoader::builder()
+            .version_policy(VersionPolicy::Error)
+            .load("%YAML 1.1\n--- a")
+            .unwrap();
+        assert_eq!(docs[0].as_str(), Some("a"));
     }
 
     #[test]
-    fn test_bad_hyphen() {
-        // See: https://github.com/chyh1990/yaml-rust/issues/23
-        let s = "{-";
-        assert!(YamlLoader::load_from_str(s).is_err());
+    fn test_parse_with_anchors() {
+        let s = "a: &x [1, &y {b: 2}]\n&k c: *y\nd: &x !!str 3\n---\n&x e\n";
+        let docs = YamlLoader::new().parse_from_str_with_anchors(s).unwrap();
+        let summary: Vec<Vec<_>> = docs
+            .iter()
+            .map(|(_, anchors)| {
+                anchors
+                    .iter()
+                    .map(|a| {
+                        let path = a.path.as_ref().map(YamlPath::to_string);
+                        (a.name.as_str(), path, a.mark.line())
+                    })
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                vec![
+                    ("x", Some("$.a".to_owned()), 1),
+                    ("y", Some("$.a[1]".to_owned()), 1),
+                    ("k", None, 2),
+                    ("x", Some("$.d".to_owned()), 3),
+                ],
+                vec![("x", Some("$".to_owned()), 5)],
+            ]
+        );
+        let anchors = &docs[0].1;
+        assert_eq!(anchors[0].value, docs[0].0["a"]);
+        assert_eq!(anchors[1].value, docs[0].0["c"]);
+        assert_eq!(anchors[2].value.as_str(), Some("c"));
+        assert_eq!(anchors[3].value.as_str(), Some("3"));
+        assert_eq!(docs[1].1[0].value.as_str(), Some("e"));
+
+        let docs = YamlLoader::builder()
+            .empty_stream_mode(EmptyStreamMode::Null)
+            .load_with_anchors("")
+            .unwrap();
+        assert_eq!(docs, [(Yaml::Null, vec![])]);
     }
 
     #[test]
-    fn test_issue_65() {
-        // See: https://github.com/chyh1990/yaml-rust/issues/65
-        let b = "\n\"ll\\\"ll\\\r\n\"ll\\\"ll\\\r\r\r\rU\r\r\rU";
-        assert!(YamlLoader::load_from_str(b).is_err());
+    fn test_empty_stream_mode() {
+        let empty = ["", "  \n\n", "# only a comment\n"];
+        for s in &empty {
+            assert_eq!(YamlLoader::load_from_str(s).unwrap(), vec![], "{:?}", s);
+            let builder = YamlLoader::builder()
+                .empty_stream_mode(EmptyStreamMode::Null)
+                .clone();
+            assert_eq!(builder.load(s).unwrap(), vec![Yaml::Null]);
+            let docs = builder.load_with_spans(s).unwrap();
+            assert_eq!(docs[0].1.slice(s), "");
+            let err = YamlLoader::builder()
+                .empty_stream_mode(EmptyStreamMode::Error)
+                .load(s)
+                .unwrap_err();
+            assert_eq!(err.info(), "found no documents in the stream");
+        }
+
+        // started documents are never empty streams
+        for s in &["---", "--- # comment\n...\n"] {
+            let builder = YamlLoader::builder()
+                .empty_stream_mode(EmptyStreamMode::Error)
+                .clone();
+            assert_eq!(builder.load(s).unwrap(), vec![Yaml::Null]);
+        }
     }
 
     #[test]
-    fn test_bad_docstart() {
-        assert!(YamlLoader::load_from_str("---This used to cause an infinite loop").is_ok());
-        assert_eq!(
-            YamlLoader::load_from_str("----"),
-            Ok(vec![Yaml::String(String::from("----"))])
-        );
-        assert_eq!(
-            YamlLoader::load_from_str("--- #here goes a comment"),
-            Ok(vec![Yaml::Null])
-        );
-        assert_eq!(
-            YamlLoader::load_from_str("---- #here goes a comment"),
-            Ok(vec![Yaml::String(String::from("----"))])
-        );
+    fn test_scanner_config() {
+        let s = format!("{}: value", "k".repeat(1100));
+        assert!(YamlLoader::load_from_str(&s).is_err());
+        let mut loader = YamlLoader::new();
+        loader.scanner_config(ScannerConfig {
+            max_implicit_key_len: 2048,
+            ..ScannerConfig::default()
+        });
+        let out = loader.parse_from_str(&s).unwrap();
+        assert_eq!(out[0][&*"k".repeat(1100)].as_str(), Some("value"));
     }
 
     #[test]
-    fn test_plain_datatype_with_into_methods() {
+    fn test_strict_lookup() {
         let s = "
-- 'string'
-- \"string\"
-- string
-- 123
-- -321
-- 1.23
-- -1e4
-- true
-- false
-- !!str 0
-- !!int 100
-- !!float 2
-- !!bool true
-- !!bool false
-- 0xFF
-- 0o77
-- +12345
-- -.INF
-- .NAN
-- !!float .INF
+0: zero
+'1': one
+list: [a, b]
 ";
-        let mut out = YamlLoader::load_from_str(s).unwrap().into_iter();
-        let mut doc = out.next().unwrap().into_iter();
+        let out = YamlLoader::load_from_str(s).unwrap();
+        let doc = &out[0];
+        assert_eq!(
+            doc.get_key(&Yaml::Integer(0)).unwrap().as_str(),
+            Some("zero")
+        );
+        assert!(doc.get_key(&Yaml::Integer(1)).is_none());
+        assert_eq!(doc.get_str("1").unwrap().as_str(), Some("one"));
+        assert!(doc.get_str("0").is_none());
+        assert!(doc.get_index(0).is_none());
+        let list = doc.get_str("list").unwrap();
+        assert_eq!(list.get_index(1).unwrap().as_str(), Some("b"));
+        assert!(list.get_index(2).is_none());
+        assert!(list.get_key(&Yaml::Integer(0)).is_none());
+    }
 
-        assert_eq!(doc.next().unwrap().into_string().unwrap(), "string");
-        assert_eq!(doc.next().unwrap().into_string().unwrap(), "string");
-        assert_eq!(doc.next().unwrap().into_string().unwrap(), "string");
-        assert_eq!(doc.next().unwrap().into_i64().unwrap(), 123);
-        assert_eq!(doc.next().unwrap().into_i64().unwrap(), -321);
-        assert_eq!(doc.next().unwrap().into_f64().unwrap(), 1.23);
-        assert_eq!(doc.next().unwrap().into_f64().unwrap(), -1e4);
-        assert!(doc.next().unwrap().into_bool().unwrap());
-        assert!(!doc.next().unwrap().into_bool().unwrap());
-        assert_eq!(doc.next().unwrap().into_string().unwrap(), "0");
-        assert_eq!(doc.next().unwrap().into_i64().unwrap(), 100);
-        assert_eq!(doc.next().unwrap().into_f64().unwrap(), 2.0);
-        assert!(doc.next().unwrap().into_bool().unwrap());
-        assert!(!doc.next().unwrap().into_bool().unwrap());
-        assert_eq!(doc.next().unwrap().into_i64().unwrap(), 255);
-        assert_eq!(doc.next().unwrap().into_i64().unwrap(), 63);
-        assert_eq!(doc.next().unwrap().into_i64().unwrap(), 12345);
-        assert_eq!(doc.next().unwrap().into_f64().unwrap(), f64::NEG_INFINITY);
-        assert!(doc.next().unwrap().into_f64().is_some());
-        assert_eq!(doc.next().unwrap().into_f64().unwrap(), f64::INFINITY);
+    #[test]
+    fn test_semantic_eq() {
+        let ordered = SemanticEqOptions::default();
+        let unordered = SemanticEqOptions {
+            ignore_map_order: true,
+        };
+        let load = |s: &str| YamlLoader::load_from_str(s).unwrap().remove(0);
+
+        let a = load("[1.0, .inf, .nan, 0x10, 1e2]");
+        let b = load("[1.00, .Inf, .NAN, 16, 100.0]");
+        assert_ne!(a, b);
+        assert!(a.semantic_eq(&b, ordered));
+        assert!(!load("[1]").semantic_eq(&load("[1.0]"), ordered));
+        assert!(!load("[1.0]").semantic_eq(&load("[1.0, 2]"), ordered));
+
+        let a = load("{1.0: a, b: {c: 1, d: 2}}");
+        let b = load("{b: {d: 2, c: 1}, 1.00: a}");
+        assert!(!a.semantic_eq(&b, ordered));
+        assert!(a.semantic_eq(&b, unordered));
+        assert!(!a.semantic_eq(&load("{b: {d: 2, c: 1}, 1.00: x}"), unordered));
+        assert!(!a.semantic_eq(&load("{b: {d: 2, c: 1}}"), unordered));
+
+        // entries pair one to one
+        let a = load("{1.0: x, 1.00: x}");
+        let b = load("{1.0: x, 2: y}");
+        assert!(!a.semantic_eq(&b, unordered));
+        assert!(!b.semantic_eq(&a, unordered));
+        assert!(a.semantic_eq(&load("{1.00: x, 1.0: x}"), unordered));
     }
 
+    #[cfg(feature = "serde")]
     #[test]
-    fn test_hash_order() {
-        let s = "---
-b: ~
-a: ~
-c: ~
+    fn test_serde() {
+        let s = "
+a: [1, 2.50, ~, true]
+{b: c}: !!int x
+z: *anchor
 ";
-        let out = YamlLoader::load_from_str(s).unwrap();
-        let first = out.into_iter().next().unwrap();
-        let mut iter = first.into_hash().unwrap().into_iter();
+        let s = format!("base: &anchor {{k: v}}\n{}", s);
+        let doc = YamlLoader::load_from_str(&s).unwrap().remove(0);
+        let json = serde_json::to_string(&doc).unwrap();
+        let back: Yaml = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, doc);
+        // order and representation are preserved
+        assert_eq!(back["a"][1], Yaml::Real("2.50".to_owned()));
         assert_eq!(
-            Some((Yaml::String("b".to_owned()), Yaml::Null)),
-            iter.next()
+            back.as_hash().unwrap().keys().nth(1),
+            Some(&Yaml::from_str("a"))
         );
+
+        let json = serde_json::to_string(&Yaml::Alias(3)).unwrap();
+        assert_eq!(json, r#"{"Alias":3}"#);
+        // interned strings are written as strings
+        let json = serde_json::to_string(&Yaml::Interned("k".into())).unwrap();
+        assert_eq!(json, r#"{"String":"k"}"#);
+        let json = serde_json::to_string(&Yaml::BadValue).unwrap();
+        assert_eq!(serde_json::from_str::<Yaml>(&json).unwrap(), Yaml::BadValue);
+    }
+
+    #[test]
+    fn test_try_from() {
+        fn extract(doc: Yaml) -> Result<(i64, f64, bool, String), YamlConversionError> {
+            let mut hash: Hash = doc.try_into()?;
+            let mut take = |key: &str| hash.remove(&Yaml::String(key.to_owned())).unwrap();
+            Ok((
+                take("int").try_into()?,
+                take("real").try_into()?,
+                take("bool").try_into()?,
+                take("str").try_into()?,
+            ))
+        }
+        let s = "{int: 1, real: 1.5, bool: true, str: a}";
+        let doc = YamlLoader::load_from_str(s).unwrap().remove(0);
+        assert_eq!(extract(doc), Ok((1, 1.5, true, "a".to_owned())));
+
+        let s = "{int: 1, real: 1.5, bool: yes, str: a}";
+        let doc = YamlLoader::load_from_str(s).unwrap().remove(0);
+        let err = extract(doc).unwrap_err();
         assert_eq!(
-            Some((Yaml::String("a".to_owned()), Yaml::Null)),
-            iter.next()
+            err,
+            YamlConversionError {
+                expected: "boolean",
+                found: "string",
+            }
         );
+        assert_eq!(err.to_string(), "expected boolean, found string");
+
+        let arr: Array = Yaml::Array(vec![Yaml::Null]).try_into().unwrap();
+        assert_eq!(arr, vec![Yaml::Null]);
+        assert_eq!(Array::try_from(Yaml::Null).unwrap_err().found, "null");
         assert_eq!(
-            Some((Yaml::String("c".to_owned()), Yaml::Null)),
-            iter.next()
+            i64::try_from(Yaml::BadValue).unwrap_err().expected,
+            "integer"
         );
-        assert_eq!(None, iter.next());
     }
 
     #[test]
-    fn test_integer_key() {
+    fn test_string_maps() {
+        let doc = YamlLoader::load_from_str("{b: 1, a: [x], 2: c}")
+            .unwrap()
+            .remove(0);
+        let err = BTreeMap::<String, Yaml>::try_from(doc.clone()).unwrap_err();
+        assert_eq!(err.to_string(), "expected string key, found integer");
+        let err = HashMap::<String, Yaml>::try_from(Yaml::Null).unwrap_err();
+        assert_eq!(err.to_string(), "expected hash, found null");
+
+        let map: HashMap<String, Yaml> = doc.into_string_map(NonStringKeys::Skip).unwrap();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map["a"][0].as_str(), Some("x"));
+        let yaml = Yaml::from(map);
+        assert_eq!(yaml["b"].as_i64(), Some(1));
+
+        let mut map = BTreeMap::new();
+        map.insert("z".to_owned(), Yaml::Null);
+        map.insert("y".to_owned(), Yaml::Integer(1));
+        let yaml = Yaml::from(map.clone());
+        let keys: Vec<_> = yaml.as_hash().unwrap().keys().map(|k| k.as_str()).collect();
+        assert_eq!(keys, [Some("y"), Some("z")]);
+        assert_eq!(BTreeMap::try_from(yaml), Ok(map));
+    }
+
+    #[test]
+    fn test_strip() {
         let s = "
-0:
-    important: true
-1:
-    important: false
+_comment: top
+a: [{_comment: x, b: 1}, !t {_comment: y, c: 2}]
+d: {_comment: {_comment: nested}}
+1: one
 ";
-        let out = YamlLoader::load_from_str(s).unwrap();
-        let first = out.into_iter().next().unwrap();
-        assert!(first[0]["important"].as_bool().unwrap());
+        let mut doc = YamlLoader::load_from_str(s).unwrap().remove(0);
+        let mut seen = 0;
+        let removed = strip(&mut doc, |key| {
+            seen += 1;
+            key.as_str() == Some("_comment")
+        });
+        let paths: Vec<_> = removed.iter().map(|(path, _)| path.to_string()).collect();
+        assert_eq!(
+            paths,
+            [
+                "$._comment",
+                "$.a[0]._comment",
+                "$.a[1]._comment",
+                "$.d._comment"
+            ]
+        );
+        assert_eq!(removed[3].1["_comment"].as_str(), Some("nested"));
+        // the keys inside removed values are not checked
+        assert_eq!(seen, 9);
+        let expected = YamlLoader::load_from_str("{a: [{b: 1}, !t {c: 2}], d: {}, 1: one}")
+            .unwrap()
+            .remove(0);
+        assert_eq!(doc, expected);
+    }
+
+    #[test]
+    fn test_predicates() {
+        let s = "[{a: 1}, s, 1, 1.5, true, !x y, ~, []]";
+        let mut doc = YamlLoader::load_from_str(s)
+            .unwrap()
+            .remove(0)
+            .into_vec()
+            .unwrap();
+        doc.push(Yaml::Alias(1));
+        let predicates: [fn(&Yaml) -> bool; 9] = [
+            Yaml::is_hash,
+            Yaml::is_string,
+            Yaml::is_integer,
+            Yaml::is_real,
+            Yaml::is_boolean,
+            Yaml::is_tagged,
+            Yaml::is_null,
+            Yaml::is_array,
+            Yaml::is_alias,
+        ];
+        for (i, node) in doc.iter().enumerate() {
+            for (j, predicate) in predicates.iter().enumerate() {
+                // the predicates look through tags, so `!x y` is a string
+                let expected = i == j || (i, j) == (5, 1);
+                assert_eq!(predicate(node), expected, "{} {}", i, j);
+            }
+        }
+        let names: Vec<_> = doc.iter().map(Yaml::type_str).collect();
+        assert_eq!(
+            names,
+            [
+                "hash",
+                "string",
+                "integer",
+                "real",
+                "boolean",
+                "tagged value",
+                "null",
+                "array",
+                "alias"
+            ]
+        );
     }
 
     #[test]
@@ -882,6 +5870,121 @@ subcommands3:
         assert_eq!(Yaml::Integer(3).or(Yaml::Integer(7)), Yaml::Integer(3));
     }
 
+    #[test]
+    fn test_load_from_file() {
+        let dir = std::env::temp_dir().join(format!("yaml-rust-load-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("doc.yaml");
+        std::fs::write(&path, b"\xff\xfea\0:\0 \x001\0").unwrap();
+        let out = YamlLoader::load_from_file(&path).unwrap();
+        assert_eq!(out[0]["a"].as_i64(), Some(1));
+
+        let missing = dir.join("missing.yaml");
+        match YamlLoader::load_from_file(&missing) {
+            Err(LoadError::WithSource { name, error }) => {
+                assert_eq!(name, missing.display().to_string());
+                assert!(
+                    matches!(*error, LoadError::IO(ref err) if err.kind() == io::ErrorKind::NotFound)
+                );
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        std::fs::write(&path, "a: 1\nb: [").unwrap();
+        let err = YamlLoader::load_from_file(&path).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with(&format!("{}:3:1: ", path.display())));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_value_key_mark() {
+        let mut loader = YamlLoader::new();
+        let mut parser = Parser::new("a:\n  b: [c]\n".chars());
+        let mut marks = Vec::new();
+        loop {
+            let (ev, mark) = parser.next_event().unwrap();
+            let end = ev == Event::StreamEnd;
+            if matches!(
+                ev,
+                Event::Scalar(..) | Event::SequenceStart(..) | Event::MappingStart(..)
+            ) {
+                marks.push(loader.value_key_mark().map(|m| (m.line(), m.col())));
+            }
+            loader.on_event(ev, mark);
+            if end {
+                break;
+            }
+        }
+        // the root, `a`, the value of `a`, `b`, the value of `b` and `c`
+        assert_eq!(marks, [None, None, Some((1, 0)), None, Some((2, 2)), None]);
+    }
+
+    #[test]
+    fn test_load_error_chain() {
+        #[derive(Debug)]
+        struct Outer(io::Error);
+        impl fmt::Display for Outer {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("request failed")
+            }
+        }
+        impl Error for Outer {
+            fn source(&self) -> Option<&(dyn Error + 'static)> {
+                Some(&self.0)
+            }
+        }
+
+        // the messages of the error and its sources
+        fn chain(err: &(dyn Error + 'static)) -> Vec<String> {
+            std::iter::successors(Some(err), |&e| e.source())
+                .map(ToString::to_string)
+                .collect()
+        }
+
+        let scan = YamlLoader::load_from_str("[").unwrap_err();
+        let io_err = || io::Error::other(Outer(io::Error::other("connection reset")));
+        let errors = [
+            LoadError::Scan(scan.clone()),
+            LoadError::Scan(scan).with_source_name("a.yaml"),
+            LoadError::IO(io_err()),
+            LoadError::IO(io_err()).with_source_name("a.yaml"),
+            LoadError::Decode("invalid sequence".into()).with_source_name("a.yaml"),
+        ];
+        for err in &errors {
+            // no error in the chain repeats the message of its source
+            let messages = chain(err);
+            for pair in messages.windows(2) {
+                assert!(!pair[0].contains(&pair[1]), "{:?}", messages);
+            }
+        }
+        assert_eq!(
+            chain(&errors[3]),
+            ["a.yaml: request failed", "connection reset"]
+        );
+    }
+
+    #[test]
+    fn test_load_error_display() {
+        let err = LoadError::Decode("invalid sequence".into());
+        assert_eq!(err.to_string(), "failed to decode input: invalid sequence");
+        let err = err.with_source_name("a.yaml").with_source_name("b.yaml");
+        assert_eq!(err.source_name(), Some("b.yaml"));
+        assert_eq!(
+            err.to_string(),
+            "b.yaml: failed to decode input: invalid sequence"
+        );
+
+        let err = LoadError::from(YamlLoader::load_from_str("[").unwrap_err());
+        assert_eq!(err.source_name(), None);
+        assert!(err.source().is_none());
+        assert_eq!(
+            err.to_string(),
+            "while parsing a node, did not find expected node content at line 2 column 1, i.e. byte index 1"
+        );
+    }
+
     #[test]
     fn test_read_bom() {
         let s = b"\xef\xbb\xbf---
@@ -964,12 +6067,100 @@ c: [1, 2]
         assert!(doc["d"][0].is_badvalue());
     }
 
+    fn utf32(s: &str, big_endian: bool) -> Vec<u8> {
+        s.chars()
+            .flat_map(|c| {
+                if big_endian {
+                    (c as u32).to_be_bytes()
+                } else {
+                    (c as u32).to_le_bytes()
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_read_utf32() {
+        let s = "---\na: 1\nb: 2.2\nc: [1, 2]\n";
+        for (big_endian, bom) in [(false, true), (false, false), (true, true), (true, false)] {
+            let mut input = utf32(s, big_endian);
+            if bom {
+                input.splice(0..0, utf32("\u{feff}", big_endian));
+            }
+            let mut decoder = YamlDecoder::read(&input as &[u8]);
+            let out = decoder.decode().unwrap();
+            let doc = &out[0];
+            assert_eq!(doc["a"].as_i64().unwrap(), 1i64);
+            assert_eq!(doc["b"].as_f64().unwrap(), 2.2f64);
+            assert_eq!(doc["c"][1].as_i64().unwrap(), 2i64);
+            let expected = if big_endian {
+                YamlEncoding::Utf32Be
+            } else {
+                YamlEncoding::Utf32Le
+            };
+            assert_eq!(decoder.encoding(), Some(expected));
+        }
+    }
+
+    /// Hands out one byte per read to exercise sequences split across chunks.
+    struct ByteByByte<'a>(&'a [u8]);
+
+    impl<'a> std::io::Read for ByteByByte<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            match self.0.split_first() {
+                Some((first, rest)) if !buf.is_empty() => {
+                    buf[0] = *first;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn test_read_incremental() {
+        let s = "a: 你好\nb: [1, 2]\n";
+        let utf16: Vec<u8> = s.encode_utf16().flat_map(|u| u.to_be_bytes()).collect();
+        let mut decoder = YamlDecoder::read(ByteByByte(&utf16));
+        let out = decoder.decode().unwrap();
+        assert_eq!(out[0]["a"].as_str().unwrap(), "你好");
+        assert_eq!(out[0]["b"][1].as_i64().unwrap(), 2);
+        assert_eq!(decoder.encoding(), Some(YamlEncoding::Utf16Be));
+
+        let utf32 = utf32(s, false);
+        let out = YamlDecoder::read(ByteByByte(&utf32)).decode().unwrap();
+        assert_eq!(out[0]["a"].as_str().unwrap(), "你好");
+    }
+
+    #[test]
+    fn test_read_invalid() {
+        let s = b"a: \xff\xfe\n";
+        match YamlDecoder::read(s as &[u8]).decode() {
+            Err(LoadError::Decode(_)) => {}
+            other => panic!("expected a decode error, got {:?}", other),
+        }
+
+        // an incomplete trailing UTF-32 code unit
+        let mut input = utf32("a: 1\n", false);
+        input.push(b'b');
+        assert!(matches!(
+            YamlDecoder::read(&input as &[u8]).decode(),
+            Err(LoadError::Decode(_))
+        ));
+        let out = YamlDecoder::read(&input as &[u8])
+            .encoding_trap(encoding::DecoderTrap::Ignore)
+            .decode()
+            .unwrap();
+        assert_eq!(out[0]["a"].as_i64().unwrap(), 1);
+    }
+
     struct HelloTagParser;
 
     impl YamlScalarParser for HelloTagParser {
         fn parse_scalar(&self, tag: &TokenType, value: &str) -> Option<Yaml> {
             if let TokenType::Tag(ref handle, ref suffix) = *tag {
-                if (*handle == "!" || *handle == "yaml-rust.hello.prefix") && *suffix == "hello" {
+                if (*handle == "!" || *handle == "yaml-rust.hello.prefix:") && *suffix == "hello" {
                     return Some(Yaml::String("Hello ".to_string() + value));
                 }
             }
@@ -977,6 +6168,207 @@ c: [1, 2]
         }
     }
 
+    #[test]
+    fn test_tagged() {
+        let s = "
+ref: !Ref name
+quoted: !Ref 'true'
+seq: !GetAZs [a, b]
+map: !vault
+  id: 3
+verbatim: !<tag:example.com,2000:app/foo> x
+core: !!int 3
+";
+        let doc = &YamlLoader::load_from_str(s).unwrap()[0];
+        let tagged = |handle: &str, suffix: &str, v: Yaml| {
+            Yaml::Tagged(Tag::new(handle, suffix), Box::new(v))
+        };
+        let string = |v: &str| Yaml::String(v.to_owned());
+        assert_eq!(doc["ref"], tagged("!", "Ref", string("name")));
+        assert_eq!(doc["quoted"], tagged("!", "Ref", string("true")));
+        assert_eq!(
+            doc["seq"],
+            tagged("!", "GetAZs", Yaml::Array(vec![string("a"), string("b")]))
+        );
+        let mut map = Hash::new();
+        map.insert(string("id"), Yaml::Integer(3));
+        assert_eq!(doc["map"], tagged("!", "vault", Yaml::Hash(map)));
+        assert_eq!(
+            doc["verbatim"],
+            tagged("", "tag:example.com,2000:app/foo", string("x"))
+        );
+        assert_eq!(doc["core"], Yaml::Integer(3));
+
+        // values read as they did before tags were kept
+        assert_eq!(doc["ref"].as_str(), Some("name"));
+        assert_eq!(doc["seq"][1].as_str(), Some("b"));
+        assert_eq!(doc["map"]["id"].as_i64(), Some(3));
+        assert!(doc["seq"].is_array() && doc["map"].is_hash());
+        assert_eq!(doc["map"].get_str("id"), Some(&Yaml::Integer(3)));
+        assert_eq!(doc["seq"].clone().into_vec().map(|v| v.len()), Some(2));
+        assert_eq!(doc["quoted"].untagged(), &string("true"));
+        assert_eq!(doc["core"].untagged(), &Yaml::Integer(3));
+    }
+
+    #[test]
+    fn test_tag_display() {
+        assert_eq!(Tag::new("!", "Ref").to_string(), "!Ref");
+        assert_eq!(Tag::new("!e!", "foo").to_string(), "!e!foo");
+        assert_eq!(Tag::new("", "!").to_string(), "!");
+        assert_eq!(
+            Tag::new("tag:example.com,2000:", "foo").to_string(),
+            "!<tag:example.com,2000:foo>"
+        );
+    }
+
+    #[test]
+    fn test_tag_eq() {
+        let full = Tag::new("", "tag:clarkevans.com,2002:shape");
+        let split = Tag::new("tag:clarkevans.com,2002:", "shape");
+        assert_eq!(full, split);
+        assert_eq!(full.cmp(&split), Ordering::Equal);
+        let mut set = std::collections::HashSet::new();
+        set.insert(full);
+        assert!(set.contains(&split));
+        assert_ne!(Tag::new("!", "ab"), Tag::new("!a", "b!"));
+        assert!(Tag::new("!", "a") < Tag::new("!a", "b"));
+
+        // spec example 2.24 is equal after emitting and loading again
+        let s = "%TAG ! tag:clarkevans.com,2002:
+--- !shape
+- !circle
+  center: &ORIGIN {x: 73, y: 129}
+  radius: 7
+- !line
+  start: *ORIGIN
+  finish: { x: 89, y: 102 }
+- !label
+  start: *ORIGIN
+  color: 0xFFEEBB
+  text: Pretty vector drawing.
+";
+        let doc = &YamlLoader::load_from_str(s).unwrap()[0];
+        let mut out = String::new();
+        YamlEmitter::new(&mut out).dump(doc).unwrap();
+        assert!(out.contains("!<tag:clarkevans.com,2002:shape>"), "{}", out);
+        assert_eq!(&YamlLoader::load_from_str(&out).unwrap()[0], doc);
+    }
+
+    #[test]
+    fn test_loader_builder() {
+        let parser = HelloTagParser;
+        let mut builder = YamlLoader::builder();
+        builder
+            .scalar_parser(&parser)
+            .key_coercion(KeyCoercion::Schema)
+            .scanner_config(ScannerConfig {
+                max_anchors: Some(1),
+                max_documents: Some(1),
+                ..ScannerConfig::default()
+            });
+
+        let docs = builder.load("{'1': !hello world}").unwrap();
+        assert_eq!(docs[0][1].as_str(), Some("Hello world"));
+        // the builder can be reused
+        let docs = builder.load("'2': x").unwrap();
+        assert_eq!(docs[0][2].as_str(), Some("x"));
+        assert!(builder.load("[&a 1, &b 2]").is_err());
+        assert!(builder.load("--- 1\n--- 2\n").is_err());
+
+        let loader = builder.bad_value_mode(BadValueMode::Error).build();
+        assert!(loader.parse_from_str("!!int x").is_err());
+    }
+
+    #[test]
+    fn test_document_spans() {
+        let s = "# leading
+a: 1
+--- # second
+- b
+...
+%YAML 1.2
+---
+\"ü\": [c]
+";
+        let docs = YamlLoader::load_from_str_with_spans(s).unwrap();
+        let slices: Vec<_> = docs.iter().map(|(_, span)| span.slice(s)).collect();
+        assert_eq!(
+            slices,
+            vec!["a: 1\n", "--- # second\n- b\n", "---\n\"ü\": [c]\n"]
+        );
+        assert_eq!(docs[2].0["ü"][0].as_str(), Some("c"));
+        assert_eq!((docs[1].1.start.line(), docs[1].1.end.line()), (3, 5));
+
+        let replaced = format!(
+            "{}---\nb: 2\n{}",
+            &s[..s.find("---").unwrap()],
+            &s[s.find("...").unwrap()..]
+        );
+        let docs = YamlLoader::load_from_str(&replaced).unwrap();
+        assert_eq!(docs.len(), 3);
+        assert_eq!(docs[1]["b"].as_i64(), Some(2));
+    }
+
+    #[test]
+    fn test_extract() {
+        let s = "
+defaults: &defaults {image: base, pull: always}
+items:
+  - name: a
+  - name: b
+    <<: x
+    env: *defaults
+    list: &list [1, [2, 3]]
+  - *list
+1: one
+";
+        let full = &YamlLoader::load_from_str(s).unwrap()[0];
+        for path in &[
+            "",
+            "items",
+            "items.1.name",
+            "items.1.env.image",
+            "items.1.list.1.0",
+            "items.2.1",
+            "defaults.pull",
+            "1",
+        ] {
+            assert_eq!(
+                extract(s, path).unwrap().as_ref(),
+                lookup_path(full, path),
+                "{}",
+                path
+            );
+        }
+        assert_eq!(extract(s, "items.3").unwrap(), None);
+        assert_eq!(extract(s, "items.x").unwrap(), None);
+        assert_eq!(extract(s, "items.0.name.x").unwrap(), None);
+        assert_eq!(extract("", "a").unwrap(), None);
+
+        // parsing stops at the end of the document
+        let trailing_error = "a: {b: 1}\n---\nc: [d";
+        assert_eq!(
+            extract(trailing_error, "a.b").unwrap(),
+            Some(Yaml::Integer(1))
+        );
+        assert!(extract("a: {b: 1}\nc: [d", "a.b").is_err());
+    }
+
+    #[test]
+    fn test_extract_duplicate_keys() {
+        let s = "a: 1\nb: {c: x}\na: 2\nb: {c: y}";
+        assert_eq!(extract(s, "a").unwrap(), Some(Yaml::Integer(2)));
+        assert_eq!(extract(s, "b.c").unwrap(), Some(Yaml::from_str("y")));
+        assert_eq!(extract("a: [1, 2]\na: [3]", "a.1").unwrap(), None);
+
+        let err = extract("a: &x [b, *x]", "a").unwrap_err();
+        assert!(err.info().starts_with("found recursive alias *x"));
+        assert_eq!(
+            extract("a: &x 1\nb: &x 2\nc: *x", "c").unwrap(),
+            Some(Yaml::Integer(2))
+        );
+    }
+
     #[test]
     fn test_scalar_parser() {
         let parser = HelloTagParser;
@@ -987,6 +6379,42 @@ c: [1, 2]
         assert_eq!(doc.as_str().unwrap(), "Hello world")
     }
 
+    struct LengthParser;
+
+    impl YamlScalarParser for LengthParser {
+        fn parse_scalar(&self, _: &TokenType, value: &str) -> Option<Yaml> {
+            if value.is_empty() {
+                return None;
+            }
+            Some(Yaml::Integer(value.len() as i64))
+        }
+    }
+
+    #[test]
+    fn test_tag_parser() {
+        let s = "%TAG !p! tag:example.com,2024:\n---\n[!len abc, !p!len abcd, !len '', !!str x, !other y]";
+        let docs = YamlLoader::builder()
+            .tag_parser("!", "len", &HelloTagParser)
+            .tag_parser("!", "len", &LengthParser)
+            .tag_parser("tag:example.com,2024:", "len", &LengthParser)
+            .load(s)
+            .unwrap();
+        let doc = &docs[0];
+        assert_eq!(doc[0].as_i64(), Some(3));
+        assert_eq!(doc[1].as_i64(), Some(4));
+        // a parser that returns None leaves the scalar to the loader
+        assert!(matches!(doc[2], Yaml::Tagged(ref tag, _) if tag.suffix == "len"));
+        assert_eq!(doc[3].as_str(), Some("x"));
+        assert!(doc[4].is_tagged());
+
+        // tag parsers are tried before scalar parsers
+        let mut loader = YamlLoader::new();
+        loader.register_scalar_parser(&HelloTagParser);
+        loader.register_tag_parser("!", "hello", &LengthParser);
+        let docs = loader.parse_from_str("!hello world").unwrap();
+        assert_eq!(docs[0].as_i64(), Some(5));
+    }
+
     #[test]
     fn test_tag_directive() {
         let parser = HelloTagParser;
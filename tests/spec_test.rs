@@ -6,7 +6,7 @@ use yaml_rust::parser::{Event, EventReceiver, Parser};
 use yaml_rust::scanner::TScalarStyle;
 
 // These names match the names used in the C++ test suite.
-#[cfg_attr(feature = "cargo-clippy", allow(clippy::enum_variant_names))]
+#[allow(clippy::enum_variant_names)]
 #[derive(Clone, PartialEq, PartialOrd, Debug)]
 enum TestEvent {
     OnDocumentStart,